@@ -3,7 +3,8 @@ mod services;
 use candid::{Nat, Principal};
 use ic_cdk::api::management_canister::http_request::{TransformArgs, HttpResponse};
 use crate::services::evm_rpc_canister::BlockTag;
-use crate::services::ethtransfer::InitArg;
+use crate::services::config::{CanisterConfig, InitArg};
+use crate::services::errors::FintrackError;
 use services::budget as budget;
 use services::goals as goals;
 use services::kongswap as kongswap;
@@ -13,29 +14,29 @@ use services::kongswap as kongswap;
 // -------------------------
 
 #[ic_cdk::update]
-async fn btc_get_deposit_address(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<String, String> {
+async fn btc_get_deposit_address(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<String, FintrackError> {
     services::btc::get_deposit_address(owner, subaccount).await
 }
 
 #[ic_cdk::update]
-async fn btc_refresh_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<(), String> {
+async fn btc_refresh_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<(), FintrackError> {
     services::btc::refresh_balance(owner, subaccount).await
 }
 
 #[ic_cdk::update]
-async fn btc_get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, String> {
+async fn btc_get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
     services::btc::get_balance(owner, subaccount).await
 }
 
 /// Returns the UTXOs of the given Bitcoin address with simplified response (hash and confirmations only)
 #[ic_cdk::update]
-async fn btc_get_utxos(address: String) -> Result<Vec<services::btc::SimplifiedUtxo>, String> {
+async fn btc_get_utxos(address: String) -> Result<Vec<services::btc::SimplifiedUtxo>, FintrackError> {
     services::btc::get_utxos(address).await
 }
 
 /// Returns the 100 fee percentiles measured in millisatoshi/byte for Bitcoin network
 #[ic_cdk::update]
-async fn btc_get_current_fee_percentiles() -> Result<Vec<u64>, String> {
+async fn btc_get_current_fee_percentiles() -> Result<Vec<u64>, FintrackError> {
     services::btc::get_current_fee_percentiles().await
 }
 
@@ -44,18 +45,18 @@ async fn btc_get_current_fee_percentiles() -> Result<Vec<u64>, String> {
 // -------------------------
 
 #[ic_cdk::update]
-async fn eth_get_deposit_address(subaccount: Option<Vec<u8>>) -> Result<String, String> {
+async fn eth_get_deposit_address(subaccount: Option<Vec<u8>>) -> Result<String, FintrackError> {
     services::eth::get_deposit_address(subaccount).await
 }
 
 #[ic_cdk::update]
-async fn eth_refresh_balance(subaccount: Option<Vec<u8>>) -> Result<(), String> {
+async fn eth_refresh_balance(subaccount: Option<Vec<u8>>) -> Result<(), FintrackError> {
     services::eth::refresh_balance(subaccount).await
 }
 
 // ckETH balance (ledger)
 #[ic_cdk::update]
-async fn eth_get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, String> {
+async fn eth_get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
     services::eth::get_balance(owner, subaccount).await
 }
 
@@ -66,29 +67,29 @@ async fn eth_get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>)
 // Additional ckETH helper endpoints
 
 #[ic_cdk::query]
-async fn eth_get_minter_address() -> Result<String, String> {
+async fn eth_get_minter_address() -> Result<String, FintrackError> {
     services::eth::get_minter_address().await
 }
 
 #[ic_cdk::update]
-async fn eth_get_minter_info() -> Result<String, String> {
+async fn eth_get_minter_info() -> Result<String, FintrackError> {
     services::eth::get_minter_info().await
 }
 
 #[ic_cdk::query]
-async fn eth_estimate_withdrawal_fee() -> Result<String, String> {
+async fn eth_estimate_withdrawal_fee() -> Result<String, FintrackError> {
     services::eth::estimate_withdrawal_fee().await
 }
 
 /// Returns the historical fee data to estimate gas prices for Ethereum transactions
 #[ic_cdk::query]
-async fn eth_fee_history() -> Result<String, String> {
+async fn eth_fee_history() -> Result<String, FintrackError> {
     services::eth::fee_history().await
 }
 
 // Principal to bytes32 conversion endpoint
 #[ic_cdk::query]
-fn principal_to_bytes32(principal_text: String) -> Result<String, String> {
+fn principal_to_bytes32(principal_text: String) -> Result<String, FintrackError> {
     services::utils::principal_to_bytes32(principal_text)
 }
 
@@ -97,19 +98,19 @@ fn principal_to_bytes32(principal_text: String) -> Result<String, String> {
 // -------------------------
 
 #[ic_cdk::update]
-async fn evm_derive_address(owner: Option<Principal>) -> Result<String, String> {
+async fn evm_derive_address(owner: Option<Principal>) -> Result<String, FintrackError> {
     services::address::get_eth_address(owner).await
 }
 
 
 
 #[ic_cdk::update]
-async fn eth_get_native_balance(address: Option<String>) -> Result<Nat, String> {
+async fn eth_get_native_balance(address: Option<String>) -> Result<Nat, FintrackError> {
     services::ethtransfer::get_native_eth_balance(address).await
 }
 
 #[ic_cdk::query]
-async fn eth_get_transaction_count(owner: Option<Principal>, block: Option<String>) -> Result<u64, String> {
+async fn eth_get_transaction_count(owner: Option<Principal>, block: Option<String>) -> Result<u64, FintrackError> {
     let block_tag = match block.as_deref() {
         Some("latest") => Some(BlockTag::Latest),
         Some("finalized") => Some(BlockTag::Finalized),
@@ -122,21 +123,112 @@ async fn eth_get_transaction_count(owner: Option<Principal>, block: Option<Strin
 }
 
 #[ic_cdk::update]
-async fn btc_derive_address(owner: Option<Principal>) -> Result<String, String> {
+async fn btc_derive_address(owner: Option<Principal>) -> Result<String, FintrackError> {
     services::address::get_btc_address(owner).await
 }
 
+// -------------------------
+// Canister config (controller only)
+// -------------------------
+
+#[ic_cdk::init]
+fn init(arg: Option<InitArg>) {
+    services::config::apply_init_arg(arg);
+    services::journal::start_reconciler();
+    services::escrow_audit::start_audit();
+    goals::start_price_watch();
+    goals::start_progress_watch();
+}
+
+#[ic_cdk::query]
+fn get_config() -> Result<CanisterConfig, FintrackError> {
+    services::config::get_config()
+}
+
+#[ic_cdk::update]
+fn update_config(config: CanisterConfig) -> Result<CanisterConfig, FintrackError> {
+    services::config::update_config(config)
+}
+
+// -------------------------
+// Provider secrets (controller only, values never returned)
+// -------------------------
+
+#[ic_cdk::query]
+fn secrets_list() -> Result<Vec<services::secrets::ProviderSecretsInfo>, FintrackError> {
+    services::secrets::list()
+}
+
+#[ic_cdk::update]
+fn secrets_add_key(provider: services::secrets::SecretProvider, value: String) -> Result<services::secrets::SecretKeyInfo, FintrackError> {
+    services::secrets::add_key(provider, value)
+}
+
+#[ic_cdk::update]
+fn secrets_remove_key(provider: services::secrets::SecretProvider, key_id: u32) -> Result<(), FintrackError> {
+    services::secrets::remove_key(provider, key_id)
+}
+
+#[ic_cdk::update]
+fn secrets_rotate_keys(provider: services::secrets::SecretProvider, values: Vec<String>) -> Result<services::secrets::ProviderSecretsInfo, FintrackError> {
+    services::secrets::rotate_keys(provider, values)
+}
+
+#[ic_cdk::update]
+fn secrets_set_enabled(provider: services::secrets::SecretProvider, enabled: bool) -> Result<(), FintrackError> {
+    services::secrets::set_enabled(provider, enabled)
+}
+
+// -------------------------
+// Signing authorization (delegations + audit)
+// -------------------------
+
+#[ic_cdk::update]
+fn auth_grant_delegation(req: services::auth::DelegationRequest) -> Result<services::auth::Delegation, FintrackError> {
+    services::auth::grant_delegation(req)
+}
+
+#[ic_cdk::update]
+fn auth_revoke_delegation(delegate: Principal) -> Result<(), FintrackError> {
+    services::auth::revoke_delegation(delegate)
+}
+
+#[ic_cdk::query]
+fn auth_list_delegations() -> Vec<services::auth::Delegation> {
+    services::auth::list_delegations()
+}
+
+#[ic_cdk::query]
+fn auth_list_audit_events(limit: Option<u32>, offset: Option<u32>) -> Vec<services::auth::AuthAuditEvent> {
+    services::auth::list_audit_events(limit, offset)
+}
+
 // -------------------------
 // Transfer services
 // -------------------------
 
 #[ic_cdk::update]
-async fn btc_transfer(request: services::btctransfer::BtcTransferRequest) -> Result<services::btctransfer::BtcTransferResponse, String> {
+async fn btc_transfer(request: services::btctransfer::BtcTransferRequest) -> Result<services::btctransfer::BtcTransferResponse, FintrackError> {
     services::btctransfer::transfer_btc(request).await
 }
 
 #[ic_cdk::update]
-async fn eth_transfer(request: services::ethtransfer::EthTransferRequest) -> Result<services::ethtransfer::EthTransferResponse, String> {
+async fn btc_bump_fee(txid: String, new_fee_rate: u64) -> Result<services::btctransfer::BtcTransferResponse, FintrackError> {
+    services::btctransfer::bump_fee(txid, new_fee_rate).await
+}
+
+#[ic_cdk::update]
+async fn btc_cancel(txid: String) -> Result<services::btctransfer::BtcTransferResponse, FintrackError> {
+    services::btctransfer::cancel(txid).await
+}
+
+#[ic_cdk::update]
+fn btc_get_sent_transaction(txid: String) -> Result<services::btctransfer::SentBtcTx, FintrackError> {
+    services::btctransfer::get_sent_transaction(txid)
+}
+
+#[ic_cdk::update]
+async fn eth_transfer(request: services::ethtransfer::EthTransferRequest) -> Result<services::ethtransfer::EthTransferResponse, FintrackError> {
     services::ethtransfer::transfer_eth(request).await
 }
 
@@ -145,42 +237,42 @@ async fn eth_transfer(request: services::ethtransfer::EthTransferRequest) -> Res
 // -------------------------
 
 #[ic_cdk::update]
-async fn uniswap_send_tx(request: services::uniswap::TxRequest) -> Result<String, String> {
+async fn uniswap_send_tx(request: services::uniswap::TxRequest) -> Result<String, FintrackError> {
     services::uniswap::send_uniswap_tx(request).await
 }
 
 #[ic_cdk::update]
-async fn uniswap_send_tx_with_response(request: services::uniswap::TxRequest) -> Result<services::uniswap::UniswapTxResponse, String> {
+async fn uniswap_send_tx_with_response(request: services::uniswap::TxRequest) -> Result<services::uniswap::UniswapTxResponse, FintrackError> {
     services::uniswap::send_uniswap_tx_with_response(request).await
 }
 
 #[ic_cdk::update]
-async fn uniswap_get_gas_price() -> Result<u128, String> {
+async fn uniswap_get_gas_price() -> Result<u128, FintrackError> {
     services::uniswap::get_current_gas_price().await
 }
 
 #[ic_cdk::update]
-async fn uniswap_send_approval_tx(request: services::uniswap::TxRequest) -> Result<String, String> {
+async fn uniswap_send_approval_tx(request: services::uniswap::TxRequest) -> Result<String, FintrackError> {
     services::uniswap::send_approval_tx(request).await
 }
 
 #[ic_cdk::update]
-async fn uniswap_get_fresh_nonce(owner: Option<Principal>) -> Result<u64, String> {
+async fn uniswap_get_fresh_nonce(owner: Option<Principal>) -> Result<u64, FintrackError> {
     services::uniswap::get_fresh_nonce(owner).await
 }
 
 #[ic_cdk::update]
-async fn btc_get_utxos_for_address(address: String) -> Result<Vec<ic_cdk::bitcoin_canister::Utxo>, String> {
+async fn btc_get_utxos_for_address(address: String) -> Result<Vec<ic_cdk::bitcoin_canister::Utxo>, FintrackError> {
     services::btctransfer::get_utxos_for_address(address).await
 }
 
 #[ic_cdk::update]
-async fn btc_get_fee_percentiles() -> Result<Vec<ic_cdk::bitcoin_canister::MillisatoshiPerByte>, String> {
+async fn btc_get_fee_percentiles() -> Result<Vec<ic_cdk::bitcoin_canister::MillisatoshiPerByte>, FintrackError> {
     services::btctransfer::get_current_fee_percentiles().await
 }
 
 #[ic_cdk::update]
-async fn btc_get_native_balance(address: String) -> Result<u64, String> {
+async fn btc_get_native_balance(address: String) -> Result<u64, FintrackError> {
     services::btctransfer::get_native_btc_balance(address).await
 }
 
@@ -193,7 +285,7 @@ async fn eth_preview_fee(
     destination_address: String,
     amount: Nat,
     gas_limit: Option<u128>
-) -> Result<services::ethtransfer::EthFeePreview, String> {
+) -> Result<services::ethtransfer::EthFeePreview, FintrackError> {
     services::ethtransfer::preview_eth_fee(destination_address, amount, gas_limit).await
 }
 
@@ -202,7 +294,7 @@ async fn btc_preview_fee(
     destination_address: String,
     amount_in_satoshi: u64,
     owner: Option<Principal>
-) -> Result<services::btctransfer::BtcFeePreview, String> {
+) -> Result<services::btctransfer::BtcFeePreview, FintrackError> {
     services::btctransfer::preview_btc_fee(destination_address, amount_in_satoshi, owner).await
 }
 
@@ -215,12 +307,12 @@ async fn get_transaction_history(
     user: Principal,
     limit: Option<u32>,
     offset: Option<u32>
-) -> Result<Vec<services::transactions::Transaction>, String> {
+) -> Result<Vec<services::transactions::Transaction>, FintrackError> {
     services::transactions::get_user_transaction_history(user, limit, offset).await
 }
 
 #[ic_cdk::update]
-async fn get_user_balances(user: Principal) -> Result<services::transactions::UserBalances, String> {
+async fn get_user_balances(user: Principal) -> Result<services::transactions::UserBalances, FintrackError> {
     services::transactions::get_user_balances(user).await
 }
 
@@ -239,27 +331,27 @@ async fn clear_user_transactions(user: Principal) {
 // -------------------------
 
 #[ic_cdk::update]
-async fn get_crypto_usd_rate(crypto_id: String) -> Result<f64, String> {
+async fn get_crypto_usd_rate(crypto_id: String) -> Result<f64, FintrackError> {
     services::rates::get_crypto_usd_rate(&crypto_id).await
 }
 
 #[ic_cdk::update]
-async fn get_rates_summary() -> Result<services::rates::CryptoRates, String> {
+async fn get_rates_summary() -> Result<services::rates::CryptoRates, FintrackError> {
     services::rates::get_rates_summary().await
 }
 
 #[ic_cdk::update]
-async fn get_market_chart(coin_id: String, vs_currency: String, days: u32) -> Result<services::rates::MarketChartData, String> {
+async fn get_market_chart(coin_id: String, vs_currency: String, days: u32) -> Result<services::rates::MarketChartData, FintrackError> {
     services::rates::get_market_chart(&coin_id, &vs_currency, days).await
 }
 
 #[ic_cdk::update]
-async fn get_24h_change(coin_id: String) -> Result<f64, String> {
+async fn get_24h_change(coin_id: String) -> Result<f64, FintrackError> {
     services::rates::get_24h_change(&coin_id).await
 }
 
 #[ic_cdk::update]
-async fn get_historical_prices(coin_id: String, vs_currency: String, days: u32) -> Result<Vec<services::rates::PriceData>, String> {
+async fn get_historical_prices(coin_id: String, vs_currency: String, days: u32) -> Result<Vec<services::rates::PriceData>, FintrackError> {
     services::rates::get_historical_prices(&coin_id, &vs_currency, days).await
 }
 
@@ -268,7 +360,7 @@ async fn get_historical_prices(coin_id: String, vs_currency: String, days: u32)
 // -------------------------
 
 #[ic_cdk::update]
-async fn btc_get_network_info(address: Option<String>) -> Result<services::btc::BtcNetworkInfo, String> {
+async fn btc_get_network_info(address: Option<String>) -> Result<services::btc::BtcNetworkInfo, FintrackError> {
     services::btc::get_network_info(address).await
 }
 
@@ -279,7 +371,7 @@ async fn btc_get_network_info(address: Option<String>) -> Result<services::btc::
 // -------------------------
 
 #[ic_cdk::update]
-async fn budget_create(req: budget::BudgetCreateRequest) -> Result<budget::BudgetInfo, String> {
+async fn budget_create(req: budget::BudgetCreateRequest) -> Result<budget::BudgetInfo, FintrackError> {
     budget::create_budget(req).await
 }
 
@@ -299,70 +391,75 @@ fn budget_get(id: String) -> Option<budget::BudgetInfo> {
 }
 
 #[ic_cdk::query]
-fn budget_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<budget::BudgetEvent>, String> {
+fn budget_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<budget::BudgetEvent>, FintrackError> {
     budget::budget_list_events(id, limit, offset)
 }
 
 #[ic_cdk::query]
-fn budget_preview_accrual(id: String) -> Result<budget::BudgetAccrualPreview, String> {
+fn budget_preview_accrual(id: String) -> Result<budget::BudgetAccrualPreview, FintrackError> {
     budget::budget_preview_accrual(id)
 }
 
 #[ic_cdk::update]
-async fn budget_refresh_accrual_step(id: String, max_delta: Option<Nat>) -> Result<budget::BudgetInfo, String> {
+async fn budget_refresh_accrual_step(id: String, max_delta: Option<Nat>) -> Result<budget::BudgetInfo, FintrackError> {
     budget::budget_refresh_accrual_step(id, max_delta)
 }
 
 #[ic_cdk::update]
-async fn budget_refresh_accrual(id: String) -> Result<budget::BudgetInfo, String> {
+async fn budget_refresh_accrual(id: String) -> Result<budget::BudgetInfo, FintrackError> {
     budget::budget_refresh_accrual(id)
 }
 
 #[ic_cdk::query]
-fn budget_get_escrow_account(id: String) -> Result<budget::Account, String> {
+fn budget_get_escrow_account(id: String) -> Result<budget::Account, FintrackError> {
     budget::get_escrow_account(id)
 }
 
 #[ic_cdk::update]
-fn budget_pause(id: String) -> Result<(), String> {
+fn budget_pause(id: String) -> Result<(), FintrackError> {
     budget::pause_budget(id)
 }
 
 #[ic_cdk::update]
-fn budget_resume(id: String) -> Result<(), String> {
+fn budget_resume(id: String) -> Result<(), FintrackError> {
     budget::resume_budget(id)
 }
 
 #[ic_cdk::update]
-async fn budget_delete(id: String) -> Result<(), String> {
+async fn budget_delete(id: String) -> Result<(), FintrackError> {
     budget::delete_budget(id).await
 }
 
 #[ic_cdk::update]
-async fn budget_update(id: String, upd: budget::BudgetUpdateRequest) -> Result<budget::BudgetInfo, String> {
+async fn budget_update(id: String, upd: budget::BudgetUpdateRequest) -> Result<budget::BudgetInfo, FintrackError> {
     // pure state mutation, no await inside
     budget::update_budget(id, upd)
 }
 
 #[ic_cdk::update]
-async fn budget_trigger_lock_now(id: String) -> Result<(), String> {
+async fn budget_trigger_lock_now(id: String) -> Result<(), FintrackError> {
     budget::trigger_lock_now(id).await
 }
 
 // removed budget_trigger_unlock_now: linear vesting accrues on write
 
 #[ic_cdk::query]
-fn budget_preview_schedule(id: String) -> Result<Vec<budget::BudgetSchedulePreviewItem>, String> {
+fn budget_preview_schedule(id: String) -> Result<Vec<budget::BudgetSchedulePreviewItem>, FintrackError> {
     budget::budget_preview_schedule(id)
 }
 
+#[ic_cdk::update]
+async fn budget_get_analytics(id: String) -> Result<budget::BudgetAnalytics, FintrackError> {
+    budget::budget_get_analytics(id).await
+}
+
 #[ic_cdk::query]
-fn budget_required_allowance(id: String) -> Result<Nat, String> {
+fn budget_required_allowance(id: String) -> Result<Nat, FintrackError> {
     budget::budget_required_allowance(id)
 }
 
 #[ic_cdk::update]
-async fn budget_required_amounts(id: String) -> Result<budget::BudgetAmountRequirements, String> {
+async fn budget_required_amounts(id: String) -> Result<budget::BudgetAmountRequirements, FintrackError> {
     budget::budget_required_amounts(id).await
 }
 
@@ -371,28 +468,90 @@ async fn budget_preview_requirements(
     asset_canister: Principal,
     asset_kind: budget::AssetKind,
     amount_to_lock: Nat,
-) -> Result<budget::BudgetAmountRequirements, String> {
+) -> Result<budget::BudgetAmountRequirements, FintrackError> {
     budget::budget_preview_requirements(asset_canister, asset_kind, amount_to_lock).await
 }
 
 #[ic_cdk::update]
-async fn budget_create_and_lock(req: budget::BudgetCreateRequest) -> Result<budget::BudgetInfo, String> {
+async fn budget_create_and_lock(req: budget::BudgetCreateRequest) -> Result<budget::BudgetInfo, FintrackError> {
     budget::budget_create_and_lock(req).await
 }
 
 #[ic_cdk::update]
-async fn budget_withdraw(id: String, amount: Nat, to_subaccount: Option<Vec<u8>>) -> Result<Nat, String> {
+async fn budget_withdraw(id: String, amount: Nat, to_subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
     budget::budget_withdraw(id, amount, to_subaccount).await
 }
 
-#[ic_cdk::pre_upgrade]
-fn pre_upgrade() {
-    budget::pre_upgrade();
+#[ic_cdk::update]
+async fn budget_withdraw_envelope(id: String, envelope_id: String, amount: Nat, to_subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
+    budget::budget_withdraw_envelope(id, envelope_id, amount, to_subaccount).await
+}
+
+#[ic_cdk::update]
+async fn budget_spend(req: budget::BudgetSpendRequest) -> Result<Nat, FintrackError> {
+    budget::budget_spend(req).await
+}
+
+// -------------------------
+// Budget categories
+// -------------------------
+
+#[ic_cdk::update]
+fn budget_category_create(name: String, parent_id: Option<String>) -> Result<services::categories::Category, FintrackError> {
+    services::categories::create_category(name, parent_id)
+}
+
+#[ic_cdk::query]
+fn budget_category_list() -> Vec<services::categories::Category> {
+    services::categories::list_categories()
+}
+
+#[ic_cdk::update]
+fn budget_category_delete(id: String) -> Result<(), FintrackError> {
+    services::categories::delete_category(id)
+}
+
+#[ic_cdk::query]
+fn budget_category_report(owner: Option<Principal>, asset: Principal) -> Vec<services::categories::CategoryReport> {
+    services::categories::category_report(owner, asset)
 }
 
+// Budgets, goals & their events live in stable memory (ic_stable_structures), no pre_upgrade needed.
+// post_upgrade applies the upgrade arg to the stored config, migrates old record versions and re-arms timers.
 #[ic_cdk::post_upgrade]
-fn post_upgrade() {
+fn post_upgrade(arg: Option<InitArg>) {
+    services::config::apply_init_arg(arg);
     budget::post_upgrade();
+    goals::post_upgrade();
+    services::journal::start_reconciler();
+    services::escrow_audit::start_audit();
+    goals::start_price_watch();
+    goals::start_progress_watch();
+}
+
+// Operasi ledger yang belum pasti hasilnya (controller melihat semua, user hanya miliknya)
+#[ic_cdk::query]
+fn list_pending_operations() -> Vec<services::journal::PendingOperation> {
+    services::journal::list_pending_operations()
+}
+
+// -------------------------
+// Escrow audit endpoints
+// -------------------------
+
+#[ic_cdk::update]
+async fn escrow_check(entity: services::journal::EntityKind, id: String, asset: Option<Principal>) -> Result<services::escrow_audit::EscrowReport, FintrackError> {
+    services::escrow_audit::escrow_check(entity, id, asset).await
+}
+
+#[ic_cdk::query]
+fn escrow_list_reports(only_drift: Option<bool>) -> Vec<services::escrow_audit::EscrowReport> {
+    services::escrow_audit::escrow_list_reports(only_drift)
+}
+
+#[ic_cdk::update]
+async fn escrow_recover(entity: services::journal::EntityKind, id: String, asset: Option<Principal>, to_subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
+    services::escrow_audit::escrow_recover(entity, id, asset, to_subaccount).await
 }
 
 
@@ -402,7 +561,7 @@ fn post_upgrade() {
 // -------------------------
 
 #[ic_cdk::update]
-async fn goals_create_and_lock(req: goals::GoalCreateRequest) -> Result<goals::GoalInfo, String> {
+async fn goals_create_and_lock(req: goals::GoalCreateRequest) -> Result<goals::GoalInfo, FintrackError> {
     goals::goals_create_and_lock(req).await
 }
 
@@ -413,41 +572,136 @@ fn goals_get(id: String) -> Option<goals::GoalInfo> { goals::goals_get(id) }
 fn goals_list(owner: Option<Principal>) -> Vec<goals::GoalInfo> { goals::goals_list(owner) }
 
 #[ic_cdk::query]
-fn goals_get_progress(id: String) -> Result<goals::GoalProgress, String> { goals::goals_get_progress(id) }
+fn goals_get_progress(id: String) -> Result<goals::GoalProgress, FintrackError> { goals::goals_get_progress(id) }
+
+#[ic_cdk::update]
+fn goals_refresh(id: String) -> Result<goals::GoalInfo, FintrackError> { goals::goals_refresh(id) }
+
+#[ic_cdk::update]
+async fn goals_refresh_price(id: String) -> Result<goals::GoalInfo, FintrackError> { goals::goals_refresh_price(id).await }
+
+#[ic_cdk::update]
+fn goals_enable_early_exit(id: String, terms: goals::EarlyExitTerms) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_enable_early_exit(id, terms)
+}
+
+#[ic_cdk::update]
+async fn goals_request_early_exit(id: String, amount: Option<Nat>) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_request_early_exit(id, amount).await
+}
+
+#[ic_cdk::update]
+fn goals_cancel_early_exit(id: String) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_cancel_early_exit(id)
+}
+
+#[ic_cdk::query]
+fn goals_community_pool_balance(ledger: Principal) -> Nat { goals::goals_community_pool_balance(ledger) }
+
+#[ic_cdk::update]
+fn goals_set_milestones(id: String, milestones: Vec<goals::MilestoneSpec>) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_set_milestones(id, milestones)
+}
+
+#[ic_cdk::query]
+fn notifications_list(unread_only: Option<bool>, limit: Option<u32>, offset: Option<u32>) -> Vec<services::notifications::Notification> {
+    services::notifications::notifications_list(unread_only, limit, offset)
+}
+
+#[ic_cdk::query]
+fn notifications_unread_count() -> u32 { services::notifications::notifications_unread_count() }
+
+#[ic_cdk::update]
+fn notifications_mark_read(ids: Vec<u64>) -> u32 { services::notifications::notifications_mark_read(ids) }
+
+#[ic_cdk::update]
+fn goals_invite_member(id: String, member: Principal) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_invite_member(id, member)
+}
+
+#[ic_cdk::update]
+fn goals_accept_invite(id: String) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_accept_invite(id)
+}
+
+#[ic_cdk::query]
+fn goals_list_invitations() -> Vec<goals::GoalInfo> { goals::goals_list_invitations() }
+
+#[ic_cdk::query]
+fn goals_list_contributions(id: String) -> Result<Vec<goals::ContributorBalance>, FintrackError> {
+    goals::goals_list_contributions(id)
+}
+
+#[ic_cdk::update]
+async fn goals_propose_withdrawal(id: String, asset: Option<Principal>, amount: Nat, recipient: Option<Principal>) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_propose_withdrawal(id, asset, amount, recipient).await
+}
+
+#[ic_cdk::update]
+async fn goals_approve_withdrawal(id: String) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_approve_withdrawal(id).await
+}
+
+#[ic_cdk::update]
+fn goals_cancel_withdrawal(id: String) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_cancel_withdrawal(id)
+}
+
+#[ic_cdk::update]
+fn goals_set_auto_contribution(id: String, req: goals::AutoContributionRequest) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_set_auto_contribution(id, req)
+}
+
+#[ic_cdk::update]
+fn goals_cancel_auto_contribution(id: String) -> Result<goals::GoalInfo, FintrackError> {
+    goals::goals_cancel_auto_contribution(id)
+}
+
+#[ic_cdk::update]
+async fn goals_add_funds(id: String, amount: Nat) -> Result<goals::GoalInfo, FintrackError> { goals::goals_add_funds(id, amount).await }
+
+#[ic_cdk::update]
+async fn goals_withdraw(id: String, amount: Nat) -> Result<Nat, FintrackError> { goals::goals_withdraw(id, amount).await }
+
+#[ic_cdk::update]
+async fn goals_add_funds_asset(id: String, asset: Principal, amount: Nat) -> Result<goals::GoalInfo, FintrackError> { goals::goals_add_funds_asset(id, asset, amount).await }
+
+#[ic_cdk::update]
+async fn goals_withdraw_asset(id: String, asset: Principal, amount: Nat) -> Result<Nat, FintrackError> { goals::goals_withdraw_asset(id, asset, amount).await }
 
 #[ic_cdk::update]
-fn goals_refresh(id: String) -> Result<goals::GoalInfo, String> { goals::goals_refresh(id) }
+fn goals_set_auto_payout(id: String, enabled: bool) -> Result<goals::GoalInfo, FintrackError> { goals::goals_set_auto_payout(id, enabled) }
 
 #[ic_cdk::update]
-async fn goals_add_funds(id: String, amount: Nat) -> Result<goals::GoalInfo, String> { goals::goals_add_funds(id, amount).await }
+async fn goals_archive(id: String) -> Result<goals::GoalInfo, FintrackError> { goals::goals_archive(id).await }
 
 #[ic_cdk::update]
-async fn goals_withdraw(id: String, amount: Nat) -> Result<Nat, String> { goals::goals_withdraw(id, amount).await }
+async fn goals_delete(id: String) -> Result<(), FintrackError> { goals::goals_delete(id).await }
 
 #[ic_cdk::query]
-fn goals_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<goals::GoalEvent>, String> { goals::goals_list_events(id, limit, offset) }
+fn goals_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<goals::GoalEvent>, FintrackError> { goals::goals_list_events(id, limit, offset) }
 
 // -------------------------
 // KongSwap service endpoints
 // -------------------------
 
 #[ic_cdk::update]
-async fn kongswap_preview_swap(request: kongswap::KongSwapRequest) -> Result<kongswap::KongSwapPreview, String> {
+async fn kongswap_preview_swap(request: kongswap::KongSwapRequest) -> Result<kongswap::KongSwapPreview, FintrackError> {
     kongswap::preview_swap(request).await
 }
 
 #[ic_cdk::update]
-async fn kongswap_swap_tokens_async(request: kongswap::KongSwapRequest) -> Result<u64, String> {
+async fn kongswap_swap_tokens_async(request: kongswap::KongSwapRequest) -> Result<u64, FintrackError> {
     kongswap::swap_tokens_async(request).await
 }
 
 #[ic_cdk::query]
-async fn kongswap_get_current_price() -> Result<f64, String> {
+async fn kongswap_get_current_price() -> Result<f64, FintrackError> {
     kongswap::get_current_price().await
 }
 
 #[ic_cdk::query]
-async fn kongswap_is_service_available() -> Result<bool, String> {
+async fn kongswap_is_service_available() -> Result<bool, FintrackError> {
     kongswap::is_service_available().await
 }
 
@@ -457,23 +711,23 @@ fn kongswap_format_token_amount(amount: Nat, token: String) -> String {
 }
 
 #[ic_cdk::query]
-fn kongswap_parse_token_amount(amount_str: String, token: String) -> Result<Nat, String> {
+fn kongswap_parse_token_amount(amount_str: String, token: String) -> Result<Nat, FintrackError> {
     kongswap::parse_token_amount(&amount_str, &token)
 }
 
 // KongSwap multi-hop swap endpoints
 #[ic_cdk::query]
-async fn kongswap_get_request(request_id: u64) -> Result<kongswap::RequestsReply, String> {
+async fn kongswap_get_request(request_id: u64) -> Result<kongswap::RequestsReply, FintrackError> {
     kongswap::get_request(request_id).await
 }
 
 #[ic_cdk::query]
-async fn kongswap_poll_swap_status(request_id: u64) -> Result<kongswap::KongSwapResponse, String> {
+async fn kongswap_poll_swap_status(request_id: u64) -> Result<kongswap::KongSwapResponse, FintrackError> {
     kongswap::poll_swap_status(request_id).await
 }
 
 #[ic_cdk::query]
-async fn kongswap_get_swap_amounts(pay_token: String, pay_amount: Nat, receive_token: String) -> Result<kongswap::SwapAmountsReply, String> {
+async fn kongswap_get_swap_amounts(pay_token: String, pay_amount: Nat, receive_token: String) -> Result<kongswap::SwapAmountsReply, FintrackError> {
     kongswap::get_swap_amounts(pay_token, pay_amount, receive_token).await
 }
 
@@ -1,12 +1,12 @@
 use candid::Principal;
 use ic_cdk::api::management_canister::ecdsa::{ecdsa_public_key, EcdsaKeyId, EcdsaPublicKeyArgument};
-use bitcoin::{Address, Network};
+use bitcoin::Address;
 use sha3::{Digest, Keccak256};
 use k256::PublicKey as K256PublicKey;
 use k256::elliptic_curve::sec1::ToEncodedPoint;
 
-const DEFAULT_ECDSA_KEY_NAME: &str = "key_1"; // Use "dfx_test_key" for testnet
-const BTC_NETWORK: Network = Network::Testnet; // Testnet Bitcoin
+use super::config;
+use super::errors::FintrackError;
 
 fn principal_derivation_path(owner: Principal) -> Vec<Vec<u8>> {
     vec![owner.as_slice().to_vec()]
@@ -18,7 +18,7 @@ async fn fetch_ecdsa_pubkey(owner: Principal) -> Result<Vec<u8>, String> {
         derivation_path: principal_derivation_path(owner),
         key_id: EcdsaKeyId {
             curve: ic_cdk::api::management_canister::ecdsa::EcdsaCurve::Secp256k1,
-            name: DEFAULT_ECDSA_KEY_NAME.to_string(),
+            name: config::ecdsa_key_name(),
         },
     };
     let (res,) = ecdsa_public_key(args)
@@ -41,13 +41,13 @@ fn ethereum_address_from_pubkey_sec1(pubkey_sec1: &[u8]) -> String {
     format!("0x{}", hex::encode(addr_bytes))
 }
 
-pub async fn get_eth_address(owner: Option<Principal>) -> Result<String, String> {
+pub async fn get_eth_address(owner: Option<Principal>) -> Result<String, FintrackError> {
     let owner = owner.unwrap_or_else(ic_cdk::api::caller);
     let pubkey = fetch_ecdsa_pubkey(owner).await?;
     Ok(ethereum_address_from_pubkey_sec1(&pubkey))
 }
 
-pub async fn get_btc_address(owner: Option<Principal>) -> Result<String, String> {
+pub async fn get_btc_address(owner: Option<Principal>) -> Result<String, FintrackError> {
     let owner = owner.unwrap_or_else(ic_cdk::api::caller);
     let pubkey = fetch_ecdsa_pubkey(owner).await?;
     // Use compressed key for P2WPKH
@@ -55,7 +55,7 @@ pub async fn get_btc_address(owner: Option<Principal>) -> Result<String, String>
         .map_err(|e| format!("invalid compressed pubkey: {e}"))?;
     
     // Address::p2wpkh returns Address directly, not Result
-    let addr = Address::p2wpkh(&compressed, BTC_NETWORK);
+    let addr = Address::p2wpkh(&compressed, config::btc_network());
     Ok(addr.to_string())
 }
 
@@ -0,0 +1,256 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use super::errors::FintrackError;
+use super::storage::{self, Candid, StableText, VMem};
+
+// Otorisasi signing native BTC/ETH.
+// Default: signing selalu terikat ke caller (derivation path = caller).
+// Bertindak atas nama principal lain hanya lewat delegasi eksplisit yang dicatat owner:
+// scope, expiry dan spend cap per asset. Setiap penolakan dicatat sebagai audit event.
+
+const MAX_AUDIT_EVENTS: u64 = 10_000;
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DelegationScope {
+    BtcTransfer,     // btc_transfer, btc_preview_fee
+    EthTransfer,     // eth_transfer
+    EvmContractCall, // uniswap_send_tx, uniswap_send_approval_tx
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NativeAsset {
+    Btc, // satoshi
+    Eth, // wei
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SpendLimit {
+    pub asset: NativeAsset,
+    pub limit: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SpendCap {
+    pub asset: NativeAsset,
+    pub limit: Nat,
+    pub spent: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DelegationRequest {
+    pub delegate: Principal,
+    pub scopes: Vec<DelegationScope>,
+    pub expires_at_ns: Option<u64>,
+    pub spend_limits: Vec<SpendLimit>, // asset tanpa limit = tidak dibatasi
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Delegation {
+    pub owner: Principal,
+    pub delegate: Principal,
+    pub scopes: Vec<DelegationScope>,
+    pub expires_at_ns: Option<u64>,
+    pub spend_caps: Vec<SpendCap>,
+    pub created_at_ns: u64,
+    pub revoked_at_ns: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuthAuditEvent {
+    pub seq: u64,
+    pub at_time_ns: u64,
+    pub caller: Principal,
+    pub owner: Principal,
+    pub scope: DelegationScope,
+    pub amount: Option<Nat>,
+    pub reason: String,
+}
+
+thread_local! {
+    // key = "{owner}|{delegate}"
+    static DELEGATIONS: RefCell<StableBTreeMap<StableText, Candid<Delegation>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::AUTH_DELEGATIONS_MEMORY_ID))
+    );
+    // key = seq (monotonic)
+    static AUDIT_LOG: RefCell<StableBTreeMap<u64, Candid<AuthAuditEvent>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::AUTH_AUDIT_MEMORY_ID))
+    );
+    // cache seq berikutnya (heap), diisi ulang dari log setelah upgrade
+    static NEXT_AUDIT_SEQ: RefCell<Option<u64>> = RefCell::new(None);
+}
+
+fn delegation_key(owner: &Principal, delegate: &Principal) -> StableText {
+    StableText(format!("{}|{}", owner, delegate))
+}
+
+fn scope_asset(scope: DelegationScope) -> NativeAsset {
+    match scope {
+        DelegationScope::BtcTransfer => NativeAsset::Btc,
+        DelegationScope::EthTransfer | DelegationScope::EvmContractCall => NativeAsset::Eth,
+    }
+}
+
+fn record_rejection(caller: Principal, owner: Principal, scope: DelegationScope, amount: Option<Nat>, reason: &str) {
+    let at = time();
+    AUDIT_LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        let seq = NEXT_AUDIT_SEQ.with(|n| {
+            let mut n = n.borrow_mut();
+            let seq = n.unwrap_or_else(|| log.iter().last().map(|(k, _)| k + 1).unwrap_or(0));
+            *n = Some(seq + 1);
+            seq
+        });
+        log.insert(seq, Candid(AuthAuditEvent { seq, at_time_ns: at, caller, owner, scope, amount, reason: reason.to_string() }));
+        // Batasi ukuran log: buang event paling lama
+        while log.len() > MAX_AUDIT_EVENTS {
+            match log.iter().next().map(|(k, _)| k) {
+                Some(oldest) => { log.remove(&oldest); }
+                None => break,
+            }
+        }
+    });
+    ic_cdk::println!("auth rejected: caller={} owner={} scope={:?} reason={}", caller, owner, scope, reason);
+}
+
+/// Resolve the principal whose key may be used for signing.
+/// `owner` None atau sama dengan caller -> caller sendiri. Selain itu wajib ada delegasi aktif
+/// dengan scope yang cocok; `amount` (satoshi/wei) dibebankan ke spend cap asset terkait.
+pub fn authorize_signer(owner: Option<Principal>, scope: DelegationScope, amount: Option<Nat>) -> Result<Principal, FintrackError> {
+    let who = caller();
+    let owner = owner.unwrap_or(who);
+
+    if who == Principal::anonymous() {
+        record_rejection(who, owner, scope, amount, "anonymous caller");
+        return Err(FintrackError::unauthorized("anonymous caller cannot sign"));
+    }
+    if owner == who { return Ok(who); }
+
+    let key = delegation_key(&owner, &who);
+    let delegation = DELEGATIONS.with(|d| d.borrow().get(&key)).map(|c| c.0);
+    let mut delegation = match delegation {
+        Some(d) => d,
+        None => {
+            record_rejection(who, owner, scope, amount, "no delegation");
+            return Err(FintrackError::unauthorized("no delegation from owner"));
+        }
+    };
+
+    let now = time();
+    if delegation.revoked_at_ns.is_some() {
+        record_rejection(who, owner, scope, amount, "delegation revoked");
+        return Err(FintrackError::unauthorized("delegation revoked"));
+    }
+    if delegation.expires_at_ns.map(|exp| now >= exp).unwrap_or(false) {
+        record_rejection(who, owner, scope, amount, "delegation expired");
+        return Err(FintrackError::unauthorized("delegation expired"));
+    }
+    if !delegation.scopes.contains(&scope) {
+        record_rejection(who, owner, scope, amount, "scope not delegated");
+        return Err(FintrackError::unauthorized(format!("scope {:?} not delegated", scope)));
+    }
+
+    if let Some(amt) = amount.as_ref() {
+        let asset = scope_asset(scope);
+        if let Some(cap) = delegation.spend_caps.iter_mut().find(|c| c.asset == asset) {
+            let after = &cap.spent.0 + &amt.0;
+            if after > cap.limit.0 {
+                record_rejection(who, owner, scope, amount.clone(), "spend cap exceeded");
+                return Err(FintrackError::unauthorized(format!("spend cap exceeded (limit {}, spent {})", cap.limit, cap.spent)));
+            }
+            cap.spent = Nat(after);
+            DELEGATIONS.with(|d| d.borrow_mut().insert(key, Candid(delegation)));
+        }
+    }
+
+    Ok(owner)
+}
+
+/// Kembalikan spend yang sudah dibebankan bila signing/broadcast gagal.
+pub fn release_spend(owner: Principal, delegate: Principal, scope: DelegationScope, amount: &Nat) {
+    if owner == delegate { return; }
+    let key = delegation_key(&owner, &delegate);
+    DELEGATIONS.with(|d| {
+        let mut map = d.borrow_mut();
+        if let Some(Candid(mut delegation)) = map.get(&key) {
+            let asset = scope_asset(scope);
+            if let Some(cap) = delegation.spend_caps.iter_mut().find(|c| c.asset == asset) {
+                cap.spent = if cap.spent.0 > amount.0 { Nat(&cap.spent.0 - &amount.0) } else { Nat::from(0u64) };
+                map.insert(key, Candid(delegation));
+            }
+        }
+    });
+}
+
+pub fn grant_delegation(req: DelegationRequest) -> Result<Delegation, FintrackError> {
+    let owner = caller();
+    if owner == Principal::anonymous() { return Err(FintrackError::unauthorized("anonymous caller")); }
+    if req.delegate == owner { return Err(FintrackError::invalid_input("delegate", "must differ from owner")); }
+    if req.delegate == Principal::anonymous() { return Err(FintrackError::invalid_input("delegate", "cannot be anonymous")); }
+    if req.scopes.is_empty() { return Err(FintrackError::invalid_input("scopes", "cannot be empty")); }
+    let now = time();
+    if req.expires_at_ns.map(|exp| exp <= now).unwrap_or(false) { return Err(FintrackError::invalid_input("expires_at_ns", "must be in the future")); }
+
+    let mut scopes: Vec<DelegationScope> = Vec::new();
+    for s in req.scopes { if !scopes.contains(&s) { scopes.push(s); } }
+    let mut spend_caps: Vec<SpendCap> = Vec::new();
+    for l in req.spend_limits {
+        if spend_caps.iter().any(|c| c.asset == l.asset) { return Err(FintrackError::invalid_input("spend_limits", format!("duplicate spend limit for {:?}", l.asset))); }
+        spend_caps.push(SpendCap { asset: l.asset, limit: l.limit, spent: Nat::from(0u64) });
+    }
+
+    // Grant ulang menggantikan delegasi lama (termasuk counter spent)
+    let delegation = Delegation {
+        owner,
+        delegate: req.delegate,
+        scopes,
+        expires_at_ns: req.expires_at_ns,
+        spend_caps,
+        created_at_ns: now,
+        revoked_at_ns: None,
+    };
+    DELEGATIONS.with(|d| d.borrow_mut().insert(delegation_key(&owner, &req.delegate), Candid(delegation.clone())));
+    Ok(delegation)
+}
+
+pub fn revoke_delegation(delegate: Principal) -> Result<(), FintrackError> {
+    let owner = caller();
+    let key = delegation_key(&owner, &delegate);
+    DELEGATIONS.with(|d| {
+        let mut map = d.borrow_mut();
+        let Candid(mut delegation) = map.get(&key).ok_or_else(|| FintrackError::not_found("delegation", delegate.to_text()))?;
+        if delegation.revoked_at_ns.is_none() {
+            delegation.revoked_at_ns = Some(time());
+            map.insert(key, Candid(delegation));
+        }
+        Ok(())
+    })
+}
+
+/// Delegasi di mana caller adalah owner atau delegate
+pub fn list_delegations() -> Vec<Delegation> {
+    let who = caller();
+    DELEGATIONS.with(|d| {
+        d.borrow().iter()
+            .map(|(_, v)| v.0)
+            .filter(|dl| dl.owner == who || dl.delegate == who)
+            .collect()
+    })
+}
+
+/// Controller melihat seluruh log; user hanya event yang menyangkut dirinya (terbaru dulu)
+pub fn list_audit_events(limit: Option<u32>, offset: Option<u32>) -> Vec<AuthAuditEvent> {
+    let who = caller();
+    let is_controller = ic_cdk::api::is_controller(&who);
+    let off = offset.unwrap_or(0) as usize;
+    let lim = limit.unwrap_or(50) as usize;
+    let mut events: Vec<AuthAuditEvent> = AUDIT_LOG.with(|log| {
+        log.borrow().iter()
+            .map(|(_, v)| v.0)
+            .filter(|ev| is_controller || ev.owner == who || ev.caller == who)
+            .collect()
+    });
+    events.reverse();
+    events.into_iter().skip(off).take(lim).collect()
+}
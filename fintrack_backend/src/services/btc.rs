@@ -1,5 +1,6 @@
 use candid::{CandidType, Nat, Principal};
 use ic_cdk::api::call::call_with_payment128;
+use super::errors::FintrackError;
 use ic_cdk::{
     bitcoin_canister::{bitcoin_get_utxos, bitcoin_get_current_fee_percentiles, GetUtxosRequest, GetUtxosResponse, GetCurrentFeePercentilesRequest, GetBlockHeadersRequest, GetBlockHeadersResponse},
 };
@@ -11,21 +12,18 @@ pub struct SimplifiedUtxo {
     pub confirmations: u32,     // Number of confirmations
 }
 
-// Helper: read ckBTC minter and ledger principals from dfx.json (baked-in for now)
-// In production, consider storing in stable state or ENV via build-time feature.
+// ckBTC minter & ledger dari config canister (lihat services/config.rs)
 fn ckbtc_minter_principal() -> Principal {
-    // Mainnet ckBTC Minter
-    Principal::from_text("ml52i-qqaaa-aaaar-qaaba-cai").expect("invalid ckbtc_minter principal")
+    super::config::get().ckbtc_minter
 }
 
 fn ckbtc_ledger_principal() -> Principal {
-    // Mainnet ckBTC Ledger
-    Principal::from_text("mc6ru-gyaaa-aaaar-qaaaq-cai").expect("invalid ckbtc_ledger principal")
+    super::config::get().ckbtc_ledger
 }
 
 // NOTE: For now, these are stubs to be wired to ckBTC minter/ledger.
 
-pub async fn get_deposit_address(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<String, String> {
+pub async fn get_deposit_address(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<String, FintrackError> {
     let minter = ckbtc_minter_principal();
     #[derive(candid::CandidType)]
     struct AddressArg {
@@ -35,11 +33,11 @@ pub async fn get_deposit_address(owner: Option<Principal>, subaccount: Option<Ve
     let arg = (AddressArg { owner, subaccount },);
     let (address,): (String,) = call_with_payment128(minter, "get_btc_address", arg, 1_000_000)
         .await
-        .map_err(|e| format!("get_btc_address failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("get_btc_address failed: {:?}", e)))?;
     Ok(address)
 }
 
-pub async fn refresh_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<(), String> {
+pub async fn refresh_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<(), FintrackError> {
     let minter = ckbtc_minter_principal();
     // update_balance returns variant { Ok : vec UtxoStatus; Err : UpdateBalanceError }
 
@@ -107,18 +105,26 @@ pub async fn refresh_balance(owner: Option<Principal>, subaccount: Option<Vec<u8
         subaccount: Option<Vec<u8>>,
     }
 
+    // Deposit ke akun default memicu auto-contribution goal (OnCkBtcDeposit)
+    let deposit_owner = if subaccount.is_none() { Some(owner.unwrap_or_else(ic_cdk::caller)) } else { None };
     let arg = (UpdateArg { owner, subaccount },);
     // We don't need detailed structure, just success/failure mapping via candid.
     let (res,): (UpdateResult,) = call_with_payment128(minter, "update_balance", arg, 5_000_000)
         .await
-        .map_err(|e| format!("update_balance failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("update_balance failed: {:?}", e)))?;
     match res {
-        UpdateResult::Ok(_) => Ok(()),
-        UpdateResult::Err(e) => Err(format!("update_balance returned Err: {:?}", e)),
+        UpdateResult::Ok(statuses) => {
+            let minted = statuses.iter().any(|s| matches!(s, UtxoStatus::Minted { .. }));
+            if let (true, Some(owner)) = (minted, deposit_owner) {
+                ic_cdk::futures::spawn(super::goals::on_ckbtc_deposit(owner));
+            }
+            Ok(())
+        }
+        UpdateResult::Err(e) => Err(FintrackError::ledger(format!("update_balance returned Err: {:?}", e))),
     }
 }
 
-pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, String> {
+pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
     let ledger = ckbtc_ledger_principal();
     #[derive(CandidType)]
     struct AccountArg {
@@ -130,16 +136,16 @@ pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>)
     let arg = (AccountArg { owner: resolved_owner, subaccount },);
     let (balance,): (Nat,) = call_with_payment128(ledger, "icrc1_balance_of", arg, 1_000_000)
         .await
-        .map_err(|e| format!("icrc1_balance_of failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("icrc1_balance_of failed: {:?}", e)))?;
     Ok(balance)
 }
 
 // withdraw_with_approval removed: user should call minter directly from frontend after approving.
 
 /// Get UTXOs for a Bitcoin address using the Bitcoin canister
-pub async fn get_utxos(address: String) -> Result<Vec<SimplifiedUtxo>, String> {
-    // Mainnet Bitcoin network
-    let network = ic_cdk::bitcoin_canister::Network::Testnet;
+pub async fn get_utxos(address: String) -> Result<Vec<SimplifiedUtxo>, FintrackError> {
+    super::btctransfer::parse_btc_address(&address)?;
+    let network = super::config::ic_bitcoin_network();
     
     let request = GetUtxosRequest {
         address: address.clone(),
@@ -157,7 +163,7 @@ pub async fn get_utxos(address: String) -> Result<Vec<SimplifiedUtxo>, String> {
         cycles,
     )
     .await
-    .map_err(|e| format!("bitcoin_get_utxos failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("bitcoin_get_utxos failed: {:?}", e)))?;
     
     // Convert to simplified format - only hash and confirmations
     let simplified_utxos: Vec<SimplifiedUtxo> = response
@@ -173,9 +179,8 @@ pub async fn get_utxos(address: String) -> Result<Vec<SimplifiedUtxo>, String> {
 }
 
 /// Get current Bitcoin fee percentiles in millisatoshi/byte
-pub async fn get_current_fee_percentiles() -> Result<Vec<u64>, String> {
-    // Mainnet Bitcoin network
-    let network = ic_cdk::bitcoin_canister::Network::Testnet;
+pub async fn get_current_fee_percentiles() -> Result<Vec<u64>, FintrackError> {
+    let network = super::config::ic_bitcoin_network();
     
     let request = GetCurrentFeePercentilesRequest {
         network,
@@ -191,7 +196,7 @@ pub async fn get_current_fee_percentiles() -> Result<Vec<u64>, String> {
         cycles,
     )
     .await
-    .map_err(|e| format!("bitcoin_get_current_fee_percentiles failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("bitcoin_get_current_fee_percentiles failed: {:?}", e)))?;
     
     // Convert MillisatoshiPerByte to u64 for easier handling
     let fees: Vec<u64> = fee_percentiles
@@ -209,13 +214,14 @@ pub struct BtcNetworkInfo {
 }
 
 /// Returns network info including current block height and highest seen UTXO height
-pub async fn get_network_info(address: Option<String>) -> Result<BtcNetworkInfo, String> {
-    let network = ic_cdk::bitcoin_canister::Network::Testnet;
+pub async fn get_network_info(address: Option<String>) -> Result<BtcNetworkInfo, FintrackError> {
+    let network = super::config::ic_bitcoin_network();
 
     let mut utxo_height: u32 = 0;
     let mut current_block_height: u32 = 0;
 
     if let Some(addr) = address {
+        super::btctransfer::parse_btc_address(&addr)?;
         let utxo_request = GetUtxosRequest { address: addr, network, filter: None };
         // Lampirkan cycles yang cukup (mainnet get_utxos: min 10_000_000_000)
         let cycles: u128 = 10_000_000_000;
@@ -227,7 +233,7 @@ pub async fn get_network_info(address: Option<String>) -> Result<BtcNetworkInfo,
             cycles,
         )
         .await
-        .map_err(|e| format!("bitcoin_get_utxos failed: {:?}", e))?;
+        .map_err(|e| FintrackError::rpc(format!("bitcoin_get_utxos failed: {:?}", e)))?;
 
         // Highest UTXO height for this address
         for utxo in utxo_response.utxos.into_iter() {
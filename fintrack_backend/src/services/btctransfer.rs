@@ -1,5 +1,5 @@
-use candid::Principal;
-use ic_cdk::api::call::call_with_payment128;
+use candid::{Nat, Principal};
+use ic_cdk::api::call::{call_with_payment128, RejectionCode};
 use ic_cdk::{
     bitcoin_canister::{
         bitcoin_get_utxos, bitcoin_send_transaction, bitcoin_get_current_fee_percentiles, bitcoin_get_balance,
@@ -12,23 +12,27 @@ use bitcoin::{
     ecdsa::Signature as BitcoinSignature,
     secp256k1::{ecdsa::Signature as SecpSignature, Message},
     sighash::{EcdsaSighashType, SighashCache},
-    Address, AddressType, PublicKey, ScriptBuf, Transaction, TxOut, Witness, Network as BtcNetwork,
+    Address, AddressType, PublicKey, ScriptBuf, Transaction, TxOut, Witness,
     transaction::Version, Amount, Txid,
     absolute::LockTime,
     hashes::Hash,
 };
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
 use std::str::FromStr;
-
-const DEFAULT_ECDSA_KEY_NAME: &str = "key_1"; // Use "dfx_test_key" for testnet
-const BTC_NETWORK: BtcNetwork = BtcNetwork::Testnet; // Bitcoin testnet
-const IC_BTC_NETWORK: ic_cdk::bitcoin_canister::Network = ic_cdk::bitcoin_canister::Network::Testnet; // IC Bitcoin canister testnet
+use super::auth::{self, DelegationScope};
+use super::errors::FintrackError;
+use super::guard::EntityGuard;
+use super::config;
+use super::storage::{self, Candid, StableText, VMem};
 
 // Request struct untuk transfer BTC
 #[derive(candid::CandidType, candid::Deserialize)]
 pub struct BtcTransferRequest {
     pub destination_address: String,
     pub amount_in_satoshi: u64,
-    pub owner: Option<Principal>, // Jika None, gunakan caller
+    pub owner: Option<Principal>, // Jika None, gunakan caller; selain caller butuh delegasi
+    pub replaceable: Option<bool>, // opt-in RBF (BIP125): tx bisa dipercepat lewat btc_bump_fee / dibatalkan lewat btc_cancel
 }
 
 // Response struct untuk transfer BTC
@@ -50,9 +54,72 @@ pub struct BtcFeePreview {
     pub change_amount: u64,
 }
 
+// Input tx yang dikirim (UTXO milik owner); txid dalam urutan byte yang sama dengan Utxo.outpoint.txid
+#[derive(candid::CandidType, candid::Deserialize, Clone, Debug)]
+pub struct SentBtcInput {
+    pub txid: Vec<u8>,
+    pub vout: u32,
+    pub value: u64,
+}
+
+impl From<&Utxo> for SentBtcInput {
+    fn from(u: &Utxo) -> Self {
+        SentBtcInput { txid: u.outpoint.txid.clone(), vout: u.outpoint.vout, value: u.value }
+    }
+}
+
+// Transfer RBF yang sudah di-broadcast; disimpan supaya bisa dibangun ulang dengan fee lebih tinggi
+#[derive(candid::CandidType, candid::Deserialize, Clone, Debug)]
+pub struct SentBtcTx {
+    pub txid: String,
+    pub owner: Principal,
+    pub destination_address: String, // btc_cancel: alamat owner sendiri
+    pub amount_in_satoshi: u64,      // btc_cancel: 0, seluruh input kembali ke owner
+    pub fee_sats: u64,
+    pub fee_rate: MillisatoshiPerByte,
+    pub inputs: Vec<SentBtcInput>,
+    pub signed_tx: Vec<u8>,
+    pub created_at_ns: u64,
+    pub replaces: Option<String>,
+    pub replaced_by: Option<String>,
+}
+
+// Input dibatasi supaya tx tersimpan muat dalam satu record stable (Candid 16KB)
+const MAX_REPLACEABLE_INPUTS: usize = 40;
+// Incremental relay fee minimum BIP125: 1 sat/vB
+const MIN_RELAY_FEE_RATE: MillisatoshiPerByte = 1_000;
+// Bump di atas kelipatan ini dari persentil ke-90 ditolak (kemungkinan salah satuan)
+const MAX_FEE_RATE_MULTIPLIER: u64 = 10;
+// Batas dust output P2WPKH pada relay policy default
+const DUST_THRESHOLD_SATS: u64 = 294;
+
+thread_local! {
+    // key = txid
+    static SENT_TXS: RefCell<StableBTreeMap<StableText, Candid<SentBtcTx>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::BTC_SENT_TXS_MEMORY_ID))
+    );
+}
+
+fn load_sent_tx(txid: &str) -> Option<SentBtcTx> {
+    SENT_TXS.with(|m| m.borrow().get(&StableText(txid.to_string()))).map(|c| c.0)
+}
+
+fn store_sent_tx(tx: &SentBtcTx) {
+    SENT_TXS.with(|m| m.borrow_mut().insert(StableText(tx.txid.clone()), Candid(tx.clone())));
+}
+
 
 // Get UTXOs untuk address tertentu
-pub async fn get_utxos_for_address(address: String) -> Result<Vec<Utxo>, String> {
+// Alamat BTC yang valid untuk network canister ini
+pub(crate) fn parse_btc_address(address: &str) -> Result<Address, FintrackError> {
+    Address::from_str(address)
+        .map_err(|e| FintrackError::invalid_address(address, e))?
+        .require_network(config::btc_network())
+        .map_err(|e| FintrackError::invalid_address(address, format!("not valid for network: {}", e)))
+}
+
+pub async fn get_utxos_for_address(address: String) -> Result<Vec<Utxo>, FintrackError> {
+    parse_btc_address(&address)?;
     // Lampirkan cycles yang cukup (mainnet get_utxos: min 10_000_000_000)
     let cycles: u128 = 10_000_000_000;
     
@@ -61,19 +128,19 @@ pub async fn get_utxos_for_address(address: String) -> Result<Vec<Utxo>, String>
         "bitcoin_get_utxos",
         (GetUtxosRequest {
             address,
-            network: IC_BTC_NETWORK,
+            network: config::ic_bitcoin_network(),
             filter: None,
         },),
         cycles,
     )
     .await
-    .map_err(|e| format!("bitcoin_get_utxos failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("bitcoin_get_utxos failed: {:?}", e)))?;
 
     Ok(response.utxos)
 }
 
 // Get current fee percentiles
-pub async fn get_current_fee_percentiles() -> Result<Vec<MillisatoshiPerByte>, String> {
+pub async fn get_current_fee_percentiles() -> Result<Vec<MillisatoshiPerByte>, FintrackError> {
     // Lampirkan cycles yang cukup (mainnet get_current_fee_percentiles: min 100_000_000)
     let cycles: u128 = 100_000_000;
     
@@ -81,18 +148,19 @@ pub async fn get_current_fee_percentiles() -> Result<Vec<MillisatoshiPerByte>, S
         Principal::management_canister(),
         "bitcoin_get_current_fee_percentiles",
         (GetCurrentFeePercentilesRequest {
-            network: IC_BTC_NETWORK,
+            network: config::ic_bitcoin_network(),
         },),
         cycles,
     )
     .await
-    .map_err(|e| format!("bitcoin_get_current_fee_percentiles failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("bitcoin_get_current_fee_percentiles failed: {:?}", e)))?;
     
     Ok(fee_percentiles)
 }
 
 // Get native BTC balance for an address
-pub async fn get_native_btc_balance(address: String) -> Result<u64, String> {
+pub async fn get_native_btc_balance(address: String) -> Result<u64, FintrackError> {
+    parse_btc_address(&address)?;
     // Lampirkan cycles yang cukup (mainnet get_balance: min 100_000_000)
     let cycles: u128 = 100_000_000;
     
@@ -101,13 +169,13 @@ pub async fn get_native_btc_balance(address: String) -> Result<u64, String> {
         "bitcoin_get_balance",
         (GetBalanceRequest {
             address,
-            network: IC_BTC_NETWORK,
+            network: config::ic_bitcoin_network(),
             min_confirmations: Some(0), // Include unconfirmed transactions
         },),
         cycles,
     )
     .await
-    .map_err(|e| format!("bitcoin_get_balance failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("bitcoin_get_balance failed: {:?}", e)))?;
 
     Ok(balance)
 }
@@ -121,7 +189,7 @@ async fn get_ecdsa_public_key(owner: Principal) -> Result<Vec<u8>, String> {
         derivation_path,
         key_id: ic_cdk::api::management_canister::ecdsa::EcdsaKeyId {
             curve: ic_cdk::api::management_canister::ecdsa::EcdsaCurve::Secp256k1,
-            name: DEFAULT_ECDSA_KEY_NAME.to_string(),
+            name: config::ecdsa_key_name(),
         },
     };
     
@@ -162,7 +230,8 @@ async fn build_transaction_with_fee(
     dst_address: &Address,
     amount: Satoshi,
     fee_per_vbyte: MillisatoshiPerByte,
-) -> Result<(Transaction, Vec<TxOut>), String> {
+    replaceable: bool,
+) -> Result<(Transaction, Vec<TxOut>), FintrackError> {
     // Simple fee estimation: start with 0, then iterate
     let mut fee = 0u64;
     let max_iterations = 5;
@@ -170,23 +239,25 @@ async fn build_transaction_with_fee(
     
     loop {
         if iteration >= max_iterations {
-            return Err("Failed to estimate fee after maximum iterations".to_string());
+            return Err(FintrackError::Internal { message: "Failed to estimate fee after maximum iterations".to_string() });
         }
         iteration += 1;
         
         // Select UTXOs to spend
         let utxos_to_spend = select_utxos_greedy(own_utxos, amount, fee)?;
         if utxos_to_spend.is_empty() {
-            return Err("Insufficient funds".to_string());
+            return Err(FintrackError::InsufficientFunds { needed: candid::Nat::from(amount + fee), available: candid::Nat::from(0u64) });
         }
         
         // Build transaction
+        let inputs: Vec<SentBtcInput> = utxos_to_spend.iter().map(SentBtcInput::from).collect();
         let (transaction, prevouts) = build_transaction_simple(
-            &utxos_to_spend,
+            &inputs,
             own_address,
             dst_address,
             amount,
             fee,
+            replaceable,
         )?;
         
         // Estimate transaction size (simplified)
@@ -206,7 +277,7 @@ fn select_utxos_greedy(
     utxos: &[Utxo],
     amount: Satoshi,
     fee: u64,
-) -> Result<Vec<Utxo>, String> {
+) -> Result<Vec<Utxo>, FintrackError> {
     let mut selected_utxos = Vec::new();
     let mut total_value = 0u64;
     
@@ -219,16 +290,17 @@ fn select_utxos_greedy(
         }
     }
     
-    Err("Insufficient funds".to_string())
+    Err(FintrackError::InsufficientFunds { needed: candid::Nat::from(amount + fee), available: candid::Nat::from(total_value) })
 }
 
-// Build simple transaction
+// Build simple transaction; `replaceable` = signal RBF lewat nSequence (BIP125)
 fn build_transaction_simple(
-    utxos: &[Utxo],
+    utxos: &[SentBtcInput],
     own_address: &Address,
     dst_address: &Address,
     amount: Satoshi,
     fee: u64,
+    replaceable: bool,
 ) -> Result<(Transaction, Vec<TxOut>), String> {
     let mut transaction = Transaction {
         version: Version(2),
@@ -243,16 +315,16 @@ fn build_transaction_simple(
     for utxo in utxos {
         let outpoint = bitcoin::OutPoint {
             txid: Txid::from_raw_hash(
-                bitcoin::hashes::sha256d::Hash::from_slice(&utxo.outpoint.txid)
+                bitcoin::hashes::sha256d::Hash::from_slice(&utxo.txid)
                     .map_err(|e| format!("Invalid txid: {}", e))?
             ),
-            vout: utxo.outpoint.vout,
+            vout: utxo.vout,
         };
         
         transaction.input.push(bitcoin::TxIn {
             previous_output: outpoint,
             script_sig: ScriptBuf::new(),
-            sequence: bitcoin::Sequence::MAX,
+            sequence: if replaceable { bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME } else { bitcoin::Sequence::MAX },
             witness: Witness::new(),
         });
         
@@ -288,10 +360,14 @@ fn build_transaction_simple(
 
 // Estimate transaction size (simplified)
 fn estimate_transaction_size(transaction: &Transaction) -> usize {
+    estimate_size_for(transaction.input.len(), transaction.output.len())
+}
+
+fn estimate_size_for(inputs: usize, outputs: usize) -> usize {
     // Rough estimation: 4 bytes version + 4 bytes locktime + inputs + outputs
     let base_size = 8;
-    let input_size = transaction.input.len() * 150; // Approximate input size
-    let output_size = transaction.output.len() * 34; // Approximate output size
+    let input_size = inputs * 150; // Approximate input size
+    let output_size = outputs * 34; // Approximate output size
     
     base_size + input_size + output_size
 }
@@ -323,7 +399,7 @@ async fn sign_transaction(
         let message = Message::from(sighash);
         
         let raw_signature = sign_with_ecdsa(
-            DEFAULT_ECDSA_KEY_NAME.to_string(),
+            config::ecdsa_key_name(),
             derivation_path.clone(),
             message.as_ref().to_vec(),
         )
@@ -348,14 +424,11 @@ pub async fn preview_btc_fee(
     destination_address: String,
     amount_in_satoshi: u64,
     owner: Option<Principal>,
-) -> Result<BtcFeePreview, String> {
-    let owner = owner.unwrap_or_else(ic_cdk::api::caller);
+) -> Result<BtcFeePreview, FintrackError> {
+    let owner = auth::authorize_signer(owner, DelegationScope::BtcTransfer, None)?;
     
     // Parse destination address
-    let _dst_address = Address::from_str(&destination_address)
-        .map_err(|e| format!("Invalid destination address: {}", e))?
-        .require_network(BTC_NETWORK)
-        .map_err(|e| format!("Address not valid for network: {}", e))?;
+    let _dst_address = parse_btc_address(&destination_address)?;
     
     // Get ECDSA public key
     let public_key_bytes = get_ecdsa_public_key(owner).await?;
@@ -366,13 +439,13 @@ pub async fn preview_btc_fee(
     let own_address = Address::p2wpkh(
         &bitcoin::key::CompressedPublicKey::from_slice(&public_key_bytes)
             .map_err(|e| format!("Invalid compressed public key: {}", e))?,
-        BTC_NETWORK,
+        config::btc_network(),
     );
     
     // Get UTXOs
     let own_utxos = get_utxos_for_address(own_address.to_string()).await?;
     if own_utxos.is_empty() {
-        return Err("No UTXOs found for address".to_string());
+        return Err(FintrackError::InsufficientFunds { needed: candid::Nat::from(amount_in_satoshi), available: candid::Nat::from(0u64) });
     }
     
     // Get current fee percentiles
@@ -427,9 +500,7 @@ pub async fn preview_btc_fee(
 }
 
 // Main transfer function
-pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResponse, String> {
-    let owner = request.owner.unwrap_or_else(ic_cdk::api::caller);
-    
+pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResponse, FintrackError> {
     if request.amount_in_satoshi == 0 {
         return Ok(BtcTransferResponse {
             success: false,
@@ -437,12 +508,25 @@ pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResp
             error: Some("Amount must be greater than 0".to_string()),
         });
     }
-    
+
+    // Signing terikat ke caller; owner lain hanya via delegasi (amount + fee dibebankan ke cap)
+    let delegate = ic_cdk::api::caller();
+    let mut charged = candid::Nat::from(request.amount_in_satoshi);
+    let owner = auth::authorize_signer(request.owner, DelegationScope::BtcTransfer, Some(charged.clone()))?;
+
+    // Spend hanya dikembalikan bila tx pasti tidak diteruskan; hasil yang tidak pasti tetap dibebankan
+    let mut may_be_sent = false;
+    let result = transfer_btc_for_owner(owner, request, &mut charged, &mut may_be_sent).await;
+    if !may_be_sent && !matches!(result, Ok(BtcTransferResponse { success: true, .. })) {
+        auth::release_spend(owner, delegate, DelegationScope::BtcTransfer, &charged);
+    }
+    result
+}
+
+// `charged` bertambah fee saat fee sudah diketahui; `may_be_sent` di-set sebelum broadcast dan di-reset bila ditolak pasti
+async fn transfer_btc_for_owner(owner: Principal, request: BtcTransferRequest, charged: &mut Nat, may_be_sent: &mut bool) -> Result<BtcTransferResponse, FintrackError> {
     // Parse destination address
-    let dst_address = Address::from_str(&request.destination_address)
-        .map_err(|e| format!("Invalid destination address: {}", e))?
-        .require_network(BTC_NETWORK)
-        .map_err(|e| format!("Address not valid for network: {}", e))?;
+    let dst_address = parse_btc_address(&request.destination_address)?;
     
     // Get ECDSA public key
     let public_key_bytes = get_ecdsa_public_key(owner).await?;
@@ -453,7 +537,7 @@ pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResp
     let own_address = Address::p2wpkh(
         &bitcoin::key::CompressedPublicKey::from_slice(&public_key_bytes)
             .map_err(|e| format!("Invalid compressed public key: {}", e))?,
-        BTC_NETWORK,
+        config::btc_network(),
     );
     
     // Get UTXOs
@@ -474,6 +558,7 @@ pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResp
         .unwrap_or(1000); // Default fee if not available
     
     // Build transaction
+    let replaceable = request.replaceable.unwrap_or(false);
     let (transaction, prevouts) = build_transaction_with_fee(
         &own_public_key,
         &own_address,
@@ -481,8 +566,21 @@ pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResp
         &dst_address,
         request.amount_in_satoshi,
         fee_per_vbyte,
+        replaceable,
     )
     .await?;
+    if replaceable && transaction.input.len() > MAX_REPLACEABLE_INPUTS {
+        return Err(FintrackError::invalid_input("replaceable", format!("replaceable transfers can spend at most {} UTXOs", MAX_REPLACEABLE_INPUTS)));
+    }
+    let inputs: Vec<SentBtcInput> = transaction.input.iter().zip(&prevouts).map(|(txin, prev)| SentBtcInput {
+        txid: txin.previous_output.txid.to_byte_array().to_vec(),
+        vout: txin.previous_output.vout,
+        value: prev.value.to_sat(),
+    }).collect();
+    let fee_sats = prevouts.iter().map(|p| p.value.to_sat()).sum::<u64>() - transaction.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+    let fee = Nat::from(fee_sats);
+    auth::authorize_signer(Some(owner), DelegationScope::BtcTransfer, Some(fee.clone()))?;
+    *charged = Nat(&charged.0 + &fee.0);
     
     // Sign transaction
     let derivation_path = vec![owner.as_slice().to_vec()];
@@ -497,34 +595,334 @@ pub async fn transfer_btc(request: BtcTransferRequest) -> Result<BtcTransferResp
     
     // Send transaction
     let tx_bytes = serialize(&signed_transaction);
+    *may_be_sent = true;
+    match send_raw_transaction(tx_bytes.clone()).await {
+        Ok(_) => {
+            let txid = signed_transaction.compute_txid().to_string();
+            if replaceable {
+                store_sent_tx(&SentBtcTx {
+                    txid: txid.clone(),
+                    owner,
+                    destination_address: request.destination_address,
+                    amount_in_satoshi: request.amount_in_satoshi,
+                    fee_sats,
+                    fee_rate: fee_per_vbyte,
+                    inputs,
+                    signed_tx: tx_bytes,
+                    created_at_ns: ic_cdk::api::time(),
+                    replaces: None,
+                    replaced_by: None,
+                });
+            }
+            Ok(BtcTransferResponse {
+                success: true,
+                transaction_id: Some(txid),
+                error: None,
+            })
+        }
+        Err(e) => {
+            *may_be_sent = !is_definite_reject(e.0);
+            Ok(BtcTransferResponse {
+                success: false,
+                transaction_id: None,
+                error: Some(format!("Failed to send transaction: {:?}", e)),
+            })
+        }
+    }
+}
+
+// Penolakan pasti: tx tidak pernah diteruskan ke jaringan. SysUnknown (dan kode lain) = hasil tidak pasti
+fn is_definite_reject(code: RejectionCode) -> bool {
+    matches!(code, RejectionCode::SysTransient | RejectionCode::DestinationInvalid | RejectionCode::CanisterReject | RejectionCode::CanisterError)
+}
+
+async fn send_raw_transaction(tx_bytes: Vec<u8>) -> Result<(), (RejectionCode, String)> {
     // Lampirkan cycles yang cukup (mainnet send_transaction: 5B + 20M per byte)
     let cycles: u128 = 5_000_000_000 + (tx_bytes.len() as u128 * 20_000_000);
     
-    let send_result: Result<(), String> = call_with_payment128(
+    call_with_payment128(
         Principal::management_canister(),
         "bitcoin_send_transaction",
         (SendTransactionRequest {
-            network: IC_BTC_NETWORK,
+            network: config::ic_bitcoin_network(),
             transaction: tx_bytes,
         },),
         cycles,
     )
     .await
-    .map_err(|e| format!("bitcoin_send_transaction failed: {:?}", e));
-    
-    match send_result {
-        Ok(_) => {
-            let txid = signed_transaction.compute_txid().to_string();
-            Ok(BtcTransferResponse {
-                success: true,
-                transaction_id: Some(txid),
-                error: None,
-            })
+}
+
+// -------- Replace-by-fee --------
+
+#[derive(Clone, Copy)]
+enum Replacement {
+    BumpFee(MillisatoshiPerByte),
+    Cancel,
+}
+
+// Record tx untuk owner atau delegasinya. Caller lain mendapat NotFound yang sama dengan txid tak dikenal
+// (alasan sebenarnya tetap tercatat di audit log) supaya keberadaan txid tidak bisa ditebak.
+fn load_authorized_sent_tx(txid: &str) -> Result<SentBtcTx, FintrackError> {
+    let not_found = || FintrackError::not_found("btc transaction", txid);
+    let tx = load_sent_tx(txid).ok_or_else(not_found)?;
+    auth::authorize_signer(Some(tx.owner), DelegationScope::BtcTransfer, None).map_err(|_| not_found())?;
+    Ok(tx)
+}
+
+/// Transfer RBF yang tercatat (owner atau delegasinya). Update call supaya penolakan tercatat di audit log.
+pub fn get_sent_transaction(txid: String) -> Result<SentBtcTx, FintrackError> {
+    load_authorized_sent_tx(&txid)
+}
+
+/// Bangun ulang transfer RBF dengan fee rate lebih tinggi (msat/vB); tujuan dan amount tetap, kembalian berkurang
+pub async fn bump_fee(txid: String, new_fee_rate: MillisatoshiPerByte) -> Result<BtcTransferResponse, FintrackError> {
+    replace_transaction(txid, Replacement::BumpFee(new_fee_rate)).await
+}
+
+/// Batalkan transfer RBF: input yang sama dibelanjakan ulang ke alamat owner sendiri dengan fee lebih tinggi
+pub async fn cancel(txid: String) -> Result<BtcTransferResponse, FintrackError> {
+    replace_transaction(txid, Replacement::Cancel).await
+}
+
+async fn replace_transaction(txid: String, mode: Replacement) -> Result<BtcTransferResponse, FintrackError> {
+    // Otorisasi sebelum guard dan outcall: caller asing tidak bisa menahan guard atau membakar cycles
+    load_authorized_sent_tx(&txid)?;
+    // Dipegang sampai record pengganti tersimpan: bump/cancel paralel atas tx yang sama tidak boleh broadcast dua kali
+    let _guard = EntityGuard::acquire("btc_tx", &txid)?;
+    let original = load_sent_tx(&txid).ok_or_else(|| FintrackError::not_found("btc transaction", txid.clone()))?;
+    if let Some(next) = &original.replaced_by {
+        return Err(FintrackError::invalid_state(format!("transaction was already replaced by {}", next)));
+    }
+
+    // Rate dari user dibatasi terhadap kondisi jaringan; cancel menurunkan rate sendiri dari tx lama
+    let max_fee_rate = match mode {
+        Replacement::BumpFee(_) => max_bump_fee_rate().await?,
+        Replacement::Cancel => MillisatoshiPerByte::MAX,
+    };
+    let plan = plan_replacement(&original, mode, max_fee_rate)?;
+
+    // Fee tambahan dibebankan ke spend cap delegasi (setelah cek fee cap), sama seperti transfer
+    let delegate = ic_cdk::api::caller();
+    let extra = Nat::from(plan.fee - original.fee_sats);
+    let owner = auth::authorize_signer(Some(original.owner), DelegationScope::BtcTransfer, Some(extra.clone()))?;
+    let result = broadcast_replacement(owner, original, plan.fee_rate, plan.fee, plan.amount).await;
+    if !matches!(result, Ok(BtcTransferResponse { success: true, .. })) {
+        auth::release_spend(owner, delegate, DelegationScope::BtcTransfer, &extra);
+    }
+    result
+}
+
+// Batas atas rate bump: MAX_FEE_RATE_MULTIPLIER x persentil ke-90 fee jaringan saat ini
+async fn max_bump_fee_rate() -> Result<MillisatoshiPerByte, FintrackError> {
+    let fee_percentiles = get_current_fee_percentiles().await?;
+    let p90 = fee_percentiles.get(90).copied().unwrap_or(2500).max(MIN_RELAY_FEE_RATE);
+    Ok(p90.saturating_mul(MAX_FEE_RATE_MULTIPLIER))
+}
+
+// Fee dan output tx pengganti; amount None = seluruh input dikurangi fee kembali ke owner
+#[derive(Debug, PartialEq)]
+struct ReplacementPlan {
+    fee_rate: MillisatoshiPerByte,
+    fee: u64,
+    amount: Option<Satoshi>,
+}
+
+// Hitung fee pengganti tanpa I/O. Fee harus lebih tinggi dari rate lama dan membayar relay ukuran tx baru
+// (BIP125 aturan 3 & 4); output yang tersisa tidak boleh dust.
+fn plan_replacement(original: &SentBtcTx, mode: Replacement, max_fee_rate: MillisatoshiPerByte) -> Result<ReplacementPlan, FintrackError> {
+    let overflow = || FintrackError::invalid_input("new_fee_rate", "fee calculation overflowed");
+    let total_in = original.inputs.iter().try_fold(0u64, |acc, i| acc.checked_add(i.value)).ok_or_else(overflow)?;
+    let fee_rate = match mode {
+        Replacement::BumpFee(rate) if rate <= original.fee_rate => {
+            return Err(FintrackError::invalid_input("new_fee_rate", format!("must be higher than the current fee rate {}", original.fee_rate)));
+        }
+        Replacement::BumpFee(rate) if rate > max_fee_rate => {
+            return Err(FintrackError::invalid_input("new_fee_rate", format!("must not exceed {} ({}x the 90th percentile network fee rate)", max_fee_rate, MAX_FEE_RATE_MULTIPLIER)));
+        }
+        Replacement::BumpFee(rate) => rate,
+        // Tanpa rate dari user: naikkan 25%, minimal satu incremental relay fee; bisa di-bump lagi setelahnya
+        Replacement::Cancel => {
+            let raised = original.fee_rate.checked_mul(5).ok_or_else(overflow)? / 4;
+            raised.max(original.fee_rate.checked_add(MIN_RELAY_FEE_RATE).ok_or_else(overflow)?)
+        }
+    };
+
+    // Tx pembatalan (amount 0) yang di-bump tetap mengembalikan semua input ke owner
+    let to_self = matches!(mode, Replacement::Cancel) || original.amount_in_satoshi == 0;
+    let size = estimate_size_for(original.inputs.len(), if to_self { 1 } else { 2 }) as u64;
+    let by_rate = size.checked_mul(fee_rate).ok_or_else(overflow)? / 1000;
+    let by_relay = original.fee_sats.checked_add(size * MIN_RELAY_FEE_RATE / 1000).ok_or_else(overflow)?;
+    let fee = by_rate.max(by_relay);
+
+    if to_self {
+        if total_in < fee.saturating_add(DUST_THRESHOLD_SATS) {
+            return Err(FintrackError::InsufficientFunds { needed: Nat::from(fee.saturating_add(DUST_THRESHOLD_SATS)), available: Nat::from(total_in) });
         }
-        Err(e) => Ok(BtcTransferResponse {
+        return Ok(ReplacementPlan { fee_rate, fee, amount: None });
+    }
+    let needed = original.amount_in_satoshi.checked_add(fee).ok_or_else(overflow)?;
+    if total_in < needed {
+        return Err(FintrackError::InsufficientFunds { needed: Nat::from(needed), available: Nat::from(total_in) });
+    }
+    let change = total_in - needed;
+    if change > 0 && change < DUST_THRESHOLD_SATS {
+        return Err(FintrackError::invalid_input("new_fee_rate", format!("would leave a change output of {} sats, below the dust limit of {} sats", change, DUST_THRESHOLD_SATS)));
+    }
+    Ok(ReplacementPlan { fee_rate, fee, amount: Some(original.amount_in_satoshi) })
+}
+
+// `amount` None = kirim seluruh input dikurangi fee kembali ke alamat owner
+async fn broadcast_replacement(owner: Principal, original: SentBtcTx, fee_rate: MillisatoshiPerByte, fee: u64, amount: Option<Satoshi>) -> Result<BtcTransferResponse, FintrackError> {
+    let public_key_bytes = get_ecdsa_public_key(owner).await?;
+    let own_public_key = PublicKey::from_slice(&public_key_bytes)
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    let own_address = Address::p2wpkh(
+        &bitcoin::key::CompressedPublicKey::from_slice(&public_key_bytes)
+            .map_err(|e| format!("Invalid compressed public key: {}", e))?,
+        config::btc_network(),
+    );
+    let dst_address = match amount {
+        None => own_address.clone(),
+        Some(_) => parse_btc_address(&original.destination_address)?,
+    };
+    let total_in: u64 = original.inputs.iter().map(|i| i.value).sum();
+
+    // Input yang sudah tidak ada di UTXO set berarti tx lama sudah terkonfirmasi
+    let own_utxos = get_utxos_for_address(own_address.to_string()).await?;
+    let unspent = original.inputs.iter().all(|i| own_utxos.iter().any(|u| u.outpoint.txid == i.txid && u.outpoint.vout == i.vout));
+    if !unspent {
+        return Err(FintrackError::invalid_state("transaction is already confirmed and cannot be replaced"));
+    }
+
+    let send_amount = amount.unwrap_or(total_in - fee);
+    let (transaction, prevouts) = build_transaction_simple(&original.inputs, &own_address, &dst_address, send_amount, fee, true)?;
+    let derivation_path = vec![owner.as_slice().to_vec()];
+    let signed_transaction = sign_transaction(&own_public_key, &own_address, transaction, &prevouts, derivation_path).await?;
+
+    let tx_bytes = serialize(&signed_transaction);
+    if let Err(e) = send_raw_transaction(tx_bytes.clone()).await {
+        return Ok(BtcTransferResponse {
             success: false,
             transaction_id: None,
             error: Some(format!("Failed to send transaction: {:?}", e)),
-        }),
+        });
+    }
+
+    let txid = signed_transaction.compute_txid().to_string();
+    store_sent_tx(&SentBtcTx {
+        txid: txid.clone(),
+        owner,
+        destination_address: dst_address.to_string(),
+        amount_in_satoshi: amount.unwrap_or(0),
+        fee_sats: fee,
+        fee_rate,
+        inputs: original.inputs.clone(),
+        signed_tx: tx_bytes,
+        created_at_ns: ic_cdk::api::time(),
+        replaces: Some(original.txid.clone()),
+        replaced_by: None,
+    });
+    store_sent_tx(&SentBtcTx { replaced_by: Some(txid.clone()), ..original });
+    Ok(BtcTransferResponse {
+        success: true,
+        transaction_id: Some(txid),
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent_tx(inputs: &[u64], amount: u64, fee_sats: u64, fee_rate: MillisatoshiPerByte) -> SentBtcTx {
+        SentBtcTx {
+            txid: "00".repeat(32),
+            owner: Principal::anonymous(),
+            destination_address: String::new(),
+            amount_in_satoshi: amount,
+            fee_sats,
+            fee_rate,
+            inputs: inputs.iter().enumerate().map(|(i, v)| SentBtcInput { txid: vec![i as u8; 32], vout: 0, value: *v }).collect(),
+            signed_tx: Vec::new(),
+            created_at_ns: 0,
+            replaces: None,
+            replaced_by: None,
+        }
+    }
+
+    fn is_invalid_input(r: &Result<ReplacementPlan, FintrackError>) -> bool {
+        matches!(r, Err(FintrackError::InvalidInput { .. }))
+    }
+
+    fn is_insufficient(r: &Result<ReplacementPlan, FintrackError>) -> bool {
+        matches!(r, Err(FintrackError::InsufficientFunds { .. }))
+    }
+
+    #[test]
+    fn bump_pays_old_fee_plus_relay_for_new_size() {
+        let original = sent_tx(&[50_000, 50_000], 60_000, 752, 2_000);
+        let size = estimate_size_for(2, 2) as u64;
+
+        // Rate sedikit di atas rate lama: aturan 4 (fee lama + 1 sat/vB ukuran baru) yang menentukan
+        let plan = plan_replacement(&original, Replacement::BumpFee(2_100), u64::MAX).unwrap();
+        assert_eq!(plan, ReplacementPlan { fee_rate: 2_100, fee: 752 + size, amount: Some(60_000) });
+
+        // Rate tinggi: fee mengikuti rate, tetap di atas minimum BIP125
+        let plan = plan_replacement(&original, Replacement::BumpFee(10_000), u64::MAX).unwrap();
+        assert_eq!(plan.fee, size * 10);
+        assert!(plan.fee >= original.fee_sats + size);
+    }
+
+    #[test]
+    fn bump_rate_must_be_higher_and_capped() {
+        let original = sent_tx(&[50_000, 50_000], 60_000, 752, 2_000);
+        assert!(is_invalid_input(&plan_replacement(&original, Replacement::BumpFee(2_000), u64::MAX)));
+        assert!(is_invalid_input(&plan_replacement(&original, Replacement::BumpFee(25_001), 25_000)));
+        assert!(plan_replacement(&original, Replacement::BumpFee(25_000), 25_000).is_ok());
+        // Perkalian size x rate yang overflow ditolak sebagai input tidak valid, bukan panic
+        assert!(is_invalid_input(&plan_replacement(&original, Replacement::BumpFee(u64::MAX), u64::MAX)));
+    }
+
+    #[test]
+    fn cancel_sends_everything_back_to_self() {
+        let original = sent_tx(&[50_000, 50_000], 60_000, 752, 2_000);
+        let size = estimate_size_for(2, 1) as u64;
+        // Rate cancel: max(125%, +1 sat/vB) = 3 sat/vB; aturan 4 tetap lebih tinggi
+        let plan = plan_replacement(&original, Replacement::Cancel, MillisatoshiPerByte::MAX).unwrap();
+        assert_eq!(plan, ReplacementPlan { fee_rate: 3_000, fee: 752 + size, amount: None });
+
+        // Bump atas tx pembatalan tetap ke alamat sendiri
+        let cancelled = sent_tx(&[50_000, 50_000], 0, plan.fee, plan.fee_rate);
+        let bumped = plan_replacement(&cancelled, Replacement::BumpFee(5_000), u64::MAX).unwrap();
+        assert_eq!(bumped, ReplacementPlan { fee_rate: 5_000, fee: size * 5, amount: None });
+    }
+
+    #[test]
+    fn insufficient_funds_and_dust_boundaries() {
+        // 1 input, 2 output; bump ke 2 sat/vB: fee = max(2 x size, fee lama + size) = 2 x size
+        let size = estimate_size_for(1, 2) as u64;
+        let fee = 2 * size;
+        let with_input = |value: u64| plan_replacement(&sent_tx(&[value], 9_000, size, 1_000), Replacement::BumpFee(2_000), u64::MAX);
+
+        assert_eq!(with_input(9_000 + fee).unwrap().fee, fee); // tanpa change
+        assert!(is_insufficient(&with_input(9_000 + fee - 1)));
+        assert!(is_invalid_input(&with_input(9_000 + fee + 1))); // change 1 sat = dust
+        assert!(is_invalid_input(&with_input(9_000 + fee + DUST_THRESHOLD_SATS - 1)));
+        assert!(with_input(9_000 + fee + DUST_THRESHOLD_SATS).is_ok());
+
+        // Cancel: sisa setelah fee harus minimal batas dust
+        let size = estimate_size_for(1, 1) as u64;
+        let fee = size + size; // fee lama + relay
+        let cancel = |value: u64| plan_replacement(&sent_tx(&[value], 500, size, 1_000), Replacement::Cancel, MillisatoshiPerByte::MAX);
+        assert!(cancel(fee + DUST_THRESHOLD_SATS).is_ok());
+        assert!(is_insufficient(&cancel(fee + DUST_THRESHOLD_SATS - 1)));
+    }
+
+    #[test]
+    fn only_definite_rejects_release_spend() {
+        assert!(is_definite_reject(RejectionCode::CanisterReject));
+        assert!(is_definite_reject(RejectionCode::SysTransient));
+        assert!(!is_definite_reject(RejectionCode::SysUnknown));
+        assert!(!is_definite_reject(RejectionCode::SysFatal));
     }
 }
@@ -2,8 +2,17 @@ use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_cdk::{caller};
 use ic_cdk::api::time;
 use ic_cdk_timers::{clear_timer, TimerId};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
 use std::collections::BTreeMap;
+use super::errors::FintrackError;
+use super::escrow_audit::{self, EscrowBooks};
+use super::guard::{self, EntityGuard};
+use super::journal::{self, EntityKind, LedgerCall, NewOperation, OperationKind, PendingOperation};
+use super::storage::{self, StableText, Versioned, VMem};
 use super::timers;
+use super::vesting::{self, Ratio, NS_PER_DAY};
+pub use super::vesting::{VestingSchedule, VestingTranche};
 
 // Budgeting with hard-lock (escrow under canister principal) and daily unlock based on calendar days in month.
 
@@ -24,6 +33,24 @@ pub struct BudgetCreateRequest {
     pub amount_to_lock: Nat, // jumlah dana yang dikunci untuk periode (base units)
     pub period_start_ns: u64, // awal periode vesting (dan jadwal lock terjadi)
     pub period_end_ns: u64,   // akhir periode vesting
+    pub recurrence: Option<BudgetRecurrence>, // None = satu periode saja
+    pub rollover: Option<RolloverPolicy>,     // None = Keep
+    pub envelopes: Option<Vec<EnvelopeSpec>>, // None = satu bucket tanpa envelope
+    pub vesting: Option<VestingSchedule>,     // None = Linear
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RecurrenceRule {
+    Weekly,                   // setiap 7 hari sejak periode pertama
+    MonthlyByDay { day: u8 }, // tanggal kalender UTC (1..=31, di-clamp ke akhir bulan)
+    Interval { every_ns: u64 },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetRecurrence {
+    pub rule: RecurrenceRule,
+    pub end_at_ns: Option<u64>,       // tidak ada periode baru yang dimulai pada/setelah waktu ini
+    pub max_occurrences: Option<u32>, // total periode, termasuk periode pertama
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -31,6 +58,47 @@ pub struct BudgetUpdateRequest {
     pub name: Option<String>,
     pub amount_to_lock: Option<Nat>,
     pub status: Option<BudgetStatus>,
+    pub rollover: Option<RolloverPolicy>,
+}
+
+// Nasib surplus (available_to_withdraw yang belum ditarik) saat periode berakhir
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RolloverPolicy {
+    Keep,                             // tetap di escrow, bisa ditarik kapan saja
+    CarryOver,                        // mengurangi transfer_from pada lock periode berikutnya
+    SweepToGoal { goal_id: String },  // dipindah ke escrow goal (asset & owner sama)
+    Refund,                           // dikembalikan ke akun owner
+}
+
+// Pembagian satu lock periode ke envelope: Fixed diambil dulu, sisanya dibagi ke Percent
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EnvelopeAllocation {
+    Percent { bps: u16 }, // basis point dari sisa setelah Fixed (total Percent = 10000)
+    Fixed { amount: Nat },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeSpec {
+    pub name: String,
+    pub category_id: Option<String>,
+    pub allocation: EnvelopeAllocation,
+}
+
+// Sub-ledger di dalam escrow budget: vesting dan withdraw per envelope,
+// field agregat BudgetInfo selalu = jumlah seluruh envelope
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub id: String, // "env-{index}", unik dalam satu budget
+    pub name: String,
+    pub category_id: Option<String>,
+    pub allocation: EnvelopeAllocation,
+    pub period_locked: Nat,
+    pub unlocked_so_far: Nat,
+    pub locked_balance: Nat,
+    pub available_to_withdraw: Nat,
+    pub funded_total: Nat,    // akumulasi semua lock (lintas periode)
+    pub withdrawn_total: Nat, // akumulasi withdraw (termasuk fee ledger)
+    pub spent_total: Option<Nat>, // akumulasi budget_spend (termasuk fee ledger); None = belum pernah
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -53,6 +121,75 @@ pub struct BudgetInfo {
     pub period_end_ns: u64,
     pub period_locked: Nat,
     pub unlocked_so_far: Nat,
+    // Recurrence: period_* di atas = periode ke-`period_index` (0-based);
+    // jadwal periode berikutnya dihitung dari periode pertama agar tidak drift
+    pub recurrence: Option<BudgetRecurrence>,
+    pub period_index: u32,
+    pub first_period_start_ns: u64,
+    pub first_period_end_ns: u64,
+    // Rollover (v3): field opt, jadi payload v2 tetap terbaca apa adanya
+    pub rollover: Option<RolloverPolicy>,
+    pub carry_over: Option<Nat>, // surplus yang menunggu dipakai lock berikutnya (tetap di escrow)
+    // Envelope (v4): None = budget tanpa envelope
+    pub envelopes: Option<Vec<Envelope>>,
+    // Pembayaran ke pihak ketiga lewat budget_spend (v5), terpisah dari withdraw ke owner
+    pub spent_total: Option<Nat>,
+    pub vesting: Option<VestingSchedule>, // v6, None = Linear
+}
+
+// Skema v1 (sebelum recurrence), dibekukan untuk migrasi
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct BudgetInfoV1 {
+    id: String,
+    owner: Principal,
+    asset_canister: Principal,
+    name: String,
+    amount_to_lock: Nat,
+    locked_balance: Nat,
+    decimals: u32,
+    available_to_withdraw: Nat,
+    status: BudgetStatus,
+    next_lock_at_ns: u64,
+    next_unlock_at_ns: u64,
+    created_at_ns: u64,
+    updated_at_ns: u64,
+    period_start_ns: u64,
+    period_end_ns: u64,
+    period_locked: Nat,
+    unlocked_so_far: Nat,
+}
+
+impl From<BudgetInfoV1> for BudgetInfo {
+    fn from(v1: BudgetInfoV1) -> Self {
+        BudgetInfo {
+            id: v1.id,
+            owner: v1.owner,
+            asset_canister: v1.asset_canister,
+            name: v1.name,
+            amount_to_lock: v1.amount_to_lock,
+            locked_balance: v1.locked_balance,
+            decimals: v1.decimals,
+            available_to_withdraw: v1.available_to_withdraw,
+            status: v1.status,
+            next_lock_at_ns: v1.next_lock_at_ns,
+            next_unlock_at_ns: v1.next_unlock_at_ns,
+            created_at_ns: v1.created_at_ns,
+            updated_at_ns: v1.updated_at_ns,
+            period_start_ns: v1.period_start_ns,
+            period_end_ns: v1.period_end_ns,
+            period_locked: v1.period_locked,
+            unlocked_so_far: v1.unlocked_so_far,
+            recurrence: None,
+            period_index: 0,
+            first_period_start_ns: v1.period_start_ns,
+            first_period_end_ns: v1.period_end_ns,
+            rollover: None,
+            carry_over: None,
+            envelopes: None,
+            spent_total: None,
+            vesting: None,
+        }
+    }
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -61,15 +198,90 @@ pub enum AssetKind {
     CkEth,
 }
 
-#[derive(Clone, Debug)]
-struct BudgetInternal {
-    info: BudgetInfo,
+// Timer hanya hidup di heap; di-arm ulang pada post_upgrade
+#[derive(Clone, Debug, Default)]
+struct BudgetTimers {
     lock_timer: Option<TimerId>,
     unlock_timer: Option<TimerId>,
 }
 
+// Versi skema record yang disimpan. Saat field BudgetInfo/BudgetEvent berubah:
+// naikkan versi, bekukan struct lama (mis. BudgetInfoV1) dan tambahkan cabang migrasi
+// di decode_budget/decode_budget_event. post_upgrade menulis ulang record lama ke versi terbaru.
+const BUDGET_RECORD_VERSION: u16 = 6;
+const BUDGET_EVENT_RECORD_VERSION: u16 = 3;
+
 thread_local! {
-    static BUDGETS: std::cell::RefCell<BTreeMap<String, BudgetInternal>> = std::cell::RefCell::new(BTreeMap::new());
+    // key = budget id
+    static BUDGETS: RefCell<StableBTreeMap<StableText, Versioned, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::BUDGETS_MEMORY_ID))
+    );
+    static BUDGET_TIMERS: RefCell<BTreeMap<String, BudgetTimers>> = RefCell::new(BTreeMap::new());
+}
+
+fn decode_budget(rec: &Versioned) -> Result<BudgetInfo, String> {
+    match rec.version {
+        1 => rec.decode::<BudgetInfoV1>().map(BudgetInfo::from),
+        2..=6 => rec.decode::<BudgetInfo>(),
+        v => Err(format!("unknown budget record version {}", v)),
+    }
+}
+
+fn load_budget(id: &str) -> Option<BudgetInfo> {
+    let rec = BUDGETS.with(|b| b.borrow().get(&StableText(id.to_string())))?;
+    match decode_budget(&rec) {
+        Ok(info) => Some(info),
+        Err(e) => { ic_cdk::println!("budget {}: {}", id, e); None }
+    }
+}
+
+fn store_budget(info: &BudgetInfo) {
+    BUDGETS.with(|b| b.borrow_mut().insert(StableText(info.id.clone()), Versioned::encode(BUDGET_RECORD_VERSION, info)));
+}
+
+fn all_budgets() -> Vec<BudgetInfo> {
+    BUDGETS.with(|b| {
+        b.borrow().iter()
+            .filter_map(|(k, rec)| match decode_budget(&rec) {
+                Ok(info) => Some(info),
+                Err(e) => { ic_cdk::println!("budget {}: {}", k.0, e); None }
+            })
+            .collect()
+    })
+}
+
+// Jalankan mutasi pada satu budget lalu tulis balik ke stable memory
+fn with_budget_mut<R>(id: &str, f: impl FnOnce(&mut BudgetInfo) -> R) -> Option<R> {
+    let mut info = load_budget(id)?;
+    let out = f(&mut info);
+    store_budget(&info);
+    Some(out)
+}
+
+fn set_lock_timer(id: &str, timer: Option<TimerId>) {
+    BUDGET_TIMERS.with(|t| t.borrow_mut().entry(id.to_string()).or_default().lock_timer = timer);
+}
+
+fn set_unlock_timer(id: &str, timer: Option<TimerId>) {
+    let old = BUDGET_TIMERS.with(|t| std::mem::replace(&mut t.borrow_mut().entry(id.to_string()).or_default().unlock_timer, timer));
+    if let Some(t) = old { let _ = clear_timer(t); }
+}
+
+fn take_lock_timer(id: &str) -> Option<TimerId> {
+    BUDGET_TIMERS.with(|t| t.borrow_mut().get_mut(id).and_then(|bt| bt.lock_timer.take()))
+}
+
+fn clear_budget_timers(id: &str) {
+    let timers = BUDGET_TIMERS.with(|t| t.borrow_mut().remove(id)).unwrap_or_default();
+    if let Some(t) = timers.lock_timer { let _ = clear_timer(t); }
+    if let Some(t) = timers.unlock_timer { let _ = clear_timer(t); }
+}
+
+// Hapus budget beserta history event dan timer-nya
+fn remove_budget(id: &str) {
+    BUDGETS.with(|b| b.borrow_mut().remove(&StableText(id.to_string())));
+    remove_events(id);
+    clear_budget_timers(id);
 }
 
 // -------- History events --------
@@ -79,6 +291,12 @@ pub enum BudgetEventKind {
     LockFailed,
     Withdraw,
     PeriodCompleted,
+    RolloverCarried,
+    RolloverSwept,
+    RolloverRefunded,
+    RolloverFailed,
+    Spend,
+    PayoutReverted, // withdraw/spend/refund delete yang ditolak ledger saat di-retry reconciler, saldo tetap di budget
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -87,20 +305,324 @@ pub struct BudgetEvent {
     pub kind: BudgetEventKind,
     pub amount: Option<Nat>,
     pub note: Option<String>,
+    pub period_index: Option<u32>, // periode yang bersangkutan (None: event v1)
+    pub counterparty: Option<Account>, // penerima dana (Spend)
+    pub category_id: Option<String>,   // kategori pengeluaran (Spend)
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct BudgetEventV1 {
+    at_time_ns: u64,
+    kind: BudgetEventKind,
+    amount: Option<Nat>,
+    note: Option<String>,
+}
+
+impl From<BudgetEventV1> for BudgetEvent {
+    fn from(v1: BudgetEventV1) -> Self {
+        BudgetEvent { at_time_ns: v1.at_time_ns, kind: v1.kind, amount: v1.amount, note: v1.note, period_index: None, counterparty: None, category_id: None }
+    }
 }
 
 thread_local! {
-    static EVENTS: std::cell::RefCell<BTreeMap<String, Vec<BudgetEvent>>> = std::cell::RefCell::new(BTreeMap::new());
+    // key = "{budget_id}|{seq:020}" -> (budget id, seq) berurutan dalam range scan
+    static EVENTS: RefCell<StableBTreeMap<StableText, Versioned, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::BUDGET_EVENTS_MEMORY_ID))
+    );
+}
+
+fn decode_budget_event(rec: &Versioned) -> Result<BudgetEvent, String> {
+    match rec.version {
+        1 => rec.decode::<BudgetEventV1>().map(BudgetEvent::from),
+        2 | 3 => rec.decode::<BudgetEvent>(),
+        v => Err(format!("unknown budget event record version {}", v)),
+    }
+}
+
+fn event_prefix(id: &str) -> String { format!("{}|", id) }
+
+fn load_events(id: &str) -> Vec<BudgetEvent> {
+    let prefix = event_prefix(id);
+    EVENTS.with(|e| {
+        e.borrow().range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .filter_map(|(_, rec)| decode_budget_event(&rec).ok())
+            .collect()
+    })
 }
 
 fn push_event(id: &str, ev: BudgetEvent) {
+    let prefix = event_prefix(id);
     EVENTS.with(|e| {
         let mut map = e.borrow_mut();
-        map.entry(id.to_string()).or_default().push(ev);
+        let seq = map.range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .count();
+        map.insert(StableText(format!("{}{:020}", prefix, seq)), Versioned::encode(BUDGET_EVENT_RECORD_VERSION, &ev));
     });
 }
 
-// daily unlock computation no longer used (linear vesting)
+fn remove_events(id: &str) {
+    let prefix = event_prefix(id);
+    EVENTS.with(|e| {
+        let mut map = e.borrow_mut();
+        let keys: Vec<StableText> = map.range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .map(|(k, _)| k)
+            .collect();
+        for k in keys { map.remove(&k); }
+    });
+}
+
+fn budget_event(bi: &BudgetInfo, kind: BudgetEventKind, amount: Option<Nat>, note: Option<String>) -> BudgetEvent {
+    BudgetEvent { at_time_ns: time(), kind, amount, note, period_index: Some(bi.period_index), counterparty: None, category_id: None }
+}
+
+// -------- Recurrence --------
+
+// Batas periode ke-`index` menurut aturan recurrence (index 0 = periode pertama)
+fn period_bounds(bi: &BudgetInfo, index: u32) -> (u64, u64) {
+    let (start0, end0) = (bi.first_period_start_ns, bi.first_period_end_ns);
+    let Some(rec) = bi.recurrence.as_ref() else { return (start0, end0) };
+    match rec.rule {
+        RecurrenceRule::Weekly => {
+            let step = (7 * timers::SEC_PER_DAY * timers::NS_PER_SEC).saturating_mul(index as u64);
+            (start0.saturating_add(step), end0.saturating_add(step))
+        }
+        RecurrenceRule::Interval { every_ns } => {
+            let step = every_ns.saturating_mul(index as u64);
+            (start0.saturating_add(step), end0.saturating_add(step))
+        }
+        RecurrenceRule::MonthlyByDay { day } => {
+            if index == 0 { return (start0, end0); }
+            (
+                timers::add_months_at_day(start0, index, day as u32),
+                timers::add_months_at_day(end0, index, timers::day_of_month(end0)),
+            )
+        }
+    }
+}
+
+// Periode berikutnya (index, start, end) selama masih dalam batas end_at_ns / max_occurrences
+fn next_period(bi: &BudgetInfo) -> Option<(u32, u64, u64)> {
+    let rec = bi.recurrence.as_ref()?;
+    let next = bi.period_index.checked_add(1)?;
+    if rec.max_occurrences.map(|m| next >= m).unwrap_or(false) { return None; }
+    let (start, end) = period_bounds(bi, next);
+    if rec.end_at_ns.map(|e| start >= e).unwrap_or(false) { return None; }
+    Some((next, start.max(bi.period_end_ns), end))
+}
+
+fn validate_recurrence(bi: &BudgetInfo) -> Result<(), FintrackError> {
+    let Some(rec) = bi.recurrence.as_ref() else { return Ok(()) };
+    match rec.rule {
+        RecurrenceRule::MonthlyByDay { day } if day == 0 || day > 31 => {
+            return Err(FintrackError::invalid_input("recurrence.rule", "day must be between 1 and 31"));
+        }
+        RecurrenceRule::Interval { every_ns } if every_ns == 0 => {
+            return Err(FintrackError::invalid_input("recurrence.rule", "every_ns must be greater than zero"));
+        }
+        _ => {}
+    }
+    if rec.max_occurrences == Some(0) { return Err(FintrackError::invalid_input("recurrence.max_occurrences", "must be at least 1")); }
+    if rec.end_at_ns.map(|e| e <= bi.first_period_start_ns).unwrap_or(false) {
+        return Err(FintrackError::invalid_input("recurrence.end_at_ns", "must be after period_start_ns"));
+    }
+    // Periode tidak boleh tumpang tindih: periode berikutnya mulai setelah periode ini selesai vesting
+    if period_bounds(bi, 1).0 < bi.first_period_end_ns {
+        return Err(FintrackError::invalid_input("recurrence.rule", "period is longer than the recurrence interval"));
+    }
+    Ok(())
+}
+
+// -------- Envelopes --------
+
+const MAX_ENVELOPES: usize = 20;
+const BPS_TOTAL: u32 = 10_000;
+
+fn validate_envelopes(specs: &[EnvelopeSpec], owner: &Principal, amount_to_lock: &Nat) -> Result<(), FintrackError> {
+    if specs.is_empty() { return Err(FintrackError::invalid_input("envelopes", "must not be empty (omit it for a budget without envelopes)")); }
+    if specs.len() > MAX_ENVELOPES { return Err(FintrackError::invalid_input("envelopes", format!("at most {} envelopes", MAX_ENVELOPES))); }
+    let mut fixed_sum = num::BigUint::from(0u32);
+    let mut bps_sum: u32 = 0;
+    let mut has_percent = false;
+    for (i, spec) in specs.iter().enumerate() {
+        if spec.name.trim().is_empty() { return Err(FintrackError::invalid_input(&format!("envelopes[{}].name", i), "must not be empty")); }
+        if let Some(cid) = spec.category_id.as_ref() {
+            if !super::categories::exists(owner, cid) { return Err(FintrackError::not_found("category", cid.clone())); }
+        }
+        match &spec.allocation {
+            EnvelopeAllocation::Percent { bps } => {
+                if *bps == 0 { return Err(FintrackError::invalid_input(&format!("envelopes[{}].allocation", i), "bps must be greater than zero")); }
+                bps_sum += *bps as u32;
+                has_percent = true;
+            }
+            EnvelopeAllocation::Fixed { amount } => {
+                if amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input(&format!("envelopes[{}].allocation", i), "amount must be greater than zero")); }
+                fixed_sum += &amount.0;
+            }
+        }
+    }
+    if has_percent {
+        if bps_sum != BPS_TOTAL { return Err(FintrackError::invalid_input("envelopes", format!("percent allocations must sum to {} bps (got {})", BPS_TOTAL, bps_sum))); }
+        if fixed_sum > amount_to_lock.0 { return Err(FintrackError::invalid_input("envelopes", "fixed allocations exceed amount_to_lock")); }
+    } else if fixed_sum != amount_to_lock.0 {
+        return Err(FintrackError::invalid_input("envelopes", "fixed allocations must sum to amount_to_lock when no percent envelope is given"));
+    }
+    Ok(())
+}
+
+fn build_envelopes(specs: Vec<EnvelopeSpec>) -> Vec<Envelope> {
+    specs.into_iter().enumerate().map(|(i, spec)| Envelope {
+        id: format!("env-{}", i),
+        name: spec.name.trim().to_string(),
+        category_id: spec.category_id,
+        allocation: spec.allocation,
+        period_locked: zero(),
+        unlocked_so_far: zero(),
+        locked_balance: zero(),
+        available_to_withdraw: zero(),
+        funded_total: zero(),
+        withdrawn_total: zero(),
+        spent_total: None,
+    }).collect()
+}
+
+// Bagi `total` ke envelope: Fixed dulu (urut, dibatasi sisa total), sisa ke Percent; dust pembulatan masuk ke Percent terakhir.
+// Jumlah share selalu sama dengan `total`, kecuali tidak ada envelope Percent untuk menampung sisa.
+fn split_allocation(envs: &[Envelope], total: &Nat) -> Vec<Nat> {
+    let mut rest = total.0.clone();
+    let fixed: Vec<Option<Nat>> = envs.iter().map(|e| match &e.allocation {
+        EnvelopeAllocation::Fixed { amount } => {
+            let share = if amount.0 > rest { rest.clone() } else { amount.0.clone() };
+            rest -= &share;
+            Some(Nat(share))
+        }
+        EnvelopeAllocation::Percent { .. } => None,
+    }).collect();
+    let last_percent = envs.iter().rposition(|e| matches!(e.allocation, EnvelopeAllocation::Percent { .. }));
+    let mut percent_given = num::BigUint::from(0u32);
+    let mut shares: Vec<Nat> = envs.iter().zip(fixed).enumerate().map(|(i, (e, fixed))| match &e.allocation {
+        EnvelopeAllocation::Fixed { .. } => fixed.unwrap_or_else(zero),
+        EnvelopeAllocation::Percent { .. } if Some(i) == last_percent => zero(),
+        EnvelopeAllocation::Percent { bps } => {
+            let share = (&rest * *bps as u32) / BPS_TOTAL;
+            percent_given += &share;
+            Nat(share)
+        }
+    }).collect();
+    if let Some(i) = last_percent { shares[i] = Nat(&rest - &percent_given); }
+    shares
+}
+
+// Kosongkan available (agregat + envelope); snapshot per envelope dipakai untuk restore
+fn drain_available(bi: &mut BudgetInfo) -> Vec<(String, Nat)> {
+    bi.available_to_withdraw = zero();
+    bi.envelopes.as_mut().map(|envs| envs.iter_mut().map(|e| (e.id.clone(), std::mem::replace(&mut e.available_to_withdraw, zero()))).collect()).unwrap_or_default()
+}
+
+// Kebalikan drain_available; envelope dicocokkan lewat id
+fn restore_available(bi: &mut BudgetInfo, total: &Nat, per_envelope: &[(String, Nat)]) {
+    bi.available_to_withdraw = Nat(&bi.available_to_withdraw.0 + &total.0);
+    for (eid, amt) in per_envelope {
+        if let Some(e) = bi.envelopes.iter_mut().flatten().find(|e| &e.id == eid) {
+            e.available_to_withdraw = Nat(&e.available_to_withdraw.0 + &amt.0);
+        }
+    }
+}
+
+// Reserve-then-commit payout: potong dari available (agregat + envelope) sebelum await ke ledger
+fn reserve_available(bi: &mut BudgetInfo, envelope_id: Option<&str>, amount: &Nat) {
+    bi.available_to_withdraw = Nat(&bi.available_to_withdraw.0 - &amount.0);
+    if let Some(env) = envelope_id.and_then(|eid| bi.envelopes.iter_mut().flatten().find(|e| e.id == eid)) {
+        env.available_to_withdraw = Nat(&env.available_to_withdraw.0 - &amount.0);
+    }
+}
+
+// Kembalikan reservasi bila fee/transfer gagal
+fn release_reservation(bi: &mut BudgetInfo, envelope_id: Option<&str>, amount: &Nat) {
+    bi.available_to_withdraw = Nat(&bi.available_to_withdraw.0 + &amount.0);
+    if let Some(env) = envelope_id.and_then(|eid| bi.envelopes.iter_mut().flatten().find(|e| e.id == eid)) {
+        env.available_to_withdraw = Nat(&env.available_to_withdraw.0 + &amount.0);
+    }
+}
+
+pub(crate) fn envelopes_by_owner(owner: &Principal, asset: &Principal) -> Vec<Envelope> {
+    all_budgets().into_iter()
+        .filter(|bi| &bi.owner == owner && &bi.asset_canister == asset)
+        .flat_map(|bi| bi.envelopes.unwrap_or_default())
+        .collect()
+}
+
+pub(crate) fn category_in_use(owner: &Principal, category_id: &str) -> bool {
+    all_budgets().iter()
+        .filter(|bi| &bi.owner == owner)
+        .flat_map(|bi| bi.envelopes.iter().flatten())
+        .any(|e| e.category_id.as_deref() == Some(category_id))
+}
+
+// Total Spend per category_id dari event budget milik owner untuk satu asset
+pub(crate) fn spend_by_category(owner: &Principal, asset: &Principal) -> BTreeMap<String, Nat> {
+    let mut out: BTreeMap<String, Nat> = BTreeMap::new();
+    for bi in all_budgets().into_iter().filter(|bi| &bi.owner == owner && &bi.asset_canister == asset) {
+        for ev in load_events(&bi.id) {
+            if ev.kind != BudgetEventKind::Spend { continue; }
+            let (Some(cid), Some(amount)) = (ev.category_id, ev.amount) else { continue };
+            let entry = out.entry(cid).or_insert_with(zero);
+            *entry = Nat(&entry.0 + &amount.0);
+        }
+    }
+    out
+}
+
+// -------- Vesting schedule --------
+
+fn vesting_schedule(bi: &BudgetInfo) -> &VestingSchedule {
+    bi.vesting.as_ref().unwrap_or(&VestingSchedule::Linear)
+}
+
+// Rasio vested periode aktif; tranche selalu relatif ke periode pertama
+fn vested_ratio(bi: &BudgetInfo, elapsed: u64, duration: u64) -> Ratio {
+    vesting::vested_ratio(vesting_schedule(bi), bi.first_period_start_ns, elapsed, duration)
+}
+
+// Accrual periode aktif sampai `now` sesuai vesting schedule (dibatasi max_delta bila ada, berlaku untuk total semua envelope).
+// Return true bila periode baru saja fully vested; budget recurring tetap Active sampai periode terakhir.
+fn accrue(bi: &mut BudgetInfo, now: u64, max_delta: Option<&Nat>) -> bool {
+    let (elapsed, duration) = vesting::elapsed(bi.period_start_ns, bi.period_end_ns, now);
+    let ratio = vested_ratio(bi, elapsed, duration);
+    let mut cap = max_delta.map(|m| m.0.clone());
+    let newly = match bi.envelopes.as_mut() {
+        Some(envs) => {
+            let mut total = num::BigUint::from(0u32);
+            for env in envs.iter_mut() {
+                let n = vesting::vest_delta(&env.period_locked, &env.unlocked_so_far, &env.locked_balance, &ratio, cap.as_ref());
+                if n == num::BigUint::from(0u32) { continue; }
+                env.unlocked_so_far = Nat(&env.unlocked_so_far.0 + &n);
+                vesting::release(&mut env.locked_balance, &mut env.available_to_withdraw, &n);
+                if let Some(c) = cap.as_mut() { *c -= &n; }
+                total += n;
+            }
+            total
+        }
+        None => vesting::vest_delta(&bi.period_locked, &bi.unlocked_so_far, &bi.locked_balance, &ratio, cap.as_ref()),
+    };
+    if newly == num::BigUint::from(0u32) { return false; }
+    bi.unlocked_so_far = Nat(&bi.unlocked_so_far.0 + &newly);
+    vesting::release(&mut bi.locked_balance, &mut bi.available_to_withdraw, &newly);
+    bi.updated_at_ns = now;
+    if vesting::fully_vested(&bi.period_locked, &bi.unlocked_so_far) && bi.status != BudgetStatus::Completed {
+        if next_period(bi).is_none() { bi.status = BudgetStatus::Completed; }
+        return true;
+    }
+    false
+}
+
+fn accrue_and_log(id: &str, bi: &mut BudgetInfo, now: u64, max_delta: Option<&Nat>) {
+    if accrue(bi, now, max_delta) {
+        push_event(id, budget_event(bi, BudgetEventKind::PeriodCompleted, Some(bi.period_locked.clone()), None));
+    }
+}
 
 fn budget_key(owner: &Principal, id: &str) -> String {
     format!("{}::{}", owner, id)
@@ -117,41 +639,12 @@ fn escrow_subaccount(owner: &Principal, budget_id: &str) -> [u8; 32] {
     out
 }
 
-// ICRC-1/2 minimal interfaces we need
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub struct TransferArg {
-    pub from_subaccount: Option<Vec<u8>>,
-    pub to: Account,
-    pub amount: Nat,
-    pub fee: Option<Nat>,
-    pub memo: Option<Vec<u8>>,
-    pub created_at_time: Option<u64>,
-}
-
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub struct TransferFromArg {
-    pub from: Account,
-    pub to: Account,
-    pub amount: Nat,
-    pub fee: Option<Nat>,
-    pub memo: Option<Vec<u8>>,
-    pub created_at_time: Option<u64>,
-    pub spender_subaccount: Option<Vec<u8>>,
-}
-
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Account {
     pub owner: Principal,
     pub subaccount: Option<Vec<u8>>, // 32 bytes if present
 }
 
-async fn icrc2_transfer_from(token: Principal, arg: TransferFromArg) -> Result<Nat, String> {
-    ic_cdk::call::<_, (Result<Nat, String>,)>(token, "icrc2_transfer_from", (arg,))
-        .await
-        .map_err(|e| format!("call failed: {:?}", e))
-        .and_then(|(res,)| res.map_err(|e| e))
-}
-
 // Tidak perlu ambil dari canister; praktis: mapping statik
 fn decimals_for_asset(kind: &AssetKind) -> u32 {
     match kind {
@@ -160,16 +653,9 @@ fn decimals_for_asset(kind: &AssetKind) -> u32 {
     }
 }
 
-async fn icrc1_transfer(token: Principal, arg: TransferArg) -> Result<Nat, String> {
-    ic_cdk::call::<_, (Result<Nat, String>,)>(token, "icrc1_transfer", (arg,))
-        .await
-        .map_err(|e| format!("call failed: {:?}", e))
-        .and_then(|(res,)| res.map_err(|e| e))
-}
-
-async fn icrc1_fee(token: Principal) -> Result<Nat, String> {
+async fn icrc1_fee(token: Principal) -> Result<Nat, FintrackError> {
     let (fee,): (Nat,) = ic_cdk::api::call::call_with_payment128(token, "icrc1_fee", (), 1_000_000).await
-        .map_err(|e| format!("call failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("call failed: {:?}", e)))?;
     Ok(fee)
 }
 
@@ -187,78 +673,264 @@ fn schedule_lock_timer_at(budget_id: String, at_ns: u64) -> TimerId {
     })
 }
 
-// Removed unlock timer for linear vesting
+// Timer akhir periode: vest penuh lalu (recurring) lanjut ke periode berikutnya
+fn schedule_period_end_timer_at(budget_id: String, at_ns: u64) -> TimerId {
+    timers::schedule_at(at_ns, move || {
+        ic_cdk::futures::spawn(async move { handle_period_end(budget_id).await; });
+    })
+}
 
-async fn handle_period_lock(budget_id: String) {
-    // Take old timer id (single-shot); next_lock_at_ns tidak dipakai lagi
-    let old_timer = BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        if let Some(bi) = map.get_mut(&budget_id) {
-            bi.lock_timer.take()
-        } else { None }
-    });
+// Arm timer sesuai state budget (dipakai resume/update/post_upgrade)
+fn arm_budget_timers(bi: &BudgetInfo) {
+    if bi.status != BudgetStatus::Active { return; }
+    if bi.period_locked.0 == num::BigUint::from(0u32) {
+        let lt = schedule_lock_timer_at(bi.id.clone(), bi.next_lock_at_ns);
+        set_lock_timer(&bi.id, Some(lt));
+    } else {
+        let et = schedule_period_end_timer_at(bi.id.clone(), bi.period_end_ns);
+        set_unlock_timer(&bi.id, Some(et));
+    }
+}
 
-    let (owner, asset, amount, escrow) = BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&budget_id).expect("budget not found");
-        (
-            bi.info.owner,
-            bi.info.asset_canister,
-            bi.info.amount_to_lock.clone(),
-            canister_escrow_account(escrow_subaccount(&bi.info.owner, &bi.info.id)),
-        )
-    });
+fn zero() -> Nat { Nat(num::BigUint::from(0u32)) }
+
+fn rollover_policy(bi: &BudgetInfo) -> RolloverPolicy {
+    bi.rollover.clone().unwrap_or(RolloverPolicy::Keep)
+}
+
+fn validate_rollover(policy: &RolloverPolicy, owner: Principal, asset: Principal) -> Result<(), FintrackError> {
+    if let RolloverPolicy::SweepToGoal { goal_id } = policy {
+        super::goals::sweep_target(goal_id, owner, asset)?;
+    }
+    Ok(())
+}
 
-    let from = principal_account(owner);
-    let to = escrow;
+// Kirim surplus dari escrow budget (fee ledger dipotong dari surplus) lewat journal.
+// Seluruh available (termasuk per envelope) dikosongkan sebelum await; dikembalikan bila transfer pasti gagal.
+async fn transfer_surplus(bi: &BudgetInfo, surplus: Nat, to: Account, kind: OperationKind, goal_id: Option<String>, memo: &[u8]) -> Result<Nat, FintrackError> {
+    let fee = icrc1_fee(bi.asset_canister).await?;
+    if surplus.0 <= fee.0 { return Err(FintrackError::invalid_state(format!("surplus {} does not cover the ledger fee {}", surplus, fee))); }
+    let net = Nat(&surplus.0 - &fee.0);
+    let reserved = with_budget_mut(&bi.id, |b| {
+        if b.available_to_withdraw.0 != surplus.0 { return None; } // sudah ditarik selama await
+        Some(drain_available(b))
+    }).flatten();
+    let Some(per_envelope) = reserved else { return Err(FintrackError::invalid_state("surplus changed during rollover")) };
+    let escrow = canister_escrow_account(escrow_subaccount(&bi.owner, &bi.id));
+    let mut op = NewOperation::new(kind, EntityKind::Budget, &bi.id, bi.owner, bi.asset_canister, LedgerCall::Transfer { from_subaccount: escrow.subaccount, to }, net.clone()).with_memo(memo);
+    op.reserved = surplus.clone();
+    op.goal_id = goal_id;
+    op.envelope_amounts = Some(per_envelope.clone());
+    match journal::execute(op).await {
+        Ok(_) => Ok(net),
+        Err(e) if journal::is_in_doubt(&e) => Err(e), // diselesaikan reconciler, available tetap kosong
+        Err(e) => {
+            with_budget_mut(&bi.id, |b| restore_available(b, &surplus, &per_envelope));
+            Err(e)
+        }
+    }
+}
 
-    let arg = TransferFromArg {
-        from, to, amount: amount.clone(),
-        fee: None,
-        memo: Some(b"budget_monthly_lock".to_vec()),
-        created_at_time: Some(time()),
-        spender_subaccount: None,
+fn record_sweep(bi: &BudgetInfo, goal_id: &str, surplus: Nat, net: Nat) {
+    super::goals::credit_sweep(goal_id, net.clone(), format!("budget rollover from {}", bi.id));
+    push_event(&bi.id, budget_event(bi, BudgetEventKind::RolloverSwept, Some(surplus), Some(format!("goal:{} net:{}", goal_id, net))));
+}
+
+fn record_refund(bi: &BudgetInfo, surplus: Nat, net: Nat) {
+    push_event(&bi.id, budget_event(bi, BudgetEventKind::RolloverRefunded, Some(surplus), Some(format!("net:{}", net))));
+}
+
+// Terapkan rollover policy pada surplus periode yang baru selesai
+async fn apply_rollover(bi: BudgetInfo) {
+    let surplus = bi.available_to_withdraw.clone();
+    if surplus.0 == num::BigUint::from(0u32) { return; }
+    match rollover_policy(&bi) {
+        RolloverPolicy::Keep => {}
+        RolloverPolicy::CarryOver => {
+            // Periode terakhir: tidak ada lock berikutnya, surplus tetap available
+            if next_period(&bi).is_none() { return; }
+            with_budget_mut(&bi.id, |b| {
+                drain_available(b);
+                b.carry_over = Some(Nat(&b.carry_over.clone().unwrap_or_else(zero).0 + &surplus.0));
+                b.updated_at_ns = time();
+            });
+            push_event(&bi.id, budget_event(&bi, BudgetEventKind::RolloverCarried, Some(surplus), None));
+        }
+        RolloverPolicy::SweepToGoal { goal_id } => {
+            let result = match super::goals::sweep_target(&goal_id, bi.owner, bi.asset_canister) {
+                Ok(target) => {
+                    let to = Account { owner: target.owner, subaccount: target.subaccount };
+                    transfer_surplus(&bi, surplus.clone(), to, OperationKind::Sweep, Some(goal_id.clone()), b"budget_rollover_sweep").await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(net) => record_sweep(&bi, &goal_id, surplus, net),
+                Err(e) if journal::is_in_doubt(&e) => {} // event dicatat saat reconciler menyelesaikan operasi
+                Err(e) => push_event(&bi.id, budget_event(&bi, BudgetEventKind::RolloverFailed, Some(surplus), Some(e.to_string()))),
+            }
+        }
+        RolloverPolicy::Refund => {
+            match transfer_surplus(&bi, surplus.clone(), principal_account(bi.owner), OperationKind::Refund, None, b"budget_rollover_refund").await {
+                Ok(net) => record_refund(&bi, surplus, net),
+                Err(e) if journal::is_in_doubt(&e) => {}
+                Err(e) => push_event(&bi.id, budget_event(&bi, BudgetEventKind::RolloverFailed, Some(surplus), Some(e.to_string()))),
+            }
+        }
+    }
+}
+
+async fn handle_period_end(budget_id: String) {
+    BUDGET_TIMERS.with(|t| if let Some(bt) = t.borrow_mut().get_mut(&budget_id) { bt.unlock_timer = None; });
+    let Ok(_guard) = EntityGuard::acquire("budget", &budget_id) else {
+        // Payout/delete masih berjalan: ulangi sebentar lagi
+        let et = schedule_period_end_timer_at(budget_id.clone(), time() + guard::RETRY_DELAY_NS);
+        set_unlock_timer(&budget_id, Some(et));
+        return;
     };
+    // Completed = periode terakhir yang sudah fully vested lewat refresh; rollover tetap dijalankan
+    let runnable = |id: &str| load_budget(id)
+        .map(|bi| bi.status == BudgetStatus::Active || bi.status == BudgetStatus::Completed)
+        .unwrap_or(false);
+    if !runnable(&budget_id) { return; } // paused/failed/archived: tidak dilanjutkan
+
+    let vested = with_budget_mut(&budget_id, |bi| {
+        accrue_and_log(&budget_id, bi, time(), None);
+        bi.clone()
+    });
+    let Some(vested) = vested else { return };
+    apply_rollover(vested).await;
+    if !runnable(&budget_id) { return; } // di-pause/hapus selama transfer rollover
 
-    match icrc2_transfer_from(asset, arg).await {
-        Ok(_block) => {
-            if let Some(tid) = old_timer { let _ = clear_timer(tid); }
-            BUDGETS.with(|b| {
-                let mut map = b.borrow_mut();
-                if let Some(bi) = map.get_mut(&budget_id) {
-                    let added = bi.info.amount_to_lock.clone();
-                    bi.info.locked_balance = Nat(&bi.info.locked_balance.0 + &added.0);
-                    bi.info.period_locked = added;
-                    bi.info.unlocked_so_far = Nat(num::BigUint::from(0u32));
-                    // gunakan period_start_ns / period_end_ns yang sudah disediakan saat create
-                    bi.info.updated_at_ns = time();
-                    bi.lock_timer = None;
-                    bi.info.status = BudgetStatus::Active;
+    let next = with_budget_mut(&budget_id, |bi| {
+        let now = time();
+        let next = next_period(bi);
+        match next {
+            Some((index, start, end)) => {
+                bi.period_index = index;
+                bi.period_start_ns = start;
+                bi.period_end_ns = end;
+                bi.next_lock_at_ns = start;
+                bi.next_unlock_at_ns = end;
+                bi.period_locked = Nat(num::BigUint::from(0u32));
+                bi.unlocked_so_far = Nat(num::BigUint::from(0u32));
+                for env in bi.envelopes.iter_mut().flatten() {
+                    env.period_locked = zero();
+                    env.unlocked_so_far = zero();
                 }
-            });
-            push_event(&budget_id, BudgetEvent { at_time_ns: time(), kind: BudgetEventKind::LockSucceeded, amount: Some(amount.clone()), note: None });
+            }
+            None => bi.status = BudgetStatus::Completed,
         }
+        bi.updated_at_ns = now;
+        next
+    }).flatten();
+
+    if let Some((_, start, _)) = next {
+        let lt = schedule_lock_timer_at(budget_id.clone(), start);
+        set_lock_timer(&budget_id, Some(lt));
+    }
+}
+
+async fn handle_period_lock(budget_id: String) {
+    let Ok(guard) = EntityGuard::acquire("budget", &budget_id) else {
+        // Payout/delete masih berjalan: ulangi sebentar lagi
+        if let Some(tid) = take_lock_timer(&budget_id) { let _ = clear_timer(tid); }
+        let lt = schedule_lock_timer_at(budget_id.clone(), time() + guard::RETRY_DELAY_NS);
+        set_lock_timer(&budget_id, Some(lt));
+        return;
+    };
+    run_period_lock(budget_id, guard).await;
+}
+
+// Lock dana periode; guard dipegang pemanggil sampai selesai
+async fn run_period_lock(budget_id: String, _guard: EntityGuard) {
+    // Take old timer id (single-shot); next_lock_at_ns tidak dipakai lagi
+    let old_timer = take_lock_timer(&budget_id);
+
+    let Some(bi) = load_budget(&budget_id) else { return };
+    let (owner, asset, total, escrow) = (
+        bi.owner,
+        bi.asset_canister,
+        bi.amount_to_lock.clone(),
+        canister_escrow_account(escrow_subaccount(&bi.owner, &bi.id)),
+    );
+    // Carry-over dari periode sebelumnya sudah ada di escrow; user hanya menutup sisanya
+    let carry = bi.carry_over.clone().unwrap_or_else(zero);
+    let carry_used = if carry.0 > total.0 { total.clone() } else { carry };
+    let amount = Nat(&total.0 - &carry_used.0);
+
+    let lock_result = if amount.0 == num::BigUint::from(0u32) {
+        Ok(zero())
+    } else {
+        let call = LedgerCall::TransferFrom { from: principal_account(owner), to: escrow };
+        journal::execute(NewOperation::new(OperationKind::Lock, EntityKind::Budget, &budget_id, owner, asset, call, amount.clone()).with_memo(b"budget_monthly_lock")).await
+    };
+    if let Some(tid) = old_timer { let _ = clear_timer(tid); }
+
+    match lock_result {
+        Ok(_block) => apply_lock(&budget_id, &amount),
+        // Belum pasti: reconciler yang mengkredit (atau menandai gagal) lalu arm timer akhir periode
+        Err(e) if journal::is_in_doubt(&e) => ic_cdk::println!("monthly lock pending: {}", e),
         Err(e) => {
             ic_cdk::println!("monthly lock failed: {}", e);
-            if let Some(tid) = old_timer { let _ = clear_timer(tid); }
-            BUDGETS.with(|b| {
-                let mut map = b.borrow_mut();
-                if let Some(bi) = map.get_mut(&budget_id) {
-                    bi.info.status = BudgetStatus::Failed;
-                    bi.info.updated_at_ns = time();
-                }
-            });
-            push_event(&budget_id, BudgetEvent { at_time_ns: time(), kind: BudgetEventKind::LockFailed, amount: Some(amount.clone()), note: Some(e) });
+            fail_lock(&budget_id, &amount, &e);
         }
     }
 }
 
+// Kredit lock periode: `transferred` dari user, sisanya ditutup carry-over yang sudah ada di escrow
+fn apply_lock(budget_id: &str, transferred: &Nat) {
+    let locked = with_budget_mut(budget_id, |bi| {
+        let carry = bi.carry_over.clone().unwrap_or_else(zero);
+        let uncovered = if bi.amount_to_lock.0 > transferred.0 { &bi.amount_to_lock.0 - &transferred.0 } else { num::BigUint::from(0u32) };
+        let carry_used = if carry.0 > uncovered { Nat(uncovered) } else { carry.clone() };
+        let added = Nat(&transferred.0 + &carry_used.0);
+        let remaining_carry = &carry.0 - &carry_used.0;
+        bi.carry_over = if remaining_carry == num::BigUint::from(0u32) { None } else { Some(Nat(remaining_carry)) };
+        bi.locked_balance = Nat(&bi.locked_balance.0 + &added.0);
+        if let Some(envs) = bi.envelopes.as_mut() {
+            let shares = split_allocation(envs, &added);
+            for (env, share) in envs.iter_mut().zip(shares) {
+                env.locked_balance = Nat(&env.locked_balance.0 + &share.0);
+                env.funded_total = Nat(&env.funded_total.0 + &share.0);
+                env.period_locked = share;
+                env.unlocked_so_far = zero();
+            }
+        }
+        bi.period_locked = added;
+        bi.unlocked_so_far = Nat(num::BigUint::from(0u32));
+        // period_start_ns / period_end_ns = periode aktif (dari create atau rollover recurrence)
+        bi.updated_at_ns = time();
+        bi.status = BudgetStatus::Active;
+        (bi.clone(), carry_used)
+    });
+    let Some((bi, carry_used)) = locked else { return };
+    let note = if carry_used.0 > num::BigUint::from(0u32) { Some(format!("carry_over_used:{}", carry_used)) } else { None };
+    push_event(budget_id, budget_event(&bi, BudgetEventKind::LockSucceeded, Some(transferred.clone()), note));
+    let et = schedule_period_end_timer_at(budget_id.to_string(), bi.period_end_ns);
+    set_unlock_timer(budget_id, Some(et));
+}
+
+fn fail_lock(budget_id: &str, amount: &Nat, e: &FintrackError) {
+    let failed = with_budget_mut(budget_id, |bi| {
+        bi.status = BudgetStatus::Failed;
+        bi.updated_at_ns = time();
+        bi.clone()
+    });
+    if let Some(bi) = failed {
+        push_event(budget_id, budget_event(&bi, BudgetEventKind::LockFailed, Some(amount.clone()), Some(e.to_string())));
+    }
+}
 
-pub async fn create_budget(req: BudgetCreateRequest) -> Result<BudgetInfo, String> {
+pub async fn create_budget(req: BudgetCreateRequest) -> Result<BudgetInfo, FintrackError> {
     let owner = caller();
     // Validasi input dasar
-    if req.name.trim().is_empty() { return Err("name tidak boleh kosong".to_string()); }
-    if req.amount_to_lock.0 == num::BigUint::from(0u32) { return Err("amount_to_lock harus > 0".to_string()); }
+    if req.name.trim().is_empty() { return Err(FintrackError::invalid_input("name", "must not be empty")); }
+    if req.amount_to_lock.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount_to_lock", "must be greater than zero")); }
+    if req.period_end_ns <= req.period_start_ns { return Err(FintrackError::invalid_input("period_end_ns", "must be after period_start_ns")); }
+    if let Some(policy) = req.rollover.as_ref() { validate_rollover(policy, owner, req.asset_canister)?; }
+    if let Some(specs) = req.envelopes.as_ref() { validate_envelopes(specs, &owner, &req.amount_to_lock)?; }
+    if let Some(schedule) = req.vesting.as_ref() { vesting::validate(schedule, req.period_start_ns, req.period_end_ns, &req.amount_to_lock)?; }
     let created = time();
     let id = format!("{}-{}-{}", owner, req.asset_canister, created);
     let next_lock = req.period_start_ns;
@@ -283,58 +955,40 @@ pub async fn create_budget(req: BudgetCreateRequest) -> Result<BudgetInfo, Strin
         period_end_ns: next_unlock,
         period_locked: Nat(num::BigUint::from(0u32)),
         unlocked_so_far: Nat(num::BigUint::from(0u32)),
+        recurrence: req.recurrence,
+        period_index: 0,
+        first_period_start_ns: next_lock,
+        first_period_end_ns: next_unlock,
+        rollover: req.rollover,
+        carry_over: None,
+        envelopes: req.envelopes.map(build_envelopes),
+        spent_total: None,
+        vesting: req.vesting,
     };
+    validate_recurrence(&info)?;
 
+    store_budget(&info);
+    escrow_audit::register(EntityKind::Budget, &id, None, info.owner, info.asset_canister, escrow_subaccount(&info.owner, &id));
     let lock_tid = schedule_lock_timer_at(id.clone(), next_lock);
-    let unlock_tid = None;
-
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        map.insert(id.clone(), BudgetInternal { info: info.clone(), lock_timer: Some(lock_tid), unlock_timer: unlock_tid });
-    });
+    set_lock_timer(&id, Some(lock_tid));
 
     Ok(info)
 }
 
 pub fn list_budgets(owner: Option<Principal>) -> Vec<BudgetInfo> {
     let who = owner.unwrap_or_else(caller);
-    BUDGETS.with(|b| {
-        b.borrow().values()
-            .filter(|bi| bi.info.owner == who)
-            .map(|bi| bi.info.clone())
-            .collect()
-    })
+    all_budgets().into_iter().filter(|bi| bi.owner == who).collect()
 }
 
 pub fn get_budget(id: String) -> Option<BudgetInfo> {
-    BUDGETS.with(|b| b.borrow().get(&id).map(|bi| bi.info.clone()))
+    load_budget(&id)
 }
 
-pub fn budget_refresh_accrual(id: String) -> Result<BudgetInfo, String> {
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        let bi = map.get_mut(&id).ok_or("budget not found")?;
-        let now = time();
-        let start = bi.info.period_start_ns;
-        let end = bi.info.period_end_ns.max(start + 1);
-        let duration = end - start;
-        let clamped = if now < start { start } else if now > end { end } else { now };
-        let elapsed = clamped - start;
-        let target = if duration == 0 { bi.info.period_locked.0.clone() } else { (&bi.info.period_locked.0 * elapsed) / duration };
-        if target > bi.info.unlocked_so_far.0 {
-            let mut newly = &target - &bi.info.unlocked_so_far.0;
-            if newly > bi.info.locked_balance.0 { newly = bi.info.locked_balance.0.clone(); }
-            bi.info.unlocked_so_far = Nat(&bi.info.unlocked_so_far.0 + &newly);
-            bi.info.locked_balance = Nat(&bi.info.locked_balance.0 - &newly);
-            bi.info.available_to_withdraw = Nat(&bi.info.available_to_withdraw.0 + &newly);
-            bi.info.updated_at_ns = now;
-            if bi.info.unlocked_so_far.0 >= bi.info.period_locked.0 && bi.info.status != BudgetStatus::Completed {
-                bi.info.status = BudgetStatus::Completed;
-                push_event(&id, BudgetEvent { at_time_ns: now, kind: BudgetEventKind::PeriodCompleted, amount: Some(bi.info.period_locked.clone()), note: None });
-            }
-        }
-        Ok(bi.info.clone())
-    })
+pub fn budget_refresh_accrual(id: String) -> Result<BudgetInfo, FintrackError> {
+    with_budget_mut(&id, |bi| {
+        accrue_and_log(&id, bi, time(), None);
+        bi.clone()
+    }).ok_or_else(|| FintrackError::not_found("budget", id.clone()))
 }
 
 // ---------- Accrual preview (read-only, no state change) ----------
@@ -348,266 +1002,425 @@ pub struct BudgetAccrualPreview {
     pub projected_locked_balance: Nat,
 }
 
-pub fn budget_preview_accrual(id: String) -> Result<BudgetAccrualPreview, String> {
-    BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&id).ok_or("budget not found")?;
-        let now = time();
-        let start = bi.info.period_start_ns;
-        let end = bi.info.period_end_ns.max(start + 1);
-        let duration = end - start;
-        let clamped = if now < start { start } else if now > end { end } else { now };
-        let elapsed = clamped - start;
-        let target = if duration == 0 { bi.info.period_locked.0.clone() } else { (&bi.info.period_locked.0 * elapsed) / duration };
-        let projected_unlocked = Nat(target.clone());
-        // derive projected balances relative to current state
-        let additional = if target > bi.info.unlocked_so_far.0 { &target - &bi.info.unlocked_so_far.0 } else { num::BigUint::from(0u32) };
-        let projected_available = Nat(&bi.info.available_to_withdraw.0 + &additional);
-        let projected_locked_balance = Nat(&bi.info.locked_balance.0 - &additional);
-        Ok(BudgetAccrualPreview { now_ns: now, period_start_ns: start, period_end_ns: end, projected_unlocked, projected_available, projected_locked_balance })
-    })
+pub fn budget_preview_accrual(id: String) -> Result<BudgetAccrualPreview, FintrackError> {
+    let bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    let now = time();
+    let start = bi.period_start_ns;
+    let end = bi.period_end_ns.max(start + 1);
+    let (elapsed, duration) = vesting::elapsed(start, end, now);
+    let target = vesting::vested_amount(&bi.period_locked.0, &vested_ratio(&bi, elapsed, duration));
+    let projected_unlocked = Nat(target.clone());
+    // derive projected balances relative to current state
+    let additional = if target > bi.unlocked_so_far.0 { &target - &bi.unlocked_so_far.0 } else { num::BigUint::from(0u32) };
+    let projected_available = Nat(&bi.available_to_withdraw.0 + &additional);
+    let projected_locked_balance = Nat(&bi.locked_balance.0 - &additional);
+    Ok(BudgetAccrualPreview { now_ns: now, period_start_ns: start, period_end_ns: end, projected_unlocked, projected_available, projected_locked_balance })
 }
 
 // ---------- Accrual step (bounded) ----------
-pub fn budget_refresh_accrual_step(id: String, max_delta: Option<Nat>) -> Result<BudgetInfo, String> {
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        let bi = map.get_mut(&id).ok_or("budget not found")?;
-        let now = time();
-        let start = bi.info.period_start_ns;
-        let end = bi.info.period_end_ns.max(start + 1);
-        let duration = end - start;
-        let clamped = if now < start { start } else if now > end { end } else { now };
-        let elapsed = clamped - start;
-        let target = if duration == 0 { bi.info.period_locked.0.clone() } else { (&bi.info.period_locked.0 * elapsed) / duration };
-        if target > bi.info.unlocked_so_far.0 {
-            let mut newly = &target - &bi.info.unlocked_so_far.0;
-            if let Some(md) = max_delta.as_ref() {
-                if newly > md.0 { newly = md.0.clone(); }
-            }
-            if newly > bi.info.locked_balance.0 { newly = bi.info.locked_balance.0.clone(); }
-            bi.info.unlocked_so_far = Nat(&bi.info.unlocked_so_far.0 + &newly);
-            bi.info.locked_balance = Nat(&bi.info.locked_balance.0 - &newly);
-            bi.info.available_to_withdraw = Nat(&bi.info.available_to_withdraw.0 + &newly);
-            bi.info.updated_at_ns = now;
-            if bi.info.unlocked_so_far.0 >= bi.info.period_locked.0 && bi.info.status != BudgetStatus::Completed {
-                bi.info.status = BudgetStatus::Completed;
-                push_event(&id, BudgetEvent { at_time_ns: now, kind: BudgetEventKind::PeriodCompleted, amount: Some(bi.info.period_locked.clone()), note: None });
-            }
-        }
-        Ok(bi.info.clone())
-    })
+pub fn budget_refresh_accrual_step(id: String, max_delta: Option<Nat>) -> Result<BudgetInfo, FintrackError> {
+    with_budget_mut(&id, |bi| {
+        accrue_and_log(&id, bi, time(), max_delta.as_ref());
+        bi.clone()
+    }).ok_or_else(|| FintrackError::not_found("budget", id.clone()))
 }
 
 pub fn list_budgets_by_asset(owner: Option<Principal>, asset: Principal) -> Vec<BudgetInfo> {
     let who = owner.unwrap_or_else(caller);
-    BUDGETS.with(|b| {
-        b.borrow().values()
-            .filter(|bi| bi.info.owner == who && bi.info.asset_canister == asset)
-            .map(|bi| bi.info.clone())
-            .collect()
-    })
+    all_budgets().into_iter().filter(|bi| bi.owner == who && bi.asset_canister == asset).collect()
 }
 
-pub fn get_escrow_account(id: String) -> Result<Account, String> {
-    BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        Ok(canister_escrow_account(escrow_subaccount(&bi.info.owner, &bi.info.id)))
-    })
+pub fn get_escrow_account(id: String) -> Result<Account, FintrackError> {
+    let bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    Ok(canister_escrow_account(escrow_subaccount(&bi.owner, &bi.id)))
 }
 
-pub async fn budget_withdraw(id: String, amount: Nat, to_subaccount: Option<Vec<u8>>) -> Result<Nat, String> {
-    if amount.0 == num::BigUint::from(0u32) { return Err("amount harus > 0".to_string()); }
-    let (asset, owner, available, escrow, start, end, period_locked, unlocked_so_far) = BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        Ok((
-            bi.info.asset_canister, bi.info.owner,
-            bi.info.available_to_withdraw.clone(),
-            canister_escrow_account(escrow_subaccount(&bi.info.owner, &bi.info.id)),
-            bi.info.period_start_ns, bi.info.period_end_ns,
-            bi.info.period_locked.clone(), bi.info.unlocked_so_far.clone(),
-        ))
-    })?;
-
-    // Accrual up to now before withdrawing
-    let now = time();
-    let duration = (end.max(start + 1)) - start;
-    let clamped_now = if now < start { start } else if now > end { end } else { now };
-    let elapsed = clamped_now - start;
-    let target_unlocked = if duration == 0 { period_locked.0.clone() } else { (&period_locked.0 * elapsed) / duration };
-    let mut newly = if target_unlocked > unlocked_so_far.0 { &target_unlocked - &unlocked_so_far.0 } else { num::BigUint::from(0u32) };
-    // apply accrual to state
-    let mut just_completed = false;
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        if let Some(bi) = map.get_mut(&id) {
-            if newly > bi.info.locked_balance.0 { newly = bi.info.locked_balance.0.clone(); }
-            bi.info.unlocked_so_far = Nat(&bi.info.unlocked_so_far.0 + &newly);
-            bi.info.locked_balance = Nat(&bi.info.locked_balance.0 - &newly);
-            bi.info.available_to_withdraw = Nat(&bi.info.available_to_withdraw.0 + &newly);
-            bi.info.updated_at_ns = time();
-            if bi.info.unlocked_so_far.0 >= bi.info.period_locked.0 && bi.info.status != BudgetStatus::Completed {
-                bi.info.status = BudgetStatus::Completed;
-                just_completed = true;
-            }
-        }
-    });
+// Pembukuan escrow untuk escrow_audit: locked + available + carry_over (semuanya masih di escrow)
+fn escrow_books_of(bi: &BudgetInfo) -> EscrowBooks {
+    EscrowBooks {
+        entity_id: bi.id.clone(),
+        owner: bi.owner,
+        ledger: bi.asset_canister,
+        asset: None,
+        subaccount: escrow_subaccount(&bi.owner, &bi.id),
+        expected: Nat(&bi.locked_balance.0 + &bi.available_to_withdraw.0 + &bi.carry_over.clone().unwrap_or_else(zero).0),
+    }
+}
+
+pub(crate) fn escrow_books(id: &str) -> Option<EscrowBooks> {
+    load_budget(id).map(|bi| escrow_books_of(&bi))
+}
 
-    if just_completed { push_event(&id, BudgetEvent { at_time_ns: time(), kind: BudgetEventKind::PeriodCompleted, amount: Some(period_locked.clone()), note: None }); }
+pub(crate) fn all_escrow_books() -> Vec<EscrowBooks> {
+    all_budgets().iter().map(escrow_books_of).collect()
+}
 
-    // Re-check available after accrual
-    let avail_after = BUDGETS.with(|b| {
-        let map = b.borrow();
-        map.get(&id).map(|bi| bi.info.available_to_withdraw.clone()).ok_or("budget not found".to_string())
-    })?;
-    if amount.0 > avail_after.0 { return Err("amount > available_to_withdraw".to_string()); }
+pub async fn budget_withdraw(id: String, amount: Nat, to_subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
+    let owner = caller();
+    pay_out(id, None, amount, Payout::withdraw(Account { owner, subaccount: to_subaccount })).await
+}
 
-    // Ambil fee ledger dan kurangi dari jumlah yang dikirim, sehingga total dipotong dari available adalah `amount` (net + fee)
-    let fee = icrc1_fee(asset).await?;
-    if amount.0 <= fee.0 { return Err("amount harus > fee ledger".to_string()); }
+// Withdraw dari satu envelope; fee ledger ikut dipotong dari available envelope tersebut
+pub async fn budget_withdraw_envelope(id: String, envelope_id: String, amount: Nat, to_subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
+    let owner = caller();
+    pay_out(id, Some(envelope_id), amount, Payout::withdraw(Account { owner, subaccount: to_subaccount })).await
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetSpendRequest {
+    pub budget_id: String,
+    pub envelope_id: Option<String>, // wajib bila budget punya envelope
+    pub to: Account,                 // akun ICRC-1 merchant/penerima
+    pub amount: Nat,                 // dipotong dari available (termasuk fee ledger)
+    pub memo: Option<Vec<u8>>,       // diteruskan ke ledger, maks 32 byte
+    pub category_id: Option<String>, // default: kategori envelope
+    pub note: Option<String>,
+}
+
+const MAX_MEMO_LEN: usize = 32; // batas memo ICRC-1
+const MAX_NOTE_LEN: usize = 256;
+
+// Bayar akun ICRC-1 mana pun dari saldo yang sudah vested
+pub async fn budget_spend(req: BudgetSpendRequest) -> Result<Nat, FintrackError> {
+    if req.memo.as_ref().map(|m| m.len() > MAX_MEMO_LEN).unwrap_or(false) {
+        return Err(FintrackError::invalid_input("memo", format!("at most {} bytes", MAX_MEMO_LEN)));
+    }
+    if req.note.as_ref().map(|n| n.chars().count() > MAX_NOTE_LEN).unwrap_or(false) {
+        return Err(FintrackError::invalid_input("note", format!("at most {} characters", MAX_NOTE_LEN)));
+    }
+    if req.to.subaccount.as_ref().map(|s| s.len() != 32).unwrap_or(false) {
+        return Err(FintrackError::invalid_input("to.subaccount", "must be 32 bytes"));
+    }
+    let owner = caller();
+    let mut category_id = req.category_id.clone();
+    if let Some(cid) = category_id.as_ref() {
+        if !super::categories::exists(&owner, cid) { return Err(FintrackError::not_found("category", cid.clone())); }
+    } else if let Some(eid) = req.envelope_id.as_ref() {
+        category_id = load_budget(&req.budget_id)
+            .and_then(|bi| bi.envelopes)
+            .and_then(|envs| envs.into_iter().find(|e| &e.id == eid))
+            .and_then(|e| e.category_id);
+    }
+    let payout = Payout {
+        to: req.to,
+        memo: req.memo.unwrap_or_else(|| b"budget_spend".to_vec()),
+        kind: BudgetEventKind::Spend,
+        category_id,
+        note: req.note,
+    };
+    pay_out(req.budget_id, req.envelope_id, req.amount, payout).await
+}
+
+// Tujuan dana keluar dari escrow: withdraw ke owner atau spend ke pihak ketiga
+struct Payout {
+    to: Account,
+    memo: Vec<u8>,
+    kind: BudgetEventKind, // Withdraw | Spend
+    category_id: Option<String>,
+    note: Option<String>,
+}
+
+impl Payout {
+    fn withdraw(to: Account) -> Self {
+        Payout { to, memo: b"budget_user_withdraw".to_vec(), kind: BudgetEventKind::Withdraw, category_id: None, note: None }
+    }
+
+    fn is_spend(&self) -> bool { self.kind == BudgetEventKind::Spend }
+}
+
+async fn pay_out(id: String, envelope_id: Option<String>, amount: Nat, payout: Payout) -> Result<Nat, FintrackError> {
+    if amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount", "must be greater than zero")); }
+    let (owner, asset, escrow) = {
+        let bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+        if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+        if deletion_pending(&bi) { return Err(FintrackError::invalid_state("budget deletion is still being settled")); }
+        match (bi.envelopes.as_ref(), envelope_id.as_ref()) {
+            (Some(_), None) => return Err(FintrackError::invalid_input("envelope_id", "budget has envelopes, pay out from a specific envelope")),
+            (None, Some(_)) => return Err(FintrackError::invalid_input("envelope_id", "budget has no envelopes")),
+            (Some(envs), Some(eid)) if !envs.iter().any(|e| &e.id == eid) => return Err(FintrackError::not_found("envelope", eid.clone())),
+            _ => {}
+        }
+        (bi.owner, bi.asset_canister, canister_escrow_account(escrow_subaccount(&bi.owner, &bi.id)))
+    };
+
+    // Satu payout/rollover/delete per budget pada satu waktu; dilepas saat fungsi selesai
+    let _guard = EntityGuard::acquire("budget", &id)?;
+
+    // Accrual up to now, cek available (envelope: pakai available milik envelope), lalu reserve sebelum await
+    let reserved = with_budget_mut(&id, |bi| {
+        accrue_and_log(&id, bi, time(), None);
+        let avail_after = match envelope_id.as_ref() {
+            Some(eid) => bi.envelopes.iter().flatten().find(|e| &e.id == eid).map(|e| e.available_to_withdraw.clone()).unwrap_or_else(zero),
+            None => bi.available_to_withdraw.clone(),
+        };
+        if amount.0 > avail_after.0 { return Err(FintrackError::InsufficientFunds { needed: amount.clone(), available: avail_after }); }
+        reserve_available(bi, envelope_id.as_deref(), &amount);
+        bi.updated_at_ns = time();
+        Ok(())
+    }).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    reserved?;
+
+    // Total yang dipotong dari available adalah `amount` (net + fee ledger)
+    let release = |id: &str| with_budget_mut(id, |bi| release_reservation(bi, envelope_id.as_deref(), &amount));
+    let fee = match icrc1_fee(asset).await {
+        Ok(fee) => fee,
+        Err(e) => { release(&id); return Err(e); }
+    };
+    if amount.0 <= fee.0 {
+        release(&id);
+        return Err(FintrackError::invalid_input("amount", format!("must be greater than the ledger fee {}", fee)));
+    }
     let net = Nat(&amount.0 - &fee.0);
 
-    // Transfer dari escrow ke akun user (principal user, optional subaccount)
-    let to = Account { owner, subaccount: to_subaccount };
-    let arg = TransferArg { from_subaccount: escrow.subaccount.clone(), to, amount: net.clone(), fee: None, memo: Some(b"budget_user_withdraw".to_vec()), created_at_time: Some(time()) };
-    let res = icrc1_transfer(asset, arg).await?;
+    let kind = if payout.is_spend() { OperationKind::Spend } else { OperationKind::Withdraw };
+    let call = LedgerCall::Transfer { from_subaccount: escrow.subaccount.clone(), to: payout.to.clone() };
+    let mut op = NewOperation::new(kind, EntityKind::Budget, &id, owner, asset, call, net).with_memo(&payout.memo);
+    op.reserved = amount.clone();
+    op.envelope_id = envelope_id.clone();
+    op.category_id = payout.category_id.clone();
+    op.note = payout.note.clone();
+    let res = match journal::execute(op).await {
+        Ok(block) => block,
+        Err(e) if journal::is_in_doubt(&e) => return Err(e), // reservasi tetap, diselesaikan reconciler
+        Err(e) => { release(&id); return Err(e); }
+    };
 
-    // Update state
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        if let Some(mut bi) = map.get_mut(&id) {
-            // Kurangi total yang dipotong (net + fee) dari available: yaitu `amount`
-            bi.info.available_to_withdraw = Nat(&bi.info.available_to_withdraw.0 - &amount.0);
-            bi.info.updated_at_ns = time();
+    commit_payout(&id, envelope_id.as_deref(), &amount, &fee, &payout);
+    Ok(res)
+}
+
+// Commit payout yang sudah tereksekusi di ledger: available sudah dipotong saat reserve, tinggal akumulasi total + event
+fn commit_payout(id: &str, envelope_id: Option<&str>, amount: &Nat, fee: &Nat, payout: &Payout) {
+    let spend = payout.is_spend();
+    with_budget_mut(id, |bi| {
+        if spend { bi.spent_total = Some(Nat(&bi.spent_total.clone().unwrap_or_else(zero).0 + &amount.0)); }
+        if let Some(env) = envelope_id.and_then(|eid| bi.envelopes.iter_mut().flatten().find(|e| e.id == eid)) {
+            if spend {
+                env.spent_total = Some(Nat(&env.spent_total.clone().unwrap_or_else(zero).0 + &amount.0));
+            } else {
+                env.withdrawn_total = Nat(&env.withdrawn_total.0 + &amount.0);
+            }
         }
+        bi.updated_at_ns = time();
     });
 
     // Log event
-    push_event(&id, BudgetEvent { at_time_ns: time(), kind: BudgetEventKind::Withdraw, amount: Some(amount.clone()), note: Some(format!("fee_deducted:{}", fee.0)) });
+    if let Some(bi) = load_budget(id) {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(eid) = envelope_id { parts.push(format!("envelope:{}", eid)); }
+        if let Some(n) = payout.note.as_ref() { parts.push(n.clone()); }
+        parts.push(format!("fee_deducted:{}", fee.0));
+        let mut ev = budget_event(&bi, payout.kind.clone(), Some(amount.clone()), Some(parts.join(" ")));
+        if spend {
+            ev.counterparty = Some(payout.to.clone());
+            ev.category_id = payout.category_id.clone();
+        }
+        push_event(id, ev);
+    }
+}
 
-    Ok(res)
+// Hasil akhir operasi journal yang diselesaikan reconciler; false = tidak bisa diterapkan (entry jadi Stuck)
+pub(crate) fn settle_operation(op: &PendingOperation, result: Result<Nat, FintrackError>) -> bool {
+    let Some(bi) = load_budget(&op.entity_id) else {
+        // Budget sudah dihapus: hanya refund delete yang sukses dianggap selesai
+        return op.kind == OperationKind::Refund && result.is_ok();
+    };
+    let to = match &op.call {
+        LedgerCall::Transfer { to, .. } => to.clone(),
+        LedgerCall::TransferFrom { to, .. } => to.clone(),
+    };
+    let fee = if op.reserved.0 > op.amount.0 { Nat(&op.reserved.0 - &op.amount.0) } else { zero() };
+    match (op.kind, result) {
+        (OperationKind::Withdraw | OperationKind::Spend, Ok(_)) => {
+            let memo = op.memo.clone().unwrap_or_default();
+            let payout = if op.kind == OperationKind::Spend {
+                Payout { to, memo, kind: BudgetEventKind::Spend, category_id: op.category_id.clone(), note: op.note.clone() }
+            } else {
+                Payout::withdraw(to)
+            };
+            commit_payout(&op.entity_id, op.envelope_id.as_deref(), &op.reserved, &fee, &payout);
+        }
+        (OperationKind::Withdraw | OperationKind::Spend, Err(e)) => {
+            with_budget_mut(&op.entity_id, |b| release_reservation(b, op.envelope_id.as_deref(), &op.reserved));
+            push_event(&op.entity_id, budget_event(&bi, BudgetEventKind::PayoutReverted, Some(op.reserved.clone()), Some(e.to_string())));
+        }
+        (OperationKind::Lock, Ok(_)) => apply_lock(&op.entity_id, &op.amount),
+        (OperationKind::Lock, Err(e)) => fail_lock(&op.entity_id, &op.amount, &e),
+        (OperationKind::Sweep, Ok(_)) => match op.goal_id.as_deref() {
+            Some(goal_id) => record_sweep(&bi, goal_id, op.reserved.clone(), op.amount.clone()),
+            None => return false,
+        },
+        // Refund delete yang dikonfirmasi: baru sekarang budget yang diarsipkan dihapus
+        (OperationKind::Refund, Ok(_)) if op.memo.as_deref() == Some(DELETE_REFUND_MEMO) => {
+            remove_budget(&op.entity_id);
+        }
+        // Refund delete gagal: saldo tidak pernah dipotong, budget tetap Archived dan bisa dihapus ulang
+        (OperationKind::Refund, Err(e)) if op.memo.as_deref() == Some(DELETE_REFUND_MEMO) => {
+            push_event(&op.entity_id, budget_event(&bi, BudgetEventKind::PayoutReverted, Some(op.reserved.clone()), Some(e.to_string())));
+        }
+        (OperationKind::Refund, Ok(_)) => record_refund(&bi, op.reserved.clone(), op.amount.clone()),
+        // Rollover gagal pasti: kembalikan available yang dikosongkan transfer_surplus.
+        // Entry lama tanpa snapshot per envelope dikembalikan manual (Stuck).
+        (OperationKind::Sweep | OperationKind::Refund, Err(e)) => {
+            let per_envelope = match (&op.envelope_amounts, bi.envelopes.is_some()) {
+                (Some(amounts), _) => amounts.clone(),
+                (None, false) => Vec::new(),
+                (None, true) => return false,
+            };
+            with_budget_mut(&op.entity_id, |b| {
+                restore_available(b, &op.reserved, &per_envelope);
+                b.updated_at_ns = time();
+            });
+            push_event(&op.entity_id, budget_event(&bi, BudgetEventKind::RolloverFailed, Some(op.reserved.clone()), Some(e.to_string())));
+        }
+        // Bukan operasi budget (Recover diselesaikan journal sendiri, Penalty milik goal)
+        (OperationKind::Recover | OperationKind::Penalty, _) => return false,
+    }
+    true
 }
 
-pub fn update_budget(id: String, upd: BudgetUpdateRequest) -> Result<BudgetInfo, String> {
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        let bi = map.get_mut(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        if let Some(name) = upd.name { bi.info.name = name; }
-        if let Some(amount) = upd.amount_to_lock { bi.info.amount_to_lock = amount; }
-        if let Some(status) = upd.status.clone() {
-            match status {
-                BudgetStatus::Active => {
-                    if bi.info.status != BudgetStatus::Active {
-                        // reschedule
-                        let lt = schedule_lock_timer_at(id.clone(), bi.info.next_lock_at_ns);
-                        bi.lock_timer = Some(lt);
-                        bi.unlock_timer = None;
-                    }
-                    bi.info.status = BudgetStatus::Active;
-                }
-                BudgetStatus::Paused => {
-                    if let Some(t) = bi.lock_timer.take() { let _ = clear_timer(t); }
-                    if let Some(t) = bi.unlock_timer.take() { let _ = clear_timer(t); }
-                    bi.info.status = BudgetStatus::Paused;
-                }
-                BudgetStatus::Archived => {
-                    if let Some(t) = bi.lock_timer.take() { let _ = clear_timer(t); }
-                    if let Some(t) = bi.unlock_timer.take() { let _ = clear_timer(t); }
-                    bi.info.status = BudgetStatus::Archived;
-                }
-                BudgetStatus::Completed => {
-                    bi.info.status = BudgetStatus::Completed;
-                }
-                BudgetStatus::Failed => {
-                    bi.info.status = BudgetStatus::Failed;
+pub fn update_budget(id: String, upd: BudgetUpdateRequest) -> Result<BudgetInfo, FintrackError> {
+    let mut bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    if let Some(name) = upd.name { bi.name = name; }
+    if let Some(amount) = upd.amount_to_lock {
+        // Alokasi envelope harus tetap valid untuk jumlah lock yang baru
+        if let Some(envs) = bi.envelopes.as_ref() {
+            let specs: Vec<EnvelopeSpec> = envs.iter().map(|e| EnvelopeSpec { name: e.name.clone(), category_id: e.category_id.clone(), allocation: e.allocation.clone() }).collect();
+            validate_envelopes(&specs, &bi.owner, &amount)?;
+        }
+        if let Some(schedule) = bi.vesting.as_ref() {
+            vesting::validate(schedule, bi.first_period_start_ns, bi.first_period_end_ns, &amount)?;
+        }
+        bi.amount_to_lock = amount;
+    }
+    if let Some(policy) = upd.rollover {
+        validate_rollover(&policy, bi.owner, bi.asset_canister)?;
+        bi.rollover = Some(policy);
+    }
+    if let Some(status) = upd.status.clone() {
+        match status {
+            BudgetStatus::Active => {
+                if bi.status != BudgetStatus::Active {
+                    // reschedule
+                    clear_budget_timers(&id);
+                    bi.status = BudgetStatus::Active;
+                    arm_budget_timers(&bi);
                 }
             }
+            BudgetStatus::Paused => {
+                clear_budget_timers(&id);
+                bi.status = BudgetStatus::Paused;
+            }
+            BudgetStatus::Archived => {
+                clear_budget_timers(&id);
+                bi.status = BudgetStatus::Archived;
+            }
+            BudgetStatus::Completed => {
+                bi.status = BudgetStatus::Completed;
+            }
+            BudgetStatus::Failed => {
+                bi.status = BudgetStatus::Failed;
+            }
         }
-        bi.info.updated_at_ns = time();
-        Ok(bi.info.clone())
-    })
+    }
+    bi.updated_at_ns = time();
+    store_budget(&bi);
+    Ok(bi)
 }
 
-pub async fn trigger_lock_now(id: String) -> Result<(), String> {
-    let owner = BUDGETS.with(|b| b.borrow().get(&id).map(|bi| bi.info.owner));
+pub async fn trigger_lock_now(id: String) -> Result<(), FintrackError> {
+    let owner = load_budget(&id).map(|bi| bi.owner);
     match owner {
         Some(o) if o == caller() => {
-            handle_period_lock(id).await;
+            let guard = EntityGuard::acquire("budget", &id)?;
+            run_period_lock(id, guard).await;
             Ok(())
         }
-        Some(_) => Err("unauthorized".to_string()),
-        None => Err("budget not found".to_string())
+        Some(_) => Err(FintrackError::unauthorized("not the budget owner")),
+        None => Err(FintrackError::not_found("budget", id))
     }
 }
 
 // Removed trigger_unlock_now (linear vesting accrues on write)
 
-pub fn pause_budget(id: String) -> Result<(), String> {
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        let bi = map.get_mut(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        bi.info.status = BudgetStatus::Paused;
-        if let Some(t) = bi.lock_timer.take() { let _ = clear_timer(t); }
-        if let Some(t) = bi.unlock_timer.take() { let _ = clear_timer(t); }
-        bi.info.updated_at_ns = time();
-        Ok(())
-    })
+pub fn pause_budget(id: String) -> Result<(), FintrackError> {
+    let mut bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    bi.status = BudgetStatus::Paused;
+    clear_budget_timers(&id);
+    bi.updated_at_ns = time();
+    store_budget(&bi);
+    Ok(())
 }
 
-pub fn resume_budget(id: String) -> Result<(), String> {
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        let bi = map.get_mut(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        bi.info.status = BudgetStatus::Active;
-        let lt = schedule_lock_timer_at(id.clone(), bi.info.next_lock_at_ns);
-        bi.lock_timer = Some(lt);
-        bi.unlock_timer = None;
-        bi.info.updated_at_ns = time();
-        Ok(())
-    })
+pub fn resume_budget(id: String) -> Result<(), FintrackError> {
+    let mut bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    bi.status = BudgetStatus::Active;
+    clear_budget_timers(&id);
+    arm_budget_timers(&bi);
+    bi.updated_at_ns = time();
+    store_budget(&bi);
+    Ok(())
 }
 
-pub async fn delete_budget(id: String) -> Result<(), String> {
+const DELETE_REFUND_MEMO: &[u8] = b"budget_delete_refund_all";
+
+// Refund delete yang hasilnya belum pasti: budget Archived menunggu reconciler, saldo tidak boleh dibayar lagi
+fn deletion_pending(bi: &BudgetInfo) -> bool {
+    bi.status == BudgetStatus::Archived && journal::pending_count(EntityKind::Budget, &bi.id) > 0
+}
+
+pub async fn delete_budget(id: String) -> Result<(), FintrackError> {
     // Transfer any remaining locked_balance + available_to_withdraw back to user
-    let (asset, owner, remaining_locked, available, escrow) = BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&id).cloned();
-        match bi {
-            Some(bi) => (
-                bi.info.asset_canister,
-                bi.info.owner,
-                bi.info.locked_balance,
-                bi.info.available_to_withdraw,
-                canister_escrow_account(escrow_subaccount(&bi.info.owner, &bi.info.id)),
-            ),
-            None => (Principal::anonymous(), Principal::anonymous(), Nat(num::BigUint::from(0u32)), Nat(num::BigUint::from(0u32)), Account{ owner: ic_cdk::id(), subaccount: None })
-        }
-    });
+    let (asset, owner, remaining_locked, available, escrow) = match load_budget(&id) {
+        Some(bi) => (
+            bi.asset_canister,
+            bi.owner,
+            bi.locked_balance,
+            Nat(&bi.available_to_withdraw.0 + &bi.carry_over.unwrap_or_else(zero).0),
+            canister_escrow_account(escrow_subaccount(&bi.owner, &bi.id)),
+        ),
+        None => (Principal::anonymous(), Principal::anonymous(), Nat(num::BigUint::from(0u32)), Nat(num::BigUint::from(0u32)), Account{ owner: ic_cdk::id(), subaccount: None })
+    };
 
-    if owner != caller() { return Err("unauthorized".to_string()); }
+    if owner == Principal::anonymous() { return Err(FintrackError::not_found("budget", id)); }
+    if owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    let _guard = EntityGuard::acquire("budget", &id)?;
+    if journal::pending_count(EntityKind::Budget, &id) > 0 {
+        return Err(FintrackError::invalid_state("budget has ledger operations in flight, try again later"));
+    }
 
+    // Refund seluruh escrow lewat journal (fee ledger dipotong dari refund).
+    // Gagal pasti = budget tidak dihapus; belum pasti = budget diarsipkan dan baru dihapus
+    // setelah reconciler mengonfirmasi refund (settle_operation).
     if asset != Principal::anonymous() {
         let total = Nat(&remaining_locked.0 + &available.0);
         if total.0 > num::BigUint::from(0u32) {
-            let arg = TransferArg { from_subaccount: escrow.subaccount.clone(), to: principal_account(owner), amount: total.clone(), fee: None, memo: Some(b"budget_delete_refund_all".to_vec()), created_at_time: Some(time()) };
-            let _ = icrc1_transfer(asset, arg).await; // best-effort
+            let fee = icrc1_fee(asset).await?;
+            if total.0 > fee.0 {
+                let call = LedgerCall::Transfer { from_subaccount: escrow.subaccount.clone(), to: principal_account(owner) };
+                let mut op = NewOperation::new(OperationKind::Refund, EntityKind::Budget, &id, owner, asset, call, Nat(&total.0 - &fee.0)).with_memo(DELETE_REFUND_MEMO);
+                op.reserved = total;
+                match journal::execute(op).await {
+                    Ok(_) => {}
+                    Err(e) if journal::is_in_doubt(&e) => {
+                        clear_budget_timers(&id);
+                        with_budget_mut(&id, |bi| {
+                            bi.status = BudgetStatus::Archived;
+                            bi.updated_at_ns = time();
+                        });
+                        return Err(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
         }
     }
 
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        if let Some(mut bi) = map.remove(&id) {
-            if let Some(t) = bi.lock_timer.take() { let _ = clear_timer(t); }
-            if let Some(t) = bi.unlock_timer.take() { let _ = clear_timer(t); }
-        }
-    });
+    remove_budget(&id);
     Ok(())
 }
 
@@ -616,28 +1429,49 @@ pub async fn delete_budget(id: String) -> Result<(), String> {
 pub struct BudgetSchedulePreviewItem {
     pub kind: String, // "lock" | "unlock"
     pub at_time_ns: u64,
-    pub amount: Nat,
+    pub amount: Nat,  // lock: jumlah dikunci; unlock: tambahan yang vested pada titik ini
+    pub period_index: u32,
 }
 
-pub fn budget_preview_schedule(id: String) -> Result<Vec<BudgetSchedulePreviewItem>, String> {
-    BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        Ok(vec![
-            BudgetSchedulePreviewItem { kind: "lock".to_string(), at_time_ns: bi.info.period_start_ns, amount: bi.info.amount_to_lock.clone() },
-            BudgetSchedulePreviewItem { kind: "vest_end".to_string(), at_time_ns: bi.info.period_end_ns, amount: bi.info.amount_to_lock.clone() },
-        ])
-    })
+const SCHEDULE_PREVIEW_MAX_PERIODS: u32 = 12;
+
+pub fn budget_preview_schedule(id: String) -> Result<Vec<BudgetSchedulePreviewItem>, FintrackError> {
+    let bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    // Periode aktif + periode recurring berikutnya (dibatasi), lengkap dengan setiap tranche unlock
+    let mut items = Vec::new();
+    let mut cursor = bi.clone();
+    for _ in 0..SCHEDULE_PREVIEW_MAX_PERIODS {
+        let funded = cursor.period_index == bi.period_index && bi.period_locked.0 > num::BigUint::from(0u32);
+        let amount = if funded { bi.period_locked.clone() } else { cursor.amount_to_lock.clone() };
+        items.push(BudgetSchedulePreviewItem { kind: "lock".to_string(), at_time_ns: cursor.period_start_ns, amount: amount.clone(), period_index: cursor.period_index });
+
+        let start = cursor.period_start_ns;
+        let duration = cursor.period_end_ns.max(start + 1) - start;
+        let mut unlocked = num::BigUint::from(0u32);
+        for offset in vesting::unlock_points(vesting_schedule(&cursor), cursor.first_period_start_ns, duration) {
+            let target = vesting::vested_amount(&amount.0, &vested_ratio(&cursor, offset, duration));
+            if target <= unlocked { continue; }
+            items.push(BudgetSchedulePreviewItem { kind: "unlock".to_string(), at_time_ns: start + offset, amount: Nat(&target - &unlocked), period_index: cursor.period_index });
+            unlocked = target;
+        }
+
+        match next_period(&cursor) {
+            Some((index, start, end)) => {
+                cursor.period_index = index;
+                cursor.period_start_ns = start;
+                cursor.period_end_ns = end;
+            }
+            None => break,
+        }
+    }
+    Ok(items)
 }
 
-pub fn budget_required_allowance(id: String) -> Result<Nat, String> {
-    BUDGETS.with(|b| {
-        let map = b.borrow();
-        let bi = map.get(&id).ok_or("budget not found")?;
-        if bi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        Ok(bi.info.amount_to_lock.clone())
-    })
+pub fn budget_required_allowance(id: String) -> Result<Nat, FintrackError> {
+    let bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    Ok(bi.amount_to_lock.clone())
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -647,26 +1481,21 @@ pub struct BudgetAmountRequirements {
     pub required_user_balance: Nat // allowance + fee (disarankan tersedia di akun user)
 }
 
-pub async fn budget_required_amounts(id: String) -> Result<BudgetAmountRequirements, String> {
-    let (asset, owner, monthly) = BUDGETS.with(|b| {
-        let map = b.borrow();
-        match map.get(&id) {
-            Some(bi) => Ok((bi.info.asset_canister, bi.info.owner, bi.info.amount_to_lock.clone())),
-            None => Err("budget not found".to_string()),
-        }
-    })?;
-    if owner != caller() { return Err("unauthorized".to_string()); }
+pub async fn budget_required_amounts(id: String) -> Result<BudgetAmountRequirements, FintrackError> {
+    let (asset, owner, monthly) = match load_budget(&id) {
+        Some(bi) => (bi.asset_canister, bi.owner, bi.amount_to_lock.clone()),
+        None => return Err(FintrackError::not_found("budget", id)),
+    };
+    if owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
     let fee = icrc1_fee(asset).await?;
     let required = Nat(&monthly.0 + &fee.0);
     // Tandai period completed jika fully vested
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        if let Some(bi) = map.get_mut(&id) {
-            if bi.info.unlocked_so_far.0 >= bi.info.period_locked.0 {
-                if bi.info.status != BudgetStatus::Completed {
-                    bi.info.status = BudgetStatus::Completed;
-                    push_event(&id, BudgetEvent { at_time_ns: time(), kind: BudgetEventKind::PeriodCompleted, amount: Some(bi.info.period_locked.clone()), note: None });
-                }
+    with_budget_mut(&id, |bi| {
+        let locked = bi.period_locked.0 > num::BigUint::from(0u32);
+        if locked && bi.unlocked_so_far.0 >= bi.period_locked.0 && next_period(bi).is_none() {
+            if bi.status != BudgetStatus::Completed {
+                bi.status = BudgetStatus::Completed;
+                push_event(&id, budget_event(bi, BudgetEventKind::PeriodCompleted, Some(bi.period_locked.clone()), None));
             }
         }
     });
@@ -678,8 +1507,8 @@ pub async fn budget_preview_requirements(
     asset_canister: Principal,
     asset_kind: AssetKind,
     amount_to_lock: Nat,
-) -> Result<BudgetAmountRequirements, String> {
-    if amount_to_lock.0 == num::BigUint::from(0u32) { return Err("amount_to_lock harus > 0".to_string()); }
+) -> Result<BudgetAmountRequirements, FintrackError> {
+    if amount_to_lock.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount_to_lock", "must be greater than zero")); }
     let fee = icrc1_fee(asset_canister).await?;
     let allowance = amount_to_lock;
     let required_user_balance = Nat(&allowance.0 + &fee.0);
@@ -687,7 +1516,7 @@ pub async fn budget_preview_requirements(
 }
 
 // Create budget dan langsung lock pertama (sekali) bila memungkinkan
-pub async fn budget_create_and_lock(req: BudgetCreateRequest) -> Result<BudgetInfo, String> {
+pub async fn budget_create_and_lock(req: BudgetCreateRequest) -> Result<BudgetInfo, FintrackError> {
     // create dulu
     let info = create_budget(req.clone()).await?;
     // lalu trigger lock sekali; jika gagal karena allowance/saldo, tetap kembalikan info budget
@@ -695,46 +1524,308 @@ pub async fn budget_create_and_lock(req: BudgetCreateRequest) -> Result<BudgetIn
     Ok(info)
 }
 
-// Upgrade handling: serialize/deserialize budgets state
-// NOTE: timers don't persist; we will recreate them on post_upgrade
+// ---------- Analytics (budget vs. actual) ----------
 
-#[derive(CandidType, Deserialize, Clone)]
-pub struct PersistBudget {
-    pub info: BudgetInfo,
+// Agregat event dalam satu periode
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetPeriodStats {
+    pub period_index: u32,
+    pub period_start_ns: u64,
+    pub period_end_ns: u64,
+    pub locked: Nat,      // LockSucceeded (transfer_from user, tanpa carry-over)
+    pub withdrawn: Nat,   // Withdraw ke owner (termasuk fee)
+    pub spent: Nat,       // Spend ke pihak ketiga (termasuk fee)
+    pub rolled_over: Nat, // surplus yang di-carry/sweep/refund di akhir periode
 }
 
-thread_local! {
-    static PERSIST: std::cell::RefCell<Vec<PersistBudget>> = std::cell::RefCell::new(Vec::new());
+// Transfer ledger ber-memo budget pada akun escrow budget ini dalam jendela periode aktif
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetLedgerActivity {
+    pub synced_at_ns: u64, // waktu index ledger dibaca
+    pub transfers: u32,
+    pub locked: Nat,    // BUDGET_LOCK
+    pub withdrawn: Nat, // BUDGET_WITHDRAW (net, tanpa fee)
+    pub refunded: Nat,  // BUDGET_REFUND
 }
 
-pub fn pre_upgrade() {
-    let data: Vec<PersistBudget> = BUDGETS.with(|b| b.borrow().values().map(|bi| PersistBudget { info: bi.info.clone() }).collect());
-    PERSIST.with(|p| *p.borrow_mut() = data);
-    // Events are in-memory only for now (can be persisted similarly if needed)
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum BudgetWarning {
+    // Dengan burn rate sekarang dana periode habis sebelum period_end_ns
+    ProjectedShortfall { amount: Nat, runway_days: u64, remaining_days: u64 },
+    // Pemakaian sampai titik ini lebih tinggi dari periode lalu pada titik yang sama
+    AbovePreviousPeriod { change_bps: i64 },
+    // Periode ini belum di-lock (lock gagal / belum jatuh tempo)
+    PeriodNotFunded,
 }
 
-pub fn post_upgrade() {
-    let data = PERSIST.with(|p| p.borrow().clone());
-    BUDGETS.with(|b| {
-        let mut map = b.borrow_mut();
-        map.clear();
-        for pb in data.into_iter() {
-            let id = pb.info.id.clone();
-            let lt = schedule_lock_timer_at(id.clone(), pb.info.next_lock_at_ns);
-            map.insert(id, BudgetInternal { info: pb.info, lock_timer: Some(lt), unlock_timer: None });
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetAnalytics {
+    pub budget_id: String,
+    pub now_ns: u64,
+    pub period_index: u32,
+    pub period_start_ns: u64,
+    pub period_end_ns: u64,
+    pub elapsed_ns: u64,
+    pub remaining_ns: u64,
+    pub period_locked: Nat,
+    pub vested: Nat,                // proyeksi unlocked sampai now
+    pub used: Nat,                  // withdrawn + spent periode ini
+    pub remaining_funds: Nat,       // locked_balance + available (proyeksi, tanpa carry-over)
+    pub vest_rate_per_day: Nat,     // base units/hari
+    pub burn_rate_per_day: Nat,     // used / hari berjalan
+    pub projected_end_balance: Nat, // remaining_funds - burn sampai akhir periode (min 0)
+    pub runway_days: Option<u64>,   // None = belum ada pemakaian
+    pub current: BudgetPeriodStats,
+    pub previous: Option<BudgetPeriodStats>,
+    pub change_vs_previous_bps: Option<i64>, // pemakaian s/d titik yang sama di periode lalu
+    pub warnings: Vec<BudgetWarning>,
+    pub ledger: Option<BudgetLedgerActivity>, // None = asset tanpa index canister atau index gagal dibaca
+}
+
+const OVERSPEND_CHANGE_BPS: i64 = 2_000; // warning bila pemakaian > 120% periode lalu
+
+fn add_nat(into: &mut Nat, amount: &Nat) { *into = Nat(&into.0 + &amount.0); }
+
+// Event milik periode `index`: pakai period_index, event v1 (tanpa index) dicocokkan lewat waktu
+fn event_in_period(ev: &BudgetEvent, index: u32, start: u64, end: u64) -> bool {
+    match ev.period_index {
+        Some(i) => i == index,
+        None => ev.at_time_ns >= start && ev.at_time_ns < end,
+    }
+}
+
+fn period_stats(events: &[BudgetEvent], index: u32, start: u64, end: u64, until_ns: Option<u64>) -> BudgetPeriodStats {
+    let mut st = BudgetPeriodStats { period_index: index, period_start_ns: start, period_end_ns: end, locked: zero(), withdrawn: zero(), spent: zero(), rolled_over: zero() };
+    for ev in events.iter().filter(|ev| event_in_period(ev, index, start, end)) {
+        if until_ns.map(|u| ev.at_time_ns > u).unwrap_or(false) { continue; }
+        let Some(amount) = ev.amount.as_ref() else { continue };
+        match ev.kind {
+            BudgetEventKind::LockSucceeded => add_nat(&mut st.locked, amount),
+            BudgetEventKind::Withdraw => add_nat(&mut st.withdrawn, amount),
+            BudgetEventKind::Spend => add_nat(&mut st.spent, amount),
+            BudgetEventKind::RolloverCarried | BudgetEventKind::RolloverSwept | BudgetEventKind::RolloverRefunded => add_nat(&mut st.rolled_over, amount),
+            _ => {}
+        }
+    }
+    st
+}
+
+const LEDGER_ACTIVITY_MAX_RESULTS: u32 = 200;
+
+// Dibaca langsung dari index ledger asset budget, difilter ke akun escrow budget ini
+async fn ledger_activity(bi: &BudgetInfo, start: u64, end: u64) -> Option<BudgetLedgerActivity> {
+    let sub = escrow_subaccount(&bi.owner, &bi.id);
+    let txs = match super::transactions::budget_escrow_transactions(bi.asset_canister, sub, LEDGER_ACTIVITY_MAX_RESULTS).await {
+        Ok(txs) => txs,
+        Err(e) => {
+            ic_cdk::println!("budget {}: ledger activity unavailable: {}", bi.id, e);
+            return None;
         }
+    };
+    let mut out = BudgetLedgerActivity { synced_at_ns: time(), transfers: 0, locked: zero(), withdrawn: zero(), refunded: zero() };
+    for tx in txs.iter().filter(|tx| tx.timestamp >= start && tx.timestamp < end) {
+        out.transfers += 1;
+        match tx.operation.as_str() {
+            "BUDGET_LOCK" => add_nat(&mut out.locked, &tx.amount),
+            "BUDGET_WITHDRAW" => add_nat(&mut out.withdrawn, &tx.amount),
+            "BUDGET_REFUND" => add_nat(&mut out.refunded, &tx.amount),
+            _ => {}
+        }
+    }
+    Some(out)
+}
+
+fn used(st: &BudgetPeriodStats) -> num::BigUint { &st.withdrawn.0 + &st.spent.0 }
+
+pub async fn budget_get_analytics(id: String) -> Result<BudgetAnalytics, FintrackError> {
+    use num_traits::ToPrimitive;
+    let bi = load_budget(&id).ok_or_else(|| FintrackError::not_found("budget", id.clone()))?;
+    if bi.owner != caller() { return Err(FintrackError::unauthorized("not the budget owner")); }
+    let now = time();
+    let events = load_events(&id);
+
+    let (start, end) = (bi.period_start_ns, bi.period_end_ns.max(bi.period_start_ns + 1));
+    let duration = end - start;
+    let clamped = now.clamp(start, end);
+    let elapsed = clamped - start;
+    let remaining = end - clamped;
+
+    // Proyeksi state sampai now tanpa menulis (sama dengan accrual saat withdraw)
+    let mut projected = bi.clone();
+    accrue(&mut projected, now, None);
+    let remaining_funds = Nat(&projected.locked_balance.0 + &projected.available_to_withdraw.0);
+
+    let current = period_stats(&events, bi.period_index, start, end, None);
+    let used_now = used(&current);
+    let vest_rate_per_day = Nat((&bi.period_locked.0 * NS_PER_DAY) / duration);
+    let burn_rate_per_day = Nat((&used_now * NS_PER_DAY) / elapsed.max(1));
+
+    let burn_until_end = (&burn_rate_per_day.0 * remaining) / NS_PER_DAY;
+    let (projected_end_balance, shortfall) = if burn_until_end > remaining_funds.0 {
+        (zero(), Nat(&burn_until_end - &remaining_funds.0))
+    } else {
+        (Nat(&remaining_funds.0 - &burn_until_end), zero())
+    };
+    let runway_days = if burn_rate_per_day.0 == num::BigUint::from(0u32) {
+        None
+    } else {
+        Some((&remaining_funds.0 / &burn_rate_per_day.0).to_u64().unwrap_or(u64::MAX))
+    };
+
+    // Periode sebelumnya: total penuh + pemakaian sampai titik waktu yang sama
+    let (previous, change_vs_previous_bps) = match bi.period_index.checked_sub(1) {
+        Some(prev_index) => {
+            let (ps, pe) = period_bounds(&bi, prev_index);
+            let full = period_stats(&events, prev_index, ps, pe, None);
+            let to_date = period_stats(&events, prev_index, ps, pe, Some(ps.saturating_add(elapsed)));
+            let prev_used = used(&to_date);
+            let change = if prev_used == num::BigUint::from(0u32) {
+                None
+            } else {
+                ((&used_now * 10_000u32) / &prev_used).to_i64().map(|r| r - 10_000)
+            };
+            (Some(full), change)
+        }
+        None => (None, None),
+    };
+
+    let mut warnings = Vec::new();
+    if bi.period_locked.0 == num::BigUint::from(0u32) && now >= bi.next_lock_at_ns {
+        warnings.push(BudgetWarning::PeriodNotFunded);
+    }
+    if shortfall.0 > num::BigUint::from(0u32) {
+        warnings.push(BudgetWarning::ProjectedShortfall { amount: shortfall, runway_days: runway_days.unwrap_or(0), remaining_days: remaining / NS_PER_DAY });
+    }
+    if let Some(change) = change_vs_previous_bps {
+        if change > OVERSPEND_CHANGE_BPS { warnings.push(BudgetWarning::AbovePreviousPeriod { change_bps: change }); }
+    }
+
+    Ok(BudgetAnalytics {
+        budget_id: id,
+        now_ns: now,
+        period_index: bi.period_index,
+        period_start_ns: start,
+        period_end_ns: end,
+        elapsed_ns: elapsed,
+        remaining_ns: remaining,
+        period_locked: bi.period_locked.clone(),
+        vested: projected.unlocked_so_far.clone(),
+        used: Nat(used_now),
+        remaining_funds,
+        vest_rate_per_day,
+        burn_rate_per_day,
+        projected_end_balance,
+        runway_days,
+        current,
+        previous,
+        change_vs_previous_bps,
+        warnings,
+        ledger: ledger_activity(&bi, start, end).await,
+    })
+}
+
+// Upgrade handling: budgets & events sudah di stable memory.
+// Yang perlu dilakukan setelah upgrade: migrasi record versi lama dan arm ulang timer (timer tidak persist).
+
+fn migrate_budget_records() {
+    let stale: Vec<(StableText, Versioned)> = BUDGETS.with(|b| {
+        b.borrow().iter().filter(|(_, rec)| rec.version != BUDGET_RECORD_VERSION).collect()
     });
+    for (key, rec) in stale {
+        match decode_budget(&rec) {
+            Ok(info) => { BUDGETS.with(|b| b.borrow_mut().insert(key, Versioned::encode(BUDGET_RECORD_VERSION, &info))); }
+            Err(e) => ic_cdk::println!("budget {} migration skipped: {}", key.0, e),
+        }
+    }
+
+    let stale_events: Vec<(StableText, Versioned)> = EVENTS.with(|e| {
+        e.borrow().iter().filter(|(_, rec)| rec.version != BUDGET_EVENT_RECORD_VERSION).collect()
+    });
+    for (key, rec) in stale_events {
+        match decode_budget_event(&rec) {
+            Ok(ev) => { EVENTS.with(|e| e.borrow_mut().insert(key, Versioned::encode(BUDGET_EVENT_RECORD_VERSION, &ev))); }
+            Err(e) => ic_cdk::println!("budget event {} migration skipped: {}", key.0, e),
+        }
+    }
 }
 
-pub fn budget_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<BudgetEvent>, String> {
+pub fn post_upgrade() {
+    migrate_budget_records();
+    // Budget aktif: timer lock (periode belum di-lock) atau timer akhir periode
+    for bi in all_budgets() {
+        arm_budget_timers(&bi);
+    }
+}
+
+pub fn budget_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<BudgetEvent>, FintrackError> {
     let off = offset.unwrap_or(0) as usize;
     let lim = limit.unwrap_or(50) as usize;
-    EVENTS.with(|e| {
-        let map = e.borrow();
-        let v = map.get(&id).cloned().unwrap_or_default();
-        let end = (off + lim).min(v.len());
-        if off >= v.len() { Ok(vec![]) } else { Ok(v[off..end].to_vec()) }
-    })
+    let v = load_events(&id);
+    let end = (off + lim).min(v.len());
+    if off >= v.len() { Ok(vec![]) } else { Ok(v[off..end].to_vec()) }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percent(bps: u16) -> EnvelopeAllocation { EnvelopeAllocation::Percent { bps } }
+
+    fn fixed(amount: u64) -> EnvelopeAllocation { EnvelopeAllocation::Fixed { amount: Nat::from(amount) } }
+
+    fn specs(allocations: Vec<EnvelopeAllocation>) -> Vec<EnvelopeSpec> {
+        allocations.into_iter().enumerate().map(|(i, allocation)| EnvelopeSpec { name: format!("env {}", i), category_id: None, allocation }).collect()
+    }
+
+    fn split(allocations: Vec<EnvelopeAllocation>, total: u64) -> Vec<u64> {
+        use num_traits::ToPrimitive;
+        let envs = build_envelopes(specs(allocations));
+        split_allocation(&envs, &Nat::from(total)).into_iter().map(|n| n.0.to_u64().unwrap()).collect()
+    }
+
+    fn validate(allocations: Vec<EnvelopeAllocation>, amount_to_lock: u64) -> Result<(), FintrackError> {
+        validate_envelopes(&specs(allocations), &Principal::anonymous(), &Nat::from(amount_to_lock))
+    }
+
+    #[test]
+    fn envelopes_must_cover_amount_to_lock() {
+        assert!(validate(vec![percent(6_000), percent(4_000)], 1_000).is_ok());
+        assert!(validate(vec![fixed(300), percent(10_000)], 1_000).is_ok());
+        assert!(validate(vec![fixed(400), fixed(600)], 1_000).is_ok());
+        assert!(validate(vec![percent(6_000), percent(3_000)], 1_000).is_err());
+        assert!(validate(vec![fixed(400), fixed(500)], 1_000).is_err());
+    }
+
+    #[test]
+    fn envelope_caps_are_enforced() {
+        assert!(validate(vec![], 1_000).is_err());
+        assert!(validate(vec![percent(500); MAX_ENVELOPES], 1_000).is_ok());
+        assert!(validate(vec![percent(500); MAX_ENVELOPES + 1], 1_000).is_err());
+        assert!(validate(vec![fixed(1_001), percent(10_000)], 1_000).is_err());
+        assert!(validate(vec![percent(0), percent(10_000)], 1_000).is_err());
+        assert!(validate(vec![fixed(0), fixed(1_000)], 1_000).is_err());
+    }
+
+    #[test]
+    fn split_sums_to_total() {
+        let shares = split(vec![fixed(100), percent(3_333), percent(6_667)], 1_000);
+        assert_eq!(shares, vec![100, 299, 601]);
+        assert_eq!(shares.iter().sum::<u64>(), 1_000);
+        assert_eq!(split(vec![fixed(400), fixed(600)], 1_000), vec![400, 600]);
+    }
+
+    #[test]
+    fn split_remainder_goes_to_last_percent_envelope() {
+        assert_eq!(split(vec![percent(3_333), percent(3_333), percent(3_334)], 100), vec![33, 33, 34]);
+        assert_eq!(split(vec![percent(3_334), percent(3_333), percent(3_333)], 1), vec![0, 0, 1]);
+        assert_eq!(split(vec![percent(5_000), fixed(10), percent(5_000)], 11), vec![0, 10, 1]);
+    }
+
+    #[test]
+    fn split_caps_fixed_envelopes_at_total() {
+        assert_eq!(split(vec![fixed(600), fixed(600), percent(10_000)], 1_000), vec![600, 400, 0]);
+        assert_eq!(split(vec![fixed(600), percent(10_000)], 0), vec![0, 0]);
+    }
+}
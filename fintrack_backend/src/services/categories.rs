@@ -0,0 +1,193 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use super::budget;
+use super::errors::FintrackError;
+use super::storage::{self, Candid, StableText, VMem};
+
+// Kategori pengeluaran (groceries, rent, ...) milik satu owner, boleh bertingkat (parent/child).
+// Kategori hanya label: dana tetap di escrow budget, envelope yang menunjuk ke kategori.
+
+const MAX_CATEGORY_DEPTH: usize = 5;
+const MAX_CATEGORIES_PER_OWNER: usize = 200;
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Category {
+    pub id: String,
+    pub owner: Principal,
+    pub name: String,
+    pub parent_id: Option<String>, // None = kategori root
+    pub created_at_ns: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CategoryTotals {
+    pub envelopes: u32,
+    pub funded_total: Nat,
+    pub withdrawn_total: Nat,
+    pub spent_total: Nat, // dibayar ke pihak ketiga lewat budget_spend
+    pub locked_balance: Nat,
+    pub available_to_withdraw: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CategoryReport {
+    pub category: Category,
+    pub direct: CategoryTotals, // envelope yang langsung memakai kategori ini
+    pub total: CategoryTotals,  // direct + seluruh turunan
+}
+
+thread_local! {
+    // key = "{owner}|{category_id}" -> kategori satu owner berurutan dalam range scan
+    static CATEGORIES: RefCell<StableBTreeMap<StableText, Candid<Category>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::CATEGORIES_MEMORY_ID))
+    );
+}
+
+fn category_key(owner: &Principal, id: &str) -> StableText {
+    StableText(format!("{}|{}", owner, id))
+}
+
+fn load_category(owner: &Principal, id: &str) -> Option<Category> {
+    CATEGORIES.with(|c| c.borrow().get(&category_key(owner, id))).map(|c| c.0)
+}
+
+fn owner_categories(owner: &Principal) -> Vec<Category> {
+    let prefix = format!("{}|", owner);
+    CATEGORIES.with(|c| {
+        c.borrow().range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .map(|(_, v)| v.0)
+            .collect()
+    })
+}
+
+// Kedalaman kategori (root = 1)
+fn depth(owner: &Principal, id: &str) -> usize {
+    let mut depth = 0;
+    let mut cursor = Some(id.to_string());
+    while let Some(cid) = cursor {
+        depth += 1;
+        if depth > MAX_CATEGORY_DEPTH { break; }
+        cursor = load_category(owner, &cid).and_then(|c| c.parent_id);
+    }
+    depth
+}
+
+pub(crate) fn exists(owner: &Principal, id: &str) -> bool {
+    load_category(owner, id).is_some()
+}
+
+pub fn create_category(name: String, parent_id: Option<String>) -> Result<Category, FintrackError> {
+    let owner = caller();
+    if owner == Principal::anonymous() { return Err(FintrackError::unauthorized("anonymous caller")); }
+    let name = name.trim().to_string();
+    if name.is_empty() { return Err(FintrackError::invalid_input("name", "must not be empty")); }
+    if name.chars().count() > MAX_NAME_LEN { return Err(FintrackError::invalid_input("name", format!("at most {} characters", MAX_NAME_LEN))); }
+
+    let existing = owner_categories(&owner);
+    if existing.len() >= MAX_CATEGORIES_PER_OWNER {
+        return Err(FintrackError::invalid_state(format!("at most {} categories per owner", MAX_CATEGORIES_PER_OWNER)));
+    }
+    if let Some(pid) = parent_id.as_ref() {
+        if !exists(&owner, pid) { return Err(FintrackError::not_found("category", pid.clone())); }
+        if depth(&owner, pid) >= MAX_CATEGORY_DEPTH {
+            return Err(FintrackError::invalid_input("parent_id", format!("categories nest at most {} levels", MAX_CATEGORY_DEPTH)));
+        }
+    }
+    // Nama unik di antara saudara (case-insensitive)
+    if existing.iter().any(|c| c.parent_id == parent_id && c.name.eq_ignore_ascii_case(&name)) {
+        return Err(FintrackError::invalid_input("name", "a sibling category with this name already exists"));
+    }
+
+    let created = time();
+    let mut id = format!("cat-{}", created);
+    let mut n = 1;
+    while exists(&owner, &id) {
+        id = format!("cat-{}-{}", created, n);
+        n += 1;
+    }
+    let category = Category { id: id.clone(), owner, name, parent_id, created_at_ns: created };
+    CATEGORIES.with(|c| c.borrow_mut().insert(category_key(&owner, &id), Candid(category.clone())));
+    Ok(category)
+}
+
+pub fn list_categories() -> Vec<Category> {
+    owner_categories(&caller())
+}
+
+pub fn delete_category(id: String) -> Result<(), FintrackError> {
+    let owner = caller();
+    if !exists(&owner, &id) { return Err(FintrackError::not_found("category", id)); }
+    if owner_categories(&owner).iter().any(|c| c.parent_id.as_deref() == Some(id.as_str())) {
+        return Err(FintrackError::invalid_state("category has child categories"));
+    }
+    if budget::category_in_use(&owner, &id) {
+        return Err(FintrackError::invalid_state("category is used by a budget envelope"));
+    }
+    CATEGORIES.with(|c| c.borrow_mut().remove(&category_key(&owner, &id)));
+    Ok(())
+}
+
+fn empty_totals() -> CategoryTotals {
+    let zero = || Nat::from(0u64);
+    CategoryTotals { envelopes: 0, funded_total: zero(), withdrawn_total: zero(), spent_total: zero(), locked_balance: zero(), available_to_withdraw: zero() }
+}
+
+fn add_totals(into: &mut CategoryTotals, other: &CategoryTotals) {
+    into.envelopes += other.envelopes;
+    into.funded_total = Nat(&into.funded_total.0 + &other.funded_total.0);
+    into.withdrawn_total = Nat(&into.withdrawn_total.0 + &other.withdrawn_total.0);
+    into.spent_total = Nat(&into.spent_total.0 + &other.spent_total.0);
+    into.locked_balance = Nat(&into.locked_balance.0 + &other.locked_balance.0);
+    into.available_to_withdraw = Nat(&into.available_to_withdraw.0 + &other.available_to_withdraw.0);
+}
+
+// Total per kategori untuk satu asset (jumlah ckBTC dan ckETH tidak dijumlahkan)
+pub fn category_report(owner: Option<Principal>, asset: Principal) -> Vec<CategoryReport> {
+    let who = owner.unwrap_or_else(caller);
+    let categories = owner_categories(&who);
+
+    let mut direct: BTreeMap<String, CategoryTotals> = BTreeMap::new();
+    for env in budget::envelopes_by_owner(&who, &asset) {
+        let Some(cid) = env.category_id.clone() else { continue };
+        let t = direct.entry(cid).or_insert_with(empty_totals);
+        add_totals(t, &CategoryTotals {
+            envelopes: 1,
+            funded_total: env.funded_total,
+            withdrawn_total: env.withdrawn_total,
+            spent_total: Nat::from(0u64), // dari event Spend di bawah
+            locked_balance: env.locked_balance,
+            available_to_withdraw: env.available_to_withdraw,
+        });
+    }
+
+    // Spend dihitung per kategori event (bisa beda dari kategori envelope, atau budget tanpa envelope)
+    for (cid, spent) in budget::spend_by_category(&who, &asset) {
+        let t = direct.entry(cid).or_insert_with(empty_totals);
+        t.spent_total = Nat(&t.spent_total.0 + &spent.0);
+    }
+
+    categories.iter().map(|cat| {
+        let own = direct.get(&cat.id).cloned().unwrap_or_else(empty_totals);
+        let mut total = own.clone();
+        // Jumlahkan semua turunan: kategori yang punya `cat` di rantai parent-nya
+        for other in categories.iter().filter(|o| o.id != cat.id) {
+            let mut parent = other.parent_id.clone();
+            let mut hops = 0;
+            while let Some(pid) = parent {
+                if pid == cat.id {
+                    if let Some(t) = direct.get(&other.id) { add_totals(&mut total, t); }
+                    break;
+                }
+                hops += 1;
+                if hops > MAX_CATEGORY_DEPTH { break; }
+                parent = categories.iter().find(|c| c.id == pid).and_then(|c| c.parent_id.clone());
+            }
+        }
+        CategoryReport { category: cat.clone(), direct: own, total }
+    }).collect()
+}
@@ -0,0 +1,193 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_stable_structures::StableCell;
+use std::cell::RefCell;
+use super::ethtransfer::EthereumNetwork;
+use super::evm_rpc_canister::{EthMainnetService, EthSepoliaService, RpcApi, RpcService, RpcServices};
+use super::errors::FintrackError;
+use super::storage::{self, Candid, VMem};
+
+// Konfigurasi runtime canister: principal ledger/minter/index, network dan nama key ECDSA.
+// Diisi dari InitArg saat install/upgrade, disimpan di stable memory, bisa diubah controller.
+// Default = deployment yang selama ini di-hardcode (ckBTC/ckETH mainnet, BTC testnet, Sepolia, key_1).
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitcoinNetwork {
+    Mainnet,
+    Testnet,
+    Regtest, // dfx lokal
+}
+
+impl BitcoinNetwork {
+    pub fn ic_network(&self) -> ic_cdk::bitcoin_canister::Network {
+        match self {
+            BitcoinNetwork::Mainnet => ic_cdk::bitcoin_canister::Network::Mainnet,
+            BitcoinNetwork::Testnet => ic_cdk::bitcoin_canister::Network::Testnet,
+            BitcoinNetwork::Regtest => ic_cdk::bitcoin_canister::Network::Regtest,
+        }
+    }
+
+    pub fn btc_network(&self) -> bitcoin::Network {
+        match self {
+            BitcoinNetwork::Mainnet => bitcoin::Network::Bitcoin,
+            BitcoinNetwork::Testnet => bitcoin::Network::Testnet,
+            BitcoinNetwork::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CanisterConfig {
+    pub ecdsa_key_name: String, // "key_1" (mainnet), "test_key_1", "dfx_test_key" (lokal)
+    pub bitcoin_network: BitcoinNetwork,
+    pub ethereum_network: EthereumNetwork,
+    pub evm_rpc_canister: Principal,
+    pub evm_rpc_url: Option<String>, // hanya dipakai untuk EthereumNetwork::Local
+    pub ckbtc_minter: Principal,
+    pub ckbtc_ledger: Principal,
+    pub ckbtc_index: Principal,
+    pub cketh_minter: Principal,
+    pub cketh_ledger: Principal,
+    pub cketh_index: Principal,
+    pub kongswap_canister: Principal,
+}
+
+impl Default for CanisterConfig {
+    fn default() -> Self {
+        let p = |text: &str| Principal::from_text(text).expect("invalid default principal");
+        CanisterConfig {
+            ecdsa_key_name: "key_1".to_string(),
+            bitcoin_network: BitcoinNetwork::Testnet,
+            ethereum_network: EthereumNetwork::default(),
+            evm_rpc_canister: p("giifx-2iaaa-aaaab-qb5ua-cai"),
+            evm_rpc_url: None,
+            ckbtc_minter: p("ml52i-qqaaa-aaaar-qaaba-cai"),
+            ckbtc_ledger: p("mc6ru-gyaaa-aaaar-qaaaq-cai"),
+            ckbtc_index: p("mm444-5iaaa-aaaar-qaabq-cai"),
+            cketh_minter: p("jzenf-aiaaa-aaaar-qaa7q-cai"),
+            cketh_ledger: p("apia6-jaaaa-aaaar-qabma-cai"),
+            cketh_index: p("sh5u2-cqaaa-aaaar-qacna-cai"),
+            kongswap_canister: p("2ipq2-uqaaa-aaaar-qailq-cai"),
+        }
+    }
+}
+
+/// Argumen install/upgrade. Field yang None mempertahankan nilai tersimpan (atau default).
+#[derive(CandidType, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct InitArg {
+    pub ecdsa_key_name: Option<String>,
+    pub bitcoin_network: Option<BitcoinNetwork>,
+    pub ethereum_network: Option<EthereumNetwork>,
+    pub evm_rpc_canister: Option<Principal>,
+    pub evm_rpc_url: Option<String>,
+    pub ckbtc_minter: Option<Principal>,
+    pub ckbtc_ledger: Option<Principal>,
+    pub ckbtc_index: Option<Principal>,
+    pub cketh_minter: Option<Principal>,
+    pub cketh_ledger: Option<Principal>,
+    pub cketh_index: Option<Principal>,
+    pub kongswap_canister: Option<Principal>,
+}
+
+impl CanisterConfig {
+    fn apply(&mut self, arg: InitArg) {
+        if let Some(v) = arg.ecdsa_key_name { self.ecdsa_key_name = v; }
+        if let Some(v) = arg.bitcoin_network { self.bitcoin_network = v; }
+        if let Some(v) = arg.ethereum_network { self.ethereum_network = v; }
+        if let Some(v) = arg.evm_rpc_canister { self.evm_rpc_canister = v; }
+        if let Some(v) = arg.evm_rpc_url { self.evm_rpc_url = Some(v); }
+        if let Some(v) = arg.ckbtc_minter { self.ckbtc_minter = v; }
+        if let Some(v) = arg.ckbtc_ledger { self.ckbtc_ledger = v; }
+        if let Some(v) = arg.ckbtc_index { self.ckbtc_index = v; }
+        if let Some(v) = arg.cketh_minter { self.cketh_minter = v; }
+        if let Some(v) = arg.cketh_ledger { self.cketh_ledger = v; }
+        if let Some(v) = arg.cketh_index { self.cketh_index = v; }
+        if let Some(v) = arg.kongswap_canister { self.kongswap_canister = v; }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.ecdsa_key_name.trim().is_empty() { return Err("ecdsa_key_name cannot be empty".to_string()); }
+        if self.ethereum_network == EthereumNetwork::Local && self.evm_rpc_url.as_deref().map(str::is_empty).unwrap_or(true) {
+            return Err("evm_rpc_url is required for the Local ethereum network".to_string());
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CONFIG: RefCell<StableCell<Candid<CanisterConfig>, VMem>> = RefCell::new(
+        StableCell::init(storage::memory(storage::CONFIG_MEMORY_ID), Candid(CanisterConfig::default()))
+            .expect("failed to init config cell")
+    );
+}
+
+fn store(cfg: CanisterConfig) {
+    CONFIG.with(|c| c.borrow_mut().set(Candid(cfg))).expect("failed to store config");
+}
+
+/// Terapkan InitArg di atas config tersimpan; dipanggil dari init dan post_upgrade.
+pub fn apply_init_arg(arg: Option<InitArg>) {
+    let Some(arg) = arg else { return };
+    let mut cfg = get();
+    cfg.apply(arg);
+    if let Err(e) = cfg.validate() { ic_cdk::trap(&format!("invalid init arg: {}", e)); }
+    store(cfg);
+}
+
+/// Config aktif (dibaca setiap service)
+pub fn get() -> CanisterConfig {
+    CONFIG.with(|c| c.borrow().get().0.clone())
+}
+
+fn require_controller() -> Result<(), FintrackError> {
+    if ic_cdk::api::is_controller(&ic_cdk::caller()) { Ok(()) } else { Err(FintrackError::unauthorized("controller only")) }
+}
+
+pub fn get_config() -> Result<CanisterConfig, FintrackError> {
+    require_controller()?;
+    Ok(get())
+}
+
+pub fn update_config(cfg: CanisterConfig) -> Result<CanisterConfig, FintrackError> {
+    require_controller()?;
+    cfg.validate().map_err(|e| FintrackError::invalid_input("config", e))?;
+    store(cfg.clone());
+    Ok(cfg)
+}
+
+// -------------------------
+// Helper turunan untuk service
+// -------------------------
+
+pub fn ecdsa_key_name() -> String { get().ecdsa_key_name }
+
+pub fn ic_bitcoin_network() -> ic_cdk::bitcoin_canister::Network { get().bitcoin_network.ic_network() }
+
+pub fn btc_network() -> bitcoin::Network { get().bitcoin_network.btc_network() }
+
+pub fn chain_id() -> u64 { get().ethereum_network.chain_id() }
+
+pub fn evm_rpc_canister() -> Principal { get().evm_rpc_canister }
+
+fn local_rpc_api(cfg: &CanisterConfig) -> RpcApi {
+    RpcApi { url: cfg.evm_rpc_url.clone().unwrap_or_default(), headers: None }
+}
+
+/// Provider tunggal (requestCost, request)
+pub fn evm_rpc_service() -> RpcService {
+    let cfg = get();
+    match cfg.ethereum_network {
+        EthereumNetwork::Mainnet => RpcService::EthMainnet(EthMainnetService::PublicNode),
+        EthereumNetwork::Sepolia => RpcService::EthSepolia(EthSepoliaService::PublicNode),
+        EthereumNetwork::Local => RpcService::Custom(local_rpc_api(&cfg)),
+    }
+}
+
+/// Provider set untuk method multi-provider (eth_sendRawTransaction, eth_getTransactionCount, ...)
+pub fn evm_rpc_services() -> RpcServices {
+    let cfg = get();
+    match cfg.ethereum_network {
+        EthereumNetwork::Mainnet => RpcServices::EthMainnet(Some(vec![EthMainnetService::PublicNode])),
+        EthereumNetwork::Sepolia => RpcServices::EthSepolia(Some(vec![EthSepoliaService::PublicNode])),
+        EthereumNetwork::Local => RpcServices::Custom { chain_id: cfg.ethereum_network.chain_id(), services: vec![local_rpc_api(&cfg)] },
+    }
+}
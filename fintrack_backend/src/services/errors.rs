@@ -0,0 +1,93 @@
+use candid::{CandidType, Deserialize, Nat};
+use std::fmt;
+
+// Error terstruktur untuk candid interface (budget, goals, transfer, kongswap, rates).
+// Frontend cukup match pada variant, bukan string. Pesan teks di dalam variant berbahasa Inggris.
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum FintrackError {
+    Unauthorized { reason: String },
+    NotFound { kind: String, id: String },
+    InvalidInput { field: String, reason: String },
+    InvalidState { reason: String },
+    InsufficientFunds { needed: Nat, available: Nat },
+    LedgerError { message: String },
+    RpcError { message: String },
+    RpcInconsistent,
+    InvalidAddress { address: String, reason: String },
+    RateLimited,
+    HttpOutcallFailed { message: String },
+    Internal { message: String },
+    OperationInProgress { kind: String, id: String }, // operasi lain pada entitas yang sama belum selesai, aman untuk retry
+}
+
+impl FintrackError {
+    pub fn unauthorized(reason: impl Into<String>) -> Self {
+        FintrackError::Unauthorized { reason: reason.into() }
+    }
+
+    pub fn not_found(kind: &str, id: impl Into<String>) -> Self {
+        FintrackError::NotFound { kind: kind.to_string(), id: id.into() }
+    }
+
+    pub fn invalid_input(field: &str, reason: impl Into<String>) -> Self {
+        FintrackError::InvalidInput { field: field.to_string(), reason: reason.into() }
+    }
+
+    pub fn invalid_state(reason: impl Into<String>) -> Self {
+        FintrackError::InvalidState { reason: reason.into() }
+    }
+
+    pub fn ledger(message: impl Into<String>) -> Self {
+        FintrackError::LedgerError { message: message.into() }
+    }
+
+    pub fn rpc(message: impl Into<String>) -> Self {
+        FintrackError::RpcError { message: message.into() }
+    }
+
+    pub fn invalid_address(address: impl Into<String>, reason: impl fmt::Display) -> Self {
+        FintrackError::InvalidAddress { address: address.into(), reason: reason.to_string() }
+    }
+
+    pub fn http(message: impl Into<String>) -> Self {
+        FintrackError::HttpOutcallFailed { message: message.into() }
+    }
+
+    pub fn in_progress(kind: &str, id: impl Into<String>) -> Self {
+        FintrackError::OperationInProgress { kind: kind.to_string(), id: id.into() }
+    }
+}
+
+impl fmt::Display for FintrackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FintrackError::Unauthorized { reason } => write!(f, "unauthorized: {}", reason),
+            FintrackError::NotFound { kind, id } => write!(f, "{} not found: {}", kind, id),
+            FintrackError::InvalidInput { field, reason } => write!(f, "invalid {}: {}", field, reason),
+            FintrackError::InvalidState { reason } => write!(f, "invalid state: {}", reason),
+            FintrackError::InsufficientFunds { needed, available } => write!(f, "insufficient funds: needed {}, available {}", needed, available),
+            FintrackError::LedgerError { message } => write!(f, "ledger error: {}", message),
+            FintrackError::RpcError { message } => write!(f, "rpc error: {}", message),
+            FintrackError::RpcInconsistent => write!(f, "inconsistent rpc responses"),
+            FintrackError::InvalidAddress { address, reason } => write!(f, "invalid address {}: {}", address, reason),
+            FintrackError::RateLimited => write!(f, "rate limited by upstream provider"),
+            FintrackError::HttpOutcallFailed { message } => write!(f, "http outcall failed: {}", message),
+            FintrackError::Internal { message } => write!(f, "{}", message),
+            FintrackError::OperationInProgress { kind, id } => write!(f, "another operation on {} {} is in progress", kind, id),
+        }
+    }
+}
+
+// Helper internal yang masih mengembalikan String (mis. call antar canister) dipetakan ke Internal
+impl From<String> for FintrackError {
+    fn from(message: String) -> Self {
+        FintrackError::Internal { message }
+    }
+}
+
+impl From<&str> for FintrackError {
+    fn from(message: &str) -> Self {
+        FintrackError::Internal { message: message.to_string() }
+    }
+}
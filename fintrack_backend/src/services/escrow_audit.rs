@@ -0,0 +1,267 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use super::budget::{self, Account};
+use super::errors::FintrackError;
+use super::goals;
+use super::guard::EntityGuard;
+use super::journal::{self, EntityKind, LedgerCall, NewOperation, OperationKind};
+use super::storage::{self, Candid, StableText, VMem};
+use super::timers;
+
+// Rekonsiliasi escrow: bandingkan pembukuan canister (locked + available) dengan icrc1_balance_of
+// di subaccount escrow. Registry escrow disimpan terpisah dari budget/goal supaya escrow entitas
+// yang sudah dihapus (orphan) tetap bisa dicek dan dananya di-recover owner.
+
+const AUDIT_INTERVAL_NS: u64 = 6 * timers::SEC_PER_HOUR * timers::NS_PER_SEC;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowRecord {
+    pub entity: EntityKind,
+    pub entity_id: String,
+    pub owner: Principal,
+    pub ledger: Principal,
+    pub subaccount: Vec<u8>,
+    pub registered_at_ns: u64,
+    pub asset: Option<Principal>, // asset tambahan goal multi-asset; None = escrow utama entitas
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscrowDrift {
+    Balanced,
+    Surplus,   // saldo ledger > pembukuan: dana tidak tercatat, bisa di-recover owner
+    Deficit,   // saldo ledger < pembukuan, mis. fee ledger terpotong dari escrow saat withdraw
+    Orphaned,  // entitas sudah dihapus tapi escrow masih berisi dana
+    Unsettled, // ada operasi yang belum selesai, selisih belum bisa dinilai
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowReport {
+    pub entity: EntityKind,
+    pub entity_id: String,
+    pub owner: Principal,
+    pub ledger: Principal,
+    pub asset: Option<Principal>,
+    pub escrow: Account,
+    pub ledger_balance: Nat,
+    pub expected_balance: Nat,
+    pub difference: Nat, // selisih absolut ledger_balance vs expected_balance
+    pub drift: EscrowDrift,
+    pub entity_exists: bool,
+    pub pending_operations: u32,
+    pub checked_at_ns: u64,
+}
+
+/// Pembukuan satu escrow entitas, disediakan budget/goals
+pub struct EscrowBooks {
+    pub entity_id: String,
+    pub owner: Principal,
+    pub ledger: Principal,
+    pub asset: Option<Principal>,
+    pub subaccount: [u8; 32],
+    pub expected: Nat,
+}
+
+thread_local! {
+    // key = "{kind}|{id}", escrow asset tambahan "{kind}|{id}|{ledger}"
+    static ESCROWS: RefCell<StableBTreeMap<StableText, Candid<EscrowRecord>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::ESCROWS_MEMORY_ID))
+    );
+    // laporan terakhir per escrow, key sama dengan registry
+    static REPORTS: RefCell<StableBTreeMap<StableText, Candid<EscrowReport>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::ESCROW_REPORTS_MEMORY_ID))
+    );
+}
+
+fn entity_name(entity: EntityKind) -> &'static str {
+    match entity {
+        EntityKind::Budget => "budget",
+        EntityKind::Goal => "goal",
+    }
+}
+
+fn escrow_key(entity: EntityKind, id: &str, asset: Option<Principal>) -> StableText {
+    match asset {
+        None => StableText(format!("{}|{}", entity_name(entity), id)),
+        Some(ledger) => StableText(format!("{}|{}|{}", entity_name(entity), id, ledger)),
+    }
+}
+
+fn load_record(entity: EntityKind, id: &str, asset: Option<Principal>) -> Option<EscrowRecord> {
+    ESCROWS.with(|e| e.borrow().get(&escrow_key(entity, id, asset))).map(|c| c.0)
+}
+
+fn books_for(entity: EntityKind, id: &str, asset: Option<Principal>) -> Option<EscrowBooks> {
+    match entity {
+        EntityKind::Budget => budget::escrow_books(id).filter(|_| asset.is_none()),
+        EntityKind::Goal => goals::escrow_books(id, asset),
+    }
+}
+
+// Ledger asset utama yang disebut eksplisit diperlakukan sebagai escrow utama
+fn normalize_asset(entity: EntityKind, id: &str, asset: Option<Principal>) -> Option<Principal> {
+    let primary = books_for(entity, id, None).map(|b| b.ledger).or_else(|| load_record(entity, id, None).map(|r| r.ledger));
+    asset.filter(|ledger| primary != Some(*ledger))
+}
+
+/// Daftarkan escrow entitas baru (dipanggil saat budget/goal dibuat, sekali per asset)
+pub(crate) fn register(entity: EntityKind, id: &str, asset: Option<Principal>, owner: Principal, ledger: Principal, subaccount: [u8; 32]) {
+    let key = escrow_key(entity, id, asset);
+    if ESCROWS.with(|e| e.borrow().contains_key(&key)) { return; }
+    let rec = EscrowRecord { entity, entity_id: id.to_string(), owner, ledger, subaccount: subaccount.to_vec(), registered_at_ns: time(), asset };
+    ESCROWS.with(|e| e.borrow_mut().insert(key, Candid(rec)));
+}
+
+// Entitas yang dibuat sebelum registry ada didaftarkan saat audit
+fn register_existing() {
+    let live = budget::all_escrow_books().into_iter().map(|b| (EntityKind::Budget, b))
+        .chain(goals::all_escrow_books().into_iter().map(|b| (EntityKind::Goal, b)));
+    for (entity, b) in live {
+        register(entity, &b.entity_id, b.asset, b.owner, b.ledger, b.subaccount);
+    }
+}
+
+async fn icrc1_balance_of(ledger: Principal, account: Account) -> Result<Nat, FintrackError> {
+    let (balance,): (Nat,) = ic_cdk::call(ledger, "icrc1_balance_of", (account,)).await
+        .map_err(|e| FintrackError::ledger(format!("call failed: {:?}", e)))?;
+    Ok(balance)
+}
+
+async fn icrc1_fee(token: Principal) -> Result<Nat, FintrackError> {
+    let (fee,): (Nat,) = ic_cdk::api::call::call_with_payment128(token, "icrc1_fee", (), 1_000_000).await
+        .map_err(|e| FintrackError::ledger(format!("call failed: {:?}", e)))?;
+    Ok(fee)
+}
+
+fn escrow_owner(entity: EntityKind, id: &str) -> Result<Principal, FintrackError> {
+    books_for(entity, id, None).map(|b| b.owner)
+        .or_else(|| load_record(entity, id, None).map(|r| r.owner))
+        .ok_or_else(|| FintrackError::not_found(entity_name(entity), id))
+}
+
+fn drift_of(exists: bool, unsettled: bool, balance: &Nat, expected: &Nat) -> (EscrowDrift, Nat) {
+    let difference = if balance.0 >= expected.0 { Nat(&balance.0 - &expected.0) } else { Nat(&expected.0 - &balance.0) };
+    let drift = if unsettled {
+        EscrowDrift::Unsettled
+    } else if balance.0 == expected.0 {
+        EscrowDrift::Balanced
+    } else if !exists {
+        EscrowDrift::Orphaned
+    } else if balance.0 > expected.0 {
+        EscrowDrift::Surplus
+    } else {
+        EscrowDrift::Deficit
+    };
+    (drift, difference)
+}
+
+// Cek satu escrow terhadap ledger dan simpan laporannya
+async fn check_escrow(entity: EntityKind, id: &str, asset: Option<Principal>) -> Result<EscrowReport, FintrackError> {
+    let (owner, ledger, subaccount) = match (books_for(entity, id, asset), load_record(entity, id, asset)) {
+        (Some(b), _) => {
+            register(entity, id, asset, b.owner, b.ledger, b.subaccount);
+            (b.owner, b.ledger, b.subaccount.to_vec())
+        }
+        (None, Some(r)) => (r.owner, r.ledger, r.subaccount),
+        (None, None) => return Err(FintrackError::not_found(entity_name(entity), id)),
+    };
+    let escrow = Account { owner: ic_cdk::id(), subaccount: Some(subaccount) };
+    let balance = icrc1_balance_of(ledger, escrow.clone()).await?;
+
+    // Baca ulang pembukuan setelah await; entitas yang sedang dipakai operasi lain belum bisa dinilai
+    let books = books_for(entity, id, asset);
+    let exists = books.is_some();
+    let expected = books.map(|b| b.expected).unwrap_or_else(|| Nat::from(0u64));
+    let pending = journal::pending_count(entity, id);
+    let busy = EntityGuard::acquire(entity_name(entity), id).is_err();
+    let (drift, difference) = drift_of(exists, busy || pending > 0, &balance, &expected);
+
+    let report = EscrowReport {
+        entity, entity_id: id.to_string(), owner, ledger, asset, escrow,
+        ledger_balance: balance, expected_balance: expected, difference, drift,
+        entity_exists: exists, pending_operations: pending, checked_at_ns: time(),
+    };
+    let key = escrow_key(entity, id, asset);
+    if !exists && drift == EscrowDrift::Balanced {
+        // Escrow entitas terhapus sudah kosong: tidak perlu dipantau lagi
+        ESCROWS.with(|e| e.borrow_mut().remove(&key));
+        REPORTS.with(|r| r.borrow_mut().remove(&key));
+    } else {
+        if drift != EscrowDrift::Balanced && drift != EscrowDrift::Unsettled {
+            ic_cdk::println!("escrow_audit: {} {} {:?} by {}", entity_name(entity), id, drift, report.difference);
+        }
+        REPORTS.with(|r| r.borrow_mut().insert(key, Candid(report.clone())));
+    }
+    Ok(report)
+}
+
+async fn audit_all() {
+    register_existing();
+    let escrows: Vec<(EntityKind, String, Option<Principal>)> = ESCROWS.with(|e| {
+        e.borrow().iter().map(|(_, v)| (v.0.entity, v.0.entity_id, v.0.asset)).collect()
+    });
+    for (entity, id, asset) in escrows {
+        if let Err(e) = check_escrow(entity, &id, asset).await {
+            ic_cdk::println!("escrow_audit: {} {} skipped: {}", entity_name(entity), id, e);
+        }
+    }
+}
+
+/// Arm audit periodik (init dan post_upgrade; timer hanya hidup di heap)
+pub fn start_audit() {
+    ic_cdk_timers::set_timer_interval(std::time::Duration::from_nanos(AUDIT_INTERVAL_NS), || {
+        ic_cdk::futures::spawn(audit_all());
+    });
+}
+
+/// Cek escrow sekarang juga (owner atau controller); `asset` memilih escrow asset tambahan goal multi-asset
+pub async fn escrow_check(entity: EntityKind, id: String, asset: Option<Principal>) -> Result<EscrowReport, FintrackError> {
+    let who = caller();
+    if escrow_owner(entity, &id)? != who && !ic_cdk::api::is_controller(&who) {
+        return Err(FintrackError::unauthorized("not the escrow owner"));
+    }
+    check_escrow(entity, &id, normalize_asset(entity, &id, asset)).await
+}
+
+/// Laporan audit terakhir; controller melihat semua, user hanya escrow miliknya
+pub fn escrow_list_reports(only_drift: Option<bool>) -> Vec<EscrowReport> {
+    let who = caller();
+    let is_controller = ic_cdk::api::is_controller(&who);
+    let only_drift = only_drift.unwrap_or(false);
+    REPORTS.with(|r| {
+        r.borrow().iter()
+            .map(|(_, v)| v.0)
+            .filter(|rep| is_controller || rep.owner == who)
+            .filter(|rep| !only_drift || rep.drift != EscrowDrift::Balanced)
+            .collect()
+    })
+}
+
+/// Kirim dana escrow yang tidak tercatat (surplus / orphan) ke owner; fee ledger dipotong dari jumlahnya
+pub async fn escrow_recover(entity: EntityKind, id: String, asset: Option<Principal>, to_subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
+    let owner = escrow_owner(entity, &id)?;
+    if owner != caller() { return Err(FintrackError::unauthorized("only the escrow owner can recover unaccounted funds")); }
+
+    let asset = normalize_asset(entity, &id, asset);
+    let report = check_escrow(entity, &id, asset).await?;
+    match report.drift {
+        EscrowDrift::Surplus | EscrowDrift::Orphaned => {}
+        EscrowDrift::Unsettled => return Err(FintrackError::invalid_state("escrow has unsettled operations, try again later")),
+        EscrowDrift::Balanced | EscrowDrift::Deficit => return Err(FintrackError::invalid_state("escrow has no unaccounted funds")),
+    }
+    let _guard = EntityGuard::acquire(entity_name(entity), &id)?;
+    let fee = icrc1_fee(report.ledger).await?;
+    if report.difference.0 <= fee.0 {
+        return Err(FintrackError::invalid_state(format!("unaccounted funds {} do not cover the ledger fee {}", report.difference, fee)));
+    }
+
+    let call = LedgerCall::Transfer { from_subaccount: report.escrow.subaccount.clone(), to: Account { owner, subaccount: to_subaccount } };
+    let mut op = NewOperation::new(OperationKind::Recover, entity, &id, owner, report.ledger, call, Nat(&report.difference.0 - &fee.0)).with_memo(b"escrow_recover");
+    op.reserved = report.difference.clone();
+    let block = journal::execute(op).await?;
+
+    // Laporan lama tidak berlaku lagi; audit berikutnya menilai ulang escrow
+    REPORTS.with(|r| r.borrow_mut().remove(&escrow_key(entity, &id, asset)));
+    Ok(block)
+}
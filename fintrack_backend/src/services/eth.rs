@@ -2,41 +2,40 @@ use candid::{CandidType, Nat, Principal};
 use serde::Deserialize;
 use num_traits::ToPrimitive;
 use ic_cdk::api::call::call_with_payment128;
+use super::errors::FintrackError;
 
-// Helper: read ckETH minter and ledger principals from dfx.json (baked-in for now)
+// ckETH minter & ledger dari config canister (lihat services/config.rs)
 fn cketh_minter_principal() -> Principal {
-    // Mainnet ckETH Minter
-    Principal::from_text("jzenf-aiaaa-aaaar-qaa7q-cai").expect("invalid cketh_minter principal")
+    super::config::get().cketh_minter
 }
 
 fn cketh_ledger_principal() -> Principal {
-    // Mainnet ckETH Ledger
-    Principal::from_text("apia6-jaaaa-aaaar-qabma-cai").expect("invalid cketh_ledger principal")
+    super::config::get().cketh_ledger
 }
 
 // NOTE: For now, these are stubs to be wired to ckETH minter/ledger.
 
-pub async fn get_deposit_address(_subaccount: Option<Vec<u8>>) -> Result<String, String> {
+pub async fn get_deposit_address(_subaccount: Option<Vec<u8>>) -> Result<String, FintrackError> {
     let minter = cketh_minter_principal();
     // smart_contract_address returns the helper contract address for ETH deposits
     let (contract_address,): (String,) = call_with_payment128(minter, "smart_contract_address", (), 1_000_000)
         .await
-        .map_err(|e| format!("smart_contract_address failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("smart_contract_address failed: {:?}", e)))?;
     
     if contract_address == "N/A" {
-        return Err("Helper contract not configured".to_string());
+        return Err(FintrackError::invalid_state("helper contract not configured"));
     }
     
     Ok(contract_address)
 }
 
-pub async fn refresh_balance(_subaccount: Option<Vec<u8>>) -> Result<(), String> {
+pub async fn refresh_balance(_subaccount: Option<Vec<u8>>) -> Result<(), FintrackError> {
     // ckETH minter automatically scrapes Ethereum for deposits
     // No manual refresh needed, just return success
     Ok(())
 }
 
-pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, String> {
+pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>) -> Result<Nat, FintrackError> {
     let ledger = cketh_ledger_principal();
     #[derive(CandidType)]
     struct AccountArg {
@@ -48,7 +47,7 @@ pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>)
     let arg = (AccountArg { owner: resolved_owner, subaccount },);
     let (balance,): (Nat,) = call_with_payment128(ledger, "icrc1_balance_of", arg, 1_000_000)
         .await
-        .map_err(|e| format!("icrc1_balance_of failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("icrc1_balance_of failed: {:?}", e)))?;
     Ok(balance)
 }
 
@@ -56,15 +55,15 @@ pub async fn get_balance(owner: Option<Principal>, subaccount: Option<Vec<u8>>)
 
 // Additional helper functions for ckETH
 
-pub async fn get_minter_address() -> Result<String, String> {
+pub async fn get_minter_address() -> Result<String, FintrackError> {
     let minter = cketh_minter_principal();
     let (address,): (String,) = call_with_payment128(minter, "minter_address", (), 1_000_000)
         .await
-        .map_err(|e| format!("minter_address failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("minter_address failed: {:?}", e)))?;
     Ok(address)
 }
 
-pub async fn get_minter_info() -> Result<String, String> {
+pub async fn get_minter_info() -> Result<String, FintrackError> {
     let minter = cketh_minter_principal();
     #[derive(CandidType, Deserialize)]
     struct MinterInfoPartial {
@@ -73,7 +72,7 @@ pub async fn get_minter_info() -> Result<String, String> {
     }
     let (info,): (MinterInfoPartial,) = call_with_payment128(minter, "get_minter_info", (), 2_000_000)
         .await
-        .map_err(|e| format!("get_minter_info failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("get_minter_info failed: {:?}", e)))?;
 
     let finalized = info.last_observed_block_number.and_then(|n| n.0.to_u64());
     let scraped = info.last_eth_scraped_block_number.and_then(|n| n.0.to_u64());
@@ -85,20 +84,19 @@ pub async fn get_minter_info() -> Result<String, String> {
     Ok(json)
 }
 
-pub async fn estimate_withdrawal_fee() -> Result<String, String> {
+pub async fn estimate_withdrawal_fee() -> Result<String, FintrackError> {
     let minter = cketh_minter_principal();
     // eip_1559_transaction_price takes Option<Eip1559TransactionPriceArg> where None means ETH withdrawal
     let (fee_info,): (String,) = call_with_payment128(minter, "eip_1559_transaction_price", (None::<Option<()>>,), 2_000_000)
         .await
-        .map_err(|e| format!("eip_1559_transaction_price failed: {:?}", e))?;
+        .map_err(|e| FintrackError::ledger(format!("eip_1559_transaction_price failed: {:?}", e)))?;
     Ok(fee_info)
 }
 
 /// Get historical fee data to estimate gas prices for Ethereum transactions
-pub async fn fee_history() -> Result<String, String> {
-    // Get EVM RPC canister ID from dfx.json
-    let evm_rpc_principal = Principal::from_text("giifx-2iaaa-aaaab-qb5ua-cai")
-        .expect("invalid evm_rpc principal");
+pub async fn fee_history() -> Result<String, FintrackError> {
+    // EVM RPC canister ID dari config canister
+    let evm_rpc_principal = super::config::evm_rpc_canister();
     
     // Define the fee_history request structure
     #[derive(CandidType)]
@@ -122,7 +120,7 @@ pub async fn fee_history() -> Result<String, String> {
         2_000_000, // Add cycles for fee history query
     )
     .await
-    .map_err(|e| format!("eth_feeHistory failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("eth_feeHistory failed: {:?}", e)))?;
     
     Ok(fee_history_result)
 }
@@ -1,5 +1,4 @@
 use candid::{CandidType, Deserialize, Nat, Principal};
-use ic_cdk::init;
 use ic_cdk::api::call::call_with_payment128;
 use num_traits::ToPrimitive;
 use ic_cdk::api::management_canister::ecdsa::{
@@ -10,34 +9,29 @@ use ic_ethereum_types::Address;
 use alloy_consensus::{SignableTransaction, TxEip1559, TxEnvelope};
 use alloy_primitives::{hex, Signature, TxKind, U256};
 use super::evm_rpc_canister::{
-    BlockTag, EthMainnetService, EthSepoliaService, GetTransactionCountArgs,
-    GetTransactionCountResult, MultiGetTransactionCountResult, RpcServices, RpcConfig, RpcService,
-    RpcError, JsonRpcSource
+    BlockTag, GetTransactionCountArgs,
+    GetTransactionCountResult, MultiGetTransactionCountResult, RpcConfig,
+    RpcError
 };
 
 use num_traits::Zero;
 use std::str::FromStr;
+use super::auth::{self, DelegationScope};
+use super::config;
+use super::errors::FintrackError;
 
-// Constants
-const DEFAULT_ECDSA_KEY_NAME: &str = "key_1"; // Use "test_key_1" or "key_1" on IC
-// EVM RPC Canister ID: giifx-2iaaa-aaaab-qb5ua-cai (mainnet)
+// EVM RPC canister, network & key name diambil dari config canister
 fn get_evm_rpc_canister_id() -> Principal {
-    Principal::from_text("giifx-2iaaa-aaaab-qb5ua-cai").unwrap()
+    config::evm_rpc_canister()
 }
 
 // Helper function to estimate EVM RPC cost and add buffer
 async fn get_evm_rpc_cost_with_buffer(
-    source: JsonRpcSource,
     json_request: String,
     max_response_bytes: u64
 ) -> Result<u128, String> {
     let evm_rpc = get_evm_rpc_canister_id();
-    
-    // Convert JsonRpcSource to RpcService
-    let rpc_service = match source {
-        JsonRpcSource::EthSepolia => RpcService::EthSepolia(EthSepoliaService::PublicNode),
-        JsonRpcSource::EthMainnet => RpcService::EthMainnet(EthMainnetService::PublicNode),
-    };
+    let rpc_service = config::evm_rpc_service();
     
     // Get cost estimate
     let (cost_result,): (Result<candid::Nat, RpcError>,) = ic_cdk::api::call::call(
@@ -173,7 +167,7 @@ impl EthereumWallet {
             derivation_path: principal_derivation_path(&owner),
             key_id: EcdsaKeyId {
                 curve: EcdsaCurve::Secp256k1,
-                name: DEFAULT_ECDSA_KEY_NAME.to_string(),
+                name: config::ecdsa_key_name(),
             },
         };
         let (res,) = ecdsa_public_key(args)
@@ -199,7 +193,7 @@ impl EthereumWallet {
         let derivation_path = principal_derivation_path(&self.owner);
         let key_id = EcdsaKeyId {
             curve: EcdsaCurve::Secp256k1,
-            name: DEFAULT_ECDSA_KEY_NAME.to_string(),
+            name: config::ecdsa_key_name(),
         };
         let (result,) = ic_cdk::api::management_canister::ecdsa::sign_with_ecdsa(SignWithEcdsaArgument {
             message_hash: message_hash.to_vec(),
@@ -264,21 +258,34 @@ fn principal_derivation_path(owner: &Principal) -> Vec<Vec<u8>> {
 
 
 // Main transfer function
-pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResponse, String> {
+pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResponse, FintrackError> {
     let start_instructions = ic_cdk::api::instruction_counter();
     
     // Validate amount
     if request.amount.0.is_zero() {
-        return Err("Amount cannot be zero".to_string());
+        return Err(FintrackError::invalid_input("amount", "cannot be zero"));
     }
 
     // Parse destination address
     let _to_address = Address::from_str(&request.destination_address)
-        .map_err(|e| format!("Invalid destination address: {}", e))?;
-
-    // Get owner (caller if not specified)
-    let owner = request.owner.unwrap_or_else(ic_cdk::caller);
+        .map_err(|e| FintrackError::invalid_address(request.destination_address.clone(), e))?;
+
+    // Signing terikat ke caller; owner lain hanya via delegasi (spend dibebankan ke cap)
+    let delegate = ic_cdk::caller();
+    let amount = request.amount.clone();
+    let owner = auth::authorize_signer(request.owner, DelegationScope::EthTransfer, Some(amount.clone()))?;
+
+    // Spend hanya dikembalikan bila tx pasti tidak diterima provider; hasil yang tidak pasti tetap dibebankan
+    let mut may_be_sent = false;
+    let result = transfer_eth_for_owner(owner, request, start_instructions, &mut may_be_sent).await;
+    if !may_be_sent && !matches!(result, Ok(EthTransferResponse { success: true, .. })) {
+        auth::release_spend(owner, delegate, DelegationScope::EthTransfer, &amount);
+    }
+    result
+}
 
+// `may_be_sent` di-set saat raw tx mulai dikirim ke provider, dan di-reset bila provider menolak dengan alasan pasti
+async fn transfer_eth_for_owner(owner: Principal, request: EthTransferRequest, start_instructions: u64, may_be_sent: &mut bool) -> Result<EthTransferResponse, FintrackError> {
     // Get nonce for the owner
     let nonce = get_transaction_count(Some(owner), Some(BlockTag::Latest)).await;
 
@@ -287,7 +294,7 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
 
     // Build EIP-1559 transaction
     let transaction = TxEip1559 {
-        chain_id: EthereumNetwork::Sepolia.chain_id(), // Use Sepolia chain ID
+        chain_id: config::chain_id(),
         nonce,
         gas_limit,
         max_fee_per_gas,
@@ -323,7 +330,7 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
     );
 
     // Send transaction via EVM RPC with optimized cost estimation
-    let rpc_services = RpcServices::EthSepolia(Some(vec![EthSepoliaService::PublicNode]));
+    let rpc_services = config::evm_rpc_services();
     
     // Estimate cost for transaction sending (typically needs more cycles)
     let json_request = format!(
@@ -331,11 +338,11 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
         raw_transaction_hex
     );
     let estimated_cycles = get_evm_rpc_cost_with_buffer(
-        JsonRpcSource::EthSepolia,
         json_request,
         10_000 // 10KB max response
     ).await?;
     
+    *may_be_sent = true;
     let (result,) = call_with_payment128(
         get_evm_rpc_canister_id(),
         "eth_sendRawTransaction",
@@ -343,7 +350,7 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
         estimated_cycles,
     )
     .await
-    .map_err(|e| format!("Failed to send transaction: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("Failed to send transaction: {:?}", e)))?;
 
     match result {
         super::evm_rpc_canister::MultiSendRawTransactionResult::Consistent(send_result) => {
@@ -367,6 +374,7 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
                             })
                         }
                         super::evm_rpc_canister::SendRawTransactionStatus::NonceTooLow => {
+                            *may_be_sent = false;
                             Ok(EthTransferResponse {
                                 success: false,
                                 transaction_hash: None,
@@ -381,6 +389,7 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
                             })
                         }
                         super::evm_rpc_canister::SendRawTransactionStatus::InsufficientFunds => {
+                            *may_be_sent = false;
                             Ok(EthTransferResponse {
                                 success: false,
                                 transaction_hash: None,
@@ -399,11 +408,7 @@ pub async fn transfer_eth(request: EthTransferRequest) -> Result<EthTransferResp
             }
         }
         super::evm_rpc_canister::MultiSendRawTransactionResult::Inconsistent(_) => {
-            Ok(EthTransferResponse {
-                success: false,
-                transaction_hash: None,
-                error: Some("Inconsistent RPC response".to_string()),
-            })
+            Err(FintrackError::RpcInconsistent)
         }
     }
 }
@@ -413,15 +418,14 @@ pub async fn preview_eth_fee(
     destination_address: String,
     amount: Nat,
     gas_limit: Option<u128>,
-) -> Result<EthFeePreview, String> {
+) -> Result<EthFeePreview, FintrackError> {
     // Get current gas price
     let gas_price_json = r#"{"jsonrpc":"2.0","method":"eth_gasPrice","params":[],"id":1}"#;
     let max_response_size_bytes = 500_u64;
-    let rpc_service = RpcService::EthSepolia(EthSepoliaService::PublicNode);
+    let rpc_service = config::evm_rpc_service();
 
     // Estimate cost for gas price request
     let cycles = get_evm_rpc_cost_with_buffer(
-        JsonRpcSource::EthSepolia,
         gas_price_json.to_string(),
         max_response_size_bytes
     ).await?;
@@ -433,7 +437,7 @@ pub async fn preview_eth_fee(
         cycles,
     )
     .await
-    .map_err(|e| format!("Failed to get gas price: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("Failed to get gas price: {:?}", e)))?;
 
     let gas_price = match gas_price_response {
         super::evm_rpc_canister::RequestResult::Ok(result) => {
@@ -450,7 +454,7 @@ pub async fn preview_eth_fee(
                 .map_err(|e| format!("Failed to parse hex gas price: {}", e))?
         }
         super::evm_rpc_canister::RequestResult::Err(_) => {
-            return Err("Failed to get gas price".to_string());
+            return Err(FintrackError::rpc("Failed to get gas price"));
         }
     };
 
@@ -460,11 +464,11 @@ pub async fn preview_eth_fee(
     let (base_fee_response,) = call_with_payment128(
         get_evm_rpc_canister_id(),
         "request",
-        (RpcService::EthSepolia(EthSepoliaService::PublicNode), base_fee_json.to_string(), max_response_size_bytes),
+        (config::evm_rpc_service(), base_fee_json.to_string(), max_response_size_bytes),
         cycles,
     )
     .await
-    .map_err(|e| format!("Failed to get base fee: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("Failed to get base fee: {:?}", e)))?;
 
     let base_fee_per_gas = match base_fee_response {
         super::evm_rpc_canister::RequestResult::Ok(result) => {
@@ -530,8 +534,9 @@ pub async fn preview_eth_fee(
 }
 
 // Get native ETH balance
-pub async fn get_native_eth_balance(address: Option<String>) -> Result<Nat, String> {
-    let address = address.ok_or("Address is required for native ETH balance")?;
+pub async fn get_native_eth_balance(address: Option<String>) -> Result<Nat, FintrackError> {
+    let address = address.ok_or_else(|| FintrackError::invalid_input("address", "required for native ETH balance"))?;
+    Address::from_str(&address).map_err(|e| FintrackError::invalid_address(address.clone(), e))?;
 
     let json = format!(
         r#"{{ "jsonrpc": "2.0", "method": "eth_getBalance", "params": ["{}", "latest"], "id": 1 }}"#,
@@ -540,7 +545,7 @@ pub async fn get_native_eth_balance(address: Option<String>) -> Result<Nat, Stri
 
     let max_response_size_bytes = 500_u64;
     let cycles = 5_000_000_000u128; // Increased to 5 billion cycles
-    let rpc_service = RpcService::EthSepolia(EthSepoliaService::PublicNode);
+    let rpc_service = config::evm_rpc_service();
 
     let (response,) = call_with_payment128(
         get_evm_rpc_canister_id(),
@@ -549,27 +554,27 @@ pub async fn get_native_eth_balance(address: Option<String>) -> Result<Nat, Stri
         cycles,
     )
     .await
-    .map_err(|e| format!("RPC call failed: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("RPC call failed: {:?}", e)))?;
 
     match response {
         super::evm_rpc_canister::RequestResult::Ok(balance_result) => {
             let response: serde_json::Value = serde_json::from_str(&balance_result)
-                .map_err(|e| format!("Failed to parse response: {}", e))?;
+                .map_err(|e| FintrackError::rpc(format!("failed to parse response: {}", e)))?;
             
             let hex_balance = response
                 .get("result")
                 .and_then(|v| v.as_str())
-                .ok_or("No result in response")?;
+                .ok_or_else(|| FintrackError::rpc("no result in response"))?;
 
             // Remove "0x" prefix and convert to decimal
             let balance = hex_balance.trim_start_matches("0x");
             let big_uint = num::BigUint::parse_bytes(balance.as_bytes(), 16)
-                .ok_or("Failed to parse hex balance")?;
+                .ok_or_else(|| FintrackError::rpc("failed to parse hex balance"))?;
             
             Ok(Nat(big_uint))
         }
-        super::evm_rpc_canister::RequestResult::Err(_e) => {
-            Err("RPC error occurred".to_string())
+        super::evm_rpc_canister::RequestResult::Err(e) => {
+            Err(FintrackError::rpc(format!("RPC error: {:?}", e)))
         }
     }
 }
@@ -578,7 +583,7 @@ pub async fn get_native_eth_balance(address: Option<String>) -> Result<Nat, Stri
 pub async fn get_transaction_count(owner: Option<Principal>, block: Option<BlockTag>) -> u64 {
     let owner = owner.unwrap_or_else(ic_cdk::caller);
     let wallet = EthereumWallet::new(owner).await;
-    let rpc_services = RpcServices::EthSepolia(Some(vec![EthSepoliaService::PublicNode]));
+    let rpc_services = config::evm_rpc_services();
     
     let args = GetTransactionCountArgs {
         address: wallet.ethereum_address().to_string(),
@@ -645,18 +650,4 @@ fn nat_to_u256(value: Nat) -> U256 {
     U256::from_be_bytes(value_u256)
 }
 
-// Init function
-#[init]
-pub fn init(maybe_init: Option<InitArg>) {
-    if let Some(_init_arg) = maybe_init {
-        // Initialize state if needed
-        ic_cdk::println!("Ethereum transfer service initialized");
-    }
-}
-
-#[derive(CandidType, Deserialize, Debug, Default, PartialEq, Eq)]
-pub struct InitArg {
-    pub ethereum_network: Option<EthereumNetwork>,
-}
-
 
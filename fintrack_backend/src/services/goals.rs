@@ -1,13 +1,33 @@
 use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
 use std::collections::BTreeMap;
+use ic_cdk_timers::TimerId;
 use num_traits::ToPrimitive;
+use super::config;
+use super::errors::FintrackError;
+use super::escrow_audit::{self, EscrowBooks};
+use super::guard::{self, EntityGuard};
+use super::journal::{self, EntityKind, LedgerCall, NewOperation, OperationKind, PendingOperation};
+use super::notifications::{self, NotificationKind};
+use super::rates;
+use super::storage::{self, Candid, StableText, VMem};
+use super::timers;
+use super::vesting::{self, Outcome};
 
 // Goals: nabung dengan hard-lock ke escrow subaccount canister.
-// Satu kali lock, unlock cliff di akhir periode. Tidak ada vesting linear.
+// Satu kali lock, unlock cliff di akhir periode (vesting::cliff_at_end). Tidak ada vesting linear.
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub enum GoalStatus { Active, Completed, Failed, Archived }
+pub enum GoalStatus {
+    Active,
+    TargetReached, // target tercapai, dana tetap terkunci sampai end_ns; add funds masih boleh
+    Unlocked,      // end_ns lewat dengan target tercapai, locked sudah pindah ke available
+    Failed,        // end_ns lewat tanpa target, locked dikembalikan ke available untuk di-refund
+    Archived,      // ditutup owner, available sudah di-refund
+    Completed,     // status lama (sebelum state machine); dimigrasi ke TargetReached/Unlocked
+}
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum AssetKind { CkBtc, CkEth }
@@ -21,6 +41,114 @@ pub struct GoalCreateRequest {
     pub start_ns: u64,
     pub end_ns: u64, // unlock cliff at end
     pub initial_amount: Option<Nat>, // optional: transfer langsung saat create
+    pub fiat_target: Option<FiatTarget>, // target dalam fiat; amount_to_lock lalu diisi estimasi token dari harga saat create
+    pub early_exit: Option<EarlyExitTerms>, // opt-in: boleh keluar sebelum end_ns dengan penalti
+    pub extra_assets: Option<Vec<GoalAssetSpec>>, // goal multi-asset; wajib fiat_target supaya nilai bisa digabung
+    pub withdrawal_policy: Option<WithdrawalPolicy>, // group goal; default OwnerOnly, tidak bisa diubah setelah create
+    pub milestones: Option<Vec<MilestoneSpec>>,      // None = 25/50/75%, vec kosong = tanpa milestone
+    pub auto_payout: Option<bool>,                   // true = saat jatuh tempo available langsung dikirim ke owner (OwnerOnly)
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MilestoneTarget {
+    Percent(u32), // persen dari target, 1..=100
+    Amount(Nat),  // jumlah tersimpan dalam satuan progress (token; goal fiat: 1/100 fiat)
+    Date(u64),    // checkpoint waktu; tercapai bila pada atau setelah tanggal itu progress sudah sesuai pace linear sampai tanggal tersebut
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MilestoneSpec {
+    pub target: MilestoneTarget,
+    pub label: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GoalMilestone {
+    pub target: MilestoneTarget,
+    pub label: Option<String>,
+    pub reached_at_ns: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum WithdrawalPolicy {
+    OwnerOnly,                  // hanya creator yang menarik dana
+    ProRata,                    // tiap kontributor menarik bagiannya sesuai kontribusi bersih
+    Approval { required: u32 }, // M-of-N: withdraw lewat proposal yang disetujui `required` anggota (owner termasuk)
+}
+
+/// Atribusi dana per kontributor per asset (jumlah gross, fee ledger termasuk di withdrawn)
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Contribution {
+    pub contributor: Principal,
+    pub asset_canister: Principal,
+    pub contributed: Nat,
+    pub withdrawn: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawalProposal {
+    pub proposer: Principal,
+    pub asset_canister: Principal,
+    pub amount: Nat,
+    pub recipient: Principal,
+    pub approvals: Vec<Principal>,
+    pub created_at_ns: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GoalAssetSpec {
+    pub asset_canister: Principal, // ledger ICRC
+    pub coin_id: String,           // id coin untuk services::rates, mis. "solana"
+}
+
+/// Asset tambahan goal multi-asset; asset utama tetap di field GoalInfo
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GoalAsset {
+    pub asset_canister: Principal,
+    pub coin_id: String,
+    pub decimals: u32,
+    pub locked_balance: Nat,
+    pub available_to_withdraw: Nat,
+    pub last_price: Option<PriceSnapshot>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum PenaltyDestination {
+    Burn,            // transfer ke minting account ledger
+    Donate(Account), // akun tujuan pilihan owner
+    CommunityPool,   // subaccount pool canister, dicatat per ledger
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EarlyExitTerms {
+    pub penalty_bps: u32, // basis point dari amount yang ditarik, 100..=5000
+    pub destination: PenaltyDestination,
+    pub cooling_off_hours: u32, // jeda antara request dan eksekusi (min 24 jam); request bisa dibatalkan selama jeda
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EarlyExitRequest {
+    pub amount: Nat,  // diambil dari locked_balance
+    pub penalty: Nat, // amount * penalty_bps / 10_000
+    pub requested_at_ns: u64,
+    pub execute_at_ns: u64,
+    pub executing: bool, // dana sudah dipotong dari locked, transfer penalti berjalan/menunggu reconciler
+    pub last_error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FiatTarget {
+    pub currency: String,  // kode ISO huruf kecil, mis. "usd", "idr"
+    pub amount_minor: u64, // 1/100 unit fiat (cent/sen)
+}
+
+/// Harga 1 token utuh saat itu, dalam 1/100 unit fiat
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PriceSnapshot {
+    pub currency: String,
+    pub price_minor: u64,
+    pub at_time_ns: u64,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -38,12 +166,114 @@ pub struct GoalInfo {
     pub created_at_ns: u64,
     pub updated_at_ns: u64,
     pub status: GoalStatus,
+    pub auto_contribution: Option<AutoContribution>, // None = hanya add funds manual
+    pub fiat_target: Option<FiatTarget>, // Some = target dinilai dalam fiat lewat last_price
+    pub last_price: Option<PriceSnapshot>,
+    pub early_exit: Option<EarlyExitTerms>,
+    pub pending_early_exit: Option<EarlyExitRequest>,
+    pub extra_assets: Option<Vec<GoalAsset>>, // escrow sendiri per asset; auto-contribution dan early exit hanya asset utama
+    pub withdrawal_policy: Option<WithdrawalPolicy>, // None = OwnerOnly
+    pub members: Option<Vec<Principal>>,             // co-contributor yang sudah menerima undangan (owner tidak termasuk)
+    pub pending_invites: Option<Vec<Principal>>,
+    pub contributions: Option<Vec<Contribution>>,
+    pub pending_withdrawal: Option<WithdrawalProposal>, // policy Approval: satu proposal aktif per goal
+    pub milestones: Option<Vec<GoalMilestone>>,
+    pub behind_schedule_since: Option<u64>, // Some selama progress tertinggal dari pace linear
+    pub auto_payout: Option<bool>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContributionInterval { Daily, Weekly, Monthly, OnCkBtcDeposit }
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AutoContribution {
+    pub amount: Nat,
+    pub interval: ContributionInterval,
+    pub anchor_ns: u64,              // jadwal dihitung dari titik ini (Monthly: tanggal yang sama tiap bulan)
+    pub next_run_at_ns: Option<u64>, // None untuk OnCkBtcDeposit atau saat di-pause
+    pub max_failures: u32,           // pause otomatis setelah sekian penolakan saldo/allowance berturut-turut
+    pub consecutive_failures: u32,
+    pub paused: bool,
+    pub last_attempt_at_ns: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AutoContributionRequest {
+    pub amount: Nat,
+    pub interval: ContributionInterval,
+    pub start_at_ns: Option<u64>,  // tarikan pertama; default satu interval dari sekarang
+    pub max_failures: Option<u32>, // default 3
+}
+
+// Goals disimpan di stable memory supaya tidak hilang saat upgrade (dana escrow tetap terlacak)
+thread_local! {
+    static GOALS: RefCell<StableBTreeMap<StableText, Candid<GoalInfo>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::GOALS_MEMORY_ID))
+    );
+    // Index principal → goal supaya list tidak memindai semua goal. Dijaga store_goal/remove_goal.
+    // key = "{principal}|{goal_id}" (owner & member) atau "invite|{principal}|{goal_id}" (undangan pending)
+    static GOAL_INDEX: RefCell<StableBTreeMap<StableText, (), VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::GOAL_INDEX_MEMORY_ID))
+    );
+}
+
+fn load_goal(id: &str) -> Option<GoalInfo> {
+    GOALS.with(|g| g.borrow().get(&StableText(id.to_string()))).map(|c| c.0)
+}
+
+fn store_goal(info: &GoalInfo) {
+    let old = GOALS.with(|g| g.borrow_mut().insert(StableText(info.id.clone()), Candid(info.clone())));
+    reindex_goal(old.map(|c| c.0).as_ref(), Some(info));
+}
+
+fn remove_goal(id: &str) {
+    let old = GOALS.with(|g| g.borrow_mut().remove(&StableText(id.to_string())));
+    reindex_goal(old.map(|c| c.0).as_ref(), None);
+}
+
+fn member_prefix(who: Principal) -> String { format!("{}|", who) }
+
+fn invite_prefix(who: Principal) -> String { format!("invite|{}|", who) }
+
+// Goal yang masih menerima dana (Active/TargetReached), dipakai pemantau progress
+const ACTIVE_PREFIX: &str = "active|";
+
+fn index_keys(gi: &GoalInfo) -> Vec<String> {
+    let members = std::iter::once(&gi.owner).chain(gi.members.iter().flatten()).map(|p| format!("{}{}", member_prefix(*p), gi.id));
+    let invites = gi.pending_invites.iter().flatten().map(|p| format!("{}{}", invite_prefix(*p), gi.id));
+    let active = accepts_funds(&gi.status).then(|| format!("{}{}", ACTIVE_PREFIX, gi.id));
+    members.chain(invites).chain(active).collect()
 }
 
-#[derive(Clone, Debug)]
-struct GoalInternal { info: GoalInfo }
+// Sinkronkan index dari versi goal lama ke baru (None = belum ada / dihapus)
+fn reindex_goal(old: Option<&GoalInfo>, new: Option<&GoalInfo>) {
+    let old_keys = old.map(index_keys).unwrap_or_default();
+    let new_keys = new.map(index_keys).unwrap_or_default();
+    GOAL_INDEX.with(|i| {
+        let mut map = i.borrow_mut();
+        for k in old_keys.iter().filter(|k| !new_keys.contains(k)) { map.remove(&StableText(k.clone())); }
+        for k in new_keys.into_iter().filter(|k| !old_keys.contains(k)) { map.insert(StableText(k), ()); }
+    });
+}
+
+fn indexed_goals(prefix: String) -> Vec<GoalInfo> {
+    let ids: Vec<String> = GOAL_INDEX.with(|i| {
+        i.borrow().range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .map(|(k, _)| k.0[prefix.len()..].to_string())
+            .collect()
+    });
+    ids.iter().filter_map(|id| load_goal(id)).collect()
+}
 
-thread_local! { static GOALS: std::cell::RefCell<BTreeMap<String, GoalInternal>> = std::cell::RefCell::new(BTreeMap::new()); }
+// Jalankan mutasi pada satu goal lalu tulis balik ke stable memory
+fn with_goal_mut<R>(id: &str, f: impl FnOnce(&mut GoalInfo) -> R) -> Option<R> {
+    let mut info = load_goal(id)?;
+    let out = f(&mut info);
+    store_goal(&info);
+    Some(out)
+}
 
 // -------- History events --------
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -54,6 +284,18 @@ pub enum GoalEventKind {
     CliffUnlocked,
     TargetReached,
     Failed,
+    AutoContributionFailed,
+    EarlyExitRequested,
+    EarlyExitCancelled,
+    EarlyExit, // amount = total keluar dari locked, note = penalti
+    MemberInvited,
+    MemberJoined,
+    WithdrawalProposed,
+    WithdrawalApproved,
+    WithdrawalCancelled,
+    MilestoneReached, // amount = progress saat tercapai, note = milestone
+    BehindSchedule,   // amount = progress, note = pace yang diharapkan
+    Archived,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -62,22 +304,57 @@ pub struct GoalEvent {
     pub kind: GoalEventKind,
     pub amount: Option<Nat>,
     pub note: Option<String>,
+    pub price: Option<PriceSnapshot>, // goal fiat: harga saat kontribusi
+    pub asset: Option<Principal>, // goal multi-asset: ledger asset tambahan; None = asset utama
+    pub by: Option<Principal>, // group goal: kontributor / penerima / anggota yang bertindak
 }
 
 thread_local! {
-    static GOAL_EVENTS: std::cell::RefCell<BTreeMap<String, Vec<GoalEvent>>> = std::cell::RefCell::new(BTreeMap::new());
+    // key = "{goal_id}|{seq:020}" supaya event satu goal berurutan dalam range scan
+    static GOAL_EVENTS: RefCell<StableBTreeMap<StableText, Candid<GoalEvent>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::GOAL_EVENTS_MEMORY_ID))
+    );
+}
+
+fn goal_event_prefix(id: &str) -> String { format!("{}|", id) }
+
+fn load_goal_events(id: &str) -> Vec<GoalEvent> {
+    let prefix = goal_event_prefix(id);
+    GOAL_EVENTS.with(|e| {
+        e.borrow().range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .map(|(_, v)| v.0)
+            .collect()
+    })
 }
 
 fn push_goal_event(id: &str, ev: GoalEvent) {
+    let prefix = goal_event_prefix(id);
+    GOAL_EVENTS.with(|e| {
+        let mut map = e.borrow_mut();
+        let seq = map.range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .count();
+        map.insert(StableText(format!("{}{:020}", prefix, seq)), Candid(ev));
+    });
+}
+
+fn remove_goal_events(id: &str) {
+    let prefix = goal_event_prefix(id);
     GOAL_EVENTS.with(|e| {
         let mut map = e.borrow_mut();
-        map.entry(id.to_string()).or_default().push(ev);
+        let keys: Vec<StableText> = map.range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .map(|(k, _)| k)
+            .collect();
+        for k in keys { map.remove(&k); }
     });
 }
 
 fn decimals_for_asset(kind: &AssetKind) -> u32 { match kind { AssetKind::CkBtc => 8, AssetKind::CkEth => 18 } }
 
-// Reuse budget escrow derivation
+// Reuse budget escrow derivation. Group goal tetap satu escrow per asset (diturunkan dari creator);
+// dana anggota dipisahkan lewat GoalInfo.contributions, bukan lewat subaccount.
 fn escrow_subaccount(owner: &Principal, goal_id: &str) -> [u8; 32] {
     use sha3::{Digest, Keccak256};
     let mut hasher = Keccak256::new();
@@ -89,103 +366,188 @@ fn escrow_subaccount(owner: &Principal, goal_id: &str) -> [u8; 32] {
     out
 }
 
-#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct Account { pub owner: Principal, pub subaccount: Option<Vec<u8>> }
+pub use super::budget::Account;
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub struct TransferFromArg {
-    pub from: Account,
-    pub to: Account,
-    pub amount: Nat,
-    pub fee: Option<Nat>,
-    pub memo: Option<Vec<u8>>,
-    pub created_at_time: Option<u64>,
-    pub spender_subaccount: Option<Vec<u8>>,
+async fn icrc1_fee(token: Principal) -> Result<Nat, FintrackError> {
+    let (fee,): (Nat,) = ic_cdk::api::call::call_with_payment128(token, "icrc1_fee", (), 1_000_000).await
+        .map_err(|e| FintrackError::ledger(format!("call failed: {:?}", e)))?;
+    Ok(fee)
 }
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub struct TransferArg {
-    pub from_subaccount: Option<Vec<u8>>,
-    pub to: Account,
-    pub amount: Nat,
-    pub fee: Option<Nat>,
-    pub memo: Option<Vec<u8>>,
-    pub created_at_time: Option<u64>,
+fn principal_account(owner: Principal) -> Account { Account { owner, subaccount: None } }
+fn canister_escrow_account(sub: [u8; 32]) -> Account { Account { owner: ic_cdk::id(), subaccount: Some(sub.to_vec()) } }
+
+async fn icrc1_decimals(token: Principal) -> Result<u32, FintrackError> {
+    let (decimals,): (u8,) = ic_cdk::call(token, "icrc1_decimals", ()).await
+        .map_err(|e| FintrackError::ledger(format!("call failed: {:?}", e)))?;
+    Ok(decimals as u32)
 }
 
-async fn icrc2_transfer_from(token: Principal, arg: TransferFromArg) -> Result<Nat, String> {
-    ic_cdk::call::<_, (Result<Nat, String>,)>(token, "icrc2_transfer_from", (arg,))
-        .await.map_err(|e| format!("call failed: {:?}", e)).and_then(|(r,)| r.map_err(|e| e))
+// -------- Asset goal (utama + tambahan) --------
+
+const MAX_EXTRA_ASSETS: usize = 4;
+
+// Asset utama memakai escrow lama; asset tambahan diturunkan juga dari ledger-nya
+fn asset_escrow_subaccount(gi: &GoalInfo, ledger: Principal) -> [u8; 32] {
+    if ledger == gi.asset_canister { escrow_subaccount(&gi.owner, &gi.id) } else { escrow_subaccount(&gi.owner, &format!("{}|{}", gi.id, ledger)) }
 }
 
-async fn icrc1_transfer(token: Principal, arg: TransferArg) -> Result<Nat, String> {
-    ic_cdk::call::<_, (Result<Nat, String>,)>(token, "icrc1_transfer", (arg,))
-        .await.map_err(|e| format!("call failed: {:?}", e)).and_then(|(r,)| r.map_err(|e| e))
+// Penanda asset di event/laporan: None = asset utama
+fn asset_tag(gi: &GoalInfo, ledger: Principal) -> Option<Principal> {
+    if ledger == gi.asset_canister { None } else { Some(ledger) }
 }
 
-async fn icrc1_fee(token: Principal) -> Result<Nat, String> {
-    let (fee,): (Nat,) = ic_cdk::api::call::call_with_payment128(token, "icrc1_fee", (), 1_000_000).await
-        .map_err(|e| format!("call failed: {:?}", e))?;
-    Ok(fee)
+fn asset_ledgers(gi: &GoalInfo) -> Vec<Principal> {
+    std::iter::once(gi.asset_canister).chain(gi.extra_assets.iter().flatten().map(|a| a.asset_canister)).collect()
 }
 
-fn principal_account(owner: Principal) -> Account { Account { owner, subaccount: None } }
-fn canister_escrow_account(sub: [u8; 32]) -> Account { Account { owner: ic_cdk::id(), subaccount: Some(sub.to_vec()) } }
+fn extra_asset(gi: &GoalInfo, ledger: Principal) -> Option<&GoalAsset> {
+    gi.extra_assets.as_ref()?.iter().find(|a| a.asset_canister == ledger)
+}
+
+// (locked, available) satu asset goal
+fn asset_balances(gi: &GoalInfo, ledger: Principal) -> Option<(Nat, Nat)> {
+    if ledger == gi.asset_canister { return Some((gi.locked_balance.clone(), gi.available_to_withdraw.clone())); }
+    extra_asset(gi, ledger).map(|a| (a.locked_balance.clone(), a.available_to_withdraw.clone()))
+}
+
+fn balances_mut(gi: &mut GoalInfo, ledger: Principal) -> Option<(&mut Nat, &mut Nat)> {
+    if ledger == gi.asset_canister { return Some((&mut gi.locked_balance, &mut gi.available_to_withdraw)); }
+    gi.extra_assets.as_mut()?.iter_mut().find(|a| a.asset_canister == ledger).map(|a| (&mut a.locked_balance, &mut a.available_to_withdraw))
+}
+
+fn asset_price(gi: &GoalInfo, ledger: Principal) -> Option<&PriceSnapshot> {
+    if ledger == gi.asset_canister { gi.last_price.as_ref() } else { extra_asset(gi, ledger)?.last_price.as_ref() }
+}
+
+fn set_asset_price(gi: &mut GoalInfo, ledger: Principal, price: PriceSnapshot) {
+    if ledger == gi.asset_canister {
+        gi.last_price = Some(price);
+    } else if let Some(a) = gi.extra_assets.iter_mut().flatten().find(|a| a.asset_canister == ledger) {
+        a.last_price = Some(price);
+    }
+}
 
-pub async fn goals_create_and_lock(req: GoalCreateRequest) -> Result<GoalInfo, String> {
-    if req.name.trim().is_empty() { return Err("name tidak boleh kosong".to_string()); }
-    if req.amount_to_lock.0 == num::BigUint::from(0u32) { return Err("amount_to_lock harus > 0".to_string()); }
-    if req.end_ns <= req.start_ns { return Err("end_ns harus > start_ns".to_string()); }
+fn asset_coin(gi: &GoalInfo, ledger: Principal) -> Option<String> {
+    if ledger == gi.asset_canister { Some(coin_for_decimals(gi.decimals).to_string()) } else { extra_asset(gi, ledger).map(|a| a.coin_id.clone()) }
+}
+
+// Validasi asset tambahan dan ambil decimals + harga awal masing-masing
+async fn load_extra_assets(specs: Vec<GoalAssetSpec>, primary: Principal, target: &FiatTarget) -> Result<Vec<GoalAsset>, FintrackError> {
+    if specs.len() > MAX_EXTRA_ASSETS {
+        return Err(FintrackError::invalid_input("extra_assets", format!("at most {} extra assets", MAX_EXTRA_ASSETS)));
+    }
+    let mut assets: Vec<GoalAsset> = Vec::with_capacity(specs.len());
+    for spec in specs {
+        if spec.asset_canister == primary || assets.iter().any(|a| a.asset_canister == spec.asset_canister) {
+            return Err(FintrackError::invalid_input("extra_assets", format!("duplicate asset {}", spec.asset_canister)));
+        }
+        let coin_id = spec.coin_id.trim().to_lowercase();
+        if coin_id.is_empty() { return Err(FintrackError::invalid_input("extra_assets", "coin_id must not be empty")); }
+        let decimals = icrc1_decimals(spec.asset_canister).await?;
+        let price = fetch_price(&coin_id, &target.currency).await?;
+        assets.push(GoalAsset {
+            asset_canister: spec.asset_canister,
+            coin_id,
+            decimals,
+            locked_balance: Nat::from(0u64),
+            available_to_withdraw: Nat::from(0u64),
+            last_price: Some(price),
+        });
+    }
+    Ok(assets)
+}
+
+pub async fn goals_create_and_lock(req: GoalCreateRequest) -> Result<GoalInfo, FintrackError> {
+    if req.name.trim().is_empty() { return Err(FintrackError::invalid_input("name", "must not be empty")); }
+    if req.fiat_target.is_none() && req.amount_to_lock.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount_to_lock", "must be greater than zero")); }
+    if req.end_ns <= req.start_ns { return Err(FintrackError::invalid_input("end_ns", "must be after start_ns")); }
+    if let Some(terms) = &req.early_exit {
+        validate_early_exit_terms(terms)?;
+        if req.extra_assets.as_ref().is_some_and(|a| !a.is_empty()) {
+            return Err(FintrackError::invalid_input("early_exit", "early exit is not available for multi-asset goals"));
+        }
+    }
+    if let Some(policy) = &req.withdrawal_policy { validate_withdrawal_policy(policy, req.early_exit.is_some())?; }
+    if req.auto_payout == Some(true) && req.withdrawal_policy.as_ref().is_some_and(|p| *p != WithdrawalPolicy::OwnerOnly) {
+        return Err(FintrackError::invalid_input("auto_payout", "auto payout is only available for owner-only goals"));
+    }
+    let milestones = match &req.milestones {
+        Some(specs) => { validate_milestones(specs, req.start_ns, req.end_ns)?; milestones_from(specs, None) }
+        None => default_milestones(),
+    };
+
+    // Goal fiat: harga saat create menentukan estimasi token target
+    let decimals = decimals_for_asset(&req.asset_kind);
+    let (amount_to_lock, fiat_target, last_price) = match req.fiat_target {
+        Some(target) => {
+            if target.amount_minor == 0 { return Err(FintrackError::invalid_input("fiat_target", "amount must be greater than zero")); }
+            let target = FiatTarget { currency: target.currency.to_lowercase(), amount_minor: target.amount_minor };
+            let price = fetch_price(coin_for_decimals(decimals), &target.currency).await?;
+            (tokens_for_fiat(target.amount_minor, decimals, &price), Some(target), Some(price))
+        }
+        None => (req.amount_to_lock.clone(), None, None),
+    };
+    let extra_assets = match (req.extra_assets, &fiat_target) {
+        (Some(specs), _) if specs.is_empty() => None,
+        (Some(specs), Some(target)) => Some(load_extra_assets(specs, req.asset_canister, target).await?),
+        (Some(_), None) => return Err(FintrackError::invalid_input("extra_assets", "multi-asset goals need a fiat_target")),
+        (None, _) => None,
+    };
 
     let owner = caller();
     let created = time();
     let id = format!("goal-{}-{}-{}", owner, req.asset_canister, created);
     
-    // Mulai dengan locked_balance = 0, atau initial_amount jika ada
-    let initial_locked = req.initial_amount.as_ref().unwrap_or(&Nat(num::BigUint::from(0u32))).clone();
-    
+    // Mulai dengan locked_balance = 0; initial_amount dikredit setelah transfer tereksekusi
     let info = GoalInfo {
         id: id.clone(),
         owner,
         asset_canister: req.asset_canister,
         name: req.name,
-        amount_to_lock: req.amount_to_lock.clone(), // ini target
-        locked_balance: initial_locked.clone(),
+        amount_to_lock, // ini target (goal fiat: estimasi token)
+        locked_balance: Nat(num::BigUint::from(0u32)),
         available_to_withdraw: Nat(num::BigUint::from(0u32)),
-        decimals: decimals_for_asset(&req.asset_kind),
+        decimals,
         start_ns: req.start_ns,
         end_ns: req.end_ns,
         created_at_ns: created,
         updated_at_ns: created,
         status: GoalStatus::Active,
+        auto_contribution: None,
+        fiat_target,
+        last_price,
+        early_exit: req.early_exit,
+        pending_early_exit: None,
+        extra_assets,
+        withdrawal_policy: req.withdrawal_policy,
+        members: None,
+        pending_invites: None,
+        contributions: None,
+        pending_withdrawal: None,
+        milestones: Some(milestones),
+        behind_schedule_since: None,
+        auto_payout: req.auto_payout,
     };
 
     // Simpan goal dulu
-    GOALS.with(|g| g.borrow_mut().insert(id.clone(), GoalInternal { info: info.clone() }));
+    store_goal(&info);
+    for ledger in asset_ledgers(&info) {
+        escrow_audit::register(EntityKind::Goal, &id, asset_tag(&info, ledger), owner, ledger, asset_escrow_subaccount(&info, ledger));
+    }
 
     // Jika ada initial_amount, transfer langsung
     if let Some(initial) = req.initial_amount {
         if initial.0 > num::BigUint::from(0u32) {
-            let from = principal_account(owner);
-            let to = canister_escrow_account(escrow_subaccount(&owner, &id));
-            let arg = TransferFromArg { 
-                from, 
-                to, 
-                amount: initial.clone(), 
-                fee: None, 
-                memo: Some(b"goals_initial_lock".to_vec()), 
-                created_at_time: Some(created), 
-                spender_subaccount: None 
-            };
-            
-            match icrc2_transfer_from(req.asset_canister, arg).await {
+            let call = LedgerCall::TransferFrom { from: principal_account(owner), to: canister_escrow_account(escrow_subaccount(&owner, &id)) };
+            let op = NewOperation::new(OperationKind::Lock, EntityKind::Goal, &id, owner, req.asset_canister, call, initial.clone()).with_memo(b"goals_initial_lock");
+            match journal::execute(op).await {
                 Ok(_h) => {
                     // Update locked_balance setelah transfer berhasil
-                    GOALS.with(|g| {
-                        if let Some(gi) = g.borrow_mut().get_mut(&id) {
-                            gi.info.locked_balance = initial.clone();
-                            gi.info.updated_at_ns = time();
-                        }
+                    with_goal_mut(&id, |gi| {
+                        gi.locked_balance = initial.clone();
+                        record_contribution(gi, owner, req.asset_canister, &initial);
+                        gi.updated_at_ns = time();
                     });
                     // Log event
                     push_goal_event(&id, GoalEvent {
@@ -193,32 +555,33 @@ pub async fn goals_create_and_lock(req: GoalCreateRequest) -> Result<GoalInfo, S
                         kind: GoalEventKind::InitialLock,
                         amount: Some(initial),
                         note: None,
+                        price: info.last_price.clone(),
+                        asset: None,
+                        by: Some(owner),
                     });
                 }
+                // Goal tetap ada; reconciler mengkredit initial_amount bila transfer ternyata tereksekusi
+                Err(e) if journal::is_in_doubt(&e) => {}
                 Err(e) => {
                     // Jika transfer gagal, hapus goal yang sudah dibuat
-                    GOALS.with(|g| g.borrow_mut().remove(&id));
+                    remove_goal(&id);
                     return Err(e);
                 }
             }
         }
     }
 
-    Ok(info)
+    arm_maturity_timer(&id, info.end_ns);
+    Ok(load_goal(&id).unwrap_or(info))
 }
 
-pub fn goals_get(id: String) -> Option<GoalInfo> { 
-    // Update all goals status first
-    update_all_goals_status();
-    
-    GOALS.with(|g| g.borrow().get(&id).map(|gi| gi.info.clone())) 
-}
+// Query murni: transisi status dijalankan timer / update call (lihat advance_goal)
+pub fn goals_get(id: String) -> Option<GoalInfo> { load_goal(&id) }
+
 pub fn goals_list(owner: Option<Principal>) -> Vec<GoalInfo> {
-    // Update all goals status first
-    update_all_goals_status();
-    
     let who = owner.unwrap_or_else(caller);
-    GOALS.with(|g| g.borrow().values().filter(|gi| gi.info.owner == who).map(|gi| gi.info.clone()).collect())
+    // Group goal ikut tampil untuk anggotanya
+    indexed_goals(member_prefix(who))
 }
 
 // Cek progress goal (berapa persen target yang sudah tercapai)
@@ -227,193 +590,1637 @@ pub struct GoalProgress {
     pub goal_id: String,
     pub target_amount: Nat,
     pub current_locked: Nat,
-    pub progress_percentage: f64, // 0.0 - 100.0
+    pub progress_percentage: f64, // 0.0 - 100.0 (goal fiat: berdasarkan nilai fiat)
     pub is_target_reached: bool,
+    pub fiat_target: Option<FiatTarget>,
+    pub current_value_minor: Option<Nat>, // nilai locked dalam 1/100 fiat menurut `price`
+    pub price: Option<PriceSnapshot>,
+    pub assets: Vec<AssetValue>, // asset utama dulu, lalu asset tambahan
+    pub milestones: Vec<GoalMilestone>,
+    pub expected_now: Nat, // pace linear: progress yang seharusnya sudah tercapai sekarang (satuan sama dengan target)
+    pub behind_schedule: bool,
 }
 
-pub fn goals_get_progress(id: String) -> Result<GoalProgress, String> {
-    GOALS.with(|g| {
-        let map = g.borrow();
-        let gi = map.get(&id).ok_or_else(|| "goal not found".to_string())?;
-        if gi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        
-        let target = &gi.info.amount_to_lock.0;
-        let current = &gi.info.locked_balance.0;
-        
-        let progress = if target > &num::BigUint::from(0u32) {
-            (current.to_f64().unwrap_or(0.0) / target.to_f64().unwrap_or(1.0)) * 100.0
-        } else {
-            0.0
-        };
-        
-        Ok(GoalProgress {
-            goal_id: id,
-            target_amount: gi.info.amount_to_lock.clone(),
-            current_locked: gi.info.locked_balance.clone(),
-            progress_percentage: progress,
-            is_target_reached: current >= target,
-        })
-    })
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct AssetValue {
+    pub asset_canister: Principal,
+    pub locked_balance: Nat,
+    pub value_minor: Option<Nat>, // None = goal non-fiat atau belum ada harga
 }
 
-// Unlock cliff: hanya setelah end_ns tercapai, seluruh locked dipindahkan ke available.
-pub fn goals_refresh(id: String) -> Result<GoalInfo, String> {
-    GOALS.with(|g| {
-        let mut map = g.borrow_mut();
-        let gi = map.get_mut(&id).ok_or_else(|| "goal not found".to_string())?;
-        let now = time();
-        if now >= gi.info.end_ns && gi.info.locked_balance.0 > num::BigUint::from(0u32) {
-            let amt = gi.info.locked_balance.clone();
-            gi.info.locked_balance = Nat(num::BigUint::from(0u32));
-            gi.info.available_to_withdraw = Nat(&gi.info.available_to_withdraw.0 + &amt.0);
-            gi.info.status = GoalStatus::Completed;
-            gi.info.updated_at_ns = now;
-            
-            // Log cliff unlock event
-            push_goal_event(&id, GoalEvent {
-                at_time_ns: now,
-                kind: GoalEventKind::CliffUnlocked,
-                amount: Some(amt),
-                note: Some("Cliff period ended, funds unlocked!".to_string()),
-            });
-        }
-        Ok(gi.info.clone())
+pub fn goals_get_progress(id: String) -> Result<GoalProgress, FintrackError> {
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if !is_member(&gi, caller()) { return Err(FintrackError::unauthorized("not a goal member")); }
+
+    let (current, target) = progress_amounts(&gi);
+    let (elapsed, duration) = vesting::elapsed(gi.start_ns, gi.end_ns, time());
+
+    let progress = if target.0 > num::BigUint::from(0u32) {
+        (current.0.to_f64().unwrap_or(0.0) / target.0.to_f64().unwrap_or(1.0)) * 100.0
+    } else {
+        0.0
+    };
+
+    Ok(GoalProgress {
+        goal_id: id,
+        target_amount: gi.amount_to_lock.clone(),
+        current_locked: gi.locked_balance.clone(),
+        progress_percentage: progress,
+        is_target_reached: current.0 >= target.0,
+        current_value_minor: gi.fiat_target.as_ref().map(|_| current),
+        assets: asset_values(&gi),
+        expected_now: Nat(vesting::expected_by(&target, elapsed, duration)),
+        behind_schedule: gi.behind_schedule_since.is_some(),
+        milestones: gi.milestones.unwrap_or_default(),
+        fiat_target: gi.fiat_target,
+        price: gi.last_price,
     })
 }
 
+/// Jalankan transisi status sekarang juga (tanpa menunggu timer); unlock cliff terjadi di sini bila end_ns lewat
+pub fn goals_refresh(id: String) -> Result<GoalInfo, FintrackError> {
+    advance_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+// Ledger, owner dan escrow tujuan deposit `contributor`; transisi dulu supaya goal yang periodenya sudah lewat tidak menerima dana lagi
+fn deposit_target(id: &str, contributor: Principal, asset: Option<Principal>) -> Result<(Principal, Principal, Account), FintrackError> {
+    let gi = advance_goal(id).ok_or_else(|| FintrackError::not_found("goal", id))?;
+    if !is_member(&gi, contributor) { return Err(FintrackError::unauthorized("not a goal member")); }
+    if !accepts_funds(&gi.status) { return Err(FintrackError::invalid_state("goal is not accepting funds")); }
+    let ledger = asset.unwrap_or(gi.asset_canister);
+    if asset_balances(&gi, ledger).is_none() { return Err(FintrackError::invalid_input("asset", "asset is not part of this goal")); }
+    Ok((ledger, gi.owner, canister_escrow_account(asset_escrow_subaccount(&gi, ledger))))
+}
+
 // Add funds to existing goal (cicilan)
-pub async fn goals_add_funds(id: String, amount: Nat) -> Result<GoalInfo, String> {
-    if amount.0 == num::BigUint::from(0u32) { return Err("amount harus > 0".to_string()); }
-    
-    // Update all goals status first
-    update_all_goals_status();
-    
-    let (asset, owner, current_locked, escrow) = GOALS.with(|g| {
-        let map = g.borrow();
-        let gi = map.get(&id).ok_or_else(|| "goal not found".to_string())?;
-        if gi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        if gi.info.status != GoalStatus::Active { return Err("goal tidak aktif".to_string()); }
-        Ok((gi.info.asset_canister, gi.info.owner, gi.info.locked_balance.clone(), canister_escrow_account(escrow_subaccount(&gi.info.owner, &gi.info.id))))
-    })?;
+pub async fn goals_add_funds(id: String, amount: Nat) -> Result<GoalInfo, FintrackError> {
+    add_funds_in(id, None, amount).await
+}
 
-    // Transfer dari user ke escrow
-    let from = principal_account(owner);
-    let to = escrow;
-    let arg = TransferFromArg { 
-        from, 
-        to, 
-        amount: amount.clone(), 
-        fee: None, 
-        memo: Some(b"goals_add_funds".to_vec()), 
-        created_at_time: Some(time()), 
-        spender_subaccount: None 
-    };
-    
-    match icrc2_transfer_from(asset, arg).await {
-        Ok(_h) => {
-            GOALS.with(|g| {
-                if let Some(gi) = g.borrow_mut().get_mut(&id) {
-                    gi.info.locked_balance = Nat(&gi.info.locked_balance.0 + &amount.0);
-                    gi.info.updated_at_ns = time();
-                    
-                    // Cek apakah target sudah tercapai
-                    if gi.info.locked_balance.0 >= gi.info.amount_to_lock.0 {
-                        gi.info.status = GoalStatus::Completed;
-                        push_goal_event(&id, GoalEvent {
-                            at_time_ns: time(),
-                            kind: GoalEventKind::TargetReached,
-                            amount: Some(gi.info.amount_to_lock.clone()),
-                            note: Some("Target amount reached!".to_string()),
-                        });
-                    }
-                }
-            });
-            // Log add funds event
-            push_goal_event(&id, GoalEvent {
-                at_time_ns: time(),
-                kind: GoalEventKind::AddFunds,
-                amount: Some(amount),
-                note: None,
-            });
-            goals_get(id).ok_or_else(|| "goal not found after update".to_string())
+/// Add funds dalam salah satu asset goal multi-asset
+pub async fn goals_add_funds_asset(id: String, asset: Principal, amount: Nat) -> Result<GoalInfo, FintrackError> {
+    add_funds_in(id, Some(asset), amount).await
+}
+
+async fn add_funds_in(id: String, asset: Option<Principal>, amount: Nat) -> Result<GoalInfo, FintrackError> {
+    if amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount", "must be greater than zero")); }
+    let contributor = caller();
+    let _guard = EntityGuard::acquire("goal", &id)?;
+    let (ledger, _, _) = deposit_target(&id, contributor, asset)?;
+    let price = contribution_price(&id, ledger).await;
+    // Cek ulang setelah outcall: goal bisa berubah status (mis. lewat end_ns) atau member dikeluarkan selama await
+    let (ledger, owner, escrow) = deposit_target(&id, contributor, asset)?;
+
+    // Transfer dari kontributor ke escrow bersama goal
+    let call = LedgerCall::TransferFrom { from: principal_account(contributor), to: escrow };
+    let op = NewOperation::new(OperationKind::Lock, EntityKind::Goal, &id, owner, ledger, call, amount.clone()).with_memo(b"goals_add_funds");
+    journal::execute(op).await?;
+    credit_deposit(&id, ledger, contributor, amount, None, price);
+    goals_get(id.clone()).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+pub async fn goals_withdraw(id: String, amount: Nat) -> Result<Nat, FintrackError> {
+    withdraw_in(id, None, amount).await
+}
+
+/// Withdraw per asset setelah unlock (goal multi-asset)
+pub async fn goals_withdraw_asset(id: String, asset: Principal, amount: Nat) -> Result<Nat, FintrackError> {
+    withdraw_in(id, Some(asset), amount).await
+}
+
+async fn withdraw_in(id: String, asset: Option<Principal>, amount: Nat) -> Result<Nat, FintrackError> {
+    if amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount", "must be greater than zero")); }
+    let who = caller();
+    // Guard dulu: jatah pro-rata dihitung dari saldo yang tidak sedang dipakai withdraw lain
+    let _guard = EntityGuard::acquire("goal", &id)?;
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    let ledger = asset.unwrap_or(gi.asset_canister);
+    match policy_of(&gi) {
+        WithdrawalPolicy::OwnerOnly => {
+            if gi.owner != who { return Err(FintrackError::unauthorized("not the goal owner")); }
+        }
+        WithdrawalPolicy::ProRata => {
+            if !is_member(&gi, who) { return Err(FintrackError::unauthorized("not a goal member")); }
+            let refundable = pro_rata_refundable(&gi, who, ledger);
+            if amount.0 > refundable.0 { return Err(FintrackError::InsufficientFunds { needed: amount, available: refundable }); }
+        }
+        WithdrawalPolicy::Approval { .. } => {
+            return Err(FintrackError::invalid_state("withdrawals from this goal need member approval, use goals_propose_withdrawal"));
         }
-        Err(e) => Err(e)
     }
+    send_available(&id, ledger, amount, who).await
 }
 
-pub async fn goals_withdraw(id: String, amount: Nat) -> Result<Nat, String> {
-    if amount.0 == num::BigUint::from(0u32) { return Err("amount harus > 0".into()); }
-    let (asset, owner, available, escrow) = GOALS.with(|g| {
-        let map = g.borrow();
-        let gi = map.get(&id).ok_or_else(|| "goal not found".to_string())?;
-        if gi.info.owner != caller() { return Err("unauthorized".to_string()); }
-        Ok((gi.info.asset_canister, gi.info.owner, gi.info.available_to_withdraw.clone(), canister_escrow_account(escrow_subaccount(&gi.info.owner, &gi.info.id))))
-    })?;
-    if amount.0 > available.0 { return Err("amount > available".to_string()); }
+// Kirim `amount` dari available_to_withdraw asset `ledger` ke `recipient` (fee ledger dipotong dari amount); pemanggil memegang guard goal
+async fn send_available(id: &str, ledger: Principal, amount: Nat, recipient: Principal) -> Result<Nat, FintrackError> {
+    let (owner, available, escrow) = {
+        let gi = load_goal(id).ok_or_else(|| FintrackError::not_found("goal", id))?;
+        let (_, available) = asset_balances(&gi, ledger).ok_or_else(|| FintrackError::invalid_input("asset", "asset is not part of this goal"))?;
+        (gi.owner, available, canister_escrow_account(asset_escrow_subaccount(&gi, ledger)))
+    };
+    if amount.0 > available.0 { return Err(FintrackError::InsufficientFunds { needed: amount, available }); }
+
+    // Reserve sebelum await: withdraw lain tidak bisa memakai saldo yang sama; dikembalikan bila gagal
+    adjust_available(id, ledger, &amount, false);
+    let release = |id: &str| adjust_available(id, ledger, &amount, true);
 
     // Ambil fee ledger dan kirim net = amount - fee. State mengurangi amount penuh.
-    let fee = icrc1_fee(asset).await?;
-    if amount.0 <= fee.0 { return Err("amount harus > fee ledger".to_string()); }
+    let fee = match icrc1_fee(ledger).await {
+        Ok(fee) => fee,
+        Err(e) => { release(id); return Err(e); }
+    };
+    if amount.0 <= fee.0 {
+        release(id);
+        return Err(FintrackError::invalid_input("amount", format!("must be greater than the ledger fee {}", fee)));
+    }
     let net = Nat(&amount.0 - &fee.0);
 
     // Transfer balik ke user dengan net amount
-    let arg = TransferArg { from_subaccount: escrow.subaccount.clone(), to: principal_account(owner), amount: net.clone(), fee: None, memo: Some(b"goals_user_withdraw".to_vec()), created_at_time: Some(time()) };
-    let res = icrc1_transfer(asset, arg).await?;
-    GOALS.with(|g| if let Some(gi) = g.borrow_mut().get_mut(&id) { gi.info.available_to_withdraw = Nat(&gi.info.available_to_withdraw.0 - &amount.0); gi.info.updated_at_ns = time(); });
-    
+    let call = LedgerCall::Transfer { from_subaccount: escrow.subaccount.clone(), to: principal_account(recipient) };
+    let mut op = NewOperation::new(OperationKind::Withdraw, EntityKind::Goal, id, owner, ledger, call, net).with_memo(b"goals_user_withdraw");
+    op.reserved = amount.clone();
+    let res = match journal::execute(op).await {
+        Ok(res) => res,
+        Err(e) if journal::is_in_doubt(&e) => return Err(e), // reservasi tetap, diselesaikan reconciler
+        Err(e) => { release(id); return Err(e); }
+    };
+
     // Log withdraw event
-    push_goal_event(&id, GoalEvent {
+    log_withdraw(id, ledger, recipient, amount, &fee);
+    Ok(res)
+}
+
+// Tambah (release) atau kurangi (reserve) available_to_withdraw satu asset
+fn adjust_available(id: &str, ledger: Principal, amount: &Nat, add: bool) {
+    with_goal_mut(id, |gi| {
+        if let Some((_, available)) = balances_mut(gi, ledger) {
+            *available = if add { Nat(&available.0 + &amount.0) } else { Nat(&available.0 - &amount.0) };
+        }
+        gi.updated_at_ns = time();
+    });
+}
+
+// Withdraw tereksekusi: atribusikan ke penerima lalu catat event
+fn log_withdraw(id: &str, ledger: Principal, recipient: Principal, amount: Nat, fee: &Nat) {
+    let asset = with_goal_mut(id, |gi| {
+        record_withdrawal(gi, recipient, ledger, &amount);
+        asset_tag(gi, ledger)
+    }).flatten();
+    push_goal_event(id, GoalEvent {
         at_time_ns: time(),
         kind: GoalEventKind::Withdraw,
         amount: Some(amount),
         note: Some(format!("fee_deducted:{}", fee.0)),
+        price: None,
+        asset,
+        by: Some(recipient),
     });
-    
-    Ok(res)
 }
 
-// List events untuk goal
-pub fn goals_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<GoalEvent>, String> {
-    let off = offset.unwrap_or(0) as usize;
-    let lim = limit.unwrap_or(50) as usize;
-    GOAL_EVENTS.with(|e| {
-        let map = e.borrow();
-        let v = map.get(&id).cloned().unwrap_or_default();
-        let end = (off + lim).min(v.len());
-        if off >= v.len() { Ok(vec![]) } else { Ok(v[off..end].to_vec()) }
-    })
+// Hasil akhir operasi journal yang diselesaikan reconciler; false = goal sudah tidak ada (entry jadi Stuck)
+pub(crate) fn settle_operation(op: &PendingOperation, result: Result<Nat, FintrackError>) -> bool {
+    if load_goal(&op.entity_id).is_none() { return false; }
+    match (op.kind, result) {
+        (OperationKind::Lock, Ok(_)) => {
+            let contributor = match &op.call { LedgerCall::TransferFrom { from, .. } => from.owner, LedgerCall::Transfer { .. } => op.owner };
+            credit_deposit(&op.entity_id, op.ledger, contributor, op.amount.clone(), Some("confirmed by reconciler".to_string()), None);
+        }
+        (OperationKind::Lock, Err(_)) => {} // tidak ada yang dikredit
+        (OperationKind::Withdraw, Ok(_)) => {
+            let fee = if op.reserved.0 > op.amount.0 { Nat(&op.reserved.0 - &op.amount.0) } else { Nat::from(0u64) };
+            let recipient = match &op.call { LedgerCall::Transfer { to, .. } => to.owner, LedgerCall::TransferFrom { .. } => op.owner };
+            log_withdraw(&op.entity_id, op.ledger, recipient, op.reserved.clone(), &fee);
+        }
+        (OperationKind::Withdraw, Err(_)) => adjust_available(&op.entity_id, op.ledger, &op.reserved, true),
+        // Net early exit masuk available; owner menariknya lewat goals_withdraw
+        (OperationKind::Penalty, Ok(_)) => return finish_early_exit(&op.entity_id, Some(&op.amount)).is_some(),
+        (OperationKind::Penalty, Err(e)) => return revert_early_exit(&op.entity_id, &e),
+        _ => return false,
+    }
+    true
 }
 
-// Helper function to check and update goal status based on time and target
-fn check_and_update_goal_status(goal: &mut GoalInfo) {
-    let now = time();
-    
-    // Check if period has ended
-    if now >= goal.end_ns {
-        if goal.locked_balance.0 >= goal.amount_to_lock.0 {
-            // Target reached and period ended - Completed
-            goal.status = GoalStatus::Completed;
-        } else {
-            // Period ended but target not reached - Failed
-            goal.status = GoalStatus::Failed;
+// -------- Early exit (opt-in, dengan penalti) --------
+
+// Batas bawah supaya early exit tidak menjadi jalan pintas membuka lock tanpa biaya
+const MIN_PENALTY_BPS: u32 = 100;
+const MAX_PENALTY_BPS: u32 = 5_000;
+const MIN_COOLING_OFF_HOURS: u32 = 24;
+const MAX_COOLING_OFF_HOURS: u32 = 30 * 24;
+
+thread_local! {
+    // timer eksekusi early exit per goal (heap, di-arm ulang saat post_upgrade)
+    static EARLY_EXIT_TIMERS: RefCell<BTreeMap<String, TimerId>> = const { RefCell::new(BTreeMap::new()) };
+    // penalti yang masuk community pool, key = ledger
+    static COMMUNITY_POOL: RefCell<StableBTreeMap<StableText, Candid<Nat>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::COMMUNITY_POOL_MEMORY_ID))
+    );
+}
+
+fn validate_early_exit_terms(terms: &EarlyExitTerms) -> Result<(), FintrackError> {
+    if !(MIN_PENALTY_BPS..=MAX_PENALTY_BPS).contains(&terms.penalty_bps) {
+        return Err(FintrackError::invalid_input("early_exit", format!("penalty_bps must be between {} and {}", MIN_PENALTY_BPS, MAX_PENALTY_BPS)));
+    }
+    if !(MIN_COOLING_OFF_HOURS..=MAX_COOLING_OFF_HOURS).contains(&terms.cooling_off_hours) {
+        return Err(FintrackError::invalid_input("early_exit", format!("cooling_off_hours must be between {} and {}", MIN_COOLING_OFF_HOURS, MAX_COOLING_OFF_HOURS)));
+    }
+    Ok(())
+}
+
+// Early exit hanya menyelesaikan asset utama; goal multi-asset tidak didukung
+fn check_early_exit_supported(gi: &GoalInfo) -> Result<(), FintrackError> {
+    if gi.extra_assets.as_ref().is_some_and(|a| !a.is_empty()) {
+        return Err(FintrackError::invalid_state("early exit is not available for multi-asset goals"));
+    }
+    Ok(())
+}
+
+// Terms hanya boleh ditambahkan sebelum ada dana terkunci, supaya tidak bisa dipakai melonggarkan lock yang sudah berjalan
+fn check_early_exit_enable(gi: &GoalInfo) -> Result<(), FintrackError> {
+    if gi.early_exit.is_some() { return Err(FintrackError::invalid_state("early-exit terms are already set")); }
+    check_early_exit_supported(gi)?;
+    let zero = num::BigUint::from(0u32);
+    if asset_ledgers(gi).into_iter().any(|l| asset_balances(gi, l).is_some_and(|(locked, _)| locked.0 > zero)) {
+        return Err(FintrackError::invalid_state("early-exit terms can only be added before funds are locked"));
+    }
+    Ok(())
+}
+
+fn community_pool_account() -> Account {
+    use sha3::{Digest, Keccak256};
+    let hash = Keccak256::digest(b"fintrack-community-pool");
+    Account { owner: ic_cdk::id(), subaccount: Some(hash.to_vec()) }
+}
+
+fn credit_community_pool(ledger: Principal, amount: &Nat) {
+    let key = StableText(ledger.to_text());
+    COMMUNITY_POOL.with(|p| {
+        let mut p = p.borrow_mut();
+        let current = p.get(&key).map(|c| c.0).unwrap_or_else(|| Nat::from(0u64));
+        p.insert(key, Candid(Nat(&current.0 + &amount.0)));
+    });
+}
+
+pub fn goals_community_pool_balance(ledger: Principal) -> Nat {
+    COMMUNITY_POOL.with(|p| p.borrow().get(&StableText(ledger.to_text()))).map(|c| c.0).unwrap_or_else(|| Nat::from(0u64))
+}
+
+async fn penalty_account(dest: &PenaltyDestination, ledger: Principal) -> Result<Account, FintrackError> {
+    match dest {
+        // Burn ICRC-1 = transfer ke minting account
+        PenaltyDestination::Burn => {
+            let (acc,): (Option<Account>,) = ic_cdk::call(ledger, "icrc1_minting_account", ()).await
+                .map_err(|e| FintrackError::ledger(format!("call failed: {:?}", e)))?;
+            acc.ok_or_else(|| FintrackError::invalid_state("ledger has no minting account"))
         }
-        goal.updated_at_ns = now;
-    } else if goal.locked_balance.0 >= goal.amount_to_lock.0 {
-        // Target reached before period ended - Completed
-        goal.status = GoalStatus::Completed;
-        goal.updated_at_ns = now;
+        PenaltyDestination::Donate(account) => Ok(account.clone()),
+        PenaltyDestination::CommunityPool => Ok(community_pool_account()),
     }
-    // If period hasn't ended and target not reached, keep as Active
 }
 
-// Update all goals status based on current time
-pub fn update_all_goals_status() {
-    GOALS.with(|g| {
-        let mut map = g.borrow_mut();
-        for (_, goal_internal) in map.iter_mut() {
-            check_and_update_goal_status(&mut goal_internal.info);
+fn arm_early_exit_timer(goal_id: &str, at_ns: u64) {
+    let id = goal_id.to_string();
+    let tid = timers::schedule_at(at_ns, move || {
+        ic_cdk::futures::spawn(async move { handle_early_exit_timer(id).await; });
+    });
+    let old = EARLY_EXIT_TIMERS.with(|t| t.borrow_mut().insert(goal_id.to_string(), tid));
+    timers::cancel_timer(old);
+}
+
+fn clear_early_exit_timer(goal_id: &str) {
+    let old = EARLY_EXIT_TIMERS.with(|t| t.borrow_mut().remove(goal_id));
+    timers::cancel_timer(old);
+}
+
+async fn handle_early_exit_timer(goal_id: String) {
+    EARLY_EXIT_TIMERS.with(|t| t.borrow_mut().remove(&goal_id));
+    let Ok(_guard) = EntityGuard::acquire("goal", &goal_id) else {
+        arm_early_exit_timer(&goal_id, time() + guard::RETRY_DELAY_NS);
+        return;
+    };
+    execute_early_exit(&goal_id).await;
+}
+
+fn push_early_exit_event(goal_id: &str, kind: GoalEventKind, amount: Option<Nat>, note: String) {
+    push_goal_event(goal_id, GoalEvent { at_time_ns: time(), kind, amount, note: Some(note), price: None, asset: None, by: None });
+}
+
+// Gagal sebelum dana berpindah: request tetap pending dengan last_error
+fn record_early_exit_error(goal_id: &str, e: &FintrackError) {
+    with_goal_mut(goal_id, |gi| {
+        if let Some(req) = gi.pending_early_exit.as_mut() { req.last_error = Some(e.to_string()); }
+    });
+}
+
+// Penalti tereksekusi (atau dibebaskan): sisa amount menjadi available, request selesai. Mengembalikan net.
+fn finish_early_exit(goal_id: &str, penalty_sent: Option<&Nat>) -> Option<Nat> {
+    let (net, penalty, ledger, to_pool) = with_goal_mut(goal_id, |gi| {
+        let req = gi.pending_early_exit.take()?;
+        let penalty = if penalty_sent.is_some() { req.penalty.clone() } else { Nat::from(0u64) };
+        let net = Nat(&req.amount.0 - &penalty.0);
+        gi.available_to_withdraw = Nat(&gi.available_to_withdraw.0 + &net.0);
+        gi.updated_at_ns = time();
+        let to_pool = gi.early_exit.as_ref().is_some_and(|t| t.destination == PenaltyDestination::CommunityPool);
+        Some((net, penalty, gi.asset_canister, to_pool))
+    }).flatten()?;
+    if let (true, Some(sent)) = (to_pool, penalty_sent) { credit_community_pool(ledger, sent); }
+    let note = match penalty_sent {
+        Some(_) => format!("penalty:{}", penalty),
+        None => "penalty waived (below ledger fee)".to_string(),
+    };
+    push_early_exit_event(goal_id, GoalEventKind::EarlyExit, Some(Nat(&net.0 + &penalty.0)), note);
+    Some(net)
+}
+
+// Transfer penalti pasti gagal: amount kembali ke locked, request bisa dieksekusi ulang atau dibatalkan
+fn revert_early_exit(goal_id: &str, e: &FintrackError) -> bool {
+    with_goal_mut(goal_id, |gi| {
+        let Some(req) = gi.pending_early_exit.as_mut() else { return false };
+        gi.locked_balance = Nat(&gi.locked_balance.0 + &req.amount.0);
+        req.executing = false;
+        req.last_error = Some(e.to_string());
+        gi.updated_at_ns = time();
+        true
+    }).unwrap_or(false)
+}
+
+// Eksekusi request setelah cooling-off; pemanggil memegang guard goal
+async fn execute_early_exit(goal_id: &str) {
+    let Some(gi) = load_goal(goal_id) else { return };
+    let (Some(terms), Some(req)) = (gi.early_exit.clone(), gi.pending_early_exit.clone()) else { return };
+    if req.executing { return; }
+    if let Err(e) = check_early_exit_supported(&gi) {
+        // Request lama pada goal multi-asset: belum ada dana yang dipotong, cukup dibatalkan
+        with_goal_mut(goal_id, |gi| { gi.pending_early_exit = None; });
+        push_early_exit_event(goal_id, GoalEventKind::EarlyExitCancelled, Some(req.amount), e.to_string());
+        return;
+    }
+    if time() >= gi.end_ns {
+        // Periode sudah selesai: dana keluar lewat unlock biasa tanpa penalti
+        with_goal_mut(goal_id, |gi| { gi.pending_early_exit = None; });
+        push_early_exit_event(goal_id, GoalEventKind::EarlyExitCancelled, Some(req.amount), "goal period ended before execution".to_string());
+        return;
+    }
+    let amount = if req.amount.0 > gi.locked_balance.0 { gi.locked_balance.clone() } else { req.amount.clone() };
+    if amount.0 == num::BigUint::from(0u32) {
+        record_early_exit_error(goal_id, &FintrackError::invalid_state("no locked balance left"));
+        return;
+    }
+    let penalty = Nat(&amount.0 * terms.penalty_bps / 10_000u32);
+
+    let (fee, destination) = match (icrc1_fee(gi.asset_canister).await, penalty_account(&terms.destination, gi.asset_canister).await) {
+        (Ok(fee), Ok(dest)) => (fee, dest),
+        (Err(e), _) | (_, Err(e)) => { record_early_exit_error(goal_id, &e); return; }
+    };
+    // Burn tidak dikenai fee ledger
+    let penalty_fee = if terms.destination == PenaltyDestination::Burn { Nat::from(0u64) } else { fee };
+
+    // Reserve dari locked sebelum transfer
+    with_goal_mut(goal_id, |gi| {
+        gi.locked_balance = Nat(&gi.locked_balance.0 - &amount.0);
+        gi.updated_at_ns = time();
+        if let Some(r) = gi.pending_early_exit.as_mut() {
+            r.amount = amount.clone();
+            r.penalty = penalty.clone();
+            r.executing = true;
+            r.last_error = None;
         }
     });
+
+    let net = if penalty.0 > penalty_fee.0 {
+        let sent = Nat(&penalty.0 - &penalty_fee.0);
+        let call = LedgerCall::Transfer { from_subaccount: Some(escrow_subaccount(&gi.owner, &gi.id).to_vec()), to: destination };
+        let mut op = NewOperation::new(OperationKind::Penalty, EntityKind::Goal, goal_id, gi.owner, gi.asset_canister, call, sent.clone()).with_memo(b"goals_early_exit_penalty");
+        op.reserved = penalty.clone();
+        match journal::execute(op).await {
+            Ok(_) => finish_early_exit(goal_id, Some(&sent)),
+            // Reconciler menyelesaikan penalti; net masuk available setelah itu
+            Err(e) if journal::is_in_doubt(&e) => return,
+            Err(e) => { revert_early_exit(goal_id, &e); return; }
+        }
+    } else {
+        finish_early_exit(goal_id, None)
+    };
+
+    // Kirim net ke owner; bila gagal dana tetap di available dan bisa ditarik lewat goals_withdraw
+    if let Some(net) = net {
+        if let Err(e) = send_available(goal_id, gi.asset_canister, net, gi.owner).await {
+            ic_cdk::println!("goal {}: early exit payout deferred: {}", goal_id, e);
+        }
+    }
 }
 
+/// Aktifkan early exit untuk goal yang belum punya terms dan belum ada dana terkunci (tidak bisa diubah setelahnya)
+pub fn goals_enable_early_exit(id: String, terms: EarlyExitTerms) -> Result<GoalInfo, FintrackError> {
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    check_early_exit_enable(&gi)?;
+    if policy_of(&gi) != WithdrawalPolicy::OwnerOnly { return Err(FintrackError::invalid_state("early exit is only available for owner-only goals")); }
+    validate_early_exit_terms(&terms)?;
+    gi.early_exit = Some(terms);
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    Ok(gi)
+}
+
+/// Minta early exit; dieksekusi setelah cooling-off (langsung hanya untuk terms lama dengan cooling_off_hours = 0)
+pub async fn goals_request_early_exit(id: String, amount: Option<Nat>) -> Result<GoalInfo, FintrackError> {
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    let terms = gi.early_exit.clone().ok_or_else(|| FintrackError::invalid_state("goal has no early-exit terms"))?;
+    check_early_exit_supported(&gi)?;
+    if gi.pending_early_exit.is_some() { return Err(FintrackError::invalid_state("an early exit is already pending")); }
+    let now = time();
+    if now >= gi.end_ns { return Err(FintrackError::invalid_state("goal period has ended, use goals_refresh and goals_withdraw")); }
+    let amount = amount.unwrap_or_else(|| gi.locked_balance.clone());
+    if amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount", "must be greater than zero")); }
+    if amount.0 > gi.locked_balance.0 { return Err(FintrackError::InsufficientFunds { needed: amount, available: gi.locked_balance }); }
 
+    let penalty = Nat(&amount.0 * terms.penalty_bps / 10_000u32);
+    let execute_at_ns = now + terms.cooling_off_hours as u64 * timers::SEC_PER_HOUR * timers::NS_PER_SEC;
+    with_goal_mut(&id, |gi| {
+        gi.pending_early_exit = Some(EarlyExitRequest {
+            amount: amount.clone(),
+            penalty: penalty.clone(),
+            requested_at_ns: now,
+            execute_at_ns,
+            executing: false,
+            last_error: None,
+        });
+        gi.updated_at_ns = now;
+    });
+    push_early_exit_event(&id, GoalEventKind::EarlyExitRequested, Some(amount), format!("penalty:{};execute_at_ns:{}", penalty, execute_at_ns));
+
+    if terms.cooling_off_hours == 0 {
+        // Request sudah tersimpan: guard sibuk = eksekusi diulang lewat timer, bukan dibiarkan pending
+        match EntityGuard::acquire("goal", &id) {
+            Ok(_guard) => execute_early_exit(&id).await,
+            Err(_) => arm_early_exit_timer(&id, now + guard::RETRY_DELAY_NS),
+        }
+    } else {
+        arm_early_exit_timer(&id, execute_at_ns);
+    }
+    load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+pub fn goals_cancel_early_exit(id: String) -> Result<GoalInfo, FintrackError> {
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    let req = gi.pending_early_exit.take().ok_or_else(|| FintrackError::invalid_state("no early exit is pending"))?;
+    if req.executing { return Err(FintrackError::invalid_state("early exit is already executing")); }
+    clear_early_exit_timer(&id);
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    push_early_exit_event(&id, GoalEventKind::EarlyExitCancelled, Some(req.amount), "cancelled by owner".to_string());
+    Ok(gi)
+}
+
+// -------- Auto-contribution (standing instruction) --------
+
+const DEFAULT_MAX_CONTRIBUTION_FAILURES: u32 = 3;
+const MAX_CONTRIBUTION_FAILURES_LIMIT: u32 = 30;
+
+thread_local! {
+    // timer auto-contribution per goal (heap, di-arm ulang saat post_upgrade)
+    static CONTRIBUTION_TIMERS: RefCell<BTreeMap<String, TimerId>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+// Jadwal berikutnya setelah `after_ns`, dihitung dari anchor supaya tidak drift; None untuk OnCkBtcDeposit
+fn next_contribution_at(ac: &AutoContribution, after_ns: u64) -> Option<u64> {
+    if ac.interval == ContributionInterval::OnCkBtcDeposit { return None; }
+    if after_ns < ac.anchor_ns { return Some(ac.anchor_ns); }
+    let step = match ac.interval {
+        ContributionInterval::Daily => timers::SEC_PER_DAY * timers::NS_PER_SEC,
+        ContributionInterval::Weekly => 7 * timers::SEC_PER_DAY * timers::NS_PER_SEC,
+        ContributionInterval::Monthly => {
+            let day = timers::day_of_month(ac.anchor_ns);
+            let mut months = 1;
+            let mut at = timers::add_months_at_day(ac.anchor_ns, months, day);
+            while at <= after_ns {
+                months += 1;
+                at = timers::add_months_at_day(ac.anchor_ns, months, day);
+            }
+            return Some(at);
+        }
+        ContributionInterval::OnCkBtcDeposit => return None,
+    };
+    Some(ac.anchor_ns + ((after_ns - ac.anchor_ns) / step + 1) * step)
+}
+
+fn arm_contribution_timer(goal_id: &str, at_ns: u64) {
+    let id = goal_id.to_string();
+    let tid = timers::schedule_at(at_ns, move || {
+        ic_cdk::futures::spawn(async move { handle_contribution_timer(id).await; });
+    });
+    let old = CONTRIBUTION_TIMERS.with(|t| t.borrow_mut().insert(goal_id.to_string(), tid));
+    timers::cancel_timer(old);
+}
+
+fn clear_contribution_timer(goal_id: &str) {
+    let old = CONTRIBUTION_TIMERS.with(|t| t.borrow_mut().remove(goal_id));
+    timers::cancel_timer(old);
+}
+
+async fn handle_contribution_timer(goal_id: String) {
+    CONTRIBUTION_TIMERS.with(|t| t.borrow_mut().remove(&goal_id));
+    {
+        let Ok(_guard) = EntityGuard::acquire("goal", &goal_id) else {
+            // Goal sedang dipakai operasi lain: coba lagi sebentar lagi
+            arm_contribution_timer(&goal_id, time() + guard::RETRY_DELAY_NS);
+            return;
+        };
+        contribute(&goal_id).await;
+    }
+
+    let next = with_goal_mut(&goal_id, |gi| {
+        if !accepts_funds(&gi.status) { return None; }
+        let ac = gi.auto_contribution.as_mut()?;
+        ac.next_run_at_ns = if ac.paused { None } else { next_contribution_at(ac, time()) };
+        ac.next_run_at_ns
+    }).flatten();
+    if let Some(at) = next { arm_contribution_timer(&goal_id, at); }
+}
+
+// Satu tarikan icrc2_transfer_from sesuai instruksi; pemanggil memegang guard goal
+async fn contribute(goal_id: &str) {
+    let Some(gi) = advance_goal(goal_id) else { return };
+    if !accepts_funds(&gi.status) { return; }
+    let Some(ac) = gi.auto_contribution.clone() else { return };
+    if ac.paused || target_reached(&gi) { return; }
+
+    // Jangan melebihi target token (goal fiat: target bergerak mengikuti harga, tidak di-cap)
+    let amount = if gi.fiat_target.is_none() && ac.amount.0 > &gi.amount_to_lock.0 - &gi.locked_balance.0 {
+        Nat(&gi.amount_to_lock.0 - &gi.locked_balance.0)
+    } else {
+        ac.amount.clone()
+    };
+    let price = contribution_price(&gi.id, gi.asset_canister).await;
+    let call = LedgerCall::TransferFrom { from: principal_account(gi.owner), to: canister_escrow_account(escrow_subaccount(&gi.owner, &gi.id)) };
+    let op = NewOperation::new(OperationKind::Lock, EntityKind::Goal, &gi.id, gi.owner, gi.asset_canister, call, amount.clone()).with_memo(b"goals_auto_contribution");
+    let result = journal::execute(op).await;
+    let now = time();
+
+    match result {
+        Ok(_) => {
+            credit_deposit(goal_id, gi.asset_canister, gi.owner, amount, Some("auto_contribution".to_string()), price);
+            with_goal_mut(goal_id, |gi| {
+                if let Some(ac) = gi.auto_contribution.as_mut() {
+                    ac.last_attempt_at_ns = Some(now);
+                    ac.consecutive_failures = 0;
+                    ac.last_error = None;
+                }
+            });
+        }
+        // Reconciler mengkredit bila transfer ternyata tereksekusi
+        Err(e) if journal::is_in_doubt(&e) => {}
+        Err(e) => {
+            // Hanya penolakan saldo/allowance yang dihitung untuk auto-pause
+            let paused_after = with_goal_mut(goal_id, |gi| {
+                let ac = gi.auto_contribution.as_mut()?;
+                ac.last_attempt_at_ns = Some(now);
+                ac.last_error = Some(e.to_string());
+                if journal::is_funding_failure(&e) { ac.consecutive_failures += 1; }
+                if ac.consecutive_failures >= ac.max_failures && !ac.paused {
+                    ac.paused = true;
+                    ac.next_run_at_ns = None;
+                    return Some(ac.consecutive_failures);
+                }
+                None
+            }).flatten();
+            let note = match paused_after {
+                Some(n) => format!("{}; paused after {} consecutive failures", e, n),
+                None => e.to_string(),
+            };
+            push_goal_event(goal_id, GoalEvent { at_time_ns: now, kind: GoalEventKind::AutoContributionFailed, amount: Some(amount), note: Some(note), price: None, asset: None, by: None });
+        }
+    }
+}
+
+/// Pasang (atau ganti) instruksi auto-contribution; memasang ulang juga me-resume instruksi yang di-pause
+pub fn goals_set_auto_contribution(id: String, req: AutoContributionRequest) -> Result<GoalInfo, FintrackError> {
+    let now = time();
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    if !accepts_funds(&gi.status) { return Err(FintrackError::invalid_state("goal is not accepting funds")); }
+    if req.amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount", "must be greater than zero")); }
+    let max_failures = req.max_failures.unwrap_or(DEFAULT_MAX_CONTRIBUTION_FAILURES);
+    if max_failures == 0 || max_failures > MAX_CONTRIBUTION_FAILURES_LIMIT {
+        return Err(FintrackError::invalid_input("max_failures", format!("must be between 1 and {}", MAX_CONTRIBUTION_FAILURES_LIMIT)));
+    }
+    if req.interval == ContributionInterval::OnCkBtcDeposit && gi.asset_canister != config::get().ckbtc_ledger {
+        return Err(FintrackError::invalid_input("interval", "deposit-triggered contributions are only available for ckBTC goals"));
+    }
+    if let Some(start) = req.start_at_ns {
+        if start < now || start >= gi.end_ns { return Err(FintrackError::invalid_input("start_at_ns", "must be between now and the goal end")); }
+    }
+
+    let mut ac = AutoContribution {
+        amount: req.amount,
+        interval: req.interval,
+        anchor_ns: req.start_at_ns.unwrap_or(now),
+        next_run_at_ns: None,
+        max_failures,
+        consecutive_failures: 0,
+        paused: false,
+        last_attempt_at_ns: None,
+        last_error: None,
+    };
+    ac.next_run_at_ns = next_contribution_at(&ac, now);
+    match ac.next_run_at_ns {
+        Some(at) => arm_contribution_timer(&id, at),
+        None => clear_contribution_timer(&id),
+    }
+    gi.auto_contribution = Some(ac);
+    gi.updated_at_ns = now;
+    store_goal(&gi);
+    Ok(gi)
+}
+
+pub fn goals_cancel_auto_contribution(id: String) -> Result<GoalInfo, FintrackError> {
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    if gi.auto_contribution.take().is_none() { return Err(FintrackError::invalid_state("goal has no auto-contribution")); }
+    clear_contribution_timer(&id);
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    Ok(gi)
+}
+
+/// Deposit ckBTC baru sudah di-mint ke akun default `owner`: jalankan instruksi OnCkBtcDeposit miliknya
+pub(crate) async fn on_ckbtc_deposit(owner: Principal) {
+    let ledger = config::get().ckbtc_ledger;
+    // Index juga memuat goal tempat owner hanya member; auto-contribution milik owner goal saja
+    let ids: Vec<String> = indexed_goals(member_prefix(owner)).into_iter()
+        .filter(|gi| gi.owner == owner && gi.asset_canister == ledger && accepts_funds(&gi.status))
+        .filter(|gi| gi.auto_contribution.as_ref().is_some_and(|ac| ac.interval == ContributionInterval::OnCkBtcDeposit && !ac.paused))
+        .map(|gi| gi.id)
+        .collect();
+    for id in ids {
+        let Ok(_guard) = EntityGuard::acquire("goal", &id) else { continue };
+        contribute(&id).await;
+    }
+}
+
+// Timer tidak persist: arm ulang maturity, auto-contribution dan early exit setelah upgrade.
+// Goal berstatus lama Completed dimigrasi ke state machine di sini.
+pub fn post_upgrade() {
+    // Backfill index principal → goal dan goal aktif untuk goal yang dibuat sebelum index ada (idempoten)
+    GOALS.with(|g| for (_, v) in g.borrow().iter() { reindex_goal(None, Some(&v.0)); });
+
+    let legacy: Vec<String> = GOALS.with(|g| {
+        g.borrow().iter().map(|(_, v)| v.0).filter(|gi| gi.status == GoalStatus::Completed).map(|gi| gi.id).collect()
+    });
+    for id in legacy {
+        advance_goal(&id);
+    }
+
+    let maturing: Vec<(String, u64)> = GOALS.with(|g| {
+        g.borrow().iter().map(|(_, v)| v.0).filter(|gi| accepts_funds(&gi.status)).map(|gi| (gi.id, gi.end_ns)).collect()
+    });
+    for (id, at) in maturing {
+        arm_maturity_timer(&id, at);
+    }
+
+    let due: Vec<(String, u64)> = GOALS.with(|g| {
+        g.borrow().iter().map(|(_, v)| v.0)
+            .filter(|gi| accepts_funds(&gi.status))
+            .filter_map(|gi| gi.auto_contribution.as_ref().filter(|ac| !ac.paused).and_then(|ac| ac.next_run_at_ns).map(|at| (gi.id.clone(), at)))
+            .collect()
+    });
+    for (id, at) in due {
+        arm_contribution_timer(&id, at);
+    }
+
+    let exits: Vec<(String, u64)> = GOALS.with(|g| {
+        g.borrow().iter().map(|(_, v)| v.0)
+            .filter_map(|gi| gi.pending_early_exit.as_ref().filter(|r| !r.executing).map(|r| (gi.id.clone(), r.execute_at_ns)))
+            .collect()
+    });
+    for (id, at) in exits {
+        arm_early_exit_timer(&id, at);
+    }
+}
+
+// -------- Group goals (co-contributor + withdrawal policy) --------
+
+const MAX_GOAL_MEMBERS: usize = 20;
+
+fn validate_withdrawal_policy(policy: &WithdrawalPolicy, early_exit: bool) -> Result<(), FintrackError> {
+    if early_exit && *policy != WithdrawalPolicy::OwnerOnly {
+        return Err(FintrackError::invalid_input("early_exit", "early exit is only available for owner-only goals"));
+    }
+    if let WithdrawalPolicy::Approval { required } = policy {
+        if *required == 0 || *required as usize > MAX_GOAL_MEMBERS + 1 {
+            return Err(FintrackError::invalid_input("withdrawal_policy", format!("required approvals must be between 1 and {}", MAX_GOAL_MEMBERS + 1)));
+        }
+    }
+    Ok(())
+}
+
+fn policy_of(gi: &GoalInfo) -> WithdrawalPolicy { gi.withdrawal_policy.clone().unwrap_or(WithdrawalPolicy::OwnerOnly) }
+
+// Owner atau co-contributor yang sudah bergabung
+fn is_member(gi: &GoalInfo, who: Principal) -> bool {
+    gi.owner == who || gi.members.iter().flatten().any(|m| *m == who)
+}
+
+fn contribution_mut(gi: &mut GoalInfo, who: Principal, ledger: Principal) -> &mut Contribution {
+    let list = gi.contributions.get_or_insert_with(Vec::new);
+    let idx = match list.iter().position(|c| c.contributor == who && c.asset_canister == ledger) {
+        Some(idx) => idx,
+        None => {
+            list.push(Contribution { contributor: who, asset_canister: ledger, contributed: Nat::from(0u64), withdrawn: Nat::from(0u64) });
+            list.len() - 1
+        }
+    };
+    &mut list[idx]
+}
+
+fn record_contribution(gi: &mut GoalInfo, who: Principal, ledger: Principal, amount: &Nat) {
+    let c = contribution_mut(gi, who, ledger);
+    c.contributed = Nat(&c.contributed.0 + &amount.0);
+}
+
+fn record_withdrawal(gi: &mut GoalInfo, who: Principal, ledger: Principal, amount: &Nat) {
+    let c = contribution_mut(gi, who, ledger);
+    c.withdrawn = Nat(&c.withdrawn.0 + &amount.0);
+}
+
+// Kontribusi bersih yang belum ditarik
+fn remaining_of(c: &Contribution) -> num::BigUint {
+    if c.contributed.0 > c.withdrawn.0 { &c.contributed.0 - &c.withdrawn.0 } else { num::BigUint::from(0u32) }
+}
+
+// Jatah pro-rata: available * sisa kontribusi `who` / total sisa kontribusi asset itu
+fn pro_rata_refundable(gi: &GoalInfo, who: Principal, ledger: Principal) -> Nat {
+    let Some((_, available)) = asset_balances(gi, ledger) else { return Nat::from(0u64) };
+    let rows: Vec<&Contribution> = gi.contributions.iter().flatten().filter(|c| c.asset_canister == ledger).collect();
+    let total: num::BigUint = rows.iter().map(|c| remaining_of(c)).sum();
+    let mine: num::BigUint = rows.iter().filter(|c| c.contributor == who).map(|c| remaining_of(c)).sum();
+    if total == num::BigUint::from(0u32) { return Nat::from(0u64); }
+    Nat(available.0 * mine / total)
+}
+
+fn push_group_event(goal_id: &str, kind: GoalEventKind, amount: Option<Nat>, by: Principal, note: Option<String>) {
+    push_goal_event(goal_id, GoalEvent { at_time_ns: time(), kind, amount, note, price: None, asset: None, by: Some(by) });
+}
+
+/// Undang co-contributor ke goal (owner saja)
+pub fn goals_invite_member(id: String, member: Principal) -> Result<GoalInfo, FintrackError> {
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    if member == Principal::anonymous() { return Err(FintrackError::invalid_input("member", "anonymous principal cannot join a goal")); }
+    if is_member(&gi, member) { return Err(FintrackError::invalid_input("member", "already a goal member")); }
+    let invites = gi.pending_invites.get_or_insert_with(Vec::new);
+    if invites.contains(&member) { return Err(FintrackError::invalid_input("member", "already invited")); }
+    if gi.members.iter().flatten().count() + invites.len() >= MAX_GOAL_MEMBERS {
+        return Err(FintrackError::invalid_state(format!("a goal can have at most {} members", MAX_GOAL_MEMBERS)));
+    }
+    invites.push(member);
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    push_group_event(&id, GoalEventKind::MemberInvited, None, gi.owner, Some(member.to_text()));
+    Ok(gi)
+}
+
+/// Terima undangan goal; setelah itu caller bisa add funds dan melihat goal di goals_list
+pub fn goals_accept_invite(id: String) -> Result<GoalInfo, FintrackError> {
+    let who = caller();
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    let invites = gi.pending_invites.get_or_insert_with(Vec::new);
+    let Some(pos) = invites.iter().position(|p| *p == who) else { return Err(FintrackError::unauthorized("no pending invite for caller")) };
+    invites.remove(pos);
+    gi.members.get_or_insert_with(Vec::new).push(who);
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    push_group_event(&id, GoalEventKind::MemberJoined, None, who, None);
+    Ok(gi)
+}
+
+/// Goal yang mengundang caller dan belum diterima
+pub fn goals_list_invitations() -> Vec<GoalInfo> {
+    indexed_goals(invite_prefix(caller()))
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContributorBalance {
+    pub contributor: Principal,
+    pub asset_canister: Principal,
+    pub contributed: Nat,
+    pub withdrawn: Nat,
+    pub share_bps: u32,          // porsi sisa kontribusi terhadap total sisa asset itu
+    pub refundable: Option<Nat>, // policy ProRata: jatah yang bisa ditarik sekarang
+}
+
+/// Ledger per kontributor (anggota goal saja)
+pub fn goals_list_contributions(id: String) -> Result<Vec<ContributorBalance>, FintrackError> {
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if !is_member(&gi, caller()) { return Err(FintrackError::unauthorized("not a goal member")); }
+    let pro_rata = policy_of(&gi) == WithdrawalPolicy::ProRata;
+    let rows = gi.contributions.clone().unwrap_or_default();
+    Ok(rows.iter().map(|c| {
+        let total: num::BigUint = rows.iter().filter(|r| r.asset_canister == c.asset_canister).map(remaining_of).sum();
+        let share_bps = if total == num::BigUint::from(0u32) { 0 } else { (remaining_of(c) * 10_000u32 / total).to_u32().unwrap_or(0) };
+        ContributorBalance {
+            contributor: c.contributor,
+            asset_canister: c.asset_canister,
+            contributed: c.contributed.clone(),
+            withdrawn: c.withdrawn.clone(),
+            share_bps,
+            refundable: pro_rata.then(|| pro_rata_refundable(&gi, c.contributor, c.asset_canister)),
+        }
+    }).collect())
+}
+
+/// Ajukan withdraw untuk goal dengan policy Approval; pengaju otomatis ikut menyetujui
+pub async fn goals_propose_withdrawal(id: String, asset: Option<Principal>, amount: Nat, recipient: Option<Principal>) -> Result<GoalInfo, FintrackError> {
+    if amount.0 == num::BigUint::from(0u32) { return Err(FintrackError::invalid_input("amount", "must be greater than zero")); }
+    let who = caller();
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if !is_member(&gi, who) { return Err(FintrackError::unauthorized("not a goal member")); }
+    if !matches!(policy_of(&gi), WithdrawalPolicy::Approval { .. }) { return Err(FintrackError::invalid_state("goal does not use approval withdrawals")); }
+    if gi.pending_withdrawal.is_some() { return Err(FintrackError::invalid_state("another withdrawal proposal is pending")); }
+    let ledger = asset.unwrap_or(gi.asset_canister);
+    let (_, available) = asset_balances(&gi, ledger).ok_or_else(|| FintrackError::invalid_input("asset", "asset is not part of this goal"))?;
+    if amount.0 > available.0 { return Err(FintrackError::InsufficientFunds { needed: amount, available }); }
+    let recipient = recipient.unwrap_or(who);
+    if !is_member(&gi, recipient) { return Err(FintrackError::invalid_input("recipient", "must be a goal member")); }
+
+    let proposal = WithdrawalProposal { proposer: who, asset_canister: ledger, amount: amount.clone(), recipient, approvals: vec![who], created_at_ns: time(), last_error: None };
+    with_goal_mut(&id, |gi| { gi.pending_withdrawal = Some(proposal); gi.updated_at_ns = time(); });
+    push_group_event(&id, GoalEventKind::WithdrawalProposed, Some(amount), who, Some(format!("recipient:{}", recipient)));
+    execute_approved_withdrawal(&id).await
+}
+
+/// Setujui proposal withdraw yang aktif; dieksekusi begitu persetujuan mencukupi.
+/// Memanggil ulang setelah menyetujui mencoba eksekusi lagi (mis. setelah OperationInProgress).
+pub async fn goals_approve_withdrawal(id: String) -> Result<GoalInfo, FintrackError> {
+    let who = caller();
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if !is_member(&gi, who) { return Err(FintrackError::unauthorized("not a goal member")); }
+    let proposal = gi.pending_withdrawal.ok_or_else(|| FintrackError::invalid_state("no pending withdrawal proposal"))?;
+    if !proposal.approvals.contains(&who) {
+        with_goal_mut(&id, |gi| {
+            if let Some(p) = gi.pending_withdrawal.as_mut() { p.approvals.push(who); }
+            gi.updated_at_ns = time();
+        });
+        push_group_event(&id, GoalEventKind::WithdrawalApproved, Some(proposal.amount), who, None);
+    }
+    execute_approved_withdrawal(&id).await
+}
+
+/// Batalkan proposal withdraw (pengaju atau owner)
+pub fn goals_cancel_withdrawal(id: String) -> Result<GoalInfo, FintrackError> {
+    let who = caller();
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    let proposal = gi.pending_withdrawal.take().ok_or_else(|| FintrackError::invalid_state("no pending withdrawal proposal"))?;
+    if proposal.proposer != who && gi.owner != who { return Err(FintrackError::unauthorized("only the proposer or the goal owner can cancel")); }
+    EntityGuard::acquire("goal", &id)?; // proposal yang sedang dieksekusi tidak bisa dibatalkan
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    push_group_event(&id, GoalEventKind::WithdrawalCancelled, Some(proposal.amount), who, None);
+    Ok(gi)
+}
+
+// Eksekusi proposal bila persetujuan anggota sudah memenuhi `required`
+async fn execute_approved_withdrawal(id: &str) -> Result<GoalInfo, FintrackError> {
+    let _guard = EntityGuard::acquire("goal", id)?;
+    let gi = load_goal(id).ok_or_else(|| FintrackError::not_found("goal", id))?;
+    let (Some(proposal), WithdrawalPolicy::Approval { required }) = (gi.pending_withdrawal.clone(), policy_of(&gi)) else { return Ok(gi) };
+    let approvals = proposal.approvals.iter().filter(|p| is_member(&gi, **p)).count();
+    if approvals < required as usize { return Ok(gi); }
+
+    match send_available(id, proposal.asset_canister, proposal.amount, proposal.recipient).await {
+        Ok(_) => {}
+        // Transfer mungkin tereksekusi: proposal dilepas supaya tidak dikirim dua kali, reconciler menyelesaikan
+        Err(e) if journal::is_in_doubt(&e) => {
+            with_goal_mut(id, |gi| gi.pending_withdrawal = None);
+            return Err(e);
+        }
+        Err(e) => {
+            with_goal_mut(id, |gi| if let Some(p) = gi.pending_withdrawal.as_mut() { p.last_error = Some(e.to_string()); });
+            return Err(e);
+        }
+    }
+    with_goal_mut(id, |gi| { gi.pending_withdrawal = None; gi.updated_at_ns = time(); });
+    load_goal(id).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+// -------- Milestones dan pace (notifikasi lewat services::notifications) --------
+
+const MAX_MILESTONES: usize = 10;
+const MAX_MILESTONE_LABEL: usize = 64;
+const BEHIND_TOLERANCE_BPS: u32 = 500; // tertinggal > 5% dari target baru dianggap behind schedule
+const PROGRESS_WATCH_INTERVAL_NS: u64 = timers::SEC_PER_HOUR * timers::NS_PER_SEC;
+
+fn validate_milestones(specs: &[MilestoneSpec], start_ns: u64, end_ns: u64) -> Result<(), FintrackError> {
+    if specs.len() > MAX_MILESTONES {
+        return Err(FintrackError::invalid_input("milestones", format!("at most {} milestones", MAX_MILESTONES)));
+    }
+    for spec in specs {
+        match &spec.target {
+            MilestoneTarget::Percent(p) if *p == 0 || *p > 100 => return Err(FintrackError::invalid_input("milestones", "percent must be between 1 and 100")),
+            MilestoneTarget::Amount(a) if a.0 == num::BigUint::from(0u32) => return Err(FintrackError::invalid_input("milestones", "amount must be greater than zero")),
+            MilestoneTarget::Date(at) if *at <= start_ns || *at > end_ns => return Err(FintrackError::invalid_input("milestones", "date must be inside the goal period")),
+            _ => {}
+        }
+        if spec.label.as_ref().is_some_and(|l| l.len() > MAX_MILESTONE_LABEL) {
+            return Err(FintrackError::invalid_input("milestones", format!("label must be at most {} bytes", MAX_MILESTONE_LABEL)));
+        }
+    }
+    Ok(())
+}
+
+fn default_milestones() -> Vec<GoalMilestone> {
+    [25, 50, 75].into_iter().map(|p| GoalMilestone { target: MilestoneTarget::Percent(p), label: None, reached_at_ns: None }).collect()
+}
+
+// Milestone dengan target yang sama dengan milestone lama mempertahankan reached_at_ns
+fn milestones_from(specs: &[MilestoneSpec], previous: Option<&[GoalMilestone]>) -> Vec<GoalMilestone> {
+    specs.iter().map(|spec| GoalMilestone {
+        target: spec.target.clone(),
+        label: spec.label.clone(),
+        reached_at_ns: previous.and_then(|prev| prev.iter().find(|m| m.target == spec.target)).and_then(|m| m.reached_at_ns),
+    }).collect()
+}
+
+fn milestone_reached(target: &MilestoneTarget, saved: &Nat, goal_target: &Nat, now: u64, start_ns: u64, end_ns: u64) -> bool {
+    match target {
+        MilestoneTarget::Percent(p) => &saved.0 * 100u32 >= &goal_target.0 * *p,
+        MilestoneTarget::Amount(a) => saved.0 >= a.0,
+        // Checkpoint: progress minimal sebesar pace linear sampai tanggal itu; bila tertinggal, tercapai saat sudah mengejar
+        MilestoneTarget::Date(at) => {
+            let (elapsed, duration) = vesting::elapsed(start_ns, end_ns, *at);
+            now >= *at && saved.0 >= vesting::expected_by(goal_target, elapsed, duration)
+        }
+    }
+}
+
+fn describe_milestone(m: &GoalMilestone) -> String {
+    if let Some(label) = &m.label { return label.clone(); }
+    match &m.target {
+        MilestoneTarget::Percent(p) => format!("{}%", p),
+        MilestoneTarget::Amount(a) => format!("amount {}", a),
+        MilestoneTarget::Date(at) => format!("checkpoint at {}", at),
+    }
+}
+
+// Owner dan anggota group goal
+fn goal_recipients(gi: &GoalInfo) -> Vec<Principal> {
+    std::iter::once(gi.owner).chain(gi.members.iter().flatten().copied()).collect()
+}
+
+// Tandai milestone yang baru tercapai dan nilai pace; event + notifikasi hanya saat status berubah
+fn evaluate_progress(goal_id: &str) {
+    let Some(mut gi) = load_goal(goal_id) else { return };
+    if matches!(gi.status, GoalStatus::Failed | GoalStatus::Archived) { return; }
+    let before = gi.clone();
+    let now = time();
+    let (saved, target) = progress_amounts(&gi);
+
+    let mut reached = Vec::new();
+    let (start_ns, end_ns) = (gi.start_ns, gi.end_ns);
+    for m in gi.milestones.iter_mut().flatten() {
+        if m.reached_at_ns.is_none() && milestone_reached(&m.target, &saved, &target, now, start_ns, end_ns) {
+            m.reached_at_ns = Some(now);
+            reached.push(describe_milestone(m));
+        }
+    }
+
+    // Pace hanya dinilai selama goal aktif dan periode masih berjalan
+    let (elapsed, duration) = vesting::elapsed(gi.start_ns, gi.end_ns, now);
+    let expected = vesting::expected_by(&target, elapsed, duration);
+    let behind = gi.status == GoalStatus::Active && now < gi.end_ns && vesting::behind_pace(&saved, &target, elapsed, duration, BEHIND_TOLERANCE_BPS);
+    let newly_behind = behind && gi.behind_schedule_since.is_none();
+    gi.behind_schedule_since = if behind { gi.behind_schedule_since.or(Some(now)) } else { None };
+
+    if gi == before { return; }
+    store_goal(&gi);
+    let recipients = goal_recipients(&gi);
+    for label in reached {
+        push_goal_event(goal_id, GoalEvent { at_time_ns: now, kind: GoalEventKind::MilestoneReached, amount: Some(saved.clone()), note: Some(label.clone()), price: None, asset: None, by: None });
+        for user in &recipients {
+            notifications::notify(*user, NotificationKind::GoalMilestone, goal_id, format!("Goal \"{}\": milestone {} reached", gi.name, label));
+        }
+    }
+    if newly_behind {
+        push_goal_event(goal_id, GoalEvent { at_time_ns: now, kind: GoalEventKind::BehindSchedule, amount: Some(saved.clone()), note: Some(format!("expected:{}", expected)), price: None, asset: None, by: None });
+        for user in &recipients {
+            notifications::notify(*user, NotificationKind::GoalBehindSchedule, goal_id, format!("Goal \"{}\" is behind schedule: {} saved, {} expected by now", gi.name, saved, expected));
+        }
+    }
+}
+
+/// Ganti milestone goal (owner saja); milestone yang sama dengan sebelumnya tidak dipicu ulang
+pub fn goals_set_milestones(id: String, milestones: Vec<MilestoneSpec>) -> Result<GoalInfo, FintrackError> {
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    validate_milestones(&milestones, gi.start_ns, gi.end_ns)?;
+    gi.milestones = Some(milestones_from(&milestones, gi.milestones.as_deref()));
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    evaluate_progress(&id);
+    load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+/// Arm pemantau milestone tanggal + pace untuk goal aktif (init dan post_upgrade; timer hanya hidup di heap)
+pub fn start_progress_watch() {
+    ic_cdk_timers::set_timer_interval(std::time::Duration::from_nanos(PROGRESS_WATCH_INTERVAL_NS), || {
+        for gi in indexed_goals(ACTIVE_PREFIX.to_string()) { evaluate_progress(&gi.id); }
+    });
+}
+
+// -------- Sweep dari modul lain (rollover budget) --------
+
+/// Escrow goal tujuan sweep; goal harus masih menerima dana, milik `owner` dan memakai asset yang sama
+pub(crate) fn sweep_target(goal_id: &str, owner: Principal, asset: Principal) -> Result<Account, FintrackError> {
+    let gi = load_goal(goal_id).ok_or_else(|| FintrackError::not_found("goal", goal_id))?;
+    if gi.owner != owner { return Err(FintrackError::unauthorized("not the goal owner")); }
+    if gi.asset_canister != asset { return Err(FintrackError::invalid_input("goal_id", "goal uses a different asset")); }
+    if !accepts_funds(&gi.status) { return Err(FintrackError::invalid_state("goal is not accepting funds")); }
+    Ok(canister_escrow_account(escrow_subaccount(&gi.owner, &gi.id)))
+}
+
+/// Catat dana yang sudah ditransfer ke escrow goal lewat sweep (asset utama)
+pub(crate) fn credit_sweep(goal_id: &str, amount: Nat, note: String) {
+    if let Some(gi) = load_goal(goal_id) { credit_deposit(goal_id, gi.asset_canister, gi.owner, amount, Some(note), None); }
+}
+
+// Kredit dana `contributor` yang sudah masuk escrow asset `ledger` (add funds / sweep), lalu jalankan transisi status.
+// `price` = harga baru untuk goal fiat; tanpa itu event memakai harga terakhir yang diketahui.
+// Goal yang tidak menerima dana lagi (mis. deposit dikonfirmasi reconciler setelah goal diarsipkan) tidak di-lock:
+// dana yang terlanjur masuk escrow dicatat di available supaya bisa ditarik/di-refund.
+fn credit_deposit(goal_id: &str, ledger: Principal, contributor: Principal, amount: Nat, note: Option<String>, price: Option<PriceSnapshot>) {
+    let credited = with_goal_mut(goal_id, |gi| {
+        let open = accepts_funds(&gi.status);
+        let (locked, available) = balances_mut(gi, ledger)?;
+        if open {
+            *locked = Nat(&locked.0 + &amount.0);
+        } else {
+            *available = Nat(&available.0 + &amount.0);
+        }
+        record_contribution(gi, contributor, ledger, &amount);
+        gi.updated_at_ns = time();
+        if let Some(p) = price.clone().filter(|_| open && gi.fiat_target.is_some()) { set_asset_price(gi, ledger, p); }
+        Some((open, asset_tag(gi, ledger), asset_price(gi, ledger).cloned()))
+    }).flatten();
+    let Some((open, asset, snapshot)) = credited else { return };
+    let note = if open { note } else { Some("goal is not accepting funds, credited as withdrawable".to_string()) };
+    push_goal_event(goal_id, GoalEvent { at_time_ns: time(), kind: GoalEventKind::AddFunds, amount: Some(amount), note, price: snapshot, asset, by: Some(contributor) });
+    advance_goal(goal_id);
+    evaluate_progress(goal_id);
+}
+
+// -------- Goal fiat (nilai lewat services::rates) --------
+
+const PRICE_WATCH_INTERVAL_NS: u64 = timers::SEC_PER_HOUR * timers::NS_PER_SEC;
+
+fn coin_for_decimals(decimals: u32) -> &'static str {
+    if decimals == decimals_for_asset(&AssetKind::CkBtc) { "bitcoin" } else { "ethereum" }
+}
+
+async fn fetch_price(coin: &str, currency: &str) -> Result<PriceSnapshot, FintrackError> {
+    let rate = rates::get_crypto_fiat_rate(coin, currency).await?;
+    let price_minor = (rate * 100.0).round();
+    if !price_minor.is_finite() || price_minor < 1.0 {
+        return Err(FintrackError::invalid_state(format!("no usable {} price for {}", currency, coin)));
+    }
+    Ok(PriceSnapshot { currency: currency.to_string(), price_minor: price_minor as u64, at_time_ns: time() })
+}
+
+// Harga untuk snapshot kontribusi; gagal fetch tidak memblokir kontribusi (event memakai harga terakhir)
+async fn contribution_price(goal_id: &str, ledger: Principal) -> Option<PriceSnapshot> {
+    let gi = load_goal(goal_id)?;
+    let target = gi.fiat_target.as_ref()?;
+    let coin = asset_coin(&gi, ledger)?;
+    match fetch_price(&coin, &target.currency).await {
+        Ok(p) => Some(p),
+        Err(e) => { ic_cdk::println!("goal {}: price snapshot skipped: {}", goal_id, e); None }
+    }
+}
+
+fn ten_pow(decimals: u32) -> num::BigUint { num::BigUint::from(10u32).pow(decimals) }
+
+fn fiat_value_minor(amount: &Nat, decimals: u32, price: &PriceSnapshot) -> Nat {
+    Nat(&amount.0 * price.price_minor / ten_pow(decimals))
+}
+
+// Token yang dibutuhkan untuk mencapai target fiat (dibulatkan ke atas)
+fn tokens_for_fiat(amount_minor: u64, decimals: u32, price: &PriceSnapshot) -> Nat {
+    let num = num::BigUint::from(amount_minor) * ten_pow(decimals);
+    let den = num::BigUint::from(price.price_minor);
+    Nat((num + &den - 1u32) / den)
+}
+
+// Nilai locked per asset; asset tanpa harga belum ikut dihitung
+fn asset_values(gi: &GoalInfo) -> Vec<AssetValue> {
+    let fiat = gi.fiat_target.is_some();
+    let primary = AssetValue {
+        asset_canister: gi.asset_canister,
+        locked_balance: gi.locked_balance.clone(),
+        value_minor: gi.last_price.as_ref().filter(|_| fiat).map(|p| fiat_value_minor(&gi.locked_balance, gi.decimals, p)),
+    };
+    std::iter::once(primary).chain(gi.extra_assets.iter().flatten().map(|a| AssetValue {
+        asset_canister: a.asset_canister,
+        locked_balance: a.locked_balance.clone(),
+        value_minor: a.last_price.as_ref().filter(|_| fiat).map(|p| fiat_value_minor(&a.locked_balance, a.decimals, p)),
+    })).collect()
+}
+
+// (tersimpan, target) dalam satuan yang dibandingkan: 1/100 fiat untuk goal fiat (gabungan semua asset), token untuk goal biasa
+fn progress_amounts(gi: &GoalInfo) -> (Nat, Nat) {
+    match (&gi.fiat_target, &gi.last_price) {
+        (Some(t), Some(_)) => {
+            let saved = asset_values(gi).into_iter().filter_map(|v| v.value_minor).fold(num::BigUint::from(0u32), |acc, v| acc + v.0);
+            (Nat(saved), Nat::from(t.amount_minor))
+        }
+        _ => (gi.locked_balance.clone(), gi.amount_to_lock.clone()),
+    }
+}
+
+fn target_reached(gi: &GoalInfo) -> bool {
+    let (saved, target) = progress_amounts(gi);
+    saved.0 >= target.0
+}
+
+// Simpan harga baru satu asset lalu jalankan transisi (nilai bisa melewati target fiat)
+fn apply_price(goal_id: &str, ledger: Principal, price: PriceSnapshot) -> Option<GoalInfo> {
+    with_goal_mut(goal_id, |gi| {
+        set_asset_price(gi, ledger, price);
+        gi.updated_at_ns = time();
+    })?;
+    let updated = advance_goal(goal_id);
+    evaluate_progress(goal_id);
+    updated
+}
+
+/// Ambil harga terbaru semua asset goal fiat dan nilai ulang target
+pub async fn goals_refresh_price(id: String) -> Result<GoalInfo, FintrackError> {
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    let target = gi.fiat_target.clone().ok_or_else(|| FintrackError::invalid_state("goal has no fiat target"))?;
+    for ledger in asset_ledgers(&gi) {
+        let Some(coin) = asset_coin(&gi, ledger) else { continue };
+        let price = fetch_price(&coin, &target.currency).await?;
+        apply_price(&id, ledger, price);
+    }
+    load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+// Pantau harga goal fiat yang masih menerima dana; satu outcall per pasangan (coin, currency)
+async fn watch_fiat_goals() {
+    let assets: Vec<(String, Principal, String, String)> = GOALS.with(|g| {
+        g.borrow().iter().map(|(_, v)| v.0)
+            .filter(|gi| accepts_funds(&gi.status))
+            .flat_map(|gi| {
+                let Some(currency) = gi.fiat_target.as_ref().map(|t| t.currency.clone()) else { return Vec::new() };
+                asset_ledgers(&gi).into_iter()
+                    .filter_map(|ledger| Some((gi.id.clone(), ledger, asset_coin(&gi, ledger)?, currency.clone())))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    });
+    let mut prices: BTreeMap<(String, String), Option<PriceSnapshot>> = BTreeMap::new();
+    for (id, ledger, coin, currency) in assets {
+        let key = (coin, currency);
+        if !prices.contains_key(&key) {
+            let fetched = fetch_price(&key.0, &key.1).await
+                .map_err(|e| ic_cdk::println!("goals price watch {}/{}: {}", key.0, key.1, e)).ok();
+            prices.insert(key.clone(), fetched);
+        }
+        if let Some(Some(price)) = prices.get(&key) {
+            apply_price(&id, ledger, price.clone());
+        }
+    }
+}
+
+/// Arm pemantau harga goal fiat (init dan post_upgrade; timer hanya hidup di heap)
+pub fn start_price_watch() {
+    ic_cdk_timers::set_timer_interval(std::time::Duration::from_nanos(PRICE_WATCH_INTERVAL_NS), || {
+        ic_cdk::futures::spawn(watch_fiat_goals());
+    });
+}
+
+// Pembukuan escrow untuk escrow_audit, satu per asset
+fn escrow_books_of(gi: &GoalInfo) -> Vec<EscrowBooks> {
+    asset_ledgers(gi).into_iter().filter_map(|ledger| {
+        let (locked, available) = asset_balances(gi, ledger)?;
+        Some(EscrowBooks {
+            entity_id: gi.id.clone(),
+            owner: gi.owner,
+            ledger,
+            asset: asset_tag(gi, ledger),
+            subaccount: asset_escrow_subaccount(gi, ledger),
+            expected: Nat(&locked.0 + &available.0),
+        })
+    }).collect()
+}
+
+pub(crate) fn escrow_books(id: &str, asset: Option<Principal>) -> Option<EscrowBooks> {
+    load_goal(id).and_then(|gi| escrow_books_of(&gi).into_iter().find(|b| b.asset == asset))
+}
+
+pub(crate) fn all_escrow_books() -> Vec<EscrowBooks> {
+    GOALS.with(|g| g.borrow().iter().flat_map(|(_, v)| escrow_books_of(&v.0)).collect())
+}
+
+// List events untuk goal
+pub fn goals_list_events(id: String, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<GoalEvent>, FintrackError> {
+    let off = offset.unwrap_or(0) as usize;
+    let lim = limit.unwrap_or(50) as usize;
+    let v = load_goal_events(&id);
+    let end = (off + lim).min(v.len());
+    if off >= v.len() { Ok(vec![]) } else { Ok(v[off..end].to_vec()) }
+}
+
+// -------- Status lifecycle --------
+//
+// Status hanya berubah lewat advance_goal (timer maturity per goal di end_ns, goals_refresh, update call yang
+// menyentuh dana) dan goals_archive. Query tidak pernah memutasi status.
+//
+//   dari              | kondisi                           | ke            | efek
+//   Active            | target tercapai sebelum end_ns    | TargetReached | dana tetap locked, add funds masih boleh
+//   Active            | end_ns lewat tanpa target         | Failed        | locked -> available, di-refund lewat withdraw/archive
+//   TargetReached     | end_ns lewat                      | Unlocked      | locked -> available
+//   Completed (lama)  | -                                 | TargetReached | lalu lanjut seperti baris di atas
+//   selain Archived   | goals_archive, tidak ada locked   | Archived      | available di-refund sesuai withdrawal policy
+//   Archived          | goals_delete                      | (dihapus)     |
+//
+// TargetReached sticky: nilai goal fiat yang turun lagi di bawah target tidak mengembalikan status ke Active.
+
+fn accepts_funds(status: &GoalStatus) -> bool {
+    matches!(status, GoalStatus::Active | GoalStatus::TargetReached)
+}
+
+fn status_event(now: u64, kind: GoalEventKind, amount: Nat, note: &str, asset: Option<Principal>) -> GoalEvent {
+    GoalEvent { at_time_ns: now, kind, amount: Some(amount), note: Some(note.to_string()), price: None, asset, by: None }
+}
+
+// Satu langkah state machine pada `gi`; mengembalikan event yang perlu dicatat
+fn transition(gi: &mut GoalInfo, now: u64) -> Vec<GoalEvent> {
+    let mut events = Vec::new();
+    if gi.status == GoalStatus::Completed { gi.status = GoalStatus::TargetReached; }
+    if gi.status == GoalStatus::Active {
+        let (saved, target) = progress_amounts(gi);
+        match vesting::outcome(gi.end_ns, now, &saved, &target) {
+            Outcome::Reached => {
+                gi.status = GoalStatus::TargetReached;
+                let mut ev = status_event(now, GoalEventKind::TargetReached, saved, "Target amount reached!", None);
+                ev.price = gi.last_price.clone();
+                events.push(ev);
+            }
+            Outcome::Missed => {
+                gi.status = GoalStatus::Failed;
+                events.push(status_event(now, GoalEventKind::Failed, saved, "Goal period ended before the target was reached, funds are refundable", None));
+            }
+            Outcome::Open => {}
+        }
+    }
+    if gi.status == GoalStatus::TargetReached && now >= gi.end_ns { gi.status = GoalStatus::Unlocked; }
+    // Termasuk sisa yang kembali ke locked setelah penalti early exit gagal
+    if matches!(gi.status, GoalStatus::Unlocked | GoalStatus::Failed) {
+        for (asset, amount) in release_cliff(gi, now) {
+            events.push(status_event(now, GoalEventKind::CliffUnlocked, amount, "Cliff period ended, funds unlocked!", asset));
+        }
+    }
+    events
+}
+
+// Lepas locked semua asset menurut jadwal cliff; tiap escrow dilepas sendiri-sendiri
+fn release_cliff(gi: &mut GoalInfo, now: u64) -> Vec<(Option<Principal>, Nat)> {
+    // Goal tidak mencatat unlocked_so_far: seluruh locked saat ini diperlakukan sebagai deposit yang belum vested
+    let (elapsed, duration) = vesting::elapsed(gi.start_ns, gi.end_ns, now);
+    let ratio = vesting::vested_ratio(&vesting::cliff_at_end(gi.start_ns, gi.end_ns), gi.start_ns, elapsed, duration);
+    let mut unlocked = Vec::new();
+    for ledger in asset_ledgers(gi) {
+        let tag = asset_tag(gi, ledger);
+        let Some((locked, available)) = balances_mut(gi, ledger) else { continue };
+        let newly = vesting::vest_delta(locked, &Nat::from(0u64), locked, &ratio, None);
+        if newly > num::BigUint::from(0u32) {
+            vesting::release(locked, available, &newly);
+            unlocked.push((tag, Nat(newly)));
+        }
+    }
+    unlocked
+}
+
+// Jalankan transisi; simpan dan catat event hanya bila goal berubah. Mengembalikan goal terbaru.
+fn advance_goal(goal_id: &str) -> Option<GoalInfo> {
+    let mut gi = load_goal(goal_id)?;
+    let before = gi.clone();
+    let now = time();
+    let events = transition(&mut gi, now);
+    if gi == before { return Some(gi); }
+    gi.updated_at_ns = now;
+    store_goal(&gi);
+    for ev in events { push_goal_event(goal_id, ev); }
+    Some(gi)
+}
+
+// Pembagian refund available satu asset: OwnerOnly ke owner; ProRata/Approval ke kontributor
+// sesuai sisa kontribusi, sisa pembulatan (dan dana tanpa atribusi) ke owner
+fn refund_plan(gi: &GoalInfo, ledger: Principal) -> Vec<(Principal, Nat)> {
+    let Some((_, available)) = asset_balances(gi, ledger) else { return Vec::new() };
+    let mut plan: Vec<(Principal, Nat)> = Vec::new();
+    if policy_of(gi) != WithdrawalPolicy::OwnerOnly {
+        for c in gi.contributions.iter().flatten().filter(|c| c.asset_canister == ledger) {
+            let share = pro_rata_refundable(gi, c.contributor, ledger);
+            if share.0 > num::BigUint::from(0u32) { plan.push((c.contributor, share)); }
+        }
+    }
+    let planned: num::BigUint = plan.iter().map(|(_, a)| &a.0).sum();
+    if available.0 > planned {
+        let leftover = &available.0 - &planned;
+        match plan.iter_mut().find(|(p, _)| *p == gi.owner) {
+            Some((_, a)) => *a = Nat(&a.0 + &leftover),
+            None => plan.push((gi.owner, Nat(leftover))),
+        }
+    }
+    plan
+}
+
+/// Arsipkan goal (owner): tidak boleh ada dana locked atau early exit pending. Saldo available
+/// di-refund sesuai withdrawal policy; jumlah di bawah fee ledger tetap tercatat di available.
+pub async fn goals_archive(id: String) -> Result<GoalInfo, FintrackError> {
+    let _guard = EntityGuard::acquire("goal", &id)?;
+    let gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.status == GoalStatus::Archived { return Err(FintrackError::invalid_state("goal is already archived")); }
+    archive(&id, caller()).await
+}
+
+// Refund lalu tandai Archived (idempoten untuk goal yang sudah Archived); pemanggil memegang guard goal
+async fn archive(id: &str, who: Principal) -> Result<GoalInfo, FintrackError> {
+    let gi = advance_goal(id).ok_or_else(|| FintrackError::not_found("goal", id))?;
+    if gi.owner != who { return Err(FintrackError::unauthorized("not the goal owner")); }
+    if gi.status == GoalStatus::Archived { return Ok(gi); }
+    if gi.pending_early_exit.is_some() { return Err(FintrackError::invalid_state("an early exit is pending")); }
+    let zero = num::BigUint::from(0u32);
+    if asset_ledgers(&gi).into_iter().any(|l| asset_balances(&gi, l).is_some_and(|(locked, _)| locked.0 > zero)) {
+        return Err(FintrackError::invalid_state("funds are still locked until the goal period ends"));
+    }
+    if journal::pending_count(EntityKind::Goal, id) > 0 {
+        return Err(FintrackError::invalid_state("goal has ledger operations in flight, try again later"));
+    }
+
+    // Instruksi dan proposal tidak relevan lagi setelah goal ditutup
+    let cancelled = with_goal_mut(id, |gi| {
+        gi.auto_contribution = None;
+        gi.pending_withdrawal.take()
+    }).flatten();
+    clear_contribution_timer(id);
+    clear_maturity_timer(id);
+    if let Some(p) = cancelled {
+        push_group_event(id, GoalEventKind::WithdrawalCancelled, Some(p.amount), who, Some("goal archived".to_string()));
+    }
+
+    // Gagal di tengah: refund yang sudah terkirim tercatat, archive bisa diulang untuk sisanya
+    for ledger in asset_ledgers(&gi) {
+        let fee = icrc1_fee(ledger).await?;
+        let plan = load_goal(id).map(|gi| refund_plan(&gi, ledger)).unwrap_or_default();
+        for (recipient, amount) in plan {
+            if amount.0 <= fee.0 { continue; }
+            send_available(id, ledger, amount, recipient).await?;
+        }
+    }
+
+    let now = time();
+    with_goal_mut(id, |gi| {
+        gi.status = GoalStatus::Archived;
+        gi.behind_schedule_since = None;
+        gi.updated_at_ns = now;
+    });
+    push_goal_event(id, GoalEvent { at_time_ns: now, kind: GoalEventKind::Archived, amount: None, note: Some("archived by owner".to_string()), price: None, asset: None, by: Some(who) });
+    load_goal(id).ok_or_else(|| FintrackError::not_found("goal", id))
+}
+
+/// Hapus goal beserta history-nya (owner); goal yang belum diarsipkan diarsipkan dan di-refund dulu.
+/// Registry escrow tidak ikut dihapus supaya dust yang tersisa tetap terlihat di escrow_audit.
+pub async fn goals_delete(id: String) -> Result<(), FintrackError> {
+    let _guard = EntityGuard::acquire("goal", &id)?;
+    archive(&id, caller()).await?;
+    if journal::pending_count(EntityKind::Goal, &id) > 0 {
+        return Err(FintrackError::invalid_state("goal has ledger operations in flight, try again later"));
+    }
+    clear_contribution_timer(&id);
+    clear_early_exit_timer(&id);
+    clear_maturity_timer(&id);
+    remove_goal(&id);
+    remove_goal_events(&id);
+    Ok(())
+}
+
+// -------- Maturity (timer per goal di end_ns) --------
+
+thread_local! {
+    // timer maturity per goal (heap, di-arm ulang saat post_upgrade)
+    static MATURITY_TIMERS: RefCell<BTreeMap<String, TimerId>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+fn arm_maturity_timer(goal_id: &str, at_ns: u64) {
+    let id = goal_id.to_string();
+    let tid = timers::schedule_at(at_ns, move || {
+        ic_cdk::futures::spawn(async move { handle_maturity_timer(id).await; });
+    });
+    let old = MATURITY_TIMERS.with(|t| t.borrow_mut().insert(goal_id.to_string(), tid));
+    timers::cancel_timer(old);
+}
+
+fn clear_maturity_timer(goal_id: &str) {
+    let old = MATURITY_TIMERS.with(|t| t.borrow_mut().remove(goal_id));
+    timers::cancel_timer(old);
+}
+
+async fn handle_maturity_timer(goal_id: String) {
+    MATURITY_TIMERS.with(|t| t.borrow_mut().remove(&goal_id));
+    let Ok(_guard) = EntityGuard::acquire("goal", &goal_id) else {
+        arm_maturity_timer(&goal_id, time() + guard::RETRY_DELAY_NS);
+        return;
+    };
+    mature(&goal_id).await;
+}
+
+// Unlock di end_ns, lalu kirim seluruh available ke owner bila auto_payout; pemanggil memegang guard goal
+async fn mature(goal_id: &str) {
+    let Some(gi) = advance_goal(goal_id) else { return };
+    if gi.auto_payout != Some(true) || !matches!(gi.status, GoalStatus::Unlocked | GoalStatus::Failed) { return; }
+    for ledger in asset_ledgers(&gi) {
+        let Some((_, available)) = asset_balances(&gi, ledger) else { continue };
+        if available.0 == num::BigUint::from(0u32) { continue; }
+        // Gagal (termasuk saldo di bawah fee ledger): dana tetap di available dan bisa ditarik lewat goals_withdraw
+        if let Err(e) = send_available(goal_id, ledger, available, gi.owner).await {
+            ic_cdk::println!("goal {}: auto payout deferred: {}", goal_id, e);
+        }
+    }
+}
+
+/// Nyalakan / matikan auto payout saat jatuh tempo (owner, goal OwnerOnly yang belum jatuh tempo)
+pub fn goals_set_auto_payout(id: String, enabled: bool) -> Result<GoalInfo, FintrackError> {
+    let mut gi = load_goal(&id).ok_or_else(|| FintrackError::not_found("goal", id.clone()))?;
+    if gi.owner != caller() { return Err(FintrackError::unauthorized("not the goal owner")); }
+    if !accepts_funds(&gi.status) { return Err(FintrackError::invalid_state("goal has already matured")); }
+    if enabled && policy_of(&gi) != WithdrawalPolicy::OwnerOnly {
+        return Err(FintrackError::invalid_state("auto payout is only available for owner-only goals"));
+    }
+    gi.auto_payout = Some(enabled);
+    gi.updated_at_ns = time();
+    store_goal(&gi);
+    Ok(gi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn goal() -> GoalInfo {
+        GoalInfo {
+            id: "g1".to_string(),
+            owner: Principal::anonymous(),
+            asset_canister: Principal::management_canister(),
+            name: "test".to_string(),
+            amount_to_lock: Nat::from(1_000u64),
+            locked_balance: Nat::from(0u64),
+            available_to_withdraw: Nat::from(0u64),
+            decimals: 8,
+            start_ns: 0,
+            end_ns: 100,
+            created_at_ns: 0,
+            updated_at_ns: 0,
+            status: GoalStatus::Active,
+            auto_contribution: None,
+            fiat_target: None,
+            last_price: None,
+            early_exit: None,
+            pending_early_exit: None,
+            extra_assets: None,
+            withdrawal_policy: None,
+            members: None,
+            pending_invites: None,
+            contributions: None,
+            pending_withdrawal: None,
+            milestones: None,
+            behind_schedule_since: None,
+            auto_payout: None,
+        }
+    }
+
+    fn extra(locked: u64) -> GoalAsset {
+        GoalAsset {
+            asset_canister: Principal::from_slice(&[1]),
+            coin_id: "ethereum".to_string(),
+            decimals: 18,
+            locked_balance: Nat::from(locked),
+            available_to_withdraw: Nat::from(0u64),
+            last_price: None,
+        }
+    }
+
+    fn terms(penalty_bps: u32, cooling_off_hours: u32) -> EarlyExitTerms {
+        EarlyExitTerms { penalty_bps, destination: PenaltyDestination::Burn, cooling_off_hours }
+    }
+
+    #[test]
+    fn early_exit_terms_need_minimum_penalty_and_cooling_off() {
+        assert!(validate_early_exit_terms(&terms(MIN_PENALTY_BPS, MIN_COOLING_OFF_HOURS)).is_ok());
+        assert!(validate_early_exit_terms(&terms(MAX_PENALTY_BPS, MAX_COOLING_OFF_HOURS)).is_ok());
+        assert!(validate_early_exit_terms(&terms(0, MIN_COOLING_OFF_HOURS)).is_err());
+        assert!(validate_early_exit_terms(&terms(MIN_PENALTY_BPS, 0)).is_err());
+        assert!(validate_early_exit_terms(&terms(MAX_PENALTY_BPS + 1, MIN_COOLING_OFF_HOURS)).is_err());
+        assert!(validate_early_exit_terms(&terms(MIN_PENALTY_BPS, MAX_COOLING_OFF_HOURS + 1)).is_err());
+    }
+
+    #[test]
+    fn early_exit_terms_cannot_be_added_to_a_funded_goal() {
+        let mut gi = goal();
+        assert!(check_early_exit_enable(&gi).is_ok());
+        gi.locked_balance = Nat::from(1u64);
+        assert!(matches!(check_early_exit_enable(&gi), Err(FintrackError::InvalidState { .. })));
+        gi.locked_balance = Nat::from(0u64);
+        gi.early_exit = Some(terms(MIN_PENALTY_BPS, MIN_COOLING_OFF_HOURS));
+        assert!(check_early_exit_enable(&gi).is_err());
+    }
+
+    #[test]
+    fn early_exit_rejects_multi_asset_goals() {
+        let mut gi = goal();
+        gi.extra_assets = Some(vec![]);
+        assert!(check_early_exit_supported(&gi).is_ok());
+        gi.extra_assets = Some(vec![extra(0)]);
+        assert!(check_early_exit_supported(&gi).is_err());
+        assert!(check_early_exit_enable(&gi).is_err());
+    }
+
+    fn contribution(who: Principal, contributed: u64, withdrawn: u64) -> Contribution {
+        Contribution { contributor: who, asset_canister: Principal::management_canister(), contributed: Nat::from(contributed), withdrawn: Nat::from(withdrawn) }
+    }
+
+    fn kinds(events: &[GoalEvent]) -> Vec<GoalEventKind> { events.iter().map(|e| e.kind.clone()).collect() }
+
+    #[test]
+    fn transition_stays_active_until_target_or_deadline() {
+        let mut gi = goal();
+        gi.locked_balance = Nat::from(500u64);
+        assert!(transition(&mut gi, 50).is_empty());
+        assert_eq!(gi.status, GoalStatus::Active);
+    }
+
+    #[test]
+    fn transition_reaches_target_then_unlocks_at_end() {
+        let mut gi = goal();
+        gi.locked_balance = Nat::from(1_000u64);
+        assert_eq!(kinds(&transition(&mut gi, 50)), vec![GoalEventKind::TargetReached]);
+        assert_eq!(gi.status, GoalStatus::TargetReached);
+        assert_eq!(gi.locked_balance, Nat::from(1_000u64));
+
+        assert_eq!(kinds(&transition(&mut gi, 100)), vec![GoalEventKind::CliffUnlocked]);
+        assert_eq!(gi.status, GoalStatus::Unlocked);
+        assert_eq!(gi.locked_balance, Nat::from(0u64));
+        assert_eq!(gi.available_to_withdraw, Nat::from(1_000u64));
+        assert!(transition(&mut gi, 150).is_empty());
+    }
+
+    #[test]
+    fn transition_fails_when_deadline_passes_below_target() {
+        let mut gi = goal();
+        gi.locked_balance = Nat::from(400u64);
+        assert_eq!(kinds(&transition(&mut gi, 100)), vec![GoalEventKind::Failed, GoalEventKind::CliffUnlocked]);
+        assert_eq!(gi.status, GoalStatus::Failed);
+        assert_eq!(gi.available_to_withdraw, Nat::from(400u64));
+    }
+
+    #[test]
+    fn transition_migrates_legacy_completed_goals() {
+        let mut gi = goal();
+        gi.status = GoalStatus::Completed;
+        gi.locked_balance = Nat::from(1_000u64);
+        transition(&mut gi, 50);
+        assert_eq!(gi.status, GoalStatus::TargetReached);
+    }
+
+    #[test]
+    fn pro_rata_share_follows_remaining_contributions() {
+        let (a, b, c) = (Principal::from_slice(&[1]), Principal::from_slice(&[2]), Principal::from_slice(&[3]));
+        let ledger = Principal::management_canister();
+        let mut gi = goal();
+        gi.withdrawal_policy = Some(WithdrawalPolicy::ProRata);
+        gi.available_to_withdraw = Nat::from(1_000u64);
+        gi.contributions = Some(vec![contribution(a, 600, 0), contribution(b, 500, 100)]);
+        assert_eq!(pro_rata_refundable(&gi, a, ledger), Nat::from(600u64));
+        assert_eq!(pro_rata_refundable(&gi, b, ledger), Nat::from(400u64));
+        assert_eq!(pro_rata_refundable(&gi, c, ledger), Nat::from(0u64));
+        assert_eq!(pro_rata_refundable(&gi, a, Principal::from_slice(&[9])), Nat::from(0u64));
+    }
+
+    #[test]
+    fn pro_rata_share_rounds_down() {
+        let (a, b, c) = (Principal::from_slice(&[1]), Principal::from_slice(&[2]), Principal::from_slice(&[3]));
+        let ledger = Principal::management_canister();
+        let mut gi = goal();
+        gi.available_to_withdraw = Nat::from(100u64);
+        gi.contributions = Some(vec![contribution(a, 1, 0), contribution(b, 1, 0), contribution(c, 1, 0)]);
+        let shares: Vec<Nat> = [a, b, c].iter().map(|p| pro_rata_refundable(&gi, *p, ledger)).collect();
+        assert_eq!(shares, vec![Nat::from(33u64); 3]);
+
+        gi.contributions = Some(vec![contribution(a, 5, 5)]);
+        assert_eq!(pro_rata_refundable(&gi, a, ledger), Nat::from(0u64));
+    }
+}
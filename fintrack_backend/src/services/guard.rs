@@ -0,0 +1,36 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use super::errors::FintrackError;
+use super::timers;
+
+// Lock in-flight per entitas (budget/goal) yang menahan state selama await ke ledger.
+// Cukup di heap: upgrade hanya terjadi setelah semua call selesai (canister di-stop dulu).
+// Lepas otomatis lewat Drop, termasuk saat callback trap (ic-cdk membersihkan future yang tertunda).
+
+/// Jeda sebelum timer mencoba lagi bila entitas sedang dipakai operasi lain
+pub const RETRY_DELAY_NS: u64 = 30 * timers::NS_PER_SEC;
+
+thread_local! {
+    // key = "{kind}|{id}"
+    static IN_FLIGHT: RefCell<BTreeSet<String>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+pub struct EntityGuard {
+    key: String,
+}
+
+impl EntityGuard {
+    /// Ambil lock untuk entitas; gagal dengan OperationInProgress bila sudah dipegang operasi lain
+    pub fn acquire(kind: &str, id: &str) -> Result<Self, FintrackError> {
+        let key = format!("{}|{}", kind, id);
+        let inserted = IN_FLIGHT.with(|s| s.borrow_mut().insert(key.clone()));
+        if !inserted { return Err(FintrackError::in_progress(kind, id)); }
+        Ok(EntityGuard { key })
+    }
+}
+
+impl Drop for EntityGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.with(|s| s.borrow_mut().remove(&self.key));
+    }
+}
@@ -0,0 +1,360 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use super::budget::{self, Account};
+use super::errors::FintrackError;
+use super::goals;
+use super::guard::EntityGuard;
+use super::storage::{self, Candid, VMem};
+use super::timers;
+
+// Journal operasi escrow (lock, withdraw, spend, refund, sweep).
+// Entry ditulis ke stable memory sebelum call ke ledger dan dihapus setelah hasilnya diterapkan.
+// Entry yang tertinggal (callback trap, ledger sementara tidak tersedia) di-retry reconciler dengan
+// created_at_time yang sama, sehingga ledger men-deduplikasi transfer yang ternyata sudah tereksekusi.
+
+const RECONCILE_INTERVAL_NS: u64 = 5 * timers::SEC_PER_MIN * timers::NS_PER_SEC;
+const MAX_ATTEMPTS: u32 = 10;
+const INSUFFICIENT_ALLOWANCE: &str = "insufficient allowance";
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperationKind {
+    Lock,     // icrc2_transfer_from user -> escrow
+    Withdraw, // escrow -> owner
+    Spend,    // escrow -> pihak ketiga (budget_spend)
+    Refund,   // escrow -> owner (rollover refund / delete budget)
+    Sweep,    // escrow budget -> escrow goal (rollover)
+    Recover,  // escrow -> owner, dana yang tidak tercatat di pembukuan (escrow_audit)
+    Penalty,  // escrow goal -> burn/donasi/community pool (early exit)
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityKind { Budget, Goal }
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LedgerCall {
+    Transfer { from_subaccount: Option<Vec<u8>>, to: Account }, // icrc1_transfer dari subaccount canister
+    TransferFrom { from: Account, to: Account },                // icrc2_transfer_from dengan allowance user
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperationStatus {
+    InFlight, // call sedang berjalan, atau callback-nya trap sebelum hasil diterapkan
+    Retrying, // gagal sementara, menunggu reconciler
+    Stuck,    // tidak bisa di-retry/dikompensasi otomatis, perlu tindakan controller
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingOperation {
+    pub id: u64,
+    pub kind: OperationKind,
+    pub entity: EntityKind,
+    pub entity_id: String,
+    pub owner: Principal,
+    pub ledger: Principal,
+    pub call: LedgerCall,
+    pub amount: Nat,   // jumlah yang dikirim ke ledger
+    pub reserved: Nat, // jumlah yang dipotong dari state entitas (amount + fee bila fee ditanggung entitas)
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: u64, // tetap sama di setiap retry (deduplikasi ICRC-1)
+    pub envelope_id: Option<String>,
+    pub category_id: Option<String>,
+    pub goal_id: Option<String>, // Sweep: goal tujuan
+    pub note: Option<String>,
+    pub envelope_amounts: Option<Vec<(String, Nat)>>, // Sweep/Refund rollover: available per envelope yang dikosongkan
+    pub status: OperationStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub updated_at_ns: u64,
+}
+
+/// Operasi baru sebelum dicatat; id, created_at_time dan status diisi journal
+pub struct NewOperation {
+    pub kind: OperationKind,
+    pub entity: EntityKind,
+    pub entity_id: String,
+    pub owner: Principal,
+    pub ledger: Principal,
+    pub call: LedgerCall,
+    pub amount: Nat,
+    pub reserved: Nat,
+    pub memo: Vec<u8>,
+    pub envelope_id: Option<String>,
+    pub category_id: Option<String>,
+    pub goal_id: Option<String>,
+    pub note: Option<String>,
+    pub envelope_amounts: Option<Vec<(String, Nat)>>,
+}
+
+impl NewOperation {
+    pub fn new(kind: OperationKind, entity: EntityKind, entity_id: &str, owner: Principal, ledger: Principal, call: LedgerCall, amount: Nat) -> Self {
+        NewOperation {
+            kind, entity, entity_id: entity_id.to_string(), owner, ledger, call,
+            reserved: amount.clone(), amount, memo: Vec::new(),
+            envelope_id: None, category_id: None, goal_id: None, note: None, envelope_amounts: None,
+        }
+    }
+
+    pub fn with_memo(mut self, memo: &[u8]) -> Self {
+        self.memo = memo.to_vec();
+        self
+    }
+}
+
+thread_local! {
+    // key = id operasi (monotonic)
+    static OPERATIONS: RefCell<StableBTreeMap<u64, Candid<PendingOperation>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::OPERATIONS_MEMORY_ID))
+    );
+    // cache id berikutnya (heap), diisi ulang dari journal setelah upgrade
+    static NEXT_OPERATION_ID: RefCell<Option<u64>> = const { RefCell::new(None) };
+}
+
+fn next_id() -> u64 {
+    NEXT_OPERATION_ID.with(|n| {
+        let mut n = n.borrow_mut();
+        let id = n.unwrap_or_else(|| OPERATIONS.with(|o| o.borrow().iter().last().map(|(k, _)| k + 1).unwrap_or(0)));
+        *n = Some(id + 1);
+        id
+    })
+}
+
+fn load_operation(id: u64) -> Option<PendingOperation> {
+    OPERATIONS.with(|o| o.borrow().get(&id)).map(|c| c.0)
+}
+
+fn store_operation(op: &PendingOperation) {
+    OPERATIONS.with(|o| o.borrow_mut().insert(op.id, Candid(op.clone())));
+}
+
+fn remove_operation(id: u64) {
+    OPERATIONS.with(|o| o.borrow_mut().remove(&id));
+}
+
+// -------- Ledger call (ICRC-1/2) --------
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct TransferArg {
+    from_subaccount: Option<Vec<u8>>,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct TransferFromArg {
+    spender_subaccount: Option<Vec<u8>>,
+    from: Account,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+// Gabungan TransferError (ICRC-1) dan TransferFromError (ICRC-2)
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum LedgerTransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+enum Attempt {
+    Done(Nat),             // tereksekusi (termasuk Duplicate dari attempt sebelumnya)
+    Failed(FintrackError), // ditolak ledger, pasti tidak tereksekusi
+    Retry(String),         // belum tentu sampai ke ledger, aman di-retry
+    Unknown(String),       // status tidak bisa dipastikan lagi (di luar window deduplikasi)
+}
+
+fn classify(res: Result<(Result<Nat, LedgerTransferError>,), String>, amount: &Nat) -> Attempt {
+    match res {
+        Ok((Ok(block),)) => Attempt::Done(block),
+        Ok((Err(LedgerTransferError::Duplicate { duplicate_of }),)) => Attempt::Done(duplicate_of),
+        Ok((Err(LedgerTransferError::TemporarilyUnavailable),)) => Attempt::Retry("ledger temporarily unavailable".to_string()),
+        Ok((Err(LedgerTransferError::CreatedInFuture { ledger_time }),)) => Attempt::Retry(format!("created in future (ledger time {})", ledger_time)),
+        Ok((Err(LedgerTransferError::TooOld),)) => Attempt::Unknown("transaction too old to deduplicate".to_string()),
+        Ok((Err(LedgerTransferError::InsufficientFunds { balance }),)) => Attempt::Failed(FintrackError::InsufficientFunds { needed: amount.clone(), available: balance }),
+        Ok((Err(LedgerTransferError::InsufficientAllowance { allowance }),)) => Attempt::Failed(FintrackError::ledger(format!("{} (allowance {})", INSUFFICIENT_ALLOWANCE, allowance))),
+        Ok((Err(e),)) => Attempt::Failed(FintrackError::ledger(format!("{:?}", e))),
+        Err(e) => Attempt::Retry(format!("call failed: {}", e)),
+    }
+}
+
+async fn call_ledger(op: &PendingOperation) -> Attempt {
+    let res = match &op.call {
+        LedgerCall::Transfer { from_subaccount, to } => {
+            let arg = TransferArg {
+                from_subaccount: from_subaccount.clone(), to: to.clone(), amount: op.amount.clone(),
+                fee: None, memo: op.memo.clone(), created_at_time: Some(op.created_at_time),
+            };
+            ic_cdk::call::<_, (Result<Nat, LedgerTransferError>,)>(op.ledger, "icrc1_transfer", (arg,)).await
+        }
+        LedgerCall::TransferFrom { from, to } => {
+            let arg = TransferFromArg {
+                spender_subaccount: None, from: from.clone(), to: to.clone(), amount: op.amount.clone(),
+                fee: None, memo: op.memo.clone(), created_at_time: Some(op.created_at_time),
+            };
+            ic_cdk::call::<_, (Result<Nat, LedgerTransferError>,)>(op.ledger, "icrc2_transfer_from", (arg,)).await
+        }
+    };
+    classify(res.map_err(|e| format!("{:?}", e)), &op.amount)
+}
+
+fn operation_guard(id: u64) -> Result<EntityGuard, FintrackError> {
+    EntityGuard::acquire("operation", &id.to_string())
+}
+
+/// Ledger menolak karena saldo atau allowance user tidak cukup (bukan gangguan sementara)
+pub fn is_funding_failure(e: &FintrackError) -> bool {
+    match e {
+        FintrackError::InsufficientFunds { .. } => true,
+        FintrackError::LedgerError { message } => message.starts_with(INSUFFICIENT_ALLOWANCE),
+        _ => false,
+    }
+}
+
+/// Hasil operasi belum pasti dan sekarang dipegang reconciler: pemanggil tidak boleh commit maupun rollback
+pub fn is_in_doubt(e: &FintrackError) -> bool {
+    matches!(e, FintrackError::OperationInProgress { kind, .. } if kind == "operation")
+}
+
+/// Catat operasi, panggil ledger, lalu hapus entry bila hasilnya pasti.
+/// Ok = tereksekusi; Err biasa = pasti gagal (pemanggil rollback); Err `is_in_doubt` = diserahkan ke reconciler.
+pub async fn execute(new: NewOperation) -> Result<Nat, FintrackError> {
+    let id = next_id();
+    let now = time();
+    let mut op = PendingOperation {
+        id,
+        kind: new.kind,
+        entity: new.entity,
+        entity_id: new.entity_id,
+        owner: new.owner,
+        ledger: new.ledger,
+        call: new.call,
+        amount: new.amount,
+        reserved: new.reserved,
+        memo: Some(new.memo),
+        created_at_time: now,
+        envelope_id: new.envelope_id,
+        category_id: new.category_id,
+        goal_id: new.goal_id,
+        note: new.note,
+        envelope_amounts: new.envelope_amounts,
+        status: OperationStatus::InFlight,
+        attempts: 1,
+        last_error: None,
+        updated_at_ns: now,
+    };
+    // Guard menandai call yang masih hidup; entry InFlight tanpa guard = callback trap
+    let _guard = operation_guard(id)?;
+    store_operation(&op);
+
+    match call_ledger(&op).await {
+        Attempt::Done(block) => {
+            remove_operation(id);
+            Ok(block)
+        }
+        Attempt::Failed(e) => {
+            remove_operation(id);
+            Err(e)
+        }
+        Attempt::Retry(msg) | Attempt::Unknown(msg) => {
+            ic_cdk::println!("journal: operation {} in doubt: {}", id, msg);
+            op.status = OperationStatus::Retrying;
+            op.last_error = Some(msg);
+            op.updated_at_ns = time();
+            store_operation(&op);
+            Err(FintrackError::in_progress("operation", id.to_string()))
+        }
+    }
+}
+
+// -------- Reconciler --------
+
+// Terapkan hasil akhir ke entitas pemilik; entry yang tidak bisa diterapkan ditandai Stuck
+fn settle(mut op: PendingOperation, result: Result<Nat, FintrackError>) {
+    // Recover tidak menyentuh state entitas: selesai apa pun hasilnya
+    if op.kind == OperationKind::Recover {
+        remove_operation(op.id);
+        return;
+    }
+    let error = result.as_ref().err().map(|e| e.to_string());
+    let applied = match op.entity {
+        EntityKind::Budget => budget::settle_operation(&op, result),
+        EntityKind::Goal => goals::settle_operation(&op, result),
+    };
+    if applied {
+        remove_operation(op.id);
+    } else {
+        op.status = OperationStatus::Stuck;
+        op.last_error = error.or_else(|| Some("entity no longer exists".to_string()));
+        op.updated_at_ns = time();
+        store_operation(&op);
+    }
+}
+
+async fn reconcile() {
+    let due: Vec<PendingOperation> = OPERATIONS.with(|o| {
+        o.borrow().iter().map(|(_, v)| v.0).filter(|op| op.status != OperationStatus::Stuck).collect()
+    });
+    for op in due {
+        // Call asli masih berjalan
+        let Ok(_guard) = operation_guard(op.id) else { continue };
+        let attempt = call_ledger(&op).await;
+        let Some(mut op) = load_operation(op.id) else { continue };
+        op.attempts += 1;
+        op.updated_at_ns = time();
+        match attempt {
+            Attempt::Done(block) => settle(op, Ok(block)),
+            Attempt::Failed(e) => settle(op, Err(e)),
+            Attempt::Retry(msg) if op.attempts < MAX_ATTEMPTS => {
+                op.status = OperationStatus::Retrying;
+                op.last_error = Some(msg);
+                store_operation(&op);
+            }
+            // Reject berulang: call tidak pernah dieksekusi ledger, kompensasi ke entitas
+            Attempt::Retry(msg) => settle(op, Err(FintrackError::ledger(msg))),
+            Attempt::Unknown(msg) => {
+                op.status = OperationStatus::Stuck;
+                op.last_error = Some(msg);
+                store_operation(&op);
+            }
+        }
+    }
+}
+
+/// Arm reconciler periodik (init dan post_upgrade; timer hanya hidup di heap)
+pub fn start_reconciler() {
+    ic_cdk_timers::set_timer_interval(std::time::Duration::from_nanos(RECONCILE_INTERVAL_NS), || {
+        ic_cdk::futures::spawn(reconcile());
+    });
+}
+
+/// Jumlah operasi journal (termasuk Stuck) yang masih terbuka untuk entitas
+pub(crate) fn pending_count(entity: EntityKind, entity_id: &str) -> u32 {
+    OPERATIONS.with(|o| {
+        o.borrow().iter().filter(|(_, v)| v.0.entity == entity && v.0.entity_id == entity_id).count() as u32
+    })
+}
+
+/// Controller melihat seluruh journal; user hanya operasi miliknya
+pub fn list_pending_operations() -> Vec<PendingOperation> {
+    let who = caller();
+    let is_controller = ic_cdk::api::is_controller(&who);
+    OPERATIONS.with(|o| {
+        o.borrow().iter()
+            .map(|(_, v)| v.0)
+            .filter(|op| is_controller || op.owner == who)
+            .collect()
+    })
+}
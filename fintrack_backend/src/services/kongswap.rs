@@ -2,17 +2,15 @@ use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_cdk::api::call::call_with_payment128;
 use serde::Deserialize as SerdeDeserialize;
 use num_traits::ToPrimitive;
-
-// KongSwap Canister ID
-const KONGSWAP_CANISTER_ID: &str = "2ipq2-uqaaa-aaaar-qailq-cai";
+use super::errors::FintrackError;
 
 // Token identifiers for ckBTC and ckETH
 const CKBTC_SYMBOL: &str = "ckBTC";
 const CKETH_SYMBOL: &str = "ckETH";
 
-// Helper function to get KongSwap canister principal
+// Helper function to get KongSwap canister principal (config canister)
 fn get_kongswap_canister() -> Principal {
-    Principal::from_text(KONGSWAP_CANISTER_ID).unwrap()
+    super::config::get().kongswap_canister
 }
 
 // ============================================================================
@@ -395,7 +393,7 @@ pub async fn get_swap_amounts(
     pay_token: String,
     pay_amount: Nat,
     receive_token: String,
-) -> Result<SwapAmountsReply, String> {
+) -> Result<SwapAmountsReply, FintrackError> {
     let canister = get_kongswap_canister();
 
     let (result,): (SwapAmountsResult,) = call_with_payment128(
@@ -405,16 +403,16 @@ pub async fn get_swap_amounts(
         10_000_000_000, // 10B cycles for swap_amounts call
     )
     .await
-    .map_err(|e| format!("Failed to call swap_amounts: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("Failed to call swap_amounts: {:?}", e)))?;
 
     match result {
         SwapAmountsResult::Ok(amounts) => Ok(amounts),
-        SwapAmountsResult::Err(error) => Err(format!("KongSwap error: {}", error)),
+        SwapAmountsResult::Err(error) => Err(FintrackError::rpc(format!("KongSwap error: {}", error))),
     }
 }
 
 /// Execute a swap asynchronously (returns request_id for polling)
-pub async fn execute_swap_async(args: SwapArgs) -> Result<u64, String> {
+pub async fn execute_swap_async(args: SwapArgs) -> Result<u64, FintrackError> {
     let canister = get_kongswap_canister();
 
     let (result,): (Result<u64, String>,) = call_with_payment128(
@@ -424,13 +422,13 @@ pub async fn execute_swap_async(args: SwapArgs) -> Result<u64, String> {
         50_000_000_000, // 50B cycles for swap_async call (more expensive operation)
     )
     .await
-    .map_err(|e| format!("Failed to call swap_async: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("Failed to call swap_async: {:?}", e)))?;
 
-    result.map_err(|e| format!("KongSwap async error: {}", e))
+    result.map_err(|e| FintrackError::rpc(format!("KongSwap async error: {}", e)))
 }
 
 /// Get request status by request_id
-pub async fn get_request(request_id: u64) -> Result<RequestsReply, String> {
+pub async fn get_request(request_id: u64) -> Result<RequestsReply, FintrackError> {
     let canister = get_kongswap_canister();
 
     let (result,): (RequestsResult,) = call_with_payment128(
@@ -440,23 +438,23 @@ pub async fn get_request(request_id: u64) -> Result<RequestsReply, String> {
         5_000_000_000, // 5B cycles for requests call (query-like operation)
     )
     .await
-    .map_err(|e| format!("Failed to call requests: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("Failed to call requests: {:?}", e)))?;
 
     match result {
         RequestsResult::Ok(requests) => {
             if requests.is_empty() {
-                Err("Request not found".to_string())
+                Err(FintrackError::not_found("kongswap request", request_id.to_string()))
             } else {
                 Ok(requests[0].clone())
             }
         },
-        RequestsResult::Err(error) => Err(format!("KongSwap error: {}", error)),
+        RequestsResult::Err(error) => Err(FintrackError::rpc(format!("KongSwap error: {}", error))),
     }
 }
 
 
 /// Poll swap request status
-pub async fn poll_swap_status(request_id: u64) -> Result<KongSwapResponse, String> {
+pub async fn poll_swap_status(request_id: u64) -> Result<KongSwapResponse, FintrackError> {
     let request = get_request(request_id).await?;
     
     match request.reply {
@@ -510,14 +508,14 @@ pub async fn poll_swap_status(request_id: u64) -> Result<KongSwapResponse, Strin
 // ============================================================================
 
 /// Preview a multi-hop swap between ckBTC and ckETH (via ckUSDT)
-pub async fn preview_swap(request: KongSwapRequest) -> Result<KongSwapPreview, String> {
+pub async fn preview_swap(request: KongSwapRequest) -> Result<KongSwapPreview, FintrackError> {
     // Validate tokens - only ckBTC and ckETH supported
     if !is_valid_token(&request.from_token) || !is_valid_token(&request.to_token) {
-        return Err("Invalid token. Only ckBTC and ckETH are supported.".to_string());
+        return Err(FintrackError::invalid_input("token", "only ckBTC and ckETH are supported"));
     }
 
     if request.from_token == request.to_token {
-        return Err("Cannot swap token to itself".to_string());
+        return Err(FintrackError::invalid_input("to_token", "cannot swap token to itself"));
     }
 
     // Get swap amounts from KongSwap (handles multi-hop automatically)
@@ -542,14 +540,14 @@ pub async fn preview_swap(request: KongSwapRequest) -> Result<KongSwapPreview, S
 }
 
 /// Execute a multi-hop swap asynchronously (returns request_id for polling)
-pub async fn swap_tokens_async(request: KongSwapRequest) -> Result<u64, String> {
+pub async fn swap_tokens_async(request: KongSwapRequest) -> Result<u64, FintrackError> {
     // Validate tokens - only ckBTC and ckETH supported
     if !is_valid_token(&request.from_token) || !is_valid_token(&request.to_token) {
-        return Err("Invalid token. Only ckBTC and ckETH are supported.".to_string());
+        return Err(FintrackError::invalid_input("token", "only ckBTC and ckETH are supported"));
     }
 
     if request.from_token == request.to_token {
-        return Err("Cannot swap token to itself".to_string());
+        return Err(FintrackError::invalid_input("to_token", "cannot swap token to itself"));
     }
 
     // Build swap arguments for multi-hop swap
@@ -596,7 +594,7 @@ pub fn format_token_amount(amount: Nat, token: &str) -> String {
 }
 
 /// Parse token amount from decimal string
-pub fn parse_token_amount(amount_str: &str, token: &str) -> Result<Nat, String> {
+pub fn parse_token_amount(amount_str: &str, token: &str) -> Result<Nat, FintrackError> {
     let decimals = match token {
         CKBTC_SYMBOL => 8,  // ckBTC has 8 decimals
         CKETH_SYMBOL => 18, // ckETH has 18 decimals
@@ -604,7 +602,7 @@ pub fn parse_token_amount(amount_str: &str, token: &str) -> Result<Nat, String>
     };
 
     let amount_f64: f64 = amount_str.parse()
-        .map_err(|e| format!("Invalid amount format: {}", e))?;
+        .map_err(|e| FintrackError::invalid_input("amount", e.to_string()))?;
 
     let multiplier = 10_f64.powi(decimals as i32);
     let amount_u64 = (amount_f64 * multiplier) as u64;
@@ -613,7 +611,7 @@ pub fn parse_token_amount(amount_str: &str, token: &str) -> Result<Nat, String>
 }
 
 /// Get current price between ckBTC and ckETH (via multi-hop)
-pub async fn get_current_price() -> Result<f64, String> {
+pub async fn get_current_price() -> Result<f64, FintrackError> {
     // Use a small amount to get current price via multi-hop route
     let small_amount = Nat::from(1000u64); // 0.00001 ckBTC or 0.000000000000001 ckETH
     
@@ -627,7 +625,7 @@ pub async fn get_current_price() -> Result<f64, String> {
 }
 
 /// Check if KongSwap service is available for multi-hop swaps
-pub async fn is_service_available() -> Result<bool, String> {
+pub async fn is_service_available() -> Result<bool, FintrackError> {
     // Test with a small swap to check if multi-hop routing works
     match get_swap_amounts(
         CKBTC_SYMBOL.to_string(),
@@ -12,4 +12,14 @@ pub mod budget;
 pub mod timers;
 pub mod goals;
 pub mod kongswap;
-
+pub mod storage;
+pub mod auth;
+pub mod config;
+pub mod secrets;
+pub mod errors;
+pub mod categories;
+pub mod vesting;
+pub mod guard;
+pub mod journal;
+pub mod escrow_audit;
+pub mod notifications;
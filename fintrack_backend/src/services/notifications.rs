@@ -0,0 +1,89 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use super::storage::{self, Candid, StableText, VMem};
+
+// Inbox notifikasi per user. Service lain cukup memanggil `notify`; inbox dibatasi
+// MAX_PER_USER entry, yang paling lama dibuang lebih dulu.
+
+const MAX_PER_USER: usize = 200;
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationKind {
+    GoalMilestone,
+    GoalBehindSchedule,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub id: u64, // urut per user
+    pub kind: NotificationKind,
+    pub entity_id: String, // mis. goal id
+    pub message: String,
+    pub created_at_ns: u64,
+    pub read: bool,
+}
+
+thread_local! {
+    // key = "{principal}|{id:020}" supaya inbox satu user berurutan dalam range scan
+    static NOTIFICATIONS: RefCell<StableBTreeMap<StableText, Candid<Notification>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::NOTIFICATIONS_MEMORY_ID))
+    );
+}
+
+fn inbox_prefix(user: &Principal) -> String { format!("{}|", user) }
+
+fn notification_key(user: &Principal, id: u64) -> StableText { StableText(format!("{}{:020}", inbox_prefix(user), id)) }
+
+fn load_inbox(user: &Principal) -> Vec<Notification> {
+    let prefix = inbox_prefix(user);
+    NOTIFICATIONS.with(|n| {
+        n.borrow().range(StableText(prefix.clone())..)
+            .take_while(|(k, _)| k.0.starts_with(&prefix))
+            .map(|(_, v)| v.0)
+            .collect()
+    })
+}
+
+/// Tambahkan notifikasi ke inbox `user`
+pub(crate) fn notify(user: Principal, kind: NotificationKind, entity_id: &str, message: String) {
+    let inbox = load_inbox(&user);
+    let id = inbox.last().map(|n| n.id + 1).unwrap_or(0);
+    let note = Notification { id, kind, entity_id: entity_id.to_string(), message, created_at_ns: time(), read: false };
+    NOTIFICATIONS.with(|n| {
+        let mut map = n.borrow_mut();
+        map.insert(notification_key(&user, id), Candid(note));
+        // Buang yang paling lama bila melewati batas
+        for old in inbox.iter().take((inbox.len() + 1).saturating_sub(MAX_PER_USER)) {
+            map.remove(&notification_key(&user, old.id));
+        }
+    });
+}
+
+/// Inbox caller, terbaru dulu
+pub fn notifications_list(unread_only: Option<bool>, limit: Option<u32>, offset: Option<u32>) -> Vec<Notification> {
+    let unread_only = unread_only.unwrap_or(false);
+    load_inbox(&caller()).into_iter().rev()
+        .filter(|n| !unread_only || !n.read)
+        .skip(offset.unwrap_or(0) as usize)
+        .take(limit.unwrap_or(50) as usize)
+        .collect()
+}
+
+pub fn notifications_unread_count() -> u32 {
+    load_inbox(&caller()).iter().filter(|n| !n.read).count() as u32
+}
+
+/// Tandai dibaca; `ids` kosong = semua. Mengembalikan jumlah yang berubah.
+pub fn notifications_mark_read(ids: Vec<u64>) -> u32 {
+    let user = caller();
+    let mut changed = 0;
+    for mut n in load_inbox(&user) {
+        if n.read || !(ids.is_empty() || ids.contains(&n.id)) { continue; }
+        n.read = true;
+        NOTIFICATIONS.with(|m| m.borrow_mut().insert(notification_key(&user, n.id), Candid(n)));
+        changed += 1;
+    }
+    changed
+}
@@ -2,7 +2,9 @@ use candid::CandidType;
 use ic_cdk::api::management_canister::http_request::{
     CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformArgs, TransformContext, HttpResponse, http_request as mgmt_http_request,
 };
+use num_traits::ToPrimitive;
 use serde::Deserialize;
+use super::errors::FintrackError;
 
 #[derive(CandidType, Deserialize)]
 pub struct CryptoRates {
@@ -37,13 +39,42 @@ pub struct VolumeData {
     pub volume: f64,
 }
 
+// Demo key CoinGecko dari secrets store; tanpa key tetap jalan (rate limit publik)
+fn coingecko_headers() -> Vec<HttpHeader> {
+    let mut headers = vec![HttpHeader { name: "Accept".into(), value: "application/json".into() }];
+    if let Some(key) = super::secrets::use_key(super::secrets::SecretProvider::CoinGecko) {
+        headers.push(HttpHeader { name: "X-Cg-Demo-Api-Key".into(), value: key });
+    }
+    headers
+}
+
+// 429 dari CoinGecko -> RateLimited, status non-2xx lain -> HttpOutcallFailed
+fn check_status(response: &HttpResponse) -> Result<(), FintrackError> {
+    let status = response.status.0.to_u32().unwrap_or(0);
+    if status == 429 { return Err(FintrackError::RateLimited); }
+    if !(200..300).contains(&status) {
+        return Err(FintrackError::http(format!("unexpected HTTP status {}", status)));
+    }
+    Ok(())
+}
+
+fn decode_json(body: Vec<u8>) -> Result<serde_json::Value, FintrackError> {
+    let str_body = String::from_utf8(body).map_err(|_| FintrackError::http("Failed to decode response body"))?;
+    serde_json::from_str(&str_body).map_err(|_| FintrackError::http("Failed to parse JSON"))
+}
+
 #[ic_cdk::query]
 pub fn transform(args: TransformArgs) -> HttpResponse {
     // Drop headers for determinism; passthrough body & status
     HttpResponse { status: args.response.status, body: args.response.body, headers: vec![] }
 }
 
-pub async fn get_crypto_usd_rate(crypto_id: &str) -> Result<f64, String> {
+pub async fn get_crypto_usd_rate(crypto_id: &str) -> Result<f64, FintrackError> {
+    get_crypto_fiat_rate(crypto_id, "usd").await
+}
+
+/// Harga 1 token dalam mata uang fiat `vs_currency` (kode ISO, mis. "usd", "idr", "eur")
+pub async fn get_crypto_fiat_rate(crypto_id: &str, vs_currency: &str) -> Result<f64, FintrackError> {
     // Use Coingecko 'ids' parameter with canonical ids (bitcoin, ethereum, solana)
     let id = match crypto_id {
         "btc" | "bitcoin" => "bitcoin",
@@ -51,16 +82,17 @@ pub async fn get_crypto_usd_rate(crypto_id: &str) -> Result<f64, String> {
         "sol" | "solana" => "solana",
         other => other,
     };
+    let currency = vs_currency.to_lowercase();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_lowercase()) {
+        return Err(FintrackError::invalid_input("vs_currency", "must be a 3-letter currency code"));
+    }
 
     let url = format!(
-        "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies=usd",
-        id
+        "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies={}",
+        id, currency
     );
 
-    let headers = vec![
-        HttpHeader { name: "Accept".into(), value: "application/json".into() },
-        HttpHeader { name: "X-Cg-Demo-Api-Key".into(), value: "CG-R6KYDr2MxXQ3Y34TNTWyhuhn".into() },
-    ];
+    let headers = coingecko_headers();
 
     let request = CanisterHttpRequestArgument {
         url,
@@ -73,28 +105,23 @@ pub async fn get_crypto_usd_rate(crypto_id: &str) -> Result<f64, String> {
 
     match mgmt_http_request(request, 100_000_000).await {
         Ok((response,)) => {
-            let str_body = String::from_utf8(response.body)
-                .map_err(|_| "Failed to decode response body".to_string())?;
-            let json: serde_json::Value = serde_json::from_str(&str_body)
-                .map_err(|_| "Failed to parse JSON".to_string())?;
+            check_status(&response)?;
+            let json = decode_json(response.body)?;
             let rate = json
                 .get(id)
-                .and_then(|v| v.get("usd"))
+                .and_then(|v| v.get(currency.as_str()))
                 .and_then(|v| v.as_f64())
-                .ok_or_else(|| format!("No usd rate for {}", id))?;
+                .ok_or_else(|| FintrackError::not_found(&format!("{} rate", currency), id))?;
             Ok(rate)
         }
-        Err((code, msg)) => Err(format!("HTTP request failed. Code: {:?}, Msg: {}", code, msg)),
+        Err((code, msg)) => Err(FintrackError::http(format!("Code: {:?}, Msg: {}", code, msg))),
     }
 }
 
-pub async fn get_rates_summary() -> Result<CryptoRates, String> {
+pub async fn get_rates_summary() -> Result<CryptoRates, FintrackError> {
     // Fetch all three in a single request to reduce latency
     let url = "https://api.coingecko.com/api/v3/simple/price?ids=bitcoin,ethereum,solana&vs_currencies=usd".to_string();
-    let headers = vec![
-        HttpHeader { name: "Accept".into(), value: "application/json".into() },
-        HttpHeader { name: "X-Cg-Demo-Api-Key".into(), value: "CG-R6KYDr2MxXQ3Y34TNTWyhuhn".into() },
-    ];
+    let headers = coingecko_headers();
     let request = CanisterHttpRequestArgument {
         url,
         method: HttpMethod::GET,
@@ -105,8 +132,8 @@ pub async fn get_rates_summary() -> Result<CryptoRates, String> {
     };
     let (btc, eth, sol) = match mgmt_http_request(request, 100_000_000).await {
         Ok((response,)) => {
-            let str_body = String::from_utf8(response.body).map_err(|_| "Failed to decode response body".to_string())?;
-            let json: serde_json::Value = serde_json::from_str(&str_body).map_err(|_| "Failed to parse JSON".to_string())?;
+            check_status(&response)?;
+            let json = decode_json(response.body)?;
             let btc = json.get("bitcoin").and_then(|v| v.get("usd")).and_then(|v| v.as_f64()).unwrap_or(0.0);
             let eth = json.get("ethereum").and_then(|v| v.get("usd")).and_then(|v| v.as_f64()).unwrap_or(0.0);
             let sol = json.get("solana").and_then(|v| v.get("usd")).and_then(|v| v.as_f64()).unwrap_or(0.0);
@@ -127,7 +154,7 @@ pub async fn get_market_chart(
     coin_id: &str, 
     vs_currency: &str, 
     days: u32
-) -> Result<MarketChartData, String> {
+) -> Result<MarketChartData, FintrackError> {
     // Map coin symbols to CoinGecko IDs
     let id = match coin_id {
         "btc" | "bitcoin" => "bitcoin",
@@ -143,10 +170,7 @@ pub async fn get_market_chart(
         id, vs_currency, days
     );
 
-    let headers = vec![
-        HttpHeader { name: "Accept".into(), value: "application/json".into() },
-        HttpHeader { name: "X-Cg-Demo-Api-Key".into(), value: "CG-R6KYDr2MxXQ3Y34TNTWyhuhn".into() },
-    ];
+    let headers = coingecko_headers();
 
     let request = CanisterHttpRequestArgument {
         url,
@@ -159,16 +183,13 @@ pub async fn get_market_chart(
 
     match mgmt_http_request(request, 600_000_000).await {
         Ok((response,)) => {
-            let str_body = String::from_utf8(response.body)
-                .map_err(|_| "Failed to decode response body".to_string())?;
-            
-            let json: serde_json::Value = serde_json::from_str(&str_body)
-                .map_err(|_| "Failed to parse JSON".to_string())?;
+            check_status(&response)?;
+            let json = decode_json(response.body)?;
 
             let prices = json
                 .get("prices")
                 .and_then(|v| v.as_array())
-                .ok_or("Missing prices data")?
+                .ok_or_else(|| FintrackError::http("Missing prices data"))?
                 .iter()
                 .filter_map(|item| {
                     if let Some(arr) = item.as_array() {
@@ -189,7 +210,7 @@ pub async fn get_market_chart(
             let market_caps = json
                 .get("market_caps")
                 .and_then(|v| v.as_array())
-                .ok_or("Missing market_caps data")?
+                .ok_or_else(|| FintrackError::http("Missing market_caps data"))?
                 .iter()
                 .filter_map(|item| {
                     if let Some(arr) = item.as_array() {
@@ -210,7 +231,7 @@ pub async fn get_market_chart(
             let total_volumes = json
                 .get("total_volumes")
                 .and_then(|v| v.as_array())
-                .ok_or("Missing total_volumes data")?
+                .ok_or_else(|| FintrackError::http("Missing total_volumes data"))?
                 .iter()
                 .filter_map(|item| {
                     if let Some(arr) = item.as_array() {
@@ -234,23 +255,23 @@ pub async fn get_market_chart(
                 total_volumes,
             })
         }
-        Err((code, msg)) => Err(format!("HTTP request failed. Code: {:?}, Msg: {}", code, msg)),
+        Err((code, msg)) => Err(FintrackError::http(format!("Code: {:?}, Msg: {}", code, msg))),
     }
 }
 
-pub async fn get_24h_change(coin_id: &str) -> Result<f64, String> {
+pub async fn get_24h_change(coin_id: &str) -> Result<f64, FintrackError> {
     // Get 1 day of data to calculate 24h change
     let chart_data = get_market_chart(coin_id, "usd", 1).await?;
     
     if chart_data.prices.len() < 2 {
-        return Err("Insufficient price data for 24h change calculation".to_string());
+        return Err(FintrackError::invalid_state("insufficient price data for 24h change calculation"));
     }
 
     let current_price = chart_data.prices.last().unwrap()[1];
     let price_24h_ago = chart_data.prices.first().unwrap()[1];
     
     if price_24h_ago == 0.0 {
-        return Err("Cannot calculate 24h change: price 24h ago is zero".to_string());
+        return Err(FintrackError::invalid_state("cannot calculate 24h change: price 24h ago is zero"));
     }
 
     let change_percent = ((current_price - price_24h_ago) / price_24h_ago) * 100.0;
@@ -261,7 +282,7 @@ pub async fn get_historical_prices(
     coin_id: &str, 
     vs_currency: &str, 
     days: u32
-) -> Result<Vec<PriceData>, String> {
+) -> Result<Vec<PriceData>, FintrackError> {
     let chart_data = get_market_chart(coin_id, vs_currency, days).await?;
     
     let prices: Vec<PriceData> = chart_data.prices
@@ -0,0 +1,196 @@
+use candid::{CandidType, Deserialize};
+use ic_cdk::{caller, api::time};
+use ic_stable_structures::StableBTreeMap;
+use sha3::{Digest, Keccak256};
+use std::cell::RefCell;
+use super::errors::FintrackError;
+use super::storage::{self, Candid, StableText, VMem};
+
+// Secrets store untuk kredensial provider pihak ketiga (Moralis, BlockCypher, CoinGecko).
+// Hanya controller yang bisa mengelola. Nilai key TIDAK PERNAH keluar lewat candid maupun log:
+// response hanya berisi key_id, fingerprint (hash) dan counter pemakaian.
+
+// Satu record provider harus muat di Candid<T>::MAX_SIZE (16KB)
+const MAX_KEYS_PER_PROVIDER: usize = 5;
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecretProvider {
+    Moralis,
+    BlockCypher,
+    CoinGecko,
+}
+
+impl SecretProvider {
+    fn key(&self) -> StableText {
+        StableText(format!("{:?}", self))
+    }
+}
+
+// Sengaja tanpa Debug agar value tidak ikut tercetak
+#[derive(CandidType, Deserialize, Clone)]
+struct StoredKey {
+    key_id: u32,
+    value: String,
+    added_at_ns: u64,
+    use_count: u64,
+    last_used_at_ns: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+struct ProviderSecrets {
+    enabled: bool,
+    keys: Vec<StoredKey>,
+    next_key_id: u32,
+    cursor: u64, // round-robin antar key
+    total_uses: u64,
+    denied_uses: u64, // dipanggil saat provider disabled / tanpa key
+}
+
+impl Default for ProviderSecrets {
+    fn default() -> Self {
+        ProviderSecrets { enabled: true, keys: Vec::new(), next_key_id: 1, cursor: 0, total_uses: 0, denied_uses: 0 }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SecretKeyInfo {
+    pub key_id: u32,
+    pub fingerprint: String, // 8 hex pertama keccak256(value)
+    pub added_at_ns: u64,
+    pub use_count: u64,
+    pub last_used_at_ns: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProviderSecretsInfo {
+    pub provider: SecretProvider,
+    pub enabled: bool,
+    pub keys: Vec<SecretKeyInfo>,
+    pub total_uses: u64,
+    pub denied_uses: u64,
+}
+
+thread_local! {
+    // key = nama provider
+    static SECRETS: RefCell<StableBTreeMap<StableText, Candid<ProviderSecrets>, VMem>> = RefCell::new(
+        StableBTreeMap::init(storage::memory(storage::SECRETS_MEMORY_ID))
+    );
+}
+
+const ALL_PROVIDERS: [SecretProvider; 3] = [SecretProvider::Moralis, SecretProvider::BlockCypher, SecretProvider::CoinGecko];
+
+fn load(provider: SecretProvider) -> ProviderSecrets {
+    SECRETS.with(|s| s.borrow().get(&provider.key())).map(|c| c.0).unwrap_or_default()
+}
+
+fn store(provider: SecretProvider, secrets: ProviderSecrets) {
+    SECRETS.with(|s| s.borrow_mut().insert(provider.key(), Candid(secrets)));
+}
+
+fn fingerprint(value: &str) -> String {
+    let hash = Keccak256::digest(value.as_bytes());
+    hex::encode(&hash[..4])
+}
+
+fn info(provider: SecretProvider, secrets: &ProviderSecrets) -> ProviderSecretsInfo {
+    ProviderSecretsInfo {
+        provider,
+        enabled: secrets.enabled,
+        keys: secrets.keys.iter().map(|k| SecretKeyInfo {
+            key_id: k.key_id,
+            fingerprint: fingerprint(&k.value),
+            added_at_ns: k.added_at_ns,
+            use_count: k.use_count,
+            last_used_at_ns: k.last_used_at_ns,
+        }).collect(),
+        total_uses: secrets.total_uses,
+        denied_uses: secrets.denied_uses,
+    }
+}
+
+fn require_controller() -> Result<(), FintrackError> {
+    if ic_cdk::api::is_controller(&caller()) { Ok(()) } else { Err(FintrackError::unauthorized("controller only")) }
+}
+
+fn validate_value(value: &str) -> Result<(), FintrackError> {
+    if value.trim().is_empty() { return Err(FintrackError::invalid_input("value", "key value cannot be empty")); }
+    if value.len() > 2048 { return Err(FintrackError::invalid_input("value", "key value too long")); }
+    Ok(())
+}
+
+/// Ambil key berikutnya (round-robin) untuk dipakai di HTTP outcall dan catat pemakaiannya.
+/// None bila provider disabled atau belum punya key.
+pub fn use_key(provider: SecretProvider) -> Option<String> {
+    let mut secrets = load(provider);
+    if !secrets.enabled || secrets.keys.is_empty() {
+        secrets.denied_uses += 1;
+        store(provider, secrets);
+        return None;
+    }
+    let idx = (secrets.cursor % secrets.keys.len() as u64) as usize;
+    secrets.cursor = secrets.cursor.wrapping_add(1);
+    secrets.total_uses += 1;
+    let key = &mut secrets.keys[idx];
+    key.use_count += 1;
+    key.last_used_at_ns = Some(time());
+    let value = key.value.clone();
+    store(provider, secrets);
+    Some(value)
+}
+
+pub fn add_key(provider: SecretProvider, value: String) -> Result<SecretKeyInfo, FintrackError> {
+    require_controller()?;
+    validate_value(&value)?;
+    let mut secrets = load(provider);
+    if secrets.keys.iter().any(|k| k.value == value) { return Err(FintrackError::invalid_input("value", "key already registered")); }
+    if secrets.keys.len() >= MAX_KEYS_PER_PROVIDER { return Err(FintrackError::invalid_state(format!("at most {} keys per provider", MAX_KEYS_PER_PROVIDER))); }
+    let key_id = secrets.next_key_id;
+    secrets.next_key_id += 1;
+    secrets.keys.push(StoredKey { key_id, value, added_at_ns: time(), use_count: 0, last_used_at_ns: None });
+    let out = info(provider, &secrets).keys.into_iter().find(|k| k.key_id == key_id).expect("key just added");
+    store(provider, secrets);
+    Ok(out)
+}
+
+pub fn remove_key(provider: SecretProvider, key_id: u32) -> Result<(), FintrackError> {
+    require_controller()?;
+    let mut secrets = load(provider);
+    let before = secrets.keys.len();
+    secrets.keys.retain(|k| k.key_id != key_id);
+    if secrets.keys.len() == before { return Err(FintrackError::not_found("secret key", key_id.to_string())); }
+    store(provider, secrets);
+    Ok(())
+}
+
+/// Rotasi: ganti seluruh key provider sekaligus (key lama langsung tidak dipakai lagi)
+pub fn rotate_keys(provider: SecretProvider, values: Vec<String>) -> Result<ProviderSecretsInfo, FintrackError> {
+    require_controller()?;
+    for v in values.iter() { validate_value(v)?; }
+    if values.len() > MAX_KEYS_PER_PROVIDER { return Err(FintrackError::invalid_input("values", format!("at most {} keys per provider", MAX_KEYS_PER_PROVIDER))); }
+    let mut secrets = load(provider);
+    let now = time();
+    secrets.keys.clear();
+    secrets.cursor = 0;
+    for value in values {
+        if secrets.keys.iter().any(|k| k.value == value) { continue; }
+        let key_id = secrets.next_key_id;
+        secrets.next_key_id += 1;
+        secrets.keys.push(StoredKey { key_id, value, added_at_ns: now, use_count: 0, last_used_at_ns: None });
+    }
+    let out = info(provider, &secrets);
+    store(provider, secrets);
+    Ok(out)
+}
+
+pub fn set_enabled(provider: SecretProvider, enabled: bool) -> Result<(), FintrackError> {
+    require_controller()?;
+    let mut secrets = load(provider);
+    secrets.enabled = enabled;
+    store(provider, secrets);
+    Ok(())
+}
+
+pub fn list() -> Result<Vec<ProviderSecretsInfo>, FintrackError> {
+    require_controller()?;
+    Ok(ALL_PROVIDERS.iter().map(|p| info(*p, &load(*p))).collect())
+}
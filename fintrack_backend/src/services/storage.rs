@@ -0,0 +1,112 @@
+use candid::{decode_one, encode_one, CandidType};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager, VirtualMemory},
+    DefaultMemoryImpl,
+};
+use ic_stable_structures::storable::{Storable, BoundedStorable};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+// Shared stable memory: satu MemoryManager untuk seluruh canister.
+// Setiap service mengambil virtual memory sendiri lewat MemoryId di bawah ini.
+// JANGAN mengubah/menggunakan ulang id yang sudah dipakai, data lama akan rusak.
+
+pub type VMem = VirtualMemory<DefaultMemoryImpl>;
+
+pub const NATIVE_TXS_MEMORY_ID: u8 = 10;
+pub const AUTH_DELEGATIONS_MEMORY_ID: u8 = 11;
+pub const AUTH_AUDIT_MEMORY_ID: u8 = 12;
+pub const GOALS_MEMORY_ID: u8 = 13;
+pub const GOAL_EVENTS_MEMORY_ID: u8 = 14;
+pub const BUDGETS_MEMORY_ID: u8 = 15;
+pub const BUDGET_EVENTS_MEMORY_ID: u8 = 16;
+pub const CONFIG_MEMORY_ID: u8 = 17;
+pub const SECRETS_MEMORY_ID: u8 = 18;
+pub const CATEGORIES_MEMORY_ID: u8 = 19;
+pub const OPERATIONS_MEMORY_ID: u8 = 20;
+pub const ESCROWS_MEMORY_ID: u8 = 21;
+pub const ESCROW_REPORTS_MEMORY_ID: u8 = 22;
+pub const COMMUNITY_POOL_MEMORY_ID: u8 = 23;
+pub const NOTIFICATIONS_MEMORY_ID: u8 = 24;
+pub const BTC_SENT_TXS_MEMORY_ID: u8 = 25;
+pub const GOAL_INDEX_MEMORY_ID: u8 = 26;
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
+        MemoryManager::init(DefaultMemoryImpl::default())
+    );
+}
+
+pub fn memory(id: u8) -> VMem {
+    MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(id)))
+}
+
+/// Bounded text key for stable maps (ids, composite keys)
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct StableText(pub String);
+
+impl Storable for StableText {
+    fn to_bytes(&self) -> Cow<[u8]> { Cow::Owned(self.0.as_bytes().to_vec()) }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self { StableText(String::from_utf8_lossy(&bytes).into_owned()) }
+}
+
+impl BoundedStorable for StableText {
+    const MAX_SIZE: u32 = 256; // id budget/goal ~130 chars
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Candid-encoded value wrapper for stable maps
+#[derive(Clone, Debug)]
+pub struct Candid<T>(pub T);
+
+impl<T: CandidType + DeserializeOwned> Storable for Candid<T> {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(encode_one(&self.0).expect("candid encode for stable storage failed"))
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Candid(decode_one(&bytes).expect("candid decode from stable storage failed"))
+    }
+}
+
+impl<T: CandidType + DeserializeOwned> BoundedStorable for Candid<T> {
+    const MAX_SIZE: u32 = 16 * 1024; // satu record (bukan list), cukup longgar
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Record dengan tag versi skema: 2 byte versi (LE) + payload candid.
+/// Envelope ini tetap bisa dibaca walau struct payload berubah, sehingga migrasi
+/// dilakukan di post_upgrade (lihat `decode_*` per service) alih-alih trap saat decode.
+#[derive(Clone, Debug)]
+pub struct Versioned {
+    pub version: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Versioned {
+    pub fn encode<T: CandidType>(version: u16, value: &T) -> Self {
+        Versioned { version, payload: encode_one(value).expect("candid encode for stable storage failed") }
+    }
+
+    pub fn decode<T: CandidType + DeserializeOwned>(&self) -> Result<T, String> {
+        decode_one(&self.payload).map_err(|e| format!("decode v{} record failed: {}", self.version, e))
+    }
+}
+
+impl Storable for Versioned {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut out = Vec::with_capacity(2 + self.payload.len());
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&self.payload);
+        Cow::Owned(out)
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let version = u16::from_le_bytes([bytes[0], bytes[1]]);
+        Versioned { version, payload: bytes[2..].to_vec() }
+    }
+}
+
+impl BoundedStorable for Versioned {
+    const MAX_SIZE: u32 = 2 + 16 * 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
@@ -20,11 +20,108 @@ pub fn cancel_timer(timer: Option<TimerId>) {
     if let Some(t) = timer { let _ = clear_timer(t); }
 }
 
-// Removed calendar helpers; we now rely on explicit ns inputs
+// Kalender UTC untuk jadwal bulanan (algoritma days_from_civil / civil_from_days, Howard Hinnant).
+// Timestamp canister selalu >= 1970, jadi cukup aritmatika unsigned.
 
+fn days_from_civil(y: u64, m: u32, d: u32) -> u64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = ((m + 9) % 12) as u64; // Maret = 0
+    let doy = (153 * mp + 2) / 5 + d as u64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
 
+fn days_in_month(y: u64, m: u32) -> u32 {
+    let (ny, nm) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+    (days_from_civil(ny, nm, 1) - days_from_civil(y, m, 1)) as u32
+}
 
+/// Tanggal (1..=31) dari timestamp ns, UTC
+pub fn day_of_month(ns_timestamp: u64) -> u32 {
+    civil_from_days(ns_timestamp / NS_PER_SEC / SEC_PER_DAY).2
+}
+
+/// Geser `months` bulan ke depan pada tanggal `day` (di-clamp ke akhir bulan), jam tetap sama
+pub fn add_months_at_day(ns_timestamp: u64, months: u32, day: u32) -> u64 {
+    let ns_per_day = SEC_PER_DAY * NS_PER_SEC;
+    let time_of_day = ns_timestamp % ns_per_day;
+    let (y, m, _) = civil_from_days(ns_timestamp / ns_per_day);
+    let total = (m - 1) as u64 + months as u64;
+    let (ny, nm) = (y + total / 12, (total % 12) as u32 + 1);
+    let nd = day.clamp(1, days_in_month(ny, nm));
+    days_from_civil(ny, nm, nd) * ns_per_day + time_of_day
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    const NS_PER_DAY: u64 = SEC_PER_DAY * NS_PER_SEC;
 
+    fn at(y: u64, m: u32, d: u32) -> u64 { days_from_civil(y, m, d) * NS_PER_DAY }
 
+    #[test]
+    fn known_epoch_days() {
+        for (date, days) in [((1970, 1, 1), 0), ((2000, 3, 1), 11_017), ((2024, 2, 29), 19_782), ((2100, 3, 1), 47_541)] {
+            let (y, m, d) = date;
+            assert_eq!(days_from_civil(y, m, d), days);
+            assert_eq!(civil_from_days(days), date);
+        }
+    }
+
+    #[test]
+    fn civil_round_trip() {
+        // 1970..~2250, melewati abad non-kabisat 2100 dan 2200
+        for days in 0..100_000 {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn leap_years() {
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2000, 2), 29); // habis dibagi 400
+        assert_eq!(days_in_month(2100, 2), 28); // habis dibagi 100
+        assert_eq!(days_in_month(2024, 12), 31);
+    }
+
+    #[test]
+    fn month_end_is_clamped() {
+        assert_eq!(add_months_at_day(at(2023, 1, 31), 1, 31), at(2023, 2, 28));
+        assert_eq!(add_months_at_day(at(2024, 1, 31), 1, 31), at(2024, 2, 29));
+        assert_eq!(add_months_at_day(at(2024, 3, 31), 1, 31), at(2024, 4, 30));
+        // Tanggal anchor tetap dipakai lagi setelah bulan pendek
+        assert_eq!(add_months_at_day(at(2024, 2, 29), 1, 31), at(2024, 3, 31));
+    }
+
+    #[test]
+    fn december_rolls_into_next_year() {
+        assert_eq!(add_months_at_day(at(2023, 12, 15), 1, 15), at(2024, 1, 15));
+        assert_eq!(add_months_at_day(at(2023, 11, 30), 3, 30), at(2024, 2, 29));
+        assert_eq!(add_months_at_day(at(2023, 12, 1), 25, 1), at(2026, 1, 1));
+    }
+
+    #[test]
+    fn time_of_day_is_kept() {
+        let t = at(2024, 1, 31) + 13 * SEC_PER_HOUR * NS_PER_SEC + 5;
+        assert_eq!(add_months_at_day(t, 1, 31), at(2024, 2, 29) + 13 * SEC_PER_HOUR * NS_PER_SEC + 5);
+        assert_eq!(day_of_month(t), 31);
+    }
+}
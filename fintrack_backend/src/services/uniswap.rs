@@ -9,36 +9,28 @@ use ic_ethereum_types::Address;
 use alloy_consensus::{SignableTransaction, TxEip1559, TxEnvelope};
 use alloy_primitives::{hex, Signature, TxKind, U256, Bytes};
 use super::evm_rpc_canister::{
-    BlockTag, EthMainnetService, EthSepoliaService, GetTransactionCountArgs,
-    GetTransactionCountResult, MultiGetTransactionCountResult, RpcServices, RpcConfig, RpcService,
-    JsonRpcSource
+    BlockTag, GetTransactionCountArgs,
+    GetTransactionCountResult, MultiGetTransactionCountResult, RpcConfig
 };
 
 use num_traits::Zero;
 use std::str::FromStr;
+use super::auth::{self, DelegationScope};
+use super::config;
+use super::errors::FintrackError;
 
-// Constants
-const DEFAULT_ECDSA_KEY_NAME: &str = "key_1"; // Use "test_key_1" or "key_1" on IC
-const SEPOLIA_CHAIN_ID: u64 = 11155111;
-
-// EVM RPC Canister ID: giifx-2iaaa-aaaab-qb5ua-cai (mainnet - same for Sepolia)
+// EVM RPC canister, chain id & key name diambil dari config canister
 fn get_evm_rpc_canister_id() -> Principal {
-    Principal::from_text("giifx-2iaaa-aaaab-qb5ua-cai").unwrap()
+    config::evm_rpc_canister()
 }
 
 // Dynamic cost estimation for EVM RPC calls with buffer
 async fn get_evm_rpc_cost_with_buffer(
-    source: JsonRpcSource,
     json_request: String,
     max_response_bytes: u64,
-) -> Result<u128, String> {
+) -> Result<u128, FintrackError> {
     let evm_rpc = get_evm_rpc_canister_id();
-    
-    // Convert JsonRpcSource to RpcService
-    let rpc_service = match source {
-        JsonRpcSource::EthSepolia => RpcService::EthSepolia(EthSepoliaService::PublicNode),
-        JsonRpcSource::EthMainnet => RpcService::EthMainnet(EthMainnetService::PublicNode),
-    };
+    let rpc_service = config::evm_rpc_service();
     
     // Get cost estimate using call without cycles
     let (cost_result,): (Result<candid::Nat, super::evm_rpc_canister::RpcError>,) = ic_cdk::api::call::call(
@@ -47,9 +39,9 @@ async fn get_evm_rpc_cost_with_buffer(
         (rpc_service, json_request.clone(), max_response_bytes),
     )
     .await
-    .map_err(|e| format!("Failed to get cost estimate: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("failed to get cost estimate: {:?}", e)))?;
     
-    let base_cost = cost_result.map_err(|e| format!("Cost estimation failed: {:?}", e))?;
+    let base_cost = cost_result.map_err(|e| FintrackError::rpc(format!("cost estimation failed: {:?}", e)))?;
     let base_cost_u128 = base_cost.0.to_u128().unwrap_or(0);
     
     // Add 50% buffer for retries and response size increases
@@ -64,7 +56,7 @@ pub struct TxRequest {
     pub to: String,           // Universal Router address
     pub data: String,         // ABI encoded calldata from FE
     pub value: Option<u128>,  // ETH to send (optional)
-    pub owner: Option<Principal>, // Optional owner, defaults to caller
+    pub owner: Option<Principal>, // Optional owner, defaults to caller; other owners need a delegation
 }
 
 // Response struct
@@ -136,7 +128,7 @@ impl EthereumWallet {
             derivation_path: principal_derivation_path(&owner),
             key_id: EcdsaKeyId {
                 curve: EcdsaCurve::Secp256k1,
-                name: DEFAULT_ECDSA_KEY_NAME.to_string(),
+                name: config::ecdsa_key_name(),
             },
         };
         let (res,) = ecdsa_public_key(args)
@@ -162,7 +154,7 @@ impl EthereumWallet {
         let derivation_path = principal_derivation_path(&self.owner);
         let key_id = EcdsaKeyId {
             curve: EcdsaCurve::Secp256k1,
-            name: DEFAULT_ECDSA_KEY_NAME.to_string(),
+            name: config::ecdsa_key_name(),
         };
         let (result,) = ic_cdk::api::management_canister::ecdsa::sign_with_ecdsa(SignWithEcdsaArgument {
             message_hash: message_hash.to_vec(),
@@ -218,10 +210,10 @@ fn principal_derivation_path(owner: &Principal) -> Vec<Vec<u8>> {
 }
 
 // Get transaction count (nonce) - reused from ethtransfer.rs
-pub async fn get_transaction_count(owner: Option<Principal>, block: Option<BlockTag>) -> Result<u64, String> {
+pub async fn get_transaction_count(owner: Option<Principal>, block: Option<BlockTag>) -> Result<u64, FintrackError> {
     let owner = owner.unwrap_or_else(ic_cdk::caller);
     let wallet = EthereumWallet::new(owner).await;
-    let rpc_services = RpcServices::EthSepolia(Some(vec![EthSepoliaService::PublicNode]));
+    let rpc_services = config::evm_rpc_services();
     
     let block_tag = block.unwrap_or(BlockTag::Pending);
     let args = GetTransactionCountArgs {
@@ -239,11 +231,10 @@ pub async fn get_transaction_count(owner: Option<Principal>, block: Option<Block
             BlockTag::Pending => "pending",
             BlockTag::Earliest => "earliest",
             BlockTag::Safe => "safe",
-            BlockTag::Number(n) => return Err(format!("Unsupported block number: {}", n)),
+            BlockTag::Number(n) => return Err(FintrackError::invalid_input("block", format!("unsupported block number: {}", n))),
         }
     );
     let estimated_cycles = get_evm_rpc_cost_with_buffer(
-        JsonRpcSource::EthSepolia,
         json_request,
         500 // Small response for nonce
     ).await?;
@@ -266,17 +257,12 @@ pub async fn get_transaction_count(owner: Option<Principal>, block: Option<Block
         MultiGetTransactionCountResult::Consistent(consistent_result) => {
             match consistent_result {
                 GetTransactionCountResult::Ok(count) => Ok(nat_to_u64(count)),
-                GetTransactionCountResult::Err(_error) => {
-                    Err("failed to get transaction count".to_string())
+                GetTransactionCountResult::Err(error) => {
+                    Err(FintrackError::rpc(format!("failed to get transaction count: {:?}", error)))
                 }
             }
         }
-        MultiGetTransactionCountResult::Inconsistent(inconsistent_results) => {
-            Err(format!(
-                "inconsistent results when retrieving transaction count. Received results: {}",
-                inconsistent_results.len()
-            ))
-        }
+        MultiGetTransactionCountResult::Inconsistent(_) => Err(FintrackError::RpcInconsistent),
     }
 }
 
@@ -301,35 +287,76 @@ fn u128_to_u256(value: u128) -> U256 {
     U256::from(value)
 }
 
+// Kegagalan kirim tx. Rejected = tx pasti tidak diterima (gagal sebelum broadcast atau ditolak provider
+// dengan alasan pasti); Unknown = tx mungkin sudah sampai ke provider.
+enum SendError {
+    Rejected(FintrackError),
+    Unknown(FintrackError),
+}
+
+// Error sebelum broadcast (`?` pada validasi, nonce, estimasi cycles) selalu Rejected
+impl From<FintrackError> for SendError {
+    fn from(e: FintrackError) -> Self { SendError::Rejected(e) }
+}
+
+impl From<String> for SendError {
+    fn from(message: String) -> Self { SendError::Rejected(message.into()) }
+}
+
+// Signing terikat ke caller; owner lain hanya via delegasi EvmContractCall.
+// ETH value (wei) dibebankan ke spend cap; token yang dipindah lewat calldata tidak terukur.
+// Spend hanya dikembalikan untuk SendError::Rejected supaya retry tidak bisa melewati cap.
+async fn with_authorized_owner<F, Fut>(req: TxRequest, send: F) -> Result<String, FintrackError>
+where
+    F: FnOnce(Principal, TxRequest) -> Fut,
+    Fut: std::future::Future<Output = Result<String, SendError>>,
+{
+    let delegate = ic_cdk::caller();
+    let value = req.value.unwrap_or(0);
+    let charged = if value > 0 { Some(Nat::from(value)) } else { None };
+    let owner = auth::authorize_signer(req.owner, DelegationScope::EvmContractCall, charged.clone())?;
+
+    match send(owner, req).await {
+        Ok(hash) => Ok(hash),
+        Err(SendError::Rejected(e)) => {
+            if let Some(amount) = charged.as_ref() {
+                auth::release_spend(owner, delegate, DelegationScope::EvmContractCall, amount);
+            }
+            Err(e)
+        }
+        Err(SendError::Unknown(e)) => Err(e),
+    }
+}
+
 // Main function to send Uniswap transaction
-pub async fn send_uniswap_tx(req: TxRequest) -> Result<String, String> {
+pub async fn send_uniswap_tx(req: TxRequest) -> Result<String, FintrackError> {
+    with_authorized_owner(req, send_uniswap_tx_for_owner).await
+}
+
+async fn send_uniswap_tx_for_owner(owner: Principal, req: TxRequest) -> Result<String, SendError> {
 
     // Validate input
     let to_address = Address::from_str(&req.to)
-        .map_err(|e| format!("Invalid 'to' address: {}", e))?;
+        .map_err(|e| FintrackError::invalid_address(req.to.clone(), e))?;
 
     // Parse calldata
     let calldata = if req.data.starts_with("0x") {
         hex::decode(&req.data[2..])
-            .map_err(|e| format!("Invalid hex data: {}", e))?
+            .map_err(|e| FintrackError::invalid_input("data", format!("invalid hex: {}", e)))?
     } else {
         hex::decode(&req.data)
-            .map_err(|e| format!("Invalid hex data: {}", e))?
+            .map_err(|e| FintrackError::invalid_input("data", format!("invalid hex: {}", e)))?
     };
 
-    // Get owner (caller if not specified)
-    let owner = req.owner.unwrap_or_else(ic_cdk::caller);
-
     // Get nonce for the owner
-    let nonce = get_transaction_count(Some(owner), Some(BlockTag::Pending)).await
-        .map_err(|e| format!("Failed to get nonce: {}", e))?;
+    let nonce = get_transaction_count(Some(owner), Some(BlockTag::Pending)).await?;
 
     // Estimate gas and fees
     let (gas_limit, max_fee_per_gas, max_priority_fee_per_gas) = estimate_uniswap_fees();
 
     // Build EIP-1559 transaction
     let transaction = TxEip1559 {
-        chain_id: SEPOLIA_CHAIN_ID,
+        chain_id: config::chain_id(),
         nonce,
         gas_limit,
         max_fee_per_gas,
@@ -360,13 +387,12 @@ pub async fn send_uniswap_tx(req: TxRequest) -> Result<String, String> {
 
 
     // Send transaction via EVM RPC with dynamic cost estimation
-    let rpc_services = RpcServices::EthSepolia(Some(vec![EthSepoliaService::PublicNode]));
+    let rpc_services = config::evm_rpc_services();
     let json_request = format!(
         r#"{{"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["{}"],"id":1}}"#,
         raw_transaction_hex
     );
     let estimated_cycles = get_evm_rpc_cost_with_buffer(
-        JsonRpcSource::EthSepolia,
         json_request,
         10_000 // 10KB max response
     ).await?;
@@ -378,7 +404,7 @@ pub async fn send_uniswap_tx(req: TxRequest) -> Result<String, String> {
         estimated_cycles,
     )
     .await
-    .map_err(|e| format!("Failed to send transaction: {:?}", e))?;
+    .map_err(|e| SendError::Unknown(FintrackError::rpc(format!("failed to send transaction: {:?}", e))))?;
 
     match result {
         super::evm_rpc_canister::MultiSendRawTransactionResult::Consistent(send_result) => {
@@ -392,29 +418,30 @@ pub async fn send_uniswap_tx(req: TxRequest) -> Result<String, String> {
                             Ok("Transaction sent successfully".to_string())
                         }
                         super::evm_rpc_canister::SendRawTransactionStatus::NonceTooLow => {
-                            Err("Nonce too low".to_string())
+                            Err(SendError::Rejected(FintrackError::rpc("nonce too low")))
                         }
+                        // Bisa tertahan di mempool provider sampai gap nonce terisi
                         super::evm_rpc_canister::SendRawTransactionStatus::NonceTooHigh => {
-                            Err("Nonce too high".to_string())
+                            Err(SendError::Unknown(FintrackError::rpc("nonce too high")))
                         }
                         super::evm_rpc_canister::SendRawTransactionStatus::InsufficientFunds => {
-                            Err("Insufficient funds".to_string())
+                            Err(SendError::Rejected(FintrackError::rpc("insufficient funds for gas * price + value")))
                         }
                     }
                 }
                 super::evm_rpc_canister::SendRawTransactionResult::Err(_error) => {
-                    Err("Transaction failed".to_string())
+                    Err(SendError::Unknown(FintrackError::rpc("transaction failed")))
                 }
             }
         }
         super::evm_rpc_canister::MultiSendRawTransactionResult::Inconsistent(_) => {
-            Err("Inconsistent RPC response".to_string())
+            Err(SendError::Unknown(FintrackError::RpcInconsistent))
         }
     }
 }
 
 // Alternative function that returns structured response
-pub async fn send_uniswap_tx_with_response(req: TxRequest) -> Result<UniswapTxResponse, String> {
+pub async fn send_uniswap_tx_with_response(req: TxRequest) -> Result<UniswapTxResponse, FintrackError> {
     match send_uniswap_tx(req).await {
         Ok(tx_hash) => Ok(UniswapTxResponse {
             success: true,
@@ -424,40 +451,40 @@ pub async fn send_uniswap_tx_with_response(req: TxRequest) -> Result<UniswapTxRe
         Err(error) => Ok(UniswapTxResponse {
             success: false,
             transaction_hash: None,
-            error: Some(error),
+            error: Some(error.to_string()),
         }),
     }
 }
 
 // Function to send approval transaction (same as send_uniswap_tx but for approval)
-pub async fn send_approval_tx(req: TxRequest) -> Result<String, String> {
+pub async fn send_approval_tx(req: TxRequest) -> Result<String, FintrackError> {
+    with_authorized_owner(req, send_approval_tx_for_owner).await
+}
+
+async fn send_approval_tx_for_owner(owner: Principal, req: TxRequest) -> Result<String, SendError> {
 
     // Validate input
     let to_address = Address::from_str(&req.to)
-        .map_err(|e| format!("Invalid 'to' address: {}", e))?;
+        .map_err(|e| FintrackError::invalid_address(req.to.clone(), e))?;
 
     // Parse calldata
     let calldata = if req.data.starts_with("0x") {
         hex::decode(&req.data[2..])
-            .map_err(|e| format!("Invalid hex data: {}", e))?
+            .map_err(|e| FintrackError::invalid_input("data", format!("invalid hex: {}", e)))?
     } else {
         hex::decode(&req.data)
-            .map_err(|e| format!("Invalid hex data: {}", e))?
+            .map_err(|e| FintrackError::invalid_input("data", format!("invalid hex: {}", e)))?
     };
 
-    // Get owner (caller if not specified)
-    let owner = req.owner.unwrap_or_else(ic_cdk::caller);
-
     // Get nonce for the owner
-    let nonce = get_transaction_count(Some(owner), Some(BlockTag::Pending)).await
-        .map_err(|e| format!("Failed to get nonce: {}", e))?;
+    let nonce = get_transaction_count(Some(owner), Some(BlockTag::Pending)).await?;
 
     // Estimate gas and fees (approval typically needs less gas)
     let (gas_limit, max_fee_per_gas, max_priority_fee_per_gas) = estimate_approval_fees();
 
     // Build EIP-1559 transaction
     let transaction = TxEip1559 {
-        chain_id: SEPOLIA_CHAIN_ID,
+        chain_id: config::chain_id(),
         nonce,
         gas_limit,
         max_fee_per_gas,
@@ -488,13 +515,12 @@ pub async fn send_approval_tx(req: TxRequest) -> Result<String, String> {
 
 
     // Send approval transaction via EVM RPC with dynamic cost estimation
-    let rpc_services = RpcServices::EthSepolia(Some(vec![EthSepoliaService::PublicNode]));
+    let rpc_services = config::evm_rpc_services();
     let json_request = format!(
         r#"{{"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["{}"],"id":1}}"#,
         raw_transaction_hex
     );
     let estimated_cycles = get_evm_rpc_cost_with_buffer(
-        JsonRpcSource::EthSepolia,
         json_request,
         10_000 // 10KB max response
     ).await?;
@@ -506,7 +532,7 @@ pub async fn send_approval_tx(req: TxRequest) -> Result<String, String> {
         estimated_cycles,
     )
     .await
-    .map_err(|e| format!("Failed to send approval transaction: {:?}", e))?;
+    .map_err(|e| SendError::Unknown(FintrackError::rpc(format!("failed to send approval transaction: {:?}", e))))?;
 
     match result {
         super::evm_rpc_canister::MultiSendRawTransactionResult::Consistent(send_result) => {
@@ -520,23 +546,24 @@ pub async fn send_approval_tx(req: TxRequest) -> Result<String, String> {
                             Ok("Approval transaction sent successfully".to_string())
                         }
                         super::evm_rpc_canister::SendRawTransactionStatus::NonceTooLow => {
-                            Err("Nonce too low".to_string())
+                            Err(SendError::Rejected(FintrackError::rpc("nonce too low")))
                         }
+                        // Bisa tertahan di mempool provider sampai gap nonce terisi
                         super::evm_rpc_canister::SendRawTransactionStatus::NonceTooHigh => {
-                            Err("Nonce too high".to_string())
+                            Err(SendError::Unknown(FintrackError::rpc("nonce too high")))
                         }
                         super::evm_rpc_canister::SendRawTransactionStatus::InsufficientFunds => {
-                            Err("Insufficient funds".to_string())
+                            Err(SendError::Rejected(FintrackError::rpc("insufficient funds for gas * price + value")))
                         }
                     }
                 }
                 super::evm_rpc_canister::SendRawTransactionResult::Err(_error) => {
-                    Err("Approval transaction failed".to_string())
+                    Err(SendError::Unknown(FintrackError::rpc("approval transaction failed")))
                 }
             }
         }
         super::evm_rpc_canister::MultiSendRawTransactionResult::Inconsistent(_) => {
-            Err("Inconsistent RPC response".to_string())
+            Err(SendError::Unknown(FintrackError::RpcInconsistent))
         }
     }
 }
@@ -550,19 +577,18 @@ fn estimate_approval_fees() -> (u128, u128, u128) {
 }
 
 // Function to get fresh nonce after approval
-pub async fn get_fresh_nonce(owner: Option<Principal>) -> Result<u64, String> {
+pub async fn get_fresh_nonce(owner: Option<Principal>) -> Result<u64, FintrackError> {
     let owner = owner.unwrap_or_else(ic_cdk::caller);
     get_transaction_count(Some(owner), Some(BlockTag::Pending)).await
 }
 
 // Helper function to get current gas price for fee estimation
-pub async fn get_current_gas_price() -> Result<u128, String> {
+pub async fn get_current_gas_price() -> Result<u128, FintrackError> {
     let gas_price_json = r#"{"jsonrpc":"2.0","method":"eth_gasPrice","params":[],"id":1}"#;
     let max_response_size_bytes = 500_u64;
     
     // Use dynamic cost estimation for gas price query
     let estimated_cycles = get_evm_rpc_cost_with_buffer(
-        JsonRpcSource::EthSepolia,
         gas_price_json.to_string(),
         max_response_size_bytes
     ).await?;
@@ -570,28 +596,28 @@ pub async fn get_current_gas_price() -> Result<u128, String> {
     let (gas_price_response,) = call_with_payment128(
         get_evm_rpc_canister_id(),
         "request",
-        (RpcService::EthSepolia(EthSepoliaService::PublicNode), gas_price_json.to_string(), max_response_size_bytes),
+        (config::evm_rpc_service(), gas_price_json.to_string(), max_response_size_bytes),
         estimated_cycles,
     )
     .await
-    .map_err(|e| format!("Failed to get gas price: {:?}", e))?;
+    .map_err(|e| FintrackError::rpc(format!("failed to get gas price: {:?}", e)))?;
 
     match gas_price_response {
         super::evm_rpc_canister::RequestResult::Ok(result) => {
             let response: serde_json::Value = serde_json::from_str(&result)
-                .map_err(|e| format!("Failed to parse gas price response: {}", e))?;
+                .map_err(|e| FintrackError::rpc(format!("failed to parse gas price response: {}", e)))?;
             
             let hex_gas_price = response
                 .get("result")
                 .and_then(|v| v.as_str())
-                .ok_or("No result in gas price response")?;
+                .ok_or_else(|| FintrackError::rpc("no result in gas price response"))?;
 
             let gas_price_hex = hex_gas_price.trim_start_matches("0x");
             u128::from_str_radix(gas_price_hex, 16)
-                .map_err(|e| format!("Failed to parse hex gas price: {}", e))
+                .map_err(|e| FintrackError::rpc(format!("failed to parse hex gas price: {}", e)))
         }
-        super::evm_rpc_canister::RequestResult::Err(_) => {
-            Err("Failed to get gas price".to_string())
+        super::evm_rpc_canister::RequestResult::Err(e) => {
+            Err(FintrackError::rpc(format!("failed to get gas price: {:?}", e)))
         }
     }
 }
\ No newline at end of file
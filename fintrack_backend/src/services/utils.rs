@@ -1,18 +1,19 @@
 use candid::Principal;
 use std::str::FromStr;
+use super::errors::FintrackError;
 
 /// Convert ICP principal to bytes32 format using official method
-pub fn principal_to_bytes32(principal_text: String) -> Result<String, String> {
+pub fn principal_to_bytes32(principal_text: String) -> Result<String, FintrackError> {
     // Parse principal from text
     let principal = Principal::from_str(&principal_text)
-        .map_err(|e| format!("Failed to parse principal: {}", e))?;
+        .map_err(|e| FintrackError::invalid_input("principal_text", format!("failed to parse principal: {}", e)))?;
     
     // Get principal as bytes
     let principal_bytes = principal.as_slice();
     
     // Check length constraint (max 29 bytes)
     if principal_bytes.len() > 29 {
-        return Err(format!("Principal too long: {} bytes (max 29)", principal_bytes.len()));
+        return Err(FintrackError::invalid_input("principal_text", format!("principal too long: {} bytes (max 29)", principal_bytes.len())));
     }
     
     // Create fixed 32-byte array
@@ -0,0 +1,391 @@
+use candid::{CandidType, Deserialize, Nat};
+use num::BigUint;
+use super::errors::FintrackError;
+use super::timers;
+
+// Mesin vesting murni (tanpa time() / stable memory): waktu selalu di-inject oleh pemanggil.
+// Dipakai budget (accrual per periode/envelope) dan goals (cliff di akhir periode).
+// Invariant bucket: locked + available + withdrawn == deposited, unlock tidak pernah dobel, dan monoton terhadap waktu.
+
+pub const NS_PER_DAY: u64 = timers::SEC_PER_DAY * timers::NS_PER_SEC;
+pub const MAX_VESTING_TRANCHES: usize = 64;
+// Preview: kurva kontinu di-sampling harian, maksimal sekian titik per periode
+const MAX_PREVIEW_POINTS_PER_PERIOD: u64 = 366;
+
+// Bentuk kurva unlock dalam satu periode (berlaku sama untuk setiap periode recurring dan setiap envelope)
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum VestingSchedule {
+    Linear,
+    Daily,                          // unlock per hari penuh sejak period_start_ns
+    Weekly,                         // unlock per 7 hari penuh
+    Step { every_ns: u64 },         // unlock per interval custom
+    CliffLinear { cliff_ns: u64 },  // tidak ada unlock sebelum start + cliff, lalu linear dari awal periode
+    FrontLoaded,                    // kuadratik: cepat di awal, 1 - (1 - t)^2
+    BackLoaded,                     // kuadratik: lambat di awal, t^2
+    Tranches { tranches: Vec<VestingTranche> },
+}
+
+// at_ns = waktu unlock di periode pertama; periode berikutnya digeser sejauh period_start_ns bergeser
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VestingTranche {
+    pub at_ns: u64,
+    pub amount: Nat, // total semua tranche = amount_to_lock
+}
+
+/// Fraksi vested sebagai (pembilang, penyebut), penyebut selalu > 0
+pub type Ratio = (BigUint, BigUint);
+
+/// Cliff penuh di akhir window (goals): tidak ada unlock sebelum end, semuanya di end
+pub fn cliff_at_end(start_ns: u64, end_ns: u64) -> VestingSchedule {
+    VestingSchedule::CliffLinear { cliff_ns: end_ns.saturating_sub(start_ns).max(1) }
+}
+
+/// Posisi `now` di window [start, end): (elapsed, duration), elapsed di-clamp ke 0..=duration
+pub fn elapsed(start_ns: u64, end_ns: u64, now: u64) -> (u64, u64) {
+    let end = end_ns.max(start_ns + 1);
+    let clamped = now.clamp(start_ns, end);
+    (clamped - start_ns, end - start_ns)
+}
+
+// Offset tranche dari awal periode; `origin_ns` = awal periode pertama
+fn tranche_offset(origin_ns: u64, t: &VestingTranche) -> u64 {
+    t.at_ns.saturating_sub(origin_ns)
+}
+
+/// Fraksi yang sudah vested setelah `elapsed` dari `duration`
+pub fn vested_ratio(schedule: &VestingSchedule, origin_ns: u64, elapsed: u64, duration: u64) -> Ratio {
+    let big = BigUint::from;
+    if elapsed >= duration { return (big(1u64), big(1u64)); }
+    let stepped = |every: u64| (big(elapsed - elapsed % every.max(1)), big(duration));
+    match schedule {
+        VestingSchedule::Linear => (big(elapsed), big(duration)),
+        VestingSchedule::Daily => stepped(NS_PER_DAY),
+        VestingSchedule::Weekly => stepped(7 * NS_PER_DAY),
+        VestingSchedule::Step { every_ns } => stepped(*every_ns),
+        VestingSchedule::CliffLinear { cliff_ns } => {
+            if elapsed < *cliff_ns { (big(0u64), big(1u64)) } else { (big(elapsed), big(duration)) }
+        }
+        VestingSchedule::FrontLoaded => (big(elapsed) * (big(duration) * 2u32 - big(elapsed)), big(duration) * big(duration)),
+        VestingSchedule::BackLoaded => (big(elapsed) * big(elapsed), big(duration) * big(duration)),
+        VestingSchedule::Tranches { tranches } => {
+            let total = tranches.iter().fold(big(0u64), |acc, t| acc + &t.amount.0);
+            let done = tranches.iter()
+                .filter(|t| tranche_offset(origin_ns, t) <= elapsed)
+                .fold(big(0u64), |acc, t| acc + &t.amount.0);
+            if total == big(0u64) { (big(elapsed), big(duration)) } else { (done, total) }
+        }
+    }
+}
+
+/// Jumlah yang seharusnya sudah unlocked dari `deposited` pada rasio tersebut
+pub fn vested_amount(deposited: &BigUint, ratio: &Ratio) -> BigUint {
+    (deposited * &ratio.0) / &ratio.1
+}
+
+pub fn validate(schedule: &VestingSchedule, start: u64, end: u64, amount_to_lock: &Nat) -> Result<(), FintrackError> {
+    let duration = end.saturating_sub(start);
+    match schedule {
+        VestingSchedule::Step { every_ns } if *every_ns == 0 || *every_ns > duration => {
+            Err(FintrackError::invalid_input("vesting.every_ns", "must be greater than zero and at most the period length"))
+        }
+        VestingSchedule::CliffLinear { cliff_ns } if *cliff_ns >= duration => {
+            Err(FintrackError::invalid_input("vesting.cliff_ns", "must be shorter than the period"))
+        }
+        VestingSchedule::Tranches { tranches } => {
+            if tranches.is_empty() || tranches.len() > MAX_VESTING_TRANCHES {
+                return Err(FintrackError::invalid_input("vesting.tranches", format!("must contain 1 to {} tranches", MAX_VESTING_TRANCHES)));
+            }
+            let mut sum = BigUint::from(0u32);
+            for (i, t) in tranches.iter().enumerate() {
+                if t.at_ns < start || t.at_ns > end {
+                    return Err(FintrackError::invalid_input(&format!("vesting.tranches[{}].at_ns", i), "must fall within the first period"));
+                }
+                if t.amount.0 == BigUint::from(0u32) {
+                    return Err(FintrackError::invalid_input(&format!("vesting.tranches[{}].amount", i), "must be greater than zero"));
+                }
+                sum += &t.amount.0;
+            }
+            if sum != amount_to_lock.0 {
+                return Err(FintrackError::invalid_input("vesting.tranches", "amounts must sum to amount_to_lock"));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Titik-titik unlock (offset dari awal periode) untuk preview jadwal, terurut dan unik, selalu diakhiri `duration`
+pub fn unlock_points(schedule: &VestingSchedule, origin_ns: u64, duration: u64) -> Vec<u64> {
+    let sampled = |every: u64| -> Vec<u64> {
+        let step = every.max(duration / MAX_PREVIEW_POINTS_PER_PERIOD).max(1);
+        (1..).map(|k: u64| k.saturating_mul(step)).take_while(|o| *o < duration).collect()
+    };
+    let mut points = match schedule {
+        VestingSchedule::Linear | VestingSchedule::FrontLoaded | VestingSchedule::BackLoaded | VestingSchedule::Daily => sampled(NS_PER_DAY),
+        VestingSchedule::Weekly => sampled(7 * NS_PER_DAY),
+        VestingSchedule::Step { every_ns } => sampled(*every_ns),
+        VestingSchedule::CliffLinear { cliff_ns } => {
+            let mut p: Vec<u64> = sampled(NS_PER_DAY).into_iter().filter(|o| o > cliff_ns).collect();
+            p.insert(0, (*cliff_ns).min(duration));
+            p
+        }
+        VestingSchedule::Tranches { tranches } => tranches.iter().map(|t| tranche_offset(origin_ns, t).min(duration)).collect(),
+    };
+    points.push(duration);
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+/// Porsi vesting baru satu bucket sesuai rasio: target - unlocked, dibatasi `cap` dan saldo locked
+pub fn vest_delta(deposited: &Nat, unlocked: &Nat, locked_balance: &Nat, ratio: &Ratio, cap: Option<&BigUint>) -> BigUint {
+    let target = vested_amount(&deposited.0, ratio);
+    if target <= unlocked.0 { return BigUint::from(0u32); }
+    let mut newly = &target - &unlocked.0;
+    if let Some(c) = cap {
+        if newly > *c { newly = c.clone(); }
+    }
+    if newly > locked_balance.0 { newly = locked_balance.0.clone(); }
+    newly
+}
+
+/// Pindahkan `amount` dari locked ke available (amount <= locked, dijamin vest_delta)
+pub fn release(locked: &mut Nat, available: &mut Nat, amount: &BigUint) {
+    *locked = Nat(&locked.0 - amount);
+    *available = Nat(&available.0 + amount);
+}
+
+// -------- Status --------
+
+/// Periode sudah fully vested (budget pindah ke periode berikut / Completed)
+pub fn fully_vested(deposited: &Nat, unlocked: &Nat) -> bool {
+    unlocked.0 >= deposited.0
+}
+
+/// Hasil akhir tabungan bertarget: tercapai kapan saja, gagal hanya bila window berakhir sebelum target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Open,
+    Reached,
+    Missed,
+}
+
+pub fn outcome(end_ns: u64, now: u64, saved: &Nat, target: &Nat) -> Outcome {
+    if saved.0 >= target.0 {
+        Outcome::Reached
+    } else if now >= end_ns {
+        Outcome::Missed
+    } else {
+        Outcome::Open
+    }
+}
+
+/// Jumlah yang seharusnya sudah tersimpan pada pace linear agar `target` tercapai tepat di akhir window
+pub fn expected_by(target: &Nat, elapsed: u64, duration: u64) -> BigUint {
+    vested_amount(&target.0, &vested_ratio(&VestingSchedule::Linear, 0, elapsed, duration))
+}
+
+/// Tertinggal dari pace linear lebih dari `tolerance_bps` (basis point dari target)
+pub fn behind_pace(saved: &Nat, target: &Nat, elapsed: u64, duration: u64, tolerance_bps: u32) -> bool {
+    let slack = &target.0 * tolerance_bps / 10_000u32;
+    &saved.0 + slack < expected_by(target, elapsed, duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // PRNG kecil (xorshift64*) supaya property test deterministik tanpa dependency tambahan
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+        fn below(&mut self, n: u64) -> u64 { self.next() % n.max(1) }
+    }
+
+    const CASES: u64 = 500;
+
+    fn nat(v: u64) -> Nat { Nat::from(v) }
+
+    fn random_schedule(rng: &mut Rng, start: u64, end: u64, amount: u64) -> VestingSchedule {
+        let duration = end - start;
+        match rng.below(8) {
+            0 => VestingSchedule::Linear,
+            1 => VestingSchedule::Daily,
+            2 => VestingSchedule::Weekly,
+            3 => VestingSchedule::Step { every_ns: 1 + rng.below(duration) },
+            4 => VestingSchedule::CliffLinear { cliff_ns: rng.below(duration) },
+            5 => VestingSchedule::FrontLoaded,
+            6 => VestingSchedule::BackLoaded,
+            _ => {
+                let n = 1 + rng.below(8.min(amount));
+                let mut rest = amount;
+                let tranches = (0..n).map(|i| {
+                    let part = if i + 1 == n { rest } else { 1 + rng.below(rest - (n - i - 1)) };
+                    rest -= part;
+                    VestingTranche { at_ns: start + rng.below(duration + 1), amount: nat(part) }
+                }).collect();
+                VestingSchedule::Tranches { tranches }
+            }
+        }
+    }
+
+    // Bucket simulasi seperti satu envelope/budget: deposit sekali, lalu vest dan withdraw bergantian
+    struct Bucket { deposited: Nat, unlocked: Nat, locked: Nat, available: Nat, withdrawn: Nat }
+
+    impl Bucket {
+        fn new(amount: u64) -> Self {
+            Bucket { deposited: nat(amount), unlocked: nat(0), locked: nat(amount), available: nat(0), withdrawn: nat(0) }
+        }
+        fn vest(&mut self, ratio: &Ratio, cap: Option<&BigUint>) -> BigUint {
+            let n = vest_delta(&self.deposited, &self.unlocked, &self.locked, ratio, cap);
+            self.unlocked = Nat(&self.unlocked.0 + &n);
+            release(&mut self.locked, &mut self.available, &n);
+            n
+        }
+        fn withdraw(&mut self, amount: &BigUint) {
+            self.available = Nat(&self.available.0 - amount);
+            self.withdrawn = Nat(&self.withdrawn.0 + amount);
+        }
+        fn assert_conserved(&self) {
+            assert_eq!(&self.locked.0 + &self.available.0 + &self.withdrawn.0, self.deposited.0);
+            assert_eq!(&self.unlocked.0, &(&self.available.0 + &self.withdrawn.0));
+        }
+    }
+
+    #[test]
+    fn conservation_and_no_double_unlock() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..CASES {
+            let start = rng.below(1 << 40);
+            let end = start + 1 + rng.below(60 * NS_PER_DAY);
+            let amount = 1 + rng.below(1 << 50);
+            let schedule = random_schedule(&mut rng, start, end, amount);
+            let mut b = Bucket::new(amount);
+            let mut now = start.saturating_sub(rng.below(NS_PER_DAY));
+            while now <= end + NS_PER_DAY {
+                let (el, dur) = elapsed(start, end, now);
+                let ratio = vested_ratio(&schedule, start, el, dur);
+                let cap = if rng.below(3) == 0 { Some(BigUint::from(rng.below(amount + 1))) } else { None };
+                b.vest(&ratio, cap.as_ref());
+                b.assert_conserved();
+                // Vest ulang di waktu yang sama tanpa cap tidak boleh melebihi target
+                b.vest(&ratio, None);
+                assert!(b.unlocked.0 <= vested_amount(&b.deposited.0, &ratio));
+                assert_eq!(b.vest(&ratio, None), BigUint::from(0u32));
+                if b.available.0 > BigUint::from(0u32) && rng.below(2) == 0 {
+                    let w = BigUint::from(rng.below(u64::try_from(&b.available.0).unwrap_or(u64::MAX)) + 1);
+                    b.withdraw(&w);
+                }
+                b.assert_conserved();
+                now += 1 + rng.below((end - start) / 4 + 1);
+            }
+            // Setelah window berakhir semuanya unlocked
+            let (el, dur) = elapsed(start, end, end);
+            b.vest(&vested_ratio(&schedule, start, el, dur), None);
+            assert_eq!(b.locked.0, BigUint::from(0u32));
+            assert_eq!(b.unlocked.0, b.deposited.0);
+            b.assert_conserved();
+        }
+    }
+
+    #[test]
+    fn vested_amount_is_monotonic() {
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+        for _ in 0..CASES {
+            let start = rng.below(1 << 40);
+            let end = start + 1 + rng.below(60 * NS_PER_DAY);
+            let amount = 1 + rng.below(1 << 50);
+            let deposited = BigUint::from(amount);
+            let schedule = random_schedule(&mut rng, start, end, amount);
+            let mut prev = BigUint::from(0u32);
+            let mut now = start.saturating_sub(NS_PER_DAY);
+            while now <= end + NS_PER_DAY {
+                let (el, dur) = elapsed(start, end, now);
+                let v = vested_amount(&deposited, &vested_ratio(&schedule, start, el, dur));
+                assert!(v >= prev, "{:?}: vested turun di {}", schedule, now);
+                assert!(v <= deposited);
+                prev = v;
+                now += 1 + rng.below((end - start) / 8 + 1);
+            }
+            let (el, dur) = elapsed(start, end, end);
+            assert_eq!(vested_amount(&deposited, &vested_ratio(&schedule, start, el, dur)), deposited);
+        }
+    }
+
+    #[test]
+    fn unlock_points_match_curve() {
+        let mut rng = Rng(0xA076_1D64_78BD_642F);
+        for _ in 0..CASES {
+            let start = rng.below(1 << 40);
+            let end = start + 1 + rng.below(400 * NS_PER_DAY);
+            let amount = 1 + rng.below(1 << 50);
+            let schedule = random_schedule(&mut rng, start, end, amount);
+            let points = unlock_points(&schedule, start, end - start);
+            assert!(points.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(points.last().copied(), Some(end - start));
+            assert!(points.len() as u64 <= MAX_PREVIEW_POINTS_PER_PERIOD + 2 || matches!(schedule, VestingSchedule::Tranches { .. }));
+        }
+    }
+
+    #[test]
+    fn goal_cliff_unlocks_only_at_end() {
+        let (start, end) = (1_000, 1_000 + 30 * NS_PER_DAY);
+        let schedule = cliff_at_end(start, end);
+        let locked = nat(5_000);
+        for now in [0, start, start + 1, end - 1] {
+            let (el, dur) = elapsed(start, end, now);
+            let r = vested_ratio(&schedule, start, el, dur);
+            assert_eq!(vest_delta(&locked, &nat(0), &locked, &r, None), BigUint::from(0u32));
+        }
+        let (el, dur) = elapsed(start, end, end);
+        let r = vested_ratio(&schedule, start, el, dur);
+        assert_eq!(vest_delta(&locked, &nat(0), &locked, &r, None), locked.0);
+    }
+
+    #[test]
+    fn status_transitions() {
+        let end = 200;
+        assert!(!fully_vested(&nat(10), &nat(9)));
+        assert!(fully_vested(&nat(10), &nat(10)));
+
+        let target = nat(10);
+        assert_eq!(outcome(end, 150, &nat(9), &target), Outcome::Open);
+        assert_eq!(outcome(end, 150, &nat(10), &target), Outcome::Reached);
+        assert_eq!(outcome(end, 200, &nat(9), &target), Outcome::Missed);
+        assert_eq!(outcome(end, 250, &nat(11), &target), Outcome::Reached);
+    }
+
+    #[test]
+    fn linear_pace() {
+        let target = nat(1_000);
+        assert_eq!(expected_by(&target, 0, 100), BigUint::from(0u32));
+        assert_eq!(expected_by(&target, 25, 100), BigUint::from(250u32));
+        assert_eq!(expected_by(&target, 150, 100), BigUint::from(1_000u32));
+
+        // 5% toleransi = 50: tertinggal baru terdeteksi di bawah 450 saat pace 500
+        assert!(!behind_pace(&nat(450), &target, 50, 100, 500));
+        assert!(behind_pace(&nat(449), &target, 50, 100, 500));
+        assert!(!behind_pace(&nat(0), &target, 0, 100, 0));
+        assert!(!behind_pace(&nat(1_000), &target, 100, 100, 0));
+    }
+
+    #[test]
+    fn validate_rejects_bad_schedules() {
+        let (start, end) = (0, 10 * NS_PER_DAY);
+        let amount = nat(100);
+        assert!(validate(&VestingSchedule::Step { every_ns: 0 }, start, end, &amount).is_err());
+        assert!(validate(&VestingSchedule::CliffLinear { cliff_ns: end }, start, end, &amount).is_err());
+        let tranches = |a: u64, b: u64| VestingSchedule::Tranches { tranches: vec![
+            VestingTranche { at_ns: start, amount: nat(a) },
+            VestingTranche { at_ns: end, amount: nat(b) },
+        ] };
+        assert!(validate(&tranches(40, 60), start, end, &amount).is_ok());
+        assert!(validate(&tranches(40, 50), start, end, &amount).is_err());
+        assert!(validate(&tranches(0, 100), start, end, &amount).is_err());
+    }
+}
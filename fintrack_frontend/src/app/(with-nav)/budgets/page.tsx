@@ -9,7 +9,7 @@ import { Tabs, TabsContent, TabsList, TabsTrigger } from "@/components/ui/tabs"
 import { AlertTriangle, CalendarClock, CheckCircle, Coins, RefreshCw, ShieldCheck, Sparkles, Wallet } from "lucide-react"
 import { useToast } from "@/components/ui/use-toast"
 import { Toaster } from "@/components/ui/use-toast"
-import { budgetService, type AssetKind, bitcoinService, ethereumService, balanceService, currencyService } from "@/services/backend"
+import { budgetService, type AssetKind, type BudgetEnvelopeInput, bitcoinService, ethereumService, balanceService, currencyService } from "@/services/backend"
 
 function toNsFromDatetimeLocal(value: string): bigint {
   // value example: 2025-09-08T12:34
@@ -112,6 +112,16 @@ export default function BudgetsPage() {
   const [page, setPage] = useState<number>(0)
   const pageSize = 2
   const [withdrawInput, setWithdrawInput] = useState<Record<string, string>>({})
+  const [analytics, setAnalytics] = useState<any | null>(null)
+  const [analyticsBudget, setAnalyticsBudget] = useState<any | null>(null)
+  const [categories, setCategories] = useState<any[]>([])
+  const [categoryReport, setCategoryReport] = useState<any[]>([])
+  const [newCategoryName, setNewCategoryName] = useState<string>("")
+  const [newCategoryParent, setNewCategoryParent] = useState<string>("")
+  // Envelope (opsional): persentase dari amount lock, total harus 100%
+  const [vestingKind, setVestingKind] = useState<"Linear" | "Daily" | "Weekly" | "CliffLinear" | "FrontLoaded" | "BackLoaded">("Linear")
+  const [cliffDays, setCliffDays] = useState<string>("")
+  const [envelopeRows, setEnvelopeRows] = useState<{ name: string; categoryId: string; percent: string }[]>([])
 
   useEffect(() => {
     // Default canister IDs from env to help local dev
@@ -223,6 +233,13 @@ export default function BudgetsPage() {
     try {
       const startNs = toNsFromDatetimeLocal(periodStart)
       const endNs = toNsFromDatetimeLocal(periodEnd)
+      const envelopes: BudgetEnvelopeInput[] | undefined = envelopeRows.length
+        ? envelopeRows.map((r) => ({
+            name: r.name,
+            categoryId: r.categoryId || undefined,
+            allocation: { kind: "Percent" as const, bps: Math.round(parseFloat(r.percent || "0") * 100) },
+          }))
+        : undefined
       const res = await budgetService.createAndLock({
         assetCanister,
         assetKind,
@@ -230,6 +247,10 @@ export default function BudgetsPage() {
         amountToLock: amountBaseUnit,
         periodStartNs: startNs,
         periodEndNs: endNs,
+        envelopes,
+        vesting: vestingKind === "CliffLinear"
+          ? { kind: "CliffLinear", cliffNs: BigInt(Math.round(parseFloat(cliffDays || "0") * 86400)) * BigInt(1_000_000_000) }
+          : { kind: vestingKind },
       })
       if (!res.success) {
         toast({
@@ -244,7 +265,9 @@ export default function BudgetsPage() {
         description: "Budget created.",
         variant: "success"
       })
+      setEnvelopeRows([])
       await loadBudgets()
+      await loadCategories()
     } catch (e: any) {
       toast({
         title: "Error",
@@ -344,6 +367,91 @@ export default function BudgetsPage() {
     }
   }
 
+  async function onWithdrawEnvelope(b: any, env: any) {
+    setWorkingId(b.id)
+    setError(null)
+    // Refresh dulu agar available envelope up to date, lalu tarik semuanya
+    const r = await budgetService.refreshAccrualStep(b.id)
+    if (!r.success) {
+      setError(r.error)
+      setWorkingId(null)
+      return
+    }
+    const fresh = (r.data.envelopes?.[0] || []).find((e: any) => e.id === env.id)
+    const available = BigInt(fresh?.available_to_withdraw ?? 0)
+    if (available <= BigInt(0)) {
+      setError("No balance available for withdrawal.")
+      setWorkingId(null)
+      return
+    }
+    const w = await budgetService.withdrawEnvelope(b.id, env.id, available)
+    if (!w.success) {
+      toast({
+        title: "Error",
+        description: w.error,
+        variant: "destructive"
+      })
+    } else {
+      toast({
+        title: "Success!",
+        description: `Withdraw ${env.name} tx height: ${w.data}`,
+        variant: "success"
+      })
+    }
+    setWorkingId(null)
+    await loadBudgets()
+    await loadCategories()
+  }
+
+  async function loadCategories() {
+    if (!assetCanister) return
+    const [c, r] = await Promise.all([
+      budgetService.listCategories(),
+      budgetService.categoryReport(assetCanister),
+    ])
+    if (c.success) setCategories(c.data)
+    if (r.success) setCategoryReport(r.data)
+  }
+
+  async function onCreateCategory() {
+    setError(null)
+    const r = await budgetService.createCategory(newCategoryName, newCategoryParent || undefined)
+    if (!r.success) return setError(r.error)
+    setNewCategoryName("")
+    await loadCategories()
+  }
+
+  async function onDeleteCategory(id: string) {
+    setError(null)
+    const r = await budgetService.deleteCategory(id)
+    if (!r.success) return setError(r.error)
+    await loadCategories()
+  }
+
+  // Kedalaman kategori untuk indentasi laporan
+  function categoryDepth(id: string): number {
+    let depth = 0
+    let cur = categories.find((c) => c.id === id)
+    while (cur && cur.parent_id && cur.parent_id.length && depth < 5) {
+      depth++
+      const pid = cur.parent_id[0]
+      cur = categories.find((c) => c.id === pid)
+    }
+    return depth
+  }
+
+  async function onViewAnalytics(b: any) {
+    setWorkingId(b.id)
+    setError(null)
+    const r = await budgetService.getAnalytics(b.id)
+    if (!r.success) setError(r.error)
+    else {
+      setAnalytics(r.data)
+      setAnalyticsBudget(b)
+    }
+    setWorkingId(null)
+  }
+
   async function onViewEvents(id: string) {
     setWorkingId(id)
     setError(null)
@@ -359,6 +467,7 @@ export default function BudgetsPage() {
 
   useEffect(() => {
     loadBudgets()
+    loadCategories()
     loadBalancesAndRates()
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [assetCanister])
@@ -409,7 +518,7 @@ export default function BudgetsPage() {
         <div className="flex flex-col sm:flex-row sm:items-center justify-between gap-4">
           <div>
             <h1 className="text-3xl font-bold text-white">Budgets</h1>
-            <p className="text-gray-300 mt-1">Linear vesting using ckAssets</p>
+            <p className="text-gray-300 mt-1">Scheduled vesting using ckAssets</p>
           </div>
         </div>
 
@@ -484,6 +593,47 @@ export default function BudgetsPage() {
                   </div>
                 </div>
 
+                <div className="grid grid-cols-1 md:grid-cols-2 gap-4">
+                  <div>
+                    <Label className="text-slate-300">Vesting</Label>
+                    <select value={vestingKind} onChange={(e) => setVestingKind(e.target.value as any)} className="mt-2 h-9 w-full text-sm rounded-md bg-slate-800/50 border border-slate-600 text-white px-2">
+                      <option value="Linear">Linear</option>
+                      <option value="Daily">Daily steps</option>
+                      <option value="Weekly">Weekly steps</option>
+                      <option value="CliffLinear">Cliff then linear</option>
+                      <option value="FrontLoaded">Front-loaded</option>
+                      <option value="BackLoaded">Back-loaded</option>
+                    </select>
+                  </div>
+                  {vestingKind === "CliffLinear" && (
+                    <div>
+                      <Label className="text-slate-300">Cliff (days)</Label>
+                      <Input value={cliffDays} onChange={(e) => setCliffDays(e.target.value.replace(/[^0-9.]/g, ""))} placeholder="e.g. 7" className="bg-slate-800/50 border-slate-600 text-white mt-2" />
+                    </div>
+                  )}
+                </div>
+
+                <div>
+                  <div className="flex items-center justify-between">
+                    <Label className="text-slate-300">Envelopes (optional)</Label>
+                    <Button size="sm" onClick={() => setEnvelopeRows((rows) => [...rows, { name: "", categoryId: "", percent: "" }])} className="h-7 px-2 text-xs bg-slate-700 hover:bg-slate-600">Add envelope</Button>
+                  </div>
+                  {envelopeRows.map((row, idx) => (
+                    <div key={idx} className="grid grid-cols-12 gap-2 mt-2">
+                      <Input value={row.name} onChange={(e) => setEnvelopeRows((rows) => rows.map((r, i) => i === idx ? { ...r, name: e.target.value } : r))} placeholder="Name" className="col-span-4 h-8 text-xs bg-slate-800/50 border-slate-600 text-white" />
+                      <select value={row.categoryId} onChange={(e) => setEnvelopeRows((rows) => rows.map((r, i) => i === idx ? { ...r, categoryId: e.target.value } : r))} className="col-span-4 h-8 text-xs rounded-md bg-slate-800/50 border border-slate-600 text-white px-2">
+                        <option value="">No category</option>
+                        {categories.map((c) => (<option key={c.id} value={c.id}>{"\u00a0".repeat(categoryDepth(c.id) * 2)}{c.name}</option>))}
+                      </select>
+                      <Input value={row.percent} onChange={(e) => setEnvelopeRows((rows) => rows.map((r, i) => i === idx ? { ...r, percent: e.target.value.replace(/[^0-9.]/g, "") } : r))} placeholder="%" className="col-span-3 h-8 text-xs bg-slate-800/50 border-slate-600 text-white font-mono" />
+                      <Button size="sm" onClick={() => setEnvelopeRows((rows) => rows.filter((_, i) => i !== idx))} className="col-span-1 h-8 px-2 text-xs bg-slate-700 hover:bg-slate-600">×</Button>
+                    </div>
+                  ))}
+                  {envelopeRows.length > 0 && (
+                    <div className="text-xs text-slate-400 mt-1">Total: {envelopeRows.reduce((acc, r) => acc + (parseFloat(r.percent || "0") || 0), 0)}% (must be 100%)</div>
+                  )}
+                </div>
+
                 <div className="flex flex-wrap gap-3 pt-2">
                   <Button onClick={onPreviewRequirements} disabled={loadingPreview || !assetCanister || !amountBaseUnit} className="bg-purple-600 hover:bg-purple-700 glow-purple">
                     {loadingPreview ? (<><RefreshCw className="h-4 w-4 mr-2 animate-spin" />Preview...</>) : (<><Sparkles className="h-4 w-4 mr-2" />Preview Allowance & Fee</>)}
@@ -528,6 +678,43 @@ export default function BudgetsPage() {
                 )}
               </div>
             </Card>
+
+            <Card className="p-6 bg-slate-900/80 border-purple-500/20 glow-purple">
+              <div className="flex items-center justify-between mb-4">
+                <h3 className="font-semibold text-white text-lg">Categories</h3>
+                <Button onClick={loadCategories} className="bg-purple-600 hover:bg-purple-700 glow-purple"><RefreshCw className="h-4 w-4 mr-2" />Refresh</Button>
+              </div>
+              <div className="grid grid-cols-12 gap-2">
+                <Input value={newCategoryName} onChange={(e) => setNewCategoryName(e.target.value)} placeholder="Example: Groceries" className="col-span-5 bg-slate-800/50 border-slate-600 text-white" />
+                <select value={newCategoryParent} onChange={(e) => setNewCategoryParent(e.target.value)} className="col-span-4 rounded-md bg-slate-800/50 border border-slate-600 text-white text-sm px-2">
+                  <option value="">No parent</option>
+                  {categories.map((c) => (<option key={c.id} value={c.id}>{"\u00a0".repeat(categoryDepth(c.id) * 2)}{c.name}</option>))}
+                </select>
+                <Button onClick={onCreateCategory} disabled={!newCategoryName.trim()} className="col-span-3 bg-indigo-600 hover:bg-indigo-700">Add</Button>
+              </div>
+              <div className="space-y-2 mt-4">
+                {categoryReport.length === 0 && <div className="text-slate-400 text-sm">No categories yet</div>}
+                {categoryReport.map((r) => {
+                  const dec = decimals
+                  const total = r.total
+                  const usdOf = (v: bigint) => rates ? formatUsd(dec === 8 ? (Number(v)/1e8) * (rates.btc_to_usd||0) : (Number(v)/1e18) * (rates.eth_to_usd||0)) : ""
+                  return (
+                    <div key={r.category.id} className="p-3 bg-slate-800/50 rounded-lg border border-slate-700" style={{ marginLeft: categoryDepth(r.category.id) * 16 }}>
+                      <div className="flex items-center justify-between">
+                        <div className="text-white font-medium">{r.category.name} <span className="text-xs text-slate-400">({String(total.envelopes)} envelopes)</span></div>
+                        <Button size="sm" onClick={() => onDeleteCategory(r.category.id)} className="h-7 px-2 text-xs bg-slate-700 hover:bg-slate-600">Delete</Button>
+                      </div>
+                      <div className="grid grid-cols-2 gap-1 text-xs text-slate-400 mt-1">
+                        <div>funded: <span className="font-mono text-slate-300">{toDecimalStringNat(BigInt(total.funded_total), dec)}</span> <span className="text-slate-500">{usdOf(BigInt(total.funded_total))}</span></div>
+                        <div>withdrawn: <span className="font-mono text-slate-300">{toDecimalStringNat(BigInt(total.withdrawn_total), dec)}</span> <span className="text-slate-500">{usdOf(BigInt(total.withdrawn_total))}</span></div>
+                        <div>locked: <span className="font-mono text-slate-300">{toDecimalStringNat(BigInt(total.locked_balance), dec)}</span> <span className="text-slate-500">{usdOf(BigInt(total.locked_balance))}</span></div>
+                        <div>available: <span className="font-mono text-slate-300">{toDecimalStringNat(BigInt(total.available_to_withdraw), dec)}</span> <span className="text-slate-500">{usdOf(BigInt(total.available_to_withdraw))}</span></div>
+                      </div>
+                    </div>
+                  )
+                })}
+              </div>
+            </Card>
           </div>
 
           <div className="space-y-6">
@@ -573,6 +760,12 @@ export default function BudgetsPage() {
                           })()}
                         </div>
                         <div className="text-xs text-slate-500 flex items-center gap-1 mt-1"><CalendarClock className="h-3 w-3" /> {fromNsToDatetimeLocal(BigInt(b.period_start_ns))} → {fromNsToDatetimeLocal(BigInt(b.period_end_ns))}</div>
+                        {(b.envelopes?.[0] || []).map((env: any) => (
+                          <div key={env.id} className="text-xs text-slate-400 flex items-center justify-between gap-2">
+                            <span>{env.name}{env.category_id?.length ? <span className="text-slate-500"> · {categories.find((c) => c.id === env.category_id[0])?.name ?? "?"}</span> : null}: <span className="font-mono">{toDecimalStringNat(BigInt(env.available_to_withdraw), Number(b.decimals))}</span></span>
+                            <Button size="sm" onClick={() => onWithdrawEnvelope(b, env)} disabled={workingId === b.id} className="h-6 px-2 text-[10px] bg-emerald-700 hover:bg-emerald-600">Withdraw</Button>
+                          </div>
+                        ))}
                       </div>
                       <div className="col-span-2 mb-2">
                         {previewMap[b.id] && (
@@ -613,8 +806,9 @@ export default function BudgetsPage() {
                         </div>
                         <div className="flex flex-wrap items-center gap-2 justify-start md:justify-end">
                           <Button size="sm" onClick={() => onRefreshStep(b.id)} disabled={workingId === b.id} className="h-7 px-2 text-xs bg-indigo-600 hover:bg-indigo-700"><RefreshCw className="h-3.5 w-3.5 mr-1" />Refresh</Button>
-                          <Button size="sm" onClick={() => onWithdrawFlow(b)} disabled={workingId === b.id} className="h-7 px-2 text-xs bg-emerald-600 hover:bg-emerald-700"><Wallet className="h-3.5 w-3.5 mr-1" />Withdraw</Button>
+                          <Button size="sm" onClick={() => onWithdrawFlow(b)} disabled={workingId === b.id || !!b.envelopes?.[0]} className="h-7 px-2 text-xs bg-emerald-600 hover:bg-emerald-700"><Wallet className="h-3.5 w-3.5 mr-1" />Withdraw</Button>
                           <Button size="sm" onClick={() => onViewEvents(b.id)} disabled={workingId === b.id} className="h-7 px-2 text-xs bg-purple-600 hover:bg-purple-700">Events</Button>
+                          <Button size="sm" onClick={() => onViewAnalytics(b)} disabled={workingId === b.id} className="h-7 px-2 text-xs bg-cyan-700 hover:bg-cyan-600">Analytics</Button>
                         </div>
                       </div>
                     </div>
@@ -632,6 +826,48 @@ export default function BudgetsPage() {
               )}
             </Card>
 
+            {analytics && analyticsBudget && (() => {
+              const dec = Number(analyticsBudget.decimals)
+              const sym = dec === 8 ? "ckBTC" : "ckETH"
+              const fmt = (v: any) => `${toDecimalStringNat(BigInt(v), dec)} ${sym}`
+              const runway = analytics.runway_days?.length ? `${String(analytics.runway_days[0])} days` : "-"
+              const change = analytics.change_vs_previous_bps?.length ? `${(Number(analytics.change_vs_previous_bps[0]) / 100).toFixed(1)}%` : "-"
+              return (
+                <Card className="p-6 bg-slate-900/80 border-purple-500/20 glow-purple">
+                  <div className="flex items-center justify-between mb-4">
+                    <h3 className="font-semibold text-white text-lg">Analytics · {analyticsBudget.name}</h3>
+                    <Button size="sm" onClick={() => { setAnalytics(null); setAnalyticsBudget(null) }} className="bg-slate-700 hover:bg-slate-600">Close</Button>
+                  </div>
+                  <div className="grid grid-cols-2 gap-2 text-xs text-slate-400">
+                    <div>Period #{String(analytics.period_index)}</div>
+                    <div>{fromNsToDatetimeLocal(BigInt(analytics.period_start_ns))} → {fromNsToDatetimeLocal(BigInt(analytics.period_end_ns))}</div>
+                    <div>Locked: <span className="font-mono text-slate-300">{fmt(analytics.period_locked)}</span></div>
+                    <div>Vested: <span className="font-mono text-slate-300">{fmt(analytics.vested)}</span></div>
+                    <div>Used: <span className="font-mono text-slate-300">{fmt(analytics.used)}</span></div>
+                    <div>Remaining: <span className="font-mono text-slate-300">{fmt(analytics.remaining_funds)}</span></div>
+                    <div>Burn/day: <span className="font-mono text-slate-300">{fmt(analytics.burn_rate_per_day)}</span></div>
+                    <div>Vest/day: <span className="font-mono text-slate-300">{fmt(analytics.vest_rate_per_day)}</span></div>
+                    <div>Projected end balance: <span className="font-mono text-slate-300">{fmt(analytics.projected_end_balance)}</span></div>
+                    <div>Runway: <span className="font-mono text-slate-300">{runway}</span></div>
+                    <div>vs previous period: <span className="font-mono text-slate-300">{change}</span></div>
+                    <div>Ledger sync: <span className="text-slate-300">{analytics.ledger?.length ? fromNsToDatetimeLocal(BigInt(analytics.ledger[0].synced_at_ns)) : "unavailable"}</span></div>
+                  </div>
+                  {analytics.warnings.length > 0 && (
+                    <div className="mt-3 space-y-1">
+                      {analytics.warnings.map((w: any, idx: number) => (
+                        <div key={idx} className="p-2 bg-amber-500/10 border border-amber-500/20 rounded text-amber-300 text-xs flex items-center">
+                          <AlertTriangle className="h-3.5 w-3.5 mr-2" />
+                          {w.ProjectedShortfall ? `Projected shortfall ${fmt(w.ProjectedShortfall.amount)}: runway ${String(w.ProjectedShortfall.runway_days)} of ${String(w.ProjectedShortfall.remaining_days)} remaining days`
+                            : w.AbovePreviousPeriod ? `Spending ${(Number(w.AbovePreviousPeriod.change_bps) / 100).toFixed(1)}% above the previous period`
+                            : "Current period is not funded yet"}
+                        </div>
+                      ))}
+                    </div>
+                  )}
+                </Card>
+              )
+            })()}
+
             {events && (
               <Card className="p-6 bg-slate-900/80 border-purple-500/20 glow-purple">
                 <div className="flex items-center justify-between mb-4">
@@ -656,6 +892,11 @@ export default function BudgetsPage() {
                           <span className="text-xs text-slate-400">{fromNsToDatetimeLocal(BigInt(e.at_time_ns))}</span>
                         </div>
                         <div className="text-slate-300 text-xs mt-1">Amount: <span className="font-mono text-white">{hrAmt}</span>{usd!==undefined? <span className="ml-2 text-slate-500">({formatUsdPrecise(usd)})</span> : null}</div>
+                        {e.counterparty && Array.isArray(e.counterparty) && e.counterparty.length ? (
+                          <div className="text-slate-500 text-xs mt-1">Paid to: <span className="font-mono">{String(e.counterparty[0].owner)}</span>
+                            {e.category_id && e.category_id.length ? <span className="ml-2">· {categories.find((c) => c.id === e.category_id[0])?.name ?? e.category_id[0]}</span> : null}
+                          </div>
+                        ) : null}
                         {e.note && Array.isArray(e.note) && e.note.length ? (
                           <div className="text-slate-500 text-xs mt-1">Note: <span className="font-mono">{String(e.note[0])}</span></div>
                         ) : null}
@@ -15,23 +15,22 @@ import { goalsService, balanceService, currencyService, budgetService } from "@/
 import { formatUsd, formatUsdPrecise, toDecimalStringNat, toDecimalStringBigInt, parseDecimalToBaseUnits } from "@/lib/utils"
 import { Principal } from "@dfinity/principal"
 
-// Helper function to check goal status based on time and target
-const getGoalStatus = (goal: GoalInfo): { status: string; isExpired: boolean; isCompleted: boolean } => {
+// Status mengikuti state machine backend (goals_refresh / timer); Completed = status lama sebelum migrasi
+const getGoalStatus = (goal: GoalInfo): { status: string; isExpired: boolean; isCompleted: boolean; acceptsFunds: boolean } => {
   const now = Date.now() * 1_000_000 // Convert to nanoseconds
   const isExpired = now >= Number(goal.end_ns)
-  const isTargetReached = goal.locked_balance >= goal.amount_to_lock
-  
-  if (isExpired) {
-    if (isTargetReached) {
-      return { status: 'Completed', isExpired: true, isCompleted: true }
-    } else {
-      return { status: 'Failed', isExpired: true, isCompleted: false }
-    }
-  } else if (isTargetReached) {
-    return { status: 'Completed', isExpired: false, isCompleted: true }
-  } else {
-    return { status: 'Active', isExpired: false, isCompleted: false }
+  const s = goal.status
+
+  if ('TargetReached' in s || 'Completed' in s) {
+    return { status: 'Target Reached', isExpired, isCompleted: true, acceptsFunds: !isExpired }
+  } else if ('Unlocked' in s) {
+    return { status: 'Unlocked', isExpired: true, isCompleted: true, acceptsFunds: false }
+  } else if ('Failed' in s) {
+    return { status: 'Failed', isExpired: true, isCompleted: false, acceptsFunds: false }
+  } else if ('Archived' in s) {
+    return { status: 'Archived', isExpired, isCompleted: false, acceptsFunds: false }
   }
+  return { status: 'Active', isExpired, isCompleted: false, acceptsFunds: !isExpired }
 }
 
 type GoalInfo = {
@@ -40,7 +39,7 @@ type GoalInfo = {
   amount_to_lock: bigint
   locked_balance: bigint
   available_to_withdraw: bigint
-  status: { Active?: null; Completed?: null; Failed?: null; Archived?: null }
+  status: { Active?: null; TargetReached?: null; Unlocked?: null; Failed?: null; Archived?: null; Completed?: null }
   start_ns: bigint
   end_ns: bigint
   created_at_ns: bigint
@@ -126,22 +125,8 @@ export default function GoalsPage() {
       ])
 
       if (goalsRes.success) {
+        // Unlock saat jatuh tempo dijalankan timer backend, tidak perlu refresh per goal
         setGoals(goalsRes.data)
-        
-        // Refresh all goals to unlock expired funds
-        for (const goal of goalsRes.data) {
-          try {
-            await goalsService.refresh(goal.id)
-          } catch (err) {
-            console.log('Failed to refresh goal:', goal.id, err)
-          }
-        }
-        
-        // Reload goals after refresh
-        const refreshedGoalsResult = await goalsService.list()
-        if (refreshedGoalsResult.success) {
-          setGoals(refreshedGoalsResult.data)
-        }
       } else {
         setError(goalsRes.error)
       }
@@ -752,7 +737,8 @@ export default function GoalsPage() {
                             (() => {
                               const { status } = getGoalStatus(goal)
                               return status === 'Active' ? 'default' :
-                                     status === 'Completed' ? 'secondary' :
+                                     status === 'Target Reached' || status === 'Unlocked' ? 'secondary' :
+                                     status === 'Archived' ? 'outline' :
                                      'destructive'
                             })()
                           }>
@@ -781,10 +767,7 @@ export default function GoalsPage() {
                         <Button
                           variant="outline"
                           size="sm"
-                          disabled={(() => {
-                            const { isExpired, isCompleted } = getGoalStatus(goal)
-                            return isExpired || isCompleted
-                          })()}
+                          disabled={!getGoalStatus(goal).acceptsFunds}
                           onClick={() => {
                             setSelectedGoal(goal)
                             loadGoalDetails(goal.id)
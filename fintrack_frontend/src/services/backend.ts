@@ -8,6 +8,29 @@ import type { _SERVICE } from "../../../src/declarations/fintrack_backend/fintra
 
 type Result<T> = { success: true; data: T } | { success: false; error: string }
 
+// Backend error: text (endpoint lama) atau variant FintrackError ({ NotFound: { kind, id } }, { RateLimited: null }, ...)
+export function formatBackendError(err: unknown): string {
+  if (typeof err === "string") return err
+  if (!err || typeof err !== "object") return "Unknown backend error"
+  const [kind, payload] = Object.entries(err as Record<string, any>)[0] ?? []
+  switch (kind) {
+    case "Unauthorized": return `Unauthorized: ${payload.reason}`
+    case "NotFound": return `${payload.kind} not found: ${payload.id}`
+    case "InvalidInput": return `Invalid ${payload.field}: ${payload.reason}`
+    case "InvalidState": return payload.reason
+    case "InsufficientFunds": return `Insufficient funds (needed ${payload.needed}, available ${payload.available})`
+    case "LedgerError": return `Ledger error: ${payload.message}`
+    case "RpcError": return `RPC error: ${payload.message}`
+    case "RpcInconsistent": return "RPC providers returned inconsistent responses, please retry"
+    case "InvalidAddress": return `Invalid address ${payload.address}: ${payload.reason}`
+    case "RateLimited": return "Rate limited by upstream provider, please retry later"
+    case "HttpOutcallFailed": return `HTTP outcall failed: ${payload.message}`
+    case "Internal": return payload.message
+    case "OperationInProgress": return `Another operation on this ${payload.kind} is still in progress, please retry`
+    default: return kind ?? "Unknown backend error"
+  }
+}
+
 let authClient: AuthClient | null = null
 let actor: ActorSubclass<_SERVICE> | null = null
 
@@ -132,7 +155,7 @@ export const transactionService = {
       // Fix: pass principal and opt nat32 correctly
       const res = await a.get_transaction_history(principal, [], [])
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to fetch transactions" }
     }
@@ -171,7 +194,7 @@ export const networkService = {
           return { success: true, data: { eth_finalized: parsed.eth_finalized_block_height ?? null, last_scraped: parsed.last_scraped_block_number ?? null } }
         } else {
           // Fallthrough to force-update variant
-          throw new Error(res.Err)
+          throw new Error(formatBackendError(res.Err))
         }
       } catch (_primaryErr) {
         // Fallback: create a minimal actor with eth_get_minter_info as UPDATE explicitly
@@ -190,19 +213,19 @@ export const networkService = {
 
           const { IDL } = await import("@dfinity/candid")
           const idlFactory = ({ IDL: I }: any) => {
-            const Result = I.Variant({ Ok: I.Text, Err: I.Text })
+            const Result = I.Variant({ Ok: I.Text, Err: I.Reserved })
             return I.Service({
-              // update function returning Result<Text, Text>
+              // update function returning Result<Text, FintrackError> (Err tidak di-decode)
               eth_get_minter_info: I.Func([], [Result], []),
             })
           }
           const actor: any = await createExternalActor<any>(backendCanisterId, (idlFactory as any), identity)
-          const res2 = await actor.eth_get_minter_info() as { Ok?: string; Err?: string }
+          const res2 = await actor.eth_get_minter_info() as { Ok?: string; Err?: null }
           if (res2 && "Ok" in res2) {
             const parsed = JSON.parse(res2.Ok as string)
             return { success: true, data: { eth_finalized: parsed.eth_finalized_block_height ?? null, last_scraped: parsed.last_scraped_block_number ?? null } }
           }
-          return { success: false, error: (res2 as any)?.Err || "Failed to call eth_get_minter_info (update)" }
+          return { success: false, error: (res2 as any)?.Err ? formatBackendError((res2 as any).Err) : "Failed to call eth_get_minter_info (update)" }
         } catch (fallbackErr: any) {
           return { success: false, error: fallbackErr?.message || "Failed to get ETH network status" }
         }
@@ -226,7 +249,7 @@ export const networkService = {
           } 
         }
       }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get BTC network status" }
     }
@@ -243,7 +266,7 @@ export const bitcoinService = {
         : []
       const res = await a.btc_derive_address(arg)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to derive BTC address" }
     }
@@ -256,7 +279,7 @@ export const bitcoinService = {
       // Fix: pass opt principal and opt vec nat8 correctly
       const res = await a.btc_get_balance([principal], [])
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get BTC balance" }
     }
@@ -270,7 +293,7 @@ export const bitcoinService = {
       // Fix: pass opt principal and opt vec nat8 correctly
       const res = await a.btc_get_deposit_address([principal], [])
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get BTC deposit address" }
     }
@@ -284,7 +307,7 @@ export const bitcoinService = {
       const principal = client.getIdentity().getPrincipal()
       const res = await a.btc_refresh_balance([principal], [])
       if ("Ok" in res) return { success: true, data: null }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to refresh BTC balance" }
     }
@@ -296,7 +319,7 @@ export const bitcoinService = {
       const a = await ensureActor()
       const res = await a.btc_get_native_balance(address)
       if ("Ok" in res) return { success: true, data: BigInt(res.Ok) }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get native BTC balance" }
     }
@@ -308,7 +331,7 @@ export const bitcoinService = {
       const a = await ensureActor()
       const res = await a.btc_get_utxos_for_address(address)
       if ("Ok" in res) return { success: true, data: res.Ok as any[] }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get UTXOs" }
     }
@@ -319,6 +342,8 @@ export const bitcoinService = {
     destinationAddress: string,
     amountInSats: bigint,
     ownerPrincipalText?: string,
+    // Opt-in replace-by-fee so the payment can later be bumped or cancelled
+    replaceable?: boolean,
   ): Promise<Result<string>> => {
     try {
       const a = await ensureActor()
@@ -327,6 +352,7 @@ export const bitcoinService = {
         destination_address: destinationAddress,
         amount_in_satoshi: Number(amountInSats), // candid nat64 → JS number fits up to 2^53-1
         owner: ownerOpt,
+        replaceable: replaceable !== undefined ? [replaceable] : [],
       } as any
       const res = await a.btc_transfer(req)
       if ("Ok" in res) {
@@ -334,12 +360,44 @@ export const bitcoinService = {
         if (out.success && out.transaction_id?.length) return { success: true, data: out.transaction_id[0] }
         return { success: false, error: out.error?.[0] ?? "BTC transfer failed" }
       }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to transfer BTC" }
     }
   },
 
+  // Rebroadcast a replaceable BTC transfer with a higher fee rate (millisatoshi/vB); returns the new txid
+  bumpBtcFee: async (txid: string, newFeeRate: bigint): Promise<Result<string>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.btc_bump_fee(txid, newFeeRate)
+      if ("Ok" in res) {
+        const out = res.Ok
+        if (out.success && out.transaction_id?.length) return { success: true, data: out.transaction_id[0] }
+        return { success: false, error: out.error?.[0] ?? "Fee bump failed" }
+      }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to bump BTC fee" }
+    }
+  },
+
+  // Cancel a replaceable BTC transfer by sending its inputs back to the owner's address
+  cancelBtcTransfer: async (txid: string): Promise<Result<string>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.btc_cancel(txid)
+      if ("Ok" in res) {
+        const out = res.Ok
+        if (out.success && out.transaction_id?.length) return { success: true, data: out.transaction_id[0] }
+        return { success: false, error: out.error?.[0] ?? "BTC cancel failed" }
+      }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to cancel BTC transfer" }
+    }
+  },
+
   // Preview BTC transfer fee
   previewBtcFee: async (
     destinationAddress: string,
@@ -373,7 +431,7 @@ export const bitcoinService = {
           }
         }
       }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to preview BTC fee" }
     }
@@ -491,7 +549,7 @@ export const ethereumService = {
         : []
       const res = await a.evm_derive_address(arg)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to derive ETH address" }
     }
@@ -505,7 +563,7 @@ export const ethereumService = {
       // Fix: pass opt principal and opt vec nat8 correctly
       const res = await a.eth_get_balance([principal], [])
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get ETH balance" }
     }
@@ -520,7 +578,7 @@ export const ethereumService = {
         : []
       const res = await a.eth_get_native_balance(arg)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get native ETH balance" }
     }
@@ -553,7 +611,7 @@ export const ethereumService = {
         if (out.success && out.transaction_hash?.length) return { success: true, data: out.transaction_hash[0] }
         return { success: false, error: out.error?.[0] ?? "ETH transfer failed" }
       }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to transfer ETH" }
     }
@@ -571,7 +629,7 @@ export const ethereumService = {
         : []
       const res = await a.eth_get_transaction_count(ownerOpt, blockOpt)
       if ("Ok" in res) return { success: true, data: Number(res.Ok) }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get transaction count" }
     }
@@ -612,7 +670,7 @@ export const ethereumService = {
           }
         }
       }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to preview ETH fee" }
     }
@@ -624,7 +682,7 @@ export const ethereumService = {
       // Fix: eth_get_deposit_address only takes opt vec nat8 (no principal)
       const res = await a.eth_get_deposit_address([])
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get ETH deposit address" }
     }
@@ -635,7 +693,7 @@ export const ethereumService = {
       const a = await ensureActor()
       const res = await a.eth_estimate_withdrawal_fee()
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to estimate withdrawal fee" }
     }
@@ -748,7 +806,7 @@ export const balanceService = {
       const principal = client.getIdentity().getPrincipal()
       const res = await a.get_user_balances(principal)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get balances" }
     }
@@ -792,7 +850,7 @@ export const currencyService = {
       const a = await ensureActor()
       const res = await a.get_rates_summary()
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get rates" }
     }
@@ -814,7 +872,7 @@ export const marketChartService = {
       const a = await ensureActor()
       const res = await (a as any).get_market_chart(coinId, vsCurrency, days)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get market chart" }
     }
@@ -826,7 +884,7 @@ export const marketChartService = {
       const a = await ensureActor()
       const res = await (a as any).get_24h_change(coinId)
       if ("Ok" in res) return { success: true, data: res.Ok as number }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get 24h change" }
     }
@@ -841,7 +899,7 @@ export const marketChartService = {
       const a = await ensureActor()
       const res = await (a as any).get_historical_prices(coinId, vsCurrency, days)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get historical prices" }
     }
@@ -867,9 +925,73 @@ export const marketChartService = {
   }
 }
 
-// ---------------- Budgets (single period lock + linear vesting) ----------------
+// ---------------- Budgets (period lock + linear vesting, optional recurrence) ----------------
 export type AssetKind = "CkBtc" | "CkEth"
 
+export type BudgetRecurrenceRule =
+  | { kind: "Weekly" }
+  | { kind: "MonthlyByDay"; day: number }
+  | { kind: "Interval"; everyNs: bigint }
+
+export type BudgetRecurrenceInput = { rule: BudgetRecurrenceRule; endAtNs?: bigint; maxOccurrences?: number }
+
+export type BudgetRolloverPolicy =
+  | { kind: "Keep" }
+  | { kind: "CarryOver" }
+  | { kind: "SweepToGoal"; goalId: string }
+  | { kind: "Refund" }
+
+function toCandidRollover(p?: BudgetRolloverPolicy): [] | [any] {
+  if (!p) return []
+  if (p.kind === "SweepToGoal") return [{ SweepToGoal: { goal_id: p.goalId } }]
+  return [{ [p.kind]: null }]
+}
+
+function toCandidRecurrence(r?: BudgetRecurrenceInput): [] | [any] {
+  if (!r) return []
+  const rule = r.rule.kind === "Weekly" ? { Weekly: null }
+    : r.rule.kind === "MonthlyByDay" ? { MonthlyByDay: { day: r.rule.day } }
+    : { Interval: { every_ns: r.rule.everyNs } }
+  return [{
+    rule,
+    end_at_ns: r.endAtNs !== undefined ? [r.endAtNs] : [],
+    max_occurrences: r.maxOccurrences !== undefined ? [r.maxOccurrences] : [],
+  }]
+}
+
+export type BudgetVestingSchedule =
+  | { kind: "Linear" }
+  | { kind: "Daily" }
+  | { kind: "Weekly" }
+  | { kind: "Step"; everyNs: bigint }
+  | { kind: "CliffLinear"; cliffNs: bigint }
+  | { kind: "FrontLoaded" }
+  | { kind: "BackLoaded" }
+  | { kind: "Tranches"; tranches: { atNs: bigint; amount: bigint }[] }
+
+function toCandidVesting(v?: BudgetVestingSchedule): [] | [any] {
+  if (!v) return []
+  if (v.kind === "Step") return [{ Step: { every_ns: v.everyNs } }]
+  if (v.kind === "CliffLinear") return [{ CliffLinear: { cliff_ns: v.cliffNs } }]
+  if (v.kind === "Tranches") return [{ Tranches: { tranches: v.tranches.map((t) => ({ at_ns: t.atNs, amount: t.amount })) } }]
+  return [{ [v.kind]: null }]
+}
+
+export type BudgetEnvelopeInput = {
+  name: string
+  categoryId?: string
+  allocation: { kind: "Percent"; bps: number } | { kind: "Fixed"; amount: bigint }
+}
+
+function toCandidEnvelopes(envs?: BudgetEnvelopeInput[]): [] | [any[]] {
+  if (!envs || envs.length === 0) return []
+  return [envs.map((e) => ({
+    name: e.name,
+    category_id: e.categoryId ? [e.categoryId] : [],
+    allocation: e.allocation.kind === "Percent" ? { Percent: { bps: e.allocation.bps } } : { Fixed: { amount: e.allocation.amount } },
+  }))]
+}
+
 export const budgetService = {
   listByAsset: async (assetCanister: string, owner?: string): Promise<Result<any[]>> => {
     try {
@@ -887,13 +1009,13 @@ export const budgetService = {
       const a = await ensureActor()
       const res = await a.budget_preview_requirements(Principal.fromText(assetCanister), { [assetKind]: null } as any, amountToLock)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to preview requirements" }
     }
   },
 
-  createAndLock: async (params: { assetCanister: string; assetKind: AssetKind; name: string; amountToLock: bigint; periodStartNs: bigint; periodEndNs: bigint }): Promise<Result<any>> => {
+  createAndLock: async (params: { assetCanister: string; assetKind: AssetKind; name: string; amountToLock: bigint; periodStartNs: bigint; periodEndNs: bigint; recurrence?: BudgetRecurrenceInput; rollover?: BudgetRolloverPolicy; envelopes?: BudgetEnvelopeInput[]; vesting?: BudgetVestingSchedule }): Promise<Result<any>> => {
     try {
       const a = await ensureActor()
       const req = {
@@ -903,10 +1025,14 @@ export const budgetService = {
         amount_to_lock: params.amountToLock,
         period_start_ns: params.periodStartNs,
         period_end_ns: params.periodEndNs,
+        recurrence: toCandidRecurrence(params.recurrence),
+        rollover: toCandidRollover(params.rollover),
+        envelopes: toCandidEnvelopes(params.envelopes),
+        vesting: toCandidVesting(params.vesting),
       } as any
       const res = await a.budget_create_and_lock(req)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to create budget" }
     }
@@ -917,7 +1043,7 @@ export const budgetService = {
       const a = await ensureActor()
       const res = await a.budget_preview_accrual(id)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to preview accrual" }
     }
@@ -939,7 +1065,7 @@ export const budgetService = {
       const md: [] | [bigint] = maxDelta !== undefined ? [maxDelta] : []
       const res = await a.budget_refresh_accrual_step(id, md)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to refresh accrual" }
     }
@@ -953,18 +1079,117 @@ export const budgetService = {
       if (amount <= BigInt(0)) return { success: false, error: "Amount must be > 0" }
       const res = await a.budget_withdraw(id, amount, sub)
       if ("Ok" in res) return { success: true, data: (res.Ok as bigint).toString() }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to withdraw" }
     }
   },
 
+  withdrawEnvelope: async (id: string, envelopeId: string, amount: bigint, toSub?: Uint8Array): Promise<Result<string>> => {
+    try {
+      const a = await ensureActor()
+      const sub: [] | [Uint8Array] = toSub ? [toSub] : []
+      if (amount <= BigInt(0)) return { success: false, error: "Amount must be > 0" }
+      const res = await a.budget_withdraw_envelope(id, envelopeId, amount, sub)
+      if ("Ok" in res) return { success: true, data: (res.Ok as bigint).toString() }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to withdraw from envelope" }
+    }
+  },
+
+  // Bayar akun ICRC-1 pihak ketiga dari saldo budget yang sudah vested
+  spend: async (params: { budgetId: string; envelopeId?: string; to: string; toSubaccount?: Uint8Array; amount: bigint; memo?: Uint8Array; categoryId?: string; note?: string }): Promise<Result<string>> => {
+    try {
+      const a = await ensureActor()
+      if (params.amount <= BigInt(0)) return { success: false, error: "Amount must be > 0" }
+      const res = await a.budget_spend({
+        budget_id: params.budgetId,
+        envelope_id: params.envelopeId ? [params.envelopeId] : [],
+        to: { owner: Principal.fromText(params.to), subaccount: params.toSubaccount ? [params.toSubaccount] : [] },
+        amount: params.amount,
+        memo: params.memo ? [params.memo] : [],
+        category_id: params.categoryId ? [params.categoryId] : [],
+        note: params.note ? [params.note] : [],
+      } as any)
+      if ("Ok" in res) return { success: true, data: (res.Ok as bigint).toString() }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to spend from budget" }
+    }
+  },
+
+  previewSchedule: async (id: string): Promise<Result<{ kind: string; at_time_ns: bigint; amount: bigint; period_index: number }[]>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.budget_preview_schedule(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any[] }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to preview schedule" }
+    }
+  },
+
+  getAnalytics: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.budget_get_analytics(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load budget analytics" }
+    }
+  },
+
+  listCategories: async (): Promise<Result<any[]>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.budget_category_list()
+      return { success: true, data: res as any[] }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to list categories" }
+    }
+  },
+
+  createCategory: async (name: string, parentId?: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.budget_category_create(name, parentId ? [parentId] : [])
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to create category" }
+    }
+  },
+
+  deleteCategory: async (id: string): Promise<Result<null>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.budget_category_delete(id)
+      if ("Ok" in res) return { success: true, data: null }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to delete category" }
+    }
+  },
+
+  categoryReport: async (assetCanister: string, owner?: string): Promise<Result<any[]>> => {
+    try {
+      const a = await ensureActor()
+      const ownerOpt: [] | [Principal] = owner ? [Principal.fromText(owner)] : []
+      const res = await a.budget_category_report(ownerOpt, Principal.fromText(assetCanister))
+      return { success: true, data: res as any[] }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load category report" }
+    }
+  },
+
   listEvents: async (id: string, limit = 50, offset = 0): Promise<Result<any[]>> => {
     try {
       const a = await ensureActor()
       const res = await a.budget_list_events(id, [limit], [offset])
       if ("Ok" in res) return { success: true, data: res.Ok as any[] }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to list events" }
     }
@@ -1092,6 +1317,29 @@ export const budgetService = {
 }
 
 // ---------------- Goals (cicilan/savings with cliff unlock) ----------------
+export type GoalContributionInterval = "Daily" | "Weekly" | "Monthly" | "OnCkBtcDeposit"
+// Who may withdraw from a (group) goal; fixed at creation
+export type GoalWithdrawalPolicy =
+  | { kind: "OwnerOnly" }
+  | { kind: "ProRata" }
+  | { kind: "Approval"; required: number }
+
+// Milestone target: percent of the goal, saved amount (token units, or 1/100 fiat for fiat goals), or a checkpoint date
+export type GoalMilestoneInput =
+  | { kind: "Percent"; percent: number; label?: string }
+  | { kind: "Amount"; amount: bigint; label?: string }
+  | { kind: "Date"; atNs: bigint; label?: string }
+
+const toMilestoneSpec = (m: GoalMilestoneInput) => ({
+  target: m.kind === "Percent" ? { Percent: m.percent } : m.kind === "Amount" ? { Amount: m.amount } : { Date: m.atNs },
+  label: m.label ? [m.label] : [],
+})
+
+export type GoalPenaltyDestination =
+  | { kind: "Burn" }
+  | { kind: "CommunityPool" }
+  | { kind: "Donate"; owner: string; subaccount?: Uint8Array }
+
 export const goalsService = {
   // Create goal with optional initial transfer
   createAndLock: async (params: { 
@@ -1102,6 +1350,15 @@ export const goalsService = {
     startNs: bigint; 
     endNs: bigint;
     initialAmount?: bigint;
+    // Target in fiat (e.g. { currency: "usd", amountMinor: 100000n } = $1,000); amountToLock is then estimated by the backend
+    fiatTarget?: { currency: string; amountMinor: bigint };
+    // Extra ICRC assets counted toward the fiat target (requires fiatTarget); coinId is the price id, e.g. "solana"
+    extraAssets?: { assetCanister: string; coinId: string }[];
+    withdrawalPolicy?: GoalWithdrawalPolicy;
+    // Omit for the default 25/50/75% milestones; pass [] for none
+    milestones?: GoalMilestoneInput[];
+    // Send unlocked funds back to the owner automatically at end time (owner-only goals)
+    autoPayout?: boolean;
   }): Promise<Result<any>> => {
     try {
       const a = await ensureActor()
@@ -1113,10 +1370,22 @@ export const goalsService = {
         start_ns: params.startNs,
         end_ns: params.endNs,
         initial_amount: params.initialAmount ? [params.initialAmount] : [],
+        fiat_target: params.fiatTarget ? [{ currency: params.fiatTarget.currency, amount_minor: params.fiatTarget.amountMinor }] : [],
+        early_exit: [],
+        extra_assets: params.extraAssets?.length
+          ? [params.extraAssets.map((x) => ({ asset_canister: Principal.fromText(x.assetCanister), coin_id: x.coinId }))]
+          : [],
+        withdrawal_policy: params.withdrawalPolicy
+          ? [params.withdrawalPolicy.kind === "Approval"
+              ? { Approval: { required: params.withdrawalPolicy.required } }
+              : { [params.withdrawalPolicy.kind]: null }]
+          : [],
+        milestones: params.milestones ? [params.milestones.map(toMilestoneSpec)] : [],
+        auto_payout: params.autoPayout !== undefined ? [params.autoPayout] : [],
       } as any
       const res = await a.goals_create_and_lock(req)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to create goal" }
     }
@@ -1145,13 +1414,196 @@ export const goalsService = {
     }
   },
 
+  // Standing instruction: pull `amount` from the user's account on each interval (needs ICRC-2 allowance)
+  setAutoContribution: async (id: string, params: { amount: bigint; interval: GoalContributionInterval; startAtNs?: bigint; maxFailures?: number }): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const req = {
+        amount: params.amount,
+        interval: { [params.interval]: null } as any,
+        start_at_ns: params.startAtNs !== undefined ? [params.startAtNs] : [],
+        max_failures: params.maxFailures !== undefined ? [params.maxFailures] : [],
+      } as any
+      const res = await a.goals_set_auto_contribution(id, req)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to set auto-contribution" }
+    }
+  },
+
+  refreshPrice: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_refresh_price(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to refresh goal price" }
+    }
+  },
+
+  // Opt-in early exit: penalty (basis points) goes to burn, a donation account or the community pool
+  enableEarlyExit: async (id: string, terms: { penaltyBps: number; destination: GoalPenaltyDestination; coolingOffHours: number }): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const destination = terms.destination.kind === "Donate"
+        ? { Donate: { owner: Principal.fromText(terms.destination.owner), subaccount: terms.destination.subaccount ? [terms.destination.subaccount] : [] } }
+        : { [terms.destination.kind]: null }
+      const res = await a.goals_enable_early_exit(id, { penalty_bps: terms.penaltyBps, destination, cooling_off_hours: terms.coolingOffHours } as any)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to enable early exit" }
+    }
+  },
+
+  requestEarlyExit: async (id: string, amount?: bigint): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_request_early_exit(id, amount !== undefined ? [amount] : [])
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to request early exit" }
+    }
+  },
+
+  cancelEarlyExit: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_cancel_early_exit(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to cancel early exit" }
+    }
+  },
+
+  setMilestones: async (id: string, milestones: GoalMilestoneInput[]): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_set_milestones(id, milestones.map(toMilestoneSpec) as any)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to update milestones" }
+    }
+  },
+
+  setAutoPayout: async (id: string, enabled: boolean): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_set_auto_payout(id, enabled)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to update auto payout" }
+    }
+  },
+
+  // ---- Group goals ----
+  inviteMember: async (id: string, member: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_invite_member(id, Principal.fromText(member))
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to invite member" }
+    }
+  },
+
+  acceptInvite: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_accept_invite(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to accept invite" }
+    }
+  },
+
+  listInvitations: async (): Promise<Result<any[]>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_list_invitations()
+      return { success: true, data: res as any[] }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load goal invitations" }
+    }
+  },
+
+  // Per-contributor ledger view
+  listContributions: async (id: string): Promise<Result<any[]>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_list_contributions(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any[] }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load contributions" }
+    }
+  },
+
+  // Approval policy: propose a withdrawal (the proposer approves it too)
+  proposeWithdrawal: async (id: string, amount: bigint, opts?: { assetCanister?: string; recipient?: string }): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_propose_withdrawal(
+        id,
+        opts?.assetCanister ? [Principal.fromText(opts.assetCanister)] : [],
+        amount,
+        opts?.recipient ? [Principal.fromText(opts.recipient)] : [],
+      )
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to propose withdrawal" }
+    }
+  },
+
+  approveWithdrawal: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_approve_withdrawal(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to approve withdrawal" }
+    }
+  },
+
+  cancelWithdrawal: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_cancel_withdrawal(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to cancel withdrawal" }
+    }
+  },
+
+  cancelAutoContribution: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_cancel_auto_contribution(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to cancel auto-contribution" }
+    }
+  },
+
   // Get goal progress (percentage towards target)
   getProgress: async (id: string): Promise<Result<any>> => {
     try {
       const a = await ensureActor()
       const res = await a.goals_get_progress(id)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get goal progress" }
     }
@@ -1163,7 +1615,19 @@ export const goalsService = {
       const a = await ensureActor()
       const res = await a.goals_add_funds(id, amount)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to add funds" }
+    }
+  },
+
+  // Add funds in one of the goal's extra assets (multi-asset goal)
+  addFundsAsset: async (id: string, assetCanister: string, amount: bigint): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_add_funds_asset(id, Principal.fromText(assetCanister), amount)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to add funds" }
     }
@@ -1175,12 +1639,36 @@ export const goalsService = {
       const a = await ensureActor()
       const res = await a.goals_refresh(id)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to refresh goal" }
     }
   },
 
+  // Archive goal: refund available funds according to the withdrawal policy
+  archive: async (id: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_archive(id)
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to archive goal" }
+    }
+  },
+
+  // Delete goal (archives and refunds first when needed)
+  delete: async (id: string): Promise<Result<null>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.goals_delete(id)
+      if ("Ok" in res) return { success: true, data: null }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to delete goal" }
+    }
+  },
+
   // Withdraw from goal
   withdraw: async (id: string, amount: bigint): Promise<Result<string>> => {
     try {
@@ -1188,7 +1676,20 @@ export const goalsService = {
       if (amount <= BigInt(0)) return { success: false, error: "Amount must be > 0" }
       const res = await a.goals_withdraw(id, amount)
       if ("Ok" in res) return { success: true, data: (res.Ok as bigint).toString() }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to withdraw from goal" }
+    }
+  },
+
+  // Withdraw one asset of a multi-asset goal after unlock
+  withdrawAsset: async (id: string, assetCanister: string, amount: bigint): Promise<Result<string>> => {
+    try {
+      const a = await ensureActor()
+      if (amount <= BigInt(0)) return { success: false, error: "Amount must be > 0" }
+      const res = await a.goals_withdraw_asset(id, Principal.fromText(assetCanister), amount)
+      if ("Ok" in res) return { success: true, data: (res.Ok as bigint).toString() }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to withdraw from goal" }
     }
@@ -1200,7 +1701,7 @@ export const goalsService = {
       const a = await ensureActor()
       const res = await a.goals_list_events(id, [limit], [offset])
       if ("Ok" in res) return { success: true, data: res.Ok as any[] }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to list goal events" }
     }
@@ -1294,6 +1795,82 @@ export const goalsService = {
   },
 }
 
+// ---------------- Notifications ----------------
+export const notificationsService = {
+  list: async (opts?: { unreadOnly?: boolean; limit?: number; offset?: number }): Promise<Result<any[]>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.notifications_list(
+        opts?.unreadOnly !== undefined ? [opts.unreadOnly] : [],
+        opts?.limit !== undefined ? [opts.limit] : [],
+        opts?.offset !== undefined ? [opts.offset] : [],
+      )
+      return { success: true, data: res as any[] }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load notifications" }
+    }
+  },
+
+  unreadCount: async (): Promise<Result<number>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.notifications_unread_count()
+      return { success: true, data: Number(res) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load notifications" }
+    }
+  },
+
+  // Empty ids = mark everything read
+  markRead: async (ids: bigint[] = []): Promise<Result<number>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.notifications_mark_read(ids)
+      return { success: true, data: Number(res) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to update notifications" }
+    }
+  },
+}
+
+// ---------------- Escrow audit ----------------
+export type EscrowEntity = "Budget" | "Goal"
+
+export const escrowService = {
+  // asset selects an extra-asset escrow of a multi-asset goal; omit for the main escrow
+  check: async (entity: EscrowEntity, id: string, asset?: string): Promise<Result<any>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.escrow_check({ [entity]: null } as any, id, asset ? [Principal.fromText(asset)] : [])
+      if ("Ok" in res) return { success: true, data: res.Ok as any }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to check escrow" }
+    }
+  },
+
+  listReports: async (onlyDrift = false): Promise<Result<any[]>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.escrow_list_reports([onlyDrift])
+      return { success: true, data: res as any[] }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to load escrow reports" }
+    }
+  },
+
+  recover: async (entity: EscrowEntity, id: string, toSub?: Uint8Array, asset?: string): Promise<Result<string>> => {
+    try {
+      const a = await ensureActor()
+      const res = await a.escrow_recover({ [entity]: null } as any, id, asset ? [Principal.fromText(asset)] : [], toSub ? [toSub] : [])
+      if ("Ok" in res) return { success: true, data: res.Ok.toString() }
+      return { success: false, error: formatBackendError(res.Err) }
+    } catch (e: any) {
+      return { success: false, error: e?.message || "Failed to recover escrow funds" }
+    }
+  },
+}
+
 // ---------------- Uniswap Service ----------------
 export const uniswapService = {
   // Send Uniswap transaction (simple response)
@@ -1313,7 +1890,7 @@ export const uniswapService = {
       } as any
       const res = await a.uniswap_send_tx(req)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to send Uniswap transaction" }
     }
@@ -1340,7 +1917,7 @@ export const uniswapService = {
       } as any
       const res = await a.uniswap_send_tx_with_response(req)
       if ("Ok" in res) return { success: true, data: res.Ok as any }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to send Uniswap transaction" }
     }
@@ -1352,7 +1929,7 @@ export const uniswapService = {
       const a = await ensureActor()
       const res = await a.uniswap_get_gas_price()
       if ("Ok" in res) return { success: true, data: BigInt(res.Ok) }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get gas price" }
     }
@@ -1375,7 +1952,7 @@ export const uniswapService = {
       } as any
       const res = await a.uniswap_send_approval_tx(req)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to send approval transaction" }
     }
@@ -1388,7 +1965,7 @@ export const uniswapService = {
       const req: [] | [Principal] = owner ? [Principal.fromText(owner)] : []
       const res = await a.uniswap_get_fresh_nonce(req)
       if ("Ok" in res) return { success: true, data: Number(res.Ok) }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get fresh nonce" }
     }
@@ -1427,7 +2004,7 @@ export const kongswapService = {
       }
       const res = await (a as any).kongswap_preview_swap(req)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to preview swap" }
     }
@@ -1460,7 +2037,7 @@ export const kongswapService = {
       }
       const res = await (a as any).kongswap_swap_tokens_async(req)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to execute swap" }
     }
@@ -1482,7 +2059,7 @@ export const kongswapService = {
       const a = await ensureActor()
       const res = await (a as any).kongswap_poll_swap_status(request_id)
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to poll swap status" }
     }
@@ -1494,7 +2071,7 @@ export const kongswapService = {
       const a = await ensureActor()
       const res = await (a as any).kongswap_get_current_price()
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to get current price" }
     }
@@ -1506,7 +2083,7 @@ export const kongswapService = {
       const a = await ensureActor()
       const res = await (a as any).kongswap_is_service_available()
       if ("Ok" in res) return { success: true, data: res.Ok }
-      return { success: false, error: res.Err }
+      return { success: false, error: formatBackendError(res.Err) }
     } catch (e: any) {
       return { success: false, error: e?.message || "Failed to check service availability" }
     }
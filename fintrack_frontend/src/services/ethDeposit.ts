@@ -54,6 +54,7 @@ async function principalToBytes32(principal: string): Promise<string> {
   try {
     // Import backend service secara dinamis
     const { getBackendActor } = await import('../lib/ic');
+    const { formatBackendError } = await import('./backend');
     const actor = await getBackendActor();
     
     // Call backend function
@@ -62,7 +63,7 @@ async function principalToBytes32(principal: string): Promise<string> {
     if ('Ok' in result) {
       return result.Ok; // Success case
     } else {
-      throw new Error(result.Err || 'Failed to convert principal');
+      throw new Error(formatBackendError(result.Err));
     }
   } catch (error) {
     console.error('Error converting principal to bytes32:', error);